@@ -0,0 +1,9 @@
+#version 330
+
+smooth in vec4 fragColor;
+
+out vec4 outputColor;
+void main()
+{
+    outputColor = fragColor;
+}
@@ -0,0 +1,137 @@
+#![forbid(unsafe_code)]
+
+use std::ffi::CString;
+
+use gl::types::GLsizei;
+use glam::Vec4;
+use glfw::{Action, Key, Modifiers, PWindow};
+use opengl_rend::app::{run_app_with_config, AppConfig, AppError, Application, ContextInfo};
+use opengl_rend::buffer::{ShaderStorageBuffer, Usage};
+use opengl_rend::input::Input;
+use opengl_rend::opengl::{BarrierFlags, Capability, ClearFlags, OpenGl, Primitive};
+use opengl_rend::program::{GLLocation, Program, Shader, ShaderType};
+use opengl_rend::vertex_attributes::VertexArrayObject;
+use opengl_rend::viewport::Viewport;
+
+const PARTICLE_COUNT: usize = 4096;
+const WORK_GROUP_SIZE: usize = 64;
+const PARTICLES_BINDING_INDEX: u32 = 0;
+
+struct App {
+    window: PWindow,
+    gl: OpenGl,
+    compute_program: Program,
+    delta_time_uniform: GLLocation,
+    render_program: Program,
+    particles: ShaderStorageBuffer<Vec4>,
+    // positions are read from the storage buffer, but a vao still needs to be bound to draw
+    empty_vao: VertexArrayObject,
+    delta_time: f32,
+}
+
+fn initial_particles() -> Vec<Vec4> {
+    (0..PARTICLE_COUNT)
+        .map(|i| {
+            let t = i as f32 / PARTICLE_COUNT as f32;
+            let angle = t * 97.0 * std::f32::consts::TAU;
+            let radius = 0.85f32.mul_add(t, 0.1);
+            Vec4::new(radius * angle.cos(), radius * angle.sin(), t, 1.0)
+        })
+        .collect()
+}
+
+impl Application for App {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
+        if !ContextInfo::from_window(&window).has_compute() {
+            return Err(AppError::ApplicationError(
+                "compute shaders need an OpenGL 4.3 context".into(),
+            ));
+        }
+        let mut gl = OpenGl::new(&mut window);
+
+        let comp_str = CString::new(include_str!("particles.comp")).unwrap();
+        let comp_shader = Shader::new(&comp_str, ShaderType::Compute).unwrap();
+        let compute_program = Program::new(&[comp_shader]).unwrap();
+        let delta_time_uniform = compute_program.get_uniform_location(c"deltaTime").unwrap();
+
+        let vert_str = CString::new(include_str!("vert.vert")).unwrap();
+        let frag_str = CString::new(include_str!("frag.frag")).unwrap();
+        let vert_shader = Shader::new(&vert_str, ShaderType::Vertex).unwrap();
+        let frag_shader = Shader::new(&frag_str, ShaderType::Fragment).unwrap();
+        let render_program = Program::new(&[vert_shader, frag_shader]).unwrap();
+
+        let mut particles = ShaderStorageBuffer::new();
+        particles
+            .bind_scoped()
+            .buffer_data(&initial_particles(), Usage::DynamicDraw);
+        particles.bind_base(PARTICLES_BINDING_INDEX);
+
+        gl.enable(Capability::ProgramPointSize);
+
+        Ok(Self {
+            window,
+            gl,
+            compute_program,
+            delta_time_uniform,
+            render_program,
+            particles,
+            empty_vao: VertexArrayObject::new(),
+            delta_time: 0.0,
+        })
+    }
+
+    fn update(&mut self, dt: f32, _input: &Input) {
+        self.delta_time = dt;
+    }
+
+    fn display(&mut self) {
+        let delta_time = self.delta_time;
+
+        self.compute_program.set_used();
+        self.compute_program
+            .set_uniform(self.delta_time_uniform, delta_time);
+        self.gl
+            .dispatch_compute(PARTICLE_COUNT.div_ceil(WORK_GROUP_SIZE) as u32, 1, 1);
+        self.gl.memory_barrier(BarrierFlags::ShaderStorage);
+
+        self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+        self.gl.clear(ClearFlags::Color);
+
+        self.render_program.set_used();
+        self.empty_vao.bind();
+        self.gl
+            .draw_arrays(Primitive::Points, 0, PARTICLE_COUNT as GLsizei);
+        self.empty_vao.unbind();
+        self.render_program.set_unused();
+    }
+
+    fn keyboard(&mut self, key: Key, action: Action, _modifier: Modifiers) {
+        if key == Key::Space && action == Action::Press {
+            // read the particles back from the gpu, mapping only sees the compute writes
+            // after a buffer update barrier
+            self.gl.memory_barrier(BarrierFlags::BufferUpdate);
+            let particles = self.particles.map().unwrap();
+            println!("First particle: {}", particles[0]);
+        }
+    }
+
+    fn reshape(&mut self, viewport: &Viewport) {
+        viewport.apply(&mut self.gl);
+    }
+
+    fn window(&self) -> &PWindow {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    run_app_with_config::<App>(&AppConfig {
+        title: "Compute particles".to_owned(),
+        gl_version: vec![(4, 6), (4, 3)],
+        ..AppConfig::default()
+    })
+}
@@ -0,0 +1,15 @@
+#version 430
+
+layout(std430, binding = 0) buffer Particles {
+    vec4 positions[];
+};
+
+smooth out vec4 fragColor;
+
+void main()
+{
+    vec4 particle = positions[gl_VertexID];
+    gl_Position = vec4(particle.xy, 0.0, 1.0);
+    gl_PointSize = 3.0;
+    fragColor = vec4(particle.z, 0.5, 1.0 - particle.z, 1.0);
+}
@@ -4,11 +4,12 @@ use std::ffi::CString;
 
 use gl::types::GLsizei;
 use glfw::{Action, Key, Modifiers, PWindow};
-use opengl_rend::app::{run_app, Application};
+use opengl_rend::app::{run_app, AppError, Application};
 use opengl_rend::buffer::{Target, Usage};
 use opengl_rend::opengl::{Capability, ClearFlags, CullMode, FrontFace, Primitive};
 use opengl_rend::program::{GLLocation, Shader, ShaderType};
 use opengl_rend::vertex_attributes::{DataType, VertexAttribute};
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{
     buffer::Buffer, opengl::OpenGl, program::Program, vertex_attributes::VertexArrayObject,
 };
@@ -128,7 +129,7 @@ const VERTEX_DATA: [f32;288] = [
 ];
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let mut gl = OpenGl::new(&mut window);
 
         // initialize program
@@ -136,7 +137,7 @@ impl Application for App {
         let frag_str = CString::new(include_str!("frag.frag")).unwrap();
         let vert_shader = Shader::new(&vert_str, ShaderType::Vertex).unwrap();
         let frag_shader = Shader::new(&frag_str, ShaderType::Fragment).unwrap();
-        let mut program = Program::new(&[vert_shader, frag_shader]).unwrap();
+        let program = Program::new(&[vert_shader, frag_shader]).unwrap();
 
         // initialize vertex buffer
         let mut vertex_buffer = Buffer::new(Target::ArrayBuffer);
@@ -149,9 +150,18 @@ impl Application for App {
 
         let begin_color_data = std::mem::size_of_val(&VERTEX_DATA) / 2;
 
+        // positions and colors come from the same buffer through two binding points
         vertex_array_object.bind();
-        vertex_array_object.set_attribute(0, &vec4, 0, 0);
-        vertex_array_object.set_attribute(1, &vec4, 0, begin_color_data as GLsizei);
+        vertex_array_object.set_format(0, &vec4, 0, 0);
+        vertex_array_object.set_format(1, &vec4, 0, 1);
+        let stride = vec4.size() as GLsizei;
+        vertex_array_object.bind_vertex_buffer(0, &vertex_buffer, 0, stride);
+        vertex_array_object.bind_vertex_buffer(
+            1,
+            &vertex_buffer,
+            begin_color_data as isize,
+            stride,
+        );
         // gl.polygon_mode(PolygonMode::Line);
 
         gl.enable(Capability::CullFace);
@@ -177,11 +187,9 @@ impl Application for App {
         let perspective_matrix_location =
             program.get_uniform_location(c"perspectiveMatrix").unwrap();
 
-        program.set_used();
-        program.set_uniform(perspective_matrix_location, matrix);
-        program.set_unused();
+        program.set_uniform_dsa(perspective_matrix_location, matrix);
 
-        Self {
+        Ok(Self {
             gl,
             program,
             vertex_array_object,
@@ -190,7 +198,7 @@ impl Application for App {
             offset_location,
             perspective_matrix_location,
             perspective_matrix: matrix,
-        }
+        })
     }
 
     fn display(&mut self) {
@@ -210,10 +218,10 @@ impl Application for App {
 
     fn keyboard(&mut self, _key: Key, _action: Action, _modifier: Modifiers) {}
 
-    fn reshape(&mut self, width: i32, height: i32) {
+    fn reshape(&mut self, viewport: &Viewport) {
         let frustum_scale = 1.0;
 
-        self.perspective_matrix[0] = frustum_scale / (width as f32 / height as f32);
+        self.perspective_matrix[0] = frustum_scale / viewport.aspect_ratio();
         self.perspective_matrix[5] = frustum_scale;
 
         // let z_near = 1.0;
@@ -222,12 +230,10 @@ impl Application for App {
         // self.perspective_matrix =
         //     Mat4::perspective_rh_gl(fov, width as f32 / height as f32, z_near, z_far);
 
-        self.program.set_used();
         self.program
-            .set_uniform(self.perspective_matrix_location, self.perspective_matrix);
-        self.program.set_unused();
+            .set_uniform_dsa(self.perspective_matrix_location, self.perspective_matrix);
 
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+        viewport.apply(&mut self.gl);
     }
 
     fn window(&self) -> &PWindow {
@@ -239,6 +245,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
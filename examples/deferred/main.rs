@@ -0,0 +1,236 @@
+#![forbid(unsafe_code)]
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glam::{Quat, Vec2, Vec3, Vec4};
+use glfw::{Key, PWindow};
+use opengl_rend::app::{run_app_with_config, AppConfig, AppError, Application};
+use opengl_rend::assets::MeshHandle;
+use opengl_rend::camera::{Camera, OrbitCamera};
+use opengl_rend::color::{srgb, srgb_rgb};
+use opengl_rend::gbuffer::{geometry_program, DeferredLighting, GBuffer};
+use opengl_rend::input::Input;
+use opengl_rend::lighting::{material, LightBlock, LightBuffer, PointLight, MAX_LIGHTS};
+use opengl_rend::material::ProgramHandle;
+use opengl_rend::mesh::{Mesh, MeshLoader, PendingMesh};
+use opengl_rend::opengl::{Capability, ClearFlags, CullMode, DepthFunc, FrontFace, OpenGl};
+use opengl_rend::scene::{Scene, SceneObject};
+use opengl_rend::text::TextRenderer;
+use opengl_rend::transform::Transform;
+use opengl_rend::viewport::Viewport;
+
+const LIGHTS_BINDING_INDEX: u32 = 0;
+
+/// Spheres on each side of the grid
+const GRID_SIZE: usize = 5;
+const GRID_SPACING: f32 = 2.5;
+const LIGHT_RADIUS: f32 = 5.0;
+/// Degrees per second the lights go around the grid
+const LIGHT_SPEED: f32 = 30.0;
+
+/// Spheres on a grid, on a large plane
+fn build_scene(program: &ProgramHandle, sphere: &MeshHandle, plane: &MeshHandle) -> Scene {
+    let mut scene = Scene::new();
+    let ground = scene.nodes.add_root(
+        "ground",
+        Transform::from_scale(Vec3::new(30.0, 1.0, 30.0)),
+        (),
+    );
+    scene.add(SceneObject {
+        mesh: Rc::clone(plane),
+        material: Rc::new(material(
+            Rc::clone(program),
+            srgb(0.5, 0.5, 0.5, 1.0),
+            Vec4::splat(0.1),
+            4.0,
+        )),
+        transform: ground,
+    });
+
+    let offset = (GRID_SIZE - 1) as f32 * GRID_SPACING / 2.0;
+    for x in 0..GRID_SIZE {
+        for z in 0..GRID_SIZE {
+            let position = Vec3::new(
+                (x as f32).mul_add(GRID_SPACING, -offset),
+                0.75,
+                (z as f32).mul_add(GRID_SPACING, -offset),
+            );
+            let node = scene.nodes.add_root(
+                "sphere",
+                Transform::new(position, Quat::IDENTITY, Vec3::splat(1.5)),
+                (),
+            );
+            scene.add(SceneObject {
+                mesh: Rc::clone(sphere),
+                material: Rc::new(material(
+                    Rc::clone(program),
+                    srgb(0.9, 0.9, 0.9, 1.0),
+                    Vec4::splat(0.6),
+                    32.0,
+                )),
+                transform: node,
+            });
+        }
+    }
+    scene
+}
+
+/// Hue going around the color wheel with `t` from 0 to 1
+fn hue(t: f32) -> Vec3 {
+    let channel = |offset: f32| {
+        ((t + offset) * std::f32::consts::TAU)
+            .cos()
+            .mul_add(0.5, 0.5)
+    };
+    Vec3::new(channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0))
+}
+
+struct App {
+    window: PWindow,
+    gl: OpenGl,
+    scene: Scene,
+    camera: Camera,
+    orbit: OrbitCamera,
+    lights: LightBlock,
+    light_buffer: LightBuffer,
+    // degrees around the y axis
+    light_angle: f32,
+    gbuffer: GBuffer,
+    deferred: DeferredLighting,
+    text: TextRenderer,
+    viewport: Viewport,
+}
+
+impl App {
+    fn update_lights(&mut self) {
+        self.lights.clear();
+        self.lights.set_camera_position(self.camera.position());
+        for i in 0..MAX_LIGHTS {
+            let t = i as f32 / MAX_LIGHTS as f32;
+            let (sin, cos) = t.mul_add(360.0, self.light_angle).to_radians().sin_cos();
+            self.lights.push(PointLight {
+                position: Vec3::new(cos * LIGHT_RADIUS, 1.0, sin * LIGHT_RADIUS),
+                color: srgb_rgb(hue(t)),
+                intensity: 1.5,
+                range: 6.0,
+            });
+        }
+        self.light_buffer.update(&self.lights).unwrap();
+    }
+
+    fn draw_overlay(&mut self) {
+        let help = format!(
+            "{} lights, V shows {:?}",
+            self.lights.len(),
+            self.deferred.view
+        );
+        self.text.queue(&help, Vec2::new(8.0, 8.0), Vec4::ONE);
+        let size = self.viewport.size();
+        self.text.draw(&mut self.gl, size.x as i32, size.y as i32);
+    }
+}
+
+impl Application for App {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
+        let mut gl = OpenGl::new(&mut window);
+
+        let loader = MeshLoader::default();
+        let sphere = loader.load("examples/deferred/meshes/UnitSphere.xml");
+        let plane = loader.load("examples/deferred/meshes/UnitPlane.xml");
+
+        let program = Rc::new(geometry_program().unwrap());
+        let light_buffer = LightBuffer::new(LIGHTS_BINDING_INDEX);
+
+        gl.enable(Capability::CullFace);
+        gl.cull_face(CullMode::Back);
+        gl.front_face(FrontFace::CW);
+
+        gl.enable(Capability::DepthTest);
+        gl.set_depth_mask(true);
+        gl.depth_func(DepthFunc::LessEqual);
+        gl.depth_range(0.0, 1.0);
+
+        let shared = |mesh: PendingMesh| {
+            Rc::new(RefCell::new(
+                Mesh::upload(&gl, mesh.wait().unwrap()).unwrap(),
+            ))
+        };
+        let scene = build_scene(&program, &shared(sphere), &shared(plane));
+
+        let gbuffer = GBuffer::new(1, 1).unwrap();
+        let deferred = DeferredLighting::new(LIGHTS_BINDING_INDEX).unwrap();
+        let text = TextRenderer::new(&mut gl).unwrap();
+
+        Ok(Self {
+            window,
+            gl,
+            scene,
+            camera: Camera::new(60.0, 0.1, 100.0),
+            orbit: OrbitCamera {
+                min_pitch: 5.0,
+                min_distance: 3.0,
+                max_distance: 40.0,
+                ..OrbitCamera::new(Vec3::ZERO, 90.0, 40.0, 16.0)
+            },
+            lights: LightBlock::new(Vec3::splat(0.05)),
+            light_buffer,
+            light_angle: 0.0,
+            gbuffer,
+            deferred,
+            text,
+            viewport: Viewport::new(1, 1),
+        })
+    }
+
+    fn display(&mut self) {
+        self.update_lights();
+
+        let (scene, camera) = (&self.scene, &self.camera);
+        self.gbuffer
+            .geometry_pass(&mut self.gl, |gl| scene.render(gl, camera));
+
+        let background = srgb(0.05, 0.05, 0.1, 1.0);
+        self.gl
+            .clear_color(background.x, background.y, background.z, background.w);
+        self.gl.clear(ClearFlags::Color | ClearFlags::Depth);
+        self.deferred.draw(
+            &mut self.gl,
+            &self.gbuffer,
+            &self.viewport,
+            self.camera.view_projection(),
+        );
+        self.draw_overlay();
+    }
+
+    fn update(&mut self, dt: f32, input: &Input) {
+        self.camera.update(&mut self.orbit, dt, input);
+        if input.just_pressed(Key::V) {
+            self.deferred.view = self.deferred.view.next();
+        }
+        self.light_angle = LIGHT_SPEED.mul_add(dt, self.light_angle) % 360.0;
+    }
+
+    fn reshape(&mut self, viewport: &Viewport) {
+        self.camera.reshape(viewport);
+        let size = viewport.size();
+        self.gbuffer.resize(size.x as i32, size.y as i32);
+        viewport.apply(&mut self.gl);
+        self.viewport = *viewport;
+    }
+
+    fn window(&self) -> &PWindow {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    run_app_with_config::<App>(&AppConfig {
+        title: "Deferred".to_owned(),
+        srgb: true,
+        ..AppConfig::default()
+    })
+}
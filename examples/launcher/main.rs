@@ -0,0 +1,282 @@
+#![forbid(unsafe_code)]
+use std::{ffi::CString, fmt::Write};
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use glfw::{Key, PWindow};
+use opengl_rend::app::{run_app_with_config, AppConfig, AppError, Application};
+use opengl_rend::buffer::{Buffer, Target, Usage};
+use opengl_rend::debug_draw::DebugDraw;
+use opengl_rend::input::Input;
+use opengl_rend::opengl::{ClearFlags, OpenGl, Primitive};
+use opengl_rend::program::{Program, Shader, ShaderType};
+use opengl_rend::sprite::{screen_projection, Sprite, SpriteBatch};
+use opengl_rend::text::TextRenderer;
+use opengl_rend::vertex_attributes::{DataType, VertexArrayObject, VertexAttribute, VertexLayout};
+use opengl_rend::viewport::Viewport;
+
+/// A demo owning its GL objects, they're deleted when the launcher drops it
+trait Scene {
+    fn update(&mut self, _dt: f32, _input: &Input) {}
+    fn display(&mut self, gl: &mut OpenGl, viewport: &Viewport);
+}
+
+struct SceneEntry {
+    name: &'static str,
+    create: fn(&mut OpenGl) -> Box<dyn Scene>,
+}
+
+/// Selected with the number keys in this order
+const SCENES: [SceneEntry; 3] = [
+    SceneEntry {
+        name: "Triangle",
+        create: Triangle::create,
+    },
+    SceneEntry {
+        name: "Debug lines",
+        create: Lines::create,
+    },
+    SceneEntry {
+        name: "Sprites",
+        create: Sprites::create,
+    },
+];
+const SCENE_KEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+// interleaved position and color
+#[rustfmt::skip]
+const TRIANGLE_DATA: [f32; 24] = [
+    0.0, 0.5, 0.0, 1.0,     1.0, 0.0, 0.0, 1.0,
+    0.5, -0.366, 0.0, 1.0,  0.0, 1.0, 0.0, 1.0,
+    -0.5, -0.366, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0,
+];
+
+struct Triangle {
+    program: Program,
+    vao: VertexArrayObject,
+    _vertex_buffer: Buffer<f32>,
+}
+
+impl Triangle {
+    fn create(gl: &mut OpenGl) -> Box<dyn Scene> {
+        let vert_str = CString::new(include_str!("../triangle/vert.vert")).unwrap();
+        let frag_str = CString::new(include_str!("../triangle/frag.frag")).unwrap();
+        let vert_shader = Shader::new(&vert_str, ShaderType::Vertex).unwrap();
+        let frag_shader = Shader::new(&frag_str, ShaderType::Fragment).unwrap();
+        let program = Program::new(&[vert_shader, frag_shader]).unwrap();
+
+        let mut vao = VertexArrayObject::new();
+        let mut vertex_buffer = Buffer::new(Target::ArrayBuffer);
+        let vec4 = VertexAttribute::new(4, DataType::Float, false);
+        let layout = VertexLayout::new().attribute(0, vec4).attribute(1, vec4);
+        gl.bind_vertex_array(&vao);
+        gl.bind_buffer(&vertex_buffer);
+        vertex_buffer.buffer_data(&TRIANGLE_DATA, Usage::StaticDraw);
+        vao.set_layout(&layout);
+        gl.unbind_vertex_array();
+        gl.unbind_buffer(Target::ArrayBuffer);
+        Box::new(Self {
+            program,
+            vao,
+            _vertex_buffer: vertex_buffer,
+        })
+    }
+}
+
+impl Scene for Triangle {
+    fn display(&mut self, gl: &mut OpenGl, _viewport: &Viewport) {
+        gl.clear_color(0.5, 0.5, 0.5, 0.0);
+        gl.clear(ClearFlags::Color);
+        gl.bind_program(&self.program);
+        gl.bind_vertex_array(&self.vao);
+        gl.draw_arrays(Primitive::Triangles, 0, 3);
+        gl.unbind_vertex_array();
+        gl.unbind_program();
+    }
+}
+
+struct Lines {
+    debug_draw: DebugDraw,
+    angle: f32,
+}
+
+impl Lines {
+    fn create(gl: &mut OpenGl) -> Box<dyn Scene> {
+        Box::new(Self {
+            debug_draw: DebugDraw::new(gl).unwrap(),
+            angle: 0.0,
+        })
+    }
+}
+
+impl Scene for Lines {
+    fn update(&mut self, dt: f32, _input: &Input) {
+        self.angle += dt * 0.5;
+    }
+
+    fn display(&mut self, gl: &mut OpenGl, viewport: &Viewport) {
+        gl.clear_color(0.1, 0.1, 0.15, 1.0);
+        gl.clear(ClearFlags::Color);
+        let eye = Vec3::new(self.angle.cos(), 0.6, self.angle.sin()) * 6.0;
+        let view = Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y);
+        let projection =
+            Mat4::perspective_rh_gl(45_f32.to_radians(), viewport.aspect_ratio(), 0.1, 100.0);
+        self.debug_draw
+            .grid(Vec3::ZERO, 8.0, 16, Vec4::new(0.4, 0.4, 0.4, 1.0));
+        self.debug_draw.axis(Mat4::IDENTITY, 2.0);
+        self.debug_draw
+            .sphere(Vec3::new(0.0, 1.0, 0.0), 1.0, Vec4::new(1.0, 0.8, 0.2, 1.0));
+        self.debug_draw.flush(gl, projection * view);
+    }
+}
+
+struct Sprites {
+    batch: SpriteBatch,
+    time: f32,
+}
+
+impl Sprites {
+    fn create(gl: &mut OpenGl) -> Box<dyn Scene> {
+        Box::new(Self {
+            batch: SpriteBatch::new(gl).unwrap(),
+            time: 0.0,
+        })
+    }
+}
+
+impl Scene for Sprites {
+    fn update(&mut self, dt: f32, _input: &Input) {
+        self.time += dt;
+    }
+
+    fn display(&mut self, gl: &mut OpenGl, viewport: &Viewport) {
+        gl.clear_color(0.2, 0.25, 0.3, 1.0);
+        gl.clear(ClearFlags::Color);
+        let size = viewport.size();
+        let count = 12;
+        for i in 0..count {
+            let angle = (i as f32 / count as f32).mul_add(std::f32::consts::TAU, self.time);
+            let position = size * 0.5 + Vec2::from_angle(angle) * size.min_element() * 0.3;
+            let hue = i as f32 / count as f32;
+            self.batch.draw_rect(
+                &Sprite::new(position, Vec2::splat(40.0))
+                    .with_rotation(angle, Vec2::splat(0.5))
+                    .with_color(Vec4::new(hue, 1.0 - hue, 0.8, 1.0)),
+            );
+        }
+        self.batch.flush(gl, screen_projection(size));
+    }
+}
+
+struct App {
+    window: PWindow,
+    gl: OpenGl,
+    text: TextRenderer,
+    viewport: Viewport,
+    current: usize,
+    scene: Option<Box<dyn Scene>>,
+    /// GL objects deleted when the last scene was dropped
+    deleted: usize,
+}
+
+impl App {
+    fn switch_to(&mut self, index: usize) {
+        // drop the old scene before creating the next one, so their objects never coexist
+        self.scene = None;
+        self.deleted = self.gl.flush_deletions();
+        println!(
+            "Switched to {}, deleted {} GL objects",
+            SCENES[index].name, self.deleted
+        );
+        self.current = index;
+        self.scene = Some((SCENES[index].create)(&mut self.gl));
+        self.window
+            .set_title(&format!("Launcher - {}", SCENES[index].name));
+    }
+}
+
+impl Application for App {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
+        let mut gl = OpenGl::new(&mut window);
+        let text = TextRenderer::new(&mut gl).unwrap();
+        let (width, height) = window.get_framebuffer_size();
+        println!("Scenes:");
+        for (i, scene) in SCENES.iter().enumerate() {
+            println!("  {}: {}", i + 1, scene.name);
+        }
+        let scene = (SCENES[0].create)(&mut gl);
+        Ok(Self {
+            window,
+            gl,
+            text,
+            viewport: Viewport::new(width, height),
+            current: 0,
+            scene: Some(scene),
+            deleted: 0,
+        })
+    }
+
+    fn update(&mut self, dt: f32, input: &Input) {
+        let selected = SCENE_KEYS[..SCENES.len()]
+            .iter()
+            .position(|key| input.just_pressed(*key));
+        if let Some(index) = selected {
+            self.switch_to(index);
+        } else if input.just_pressed(Key::Tab) {
+            self.switch_to((self.current + 1) % SCENES.len());
+        }
+        if let Some(scene) = &mut self.scene {
+            scene.update(dt, input);
+        }
+    }
+
+    fn display(&mut self) {
+        if let Some(scene) = &mut self.scene {
+            scene.display(&mut self.gl, &self.viewport);
+        }
+
+        let mut menu = String::new();
+        for (i, scene) in SCENES.iter().enumerate() {
+            let marker = if i == self.current { '>' } else { ' ' };
+            let _ = writeln!(menu, "{marker} {} {}", i + 1, scene.name);
+        }
+        let _ = write!(
+            menu,
+            "Number keys or Tab to switch\nLast switch deleted {} GL objects",
+            self.deleted
+        );
+        let size = self.viewport.size();
+        self.text.queue(&menu, Vec2::splat(8.0), Vec4::ONE);
+        self.text.draw(&mut self.gl, size.x as i32, size.y as i32);
+    }
+
+    fn reshape(&mut self, viewport: &Viewport) {
+        viewport.apply(&mut self.gl);
+        self.viewport = *viewport;
+    }
+
+    fn window(&self) -> &PWindow {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    run_app_with_config::<App>(&AppConfig {
+        title: "Launcher".to_owned(),
+        size: (800, 600),
+        ..AppConfig::default()
+    })
+}
@@ -0,0 +1,321 @@
+#![forbid(unsafe_code)]
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glam::{Quat, Vec2, Vec3, Vec4};
+use glfw::{Key, PWindow};
+use opengl_rend::app::{run_app_with_config, AppConfig, AppError, Application};
+use opengl_rend::assets::MeshHandle;
+use opengl_rend::camera::{Camera, OrbitCamera};
+use opengl_rend::color::{srgb, srgb_rgb};
+use opengl_rend::debug_draw::DebugDraw;
+use opengl_rend::hdr::{HdrTarget, ToneMapper};
+use opengl_rend::input::Input;
+use opengl_rend::lighting::{
+    material, DirectionalLight, LightBlock, LightBuffer, PointLight, ShadingModel, SpotLight,
+};
+use opengl_rend::material::ProgramHandle;
+use opengl_rend::mesh::{Mesh, MeshLoader, NormalLines, PendingMesh};
+use opengl_rend::opengl::{Capability, ClearFlags, CullMode, DepthFunc, FrontFace, OpenGl};
+use opengl_rend::scene::{Scene, SceneObject};
+use opengl_rend::text::TextRenderer;
+use opengl_rend::transform::Transform;
+use opengl_rend::viewport::Viewport;
+
+const LIGHTS_BINDING_INDEX: u32 = 0;
+
+const POINT_LIGHT_RADIUS: f32 = 4.0;
+const POINT_LIGHT_HEIGHT: f32 = 1.5;
+/// Degrees per second the point light goes around the spheres
+const POINT_LIGHT_SPEED: f32 = 45.0;
+const SPOT_LIGHT_POSITION: Vec3 = Vec3::new(-3.0, 5.0, 3.0);
+// the colors below are picked in sRGB and converted to the linear values lighting needs,
+// the framebuffer encodes the result back to sRGB, see `AppConfig::srgb`
+const POINT_LIGHT_COLOR: Vec3 = Vec3::new(1.0, 0.9, 0.6);
+const SPOT_LIGHT_COLOR: Vec3 = Vec3::new(0.4, 0.6, 1.0);
+
+const MODELS: [ShadingModel; 3] = [
+    ShadingModel::Gouraud,
+    ShadingModel::Phong,
+    ShadingModel::Blinn,
+];
+
+/// Spheres in a row from dull to shiny, on a large plane
+fn build_scene(program: &ProgramHandle, sphere: &MeshHandle, plane: &MeshHandle) -> Scene {
+    let mut scene = Scene::new();
+    let ground = scene.nodes.add_root(
+        "ground",
+        Transform::from_scale(Vec3::new(30.0, 1.0, 30.0)),
+        (),
+    );
+    scene.add(SceneObject {
+        mesh: Rc::clone(plane),
+        material: Rc::new(material(
+            Rc::clone(program),
+            srgb(0.4, 0.4, 0.4, 1.0),
+            Vec4::splat(0.1),
+            4.0,
+        )),
+        transform: ground,
+    });
+
+    for (i, shininess) in [2.0, 8.0, 32.0, 128.0].into_iter().enumerate() {
+        let position = Vec3::new((i as f32).mul_add(2.5, -3.75), 1.0, 0.0);
+        let node = scene.nodes.add_root(
+            "sphere",
+            Transform::new(position, Quat::IDENTITY, Vec3::splat(2.0)),
+            (),
+        );
+        scene.add(SceneObject {
+            mesh: Rc::clone(sphere),
+            material: Rc::new(material(
+                Rc::clone(program),
+                srgb(0.8, 0.3, 0.2, 1.0),
+                Vec4::splat(0.8),
+                shininess,
+            )),
+            transform: node,
+        });
+    }
+    scene
+}
+
+struct App {
+    window: PWindow,
+    gl: OpenGl,
+    programs: [ProgramHandle; 3],
+    model: usize,
+    sphere: MeshHandle,
+    plane: MeshHandle,
+    scene: Scene,
+    camera: Camera,
+    orbit: OrbitCamera,
+    lights: LightBlock,
+    light_buffer: LightBuffer,
+    // degrees around the y axis
+    point_light_angle: f32,
+    paused: bool,
+    wireframe: bool,
+    normals: Option<NormalLines>,
+    // the lights add up past 1, drawn in HDR and tone mapped to the screen
+    hdr: HdrTarget,
+    tone_mapper: ToneMapper,
+    text: TextRenderer,
+    debug_draw: DebugDraw,
+    viewport: Viewport,
+}
+
+impl App {
+    fn point_light_position(&self) -> Vec3 {
+        let (sin, cos) = self.point_light_angle.to_radians().sin_cos();
+        Vec3::new(cos, 0.0, sin) * POINT_LIGHT_RADIUS + Vec3::Y * POINT_LIGHT_HEIGHT
+    }
+
+    fn update_lights(&mut self) {
+        self.lights.clear();
+        self.lights.set_camera_position(self.camera.position());
+        self.lights.push(DirectionalLight {
+            direction: Vec3::new(-1.0, -2.0, -1.0),
+            color: Vec3::ONE,
+            intensity: 0.3,
+        });
+        self.lights.push(PointLight {
+            position: self.point_light_position(),
+            color: srgb_rgb(POINT_LIGHT_COLOR),
+            intensity: 3.0,
+            range: 10.0,
+        });
+        self.lights.push(SpotLight {
+            position: SPOT_LIGHT_POSITION,
+            direction: -SPOT_LIGHT_POSITION,
+            color: srgb_rgb(SPOT_LIGHT_COLOR),
+            intensity: 4.0,
+            range: 15.0,
+            inner_angle: 15.0,
+            outer_angle: 25.0,
+        });
+        self.light_buffer.update(&self.lights).unwrap();
+    }
+
+    fn set_model(&mut self, model: usize) {
+        self.model = model;
+        self.scene = build_scene(&self.programs[model], &self.sphere, &self.plane);
+    }
+
+    fn draw_overlay(&mut self) {
+        let help = format!(
+            "{:?} shading\n1 Gouraud  2 Phong  3 Blinn\nSpace {} the light\n\
+            T {:?} tone mapping\nUp/Down exposure {:.2}\n\
+            F wireframe  N normals {:?}",
+            MODELS[self.model],
+            if self.paused { "resumes" } else { "pauses" },
+            self.tone_mapper.tone_mapping,
+            self.tone_mapper.exposure,
+            self.normals,
+        );
+        self.text.queue(&help, Vec2::new(8.0, 8.0), Vec4::ONE);
+        let size = self.viewport.size();
+        self.text.draw(&mut self.gl, size.x as i32, size.y as i32);
+    }
+}
+
+impl Application for App {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
+        let mut gl = OpenGl::new(&mut window);
+
+        let loader = MeshLoader::default();
+        let sphere = loader.load("examples/lighting/meshes/UnitSphere.xml");
+        let plane = loader.load("examples/lighting/meshes/UnitPlane.xml");
+
+        let programs = MODELS.map(|model| Rc::new(model.program(LIGHTS_BINDING_INDEX).unwrap()));
+        let light_buffer = LightBuffer::new(LIGHTS_BINDING_INDEX);
+
+        gl.enable(Capability::CullFace);
+        gl.cull_face(CullMode::Back);
+        gl.front_face(FrontFace::CW);
+
+        gl.enable(Capability::DepthTest);
+        gl.set_depth_mask(true);
+        gl.depth_func(DepthFunc::LessEqual);
+        gl.depth_range(0.0, 1.0);
+
+        let shared = |mesh: PendingMesh| {
+            Rc::new(RefCell::new(
+                Mesh::upload(&gl, mesh.wait().unwrap()).unwrap(),
+            ))
+        };
+        let sphere = shared(sphere);
+        let plane = shared(plane);
+        let model = 1;
+        let scene = build_scene(&programs[model], &sphere, &plane);
+
+        let hdr = HdrTarget::new(1, 1).unwrap();
+        let tone_mapper = ToneMapper::new().unwrap();
+        let text = TextRenderer::new(&mut gl).unwrap();
+        let debug_draw = DebugDraw::new(&mut gl).unwrap();
+
+        Ok(Self {
+            window,
+            gl,
+            programs,
+            model,
+            sphere,
+            plane,
+            scene,
+            camera: Camera::new(60.0, 0.1, 100.0),
+            orbit: OrbitCamera {
+                min_pitch: 5.0,
+                min_distance: 3.0,
+                max_distance: 40.0,
+                ..OrbitCamera::new(Vec3::new(0.0, 1.0, 0.0), 90.0, 30.0, 12.0)
+            },
+            lights: LightBlock::new(Vec3::splat(0.1)),
+            light_buffer,
+            point_light_angle: 0.0,
+            paused: false,
+            wireframe: false,
+            normals: None,
+            hdr,
+            tone_mapper,
+            text,
+            debug_draw,
+            viewport: Viewport::new(1, 1),
+        })
+    }
+
+    fn display(&mut self) {
+        self.update_lights();
+
+        // the lights themselves
+        let point_color = srgb_rgb(POINT_LIGHT_COLOR).extend(1.0);
+        self.debug_draw
+            .sphere(self.point_light_position(), 0.1, point_color);
+        let spot_color = srgb_rgb(SPOT_LIGHT_COLOR).extend(1.0);
+        self.debug_draw.sphere(SPOT_LIGHT_POSITION, 0.1, spot_color);
+        if let Some(normals) = self.normals {
+            self.scene
+                .draw_normals(
+                    &mut self.debug_draw,
+                    normals,
+                    0.2,
+                    Vec4::new(0.2, 1.0, 0.2, 1.0),
+                )
+                .unwrap();
+        }
+
+        let view_projection = self.camera.view_projection();
+        let (scene, camera, debug_draw) = (&self.scene, &self.camera, &mut self.debug_draw);
+        let wireframe = self.wireframe;
+        let background = srgb(0.05, 0.05, 0.1, 1.0);
+        self.hdr.draw(&mut self.gl, |gl| {
+            gl.clear_color(background.x, background.y, background.z, background.w);
+            gl.clear_depth(1.0);
+            gl.clear(ClearFlags::Color | ClearFlags::Depth);
+            if wireframe {
+                scene.render_wireframe(gl, camera);
+            } else {
+                scene.render(gl, camera);
+            }
+            debug_draw.flush(gl, view_projection);
+        });
+
+        // the bars around a letterboxed viewport
+        self.gl.clear(ClearFlags::Color);
+        self.tone_mapper
+            .draw(&mut self.gl, &self.hdr, &self.viewport);
+        self.draw_overlay();
+    }
+
+    fn update(&mut self, dt: f32, input: &Input) {
+        self.camera.update(&mut self.orbit, dt, input);
+
+        for (key, model) in [(Key::Num1, 0), (Key::Num2, 1), (Key::Num3, 2)] {
+            if input.just_pressed(key) && model != self.model {
+                self.set_model(model);
+            }
+        }
+        if input.just_pressed(Key::Space) {
+            self.paused = !self.paused;
+        }
+        if input.just_pressed(Key::F) {
+            self.wireframe = !self.wireframe;
+        }
+        if input.just_pressed(Key::N) {
+            self.normals = NormalLines::next(self.normals);
+        }
+        if input.just_pressed(Key::T) {
+            self.tone_mapper.tone_mapping = self.tone_mapper.tone_mapping.next();
+        }
+        // doubles every second the key is held
+        let exposure_axis = f32::from(u8::from(input.pressed(Key::Up)))
+            - f32::from(u8::from(input.pressed(Key::Down)));
+        self.tone_mapper.exposure *= (exposure_axis * dt).exp2();
+        if !self.paused {
+            self.point_light_angle = POINT_LIGHT_SPEED.mul_add(dt, self.point_light_angle) % 360.0;
+        }
+    }
+
+    fn reshape(&mut self, viewport: &Viewport) {
+        self.camera.reshape(viewport);
+        let size = viewport.size();
+        self.hdr.resize(size.x as i32, size.y as i32);
+        viewport.apply(&mut self.gl);
+        self.viewport = *viewport;
+    }
+
+    fn window(&self) -> &PWindow {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    run_app_with_config::<App>(&AppConfig {
+        title: "Lighting".to_owned(),
+        srgb: true,
+        ..AppConfig::default()
+    })
+}
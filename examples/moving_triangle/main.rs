@@ -5,11 +5,13 @@ use std::ffi::CString;
 
 use gl::types::GLsizei;
 use glfw::{Action, Key, Modifiers, PWindow};
-use opengl_rend::app::{run_app, Application};
+use opengl_rend::app::{run_app, AppError, Application};
 use opengl_rend::buffer::{Target, Usage};
+use opengl_rend::input::Input;
 use opengl_rend::opengl::{ClearFlags, Primitive};
 use opengl_rend::program::{GLLocation, Shader, ShaderType};
 use opengl_rend::vertex_attributes::{DataType, VertexAttribute};
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{
     buffer::Buffer, opengl::OpenGl, program::Program, vertex_attributes::VertexArrayObject,
 };
@@ -21,6 +23,7 @@ struct App {
     vertex_array_object: VertexArrayObject,
     vertex_buffer: Buffer<f32>,
     elapsed_time_location: GLLocation,
+    elapsed_time: f32,
 }
 
 #[rustfmt::skip]
@@ -37,8 +40,7 @@ impl App {
     fn _compute_position_offsets(&self, loop_duration: f32) -> (f32, f32) {
         let scale = TAU / loop_duration;
 
-        let elapsed_time = self.window.glfw.get_time() as f32;
-        let loop_time = elapsed_time % loop_duration;
+        let loop_time = self.elapsed_time % loop_duration;
         let (x_offset, y_offset) = (loop_time * scale).sin_cos();
         (x_offset * 0.5, y_offset * 0.5)
     }
@@ -51,20 +53,20 @@ impl App {
             }
         }
         self.vertex_buffer.bind();
-        self.vertex_buffer.update_data(&vertices, 0);
+        self.vertex_buffer.update_data(&vertices, 0).unwrap();
         self.vertex_buffer.unbind();
     }
 }
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let gl = OpenGl::new(&mut window);
 
         let vert_str = CString::new(include_str!("vert.vert")).unwrap();
         let frag_str = CString::new(include_str!("frag.frag")).unwrap();
         let vert_shader = Shader::new(&vert_str, ShaderType::Vertex).unwrap();
         let frag_shader = Shader::new(&frag_str, ShaderType::Fragment).unwrap();
-        let mut program = Program::new(&[vert_shader, frag_shader]).unwrap();
+        let program = Program::new(&[vert_shader, frag_shader]).unwrap();
 
         let mut vertex_buffer = Buffer::new(Target::ArrayBuffer);
         vertex_buffer.bind();
@@ -82,19 +84,22 @@ impl Application for App {
         let loop_duration_location = program.get_uniform_location(c"loopDuration").unwrap();
         let frag_loop_duration_location =
             program.get_uniform_location(c"fragLoopDuration").unwrap();
-        program.set_used();
-        program.set_uniform(loop_duration_location, 5.0);
-        program.set_uniform(frag_loop_duration_location, 10.0);
-        program.set_unused();
+        program.set_uniform_dsa(loop_duration_location, 5.0);
+        program.set_uniform_dsa(frag_loop_duration_location, 10.0);
         let elapsed_time_location = program.get_uniform_location(c"time").unwrap();
-        Self {
+        Ok(Self {
             window,
             gl,
             program,
             vertex_array_object,
             vertex_buffer,
             elapsed_time_location,
-        }
+            elapsed_time: 0.0,
+        })
+    }
+
+    fn update(&mut self, dt: f32, _input: &Input) {
+        self.elapsed_time += dt;
     }
 
     fn display(&mut self) {
@@ -102,8 +107,8 @@ impl Application for App {
         self.gl.clear(ClearFlags::Color);
 
         self.program.set_used();
-        let time = self.window.glfw.get_time() as f32;
-        self.program.set_uniform(self.elapsed_time_location, time);
+        self.program
+            .set_uniform(self.elapsed_time_location, self.elapsed_time);
 
         self.vertex_buffer.bind();
         self.vertex_array_object.bind();
@@ -116,8 +121,8 @@ impl Application for App {
 
     fn keyboard(&mut self, _key: Key, _action: Action, _modifier: Modifiers) {}
 
-    fn reshape(&mut self, width: i32, height: i32) {
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+    fn reshape(&mut self, viewport: &Viewport) {
+        viewport.apply(&mut self.gl);
     }
 
     fn window(&self) -> &PWindow {
@@ -129,6 +134,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
@@ -3,13 +3,14 @@ use std::ffi::CString;
 
 use gl::types::GLsizei;
 use glfw::{Action, Key, Modifiers, PWindow};
-use opengl_rend::app::{run_app, Application};
+use opengl_rend::app::{run_app, AppError, Application};
 use opengl_rend::buffer::{Target, Usage};
 use opengl_rend::opengl::{
     Capability, ClearFlags, CullMode, DepthFunc, FrontFace, IndexSize, Primitive,
 };
 use opengl_rend::program::{GLLocation, Shader, ShaderType};
 use opengl_rend::vertex_attributes::{DataType, VertexAttribute};
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{
     buffer::Buffer, opengl::OpenGl, program::Program, vertex_attributes::VertexArrayObject,
 };
@@ -158,7 +159,7 @@ const INDEX_DATA: [u32;24] =[
 ];
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let mut gl = OpenGl::new(&mut window);
 
         // initialize program
@@ -166,7 +167,7 @@ impl Application for App {
         let frag_str = CString::new(include_str!("frag.frag")).unwrap();
         let vert_shader = Shader::new(&vert_str, ShaderType::Vertex).unwrap();
         let frag_shader = Shader::new(&frag_str, ShaderType::Fragment).unwrap();
-        let mut program = Program::new(&[vert_shader, frag_shader]).unwrap();
+        let program = Program::new(&[vert_shader, frag_shader]).unwrap();
 
         // initialize vertex buffer
         let mut vertex_buffer = Buffer::new(Target::ArrayBuffer);
@@ -219,11 +220,9 @@ impl Application for App {
         let perspective_matrix_location =
             program.get_uniform_location(c"perspectiveMatrix").unwrap();
 
-        program.set_used();
-        program.set_uniform(perspective_matrix_location, matrix);
-        program.set_unused();
+        program.set_uniform_dsa(perspective_matrix_location, matrix);
 
-        Self {
+        Ok(Self {
             gl,
             program,
             vertex_array_object: vertex_buffer_object,
@@ -234,7 +233,7 @@ impl Application for App {
             perspective_matrix_location,
             perspective_matrix: matrix,
             depth_clamping: false,
-        }
+        })
     }
 
     fn display(&mut self) {
@@ -279,18 +278,16 @@ impl Application for App {
         }
     }
 
-    fn reshape(&mut self, width: i32, height: i32) {
+    fn reshape(&mut self, viewport: &Viewport) {
         let frustum_scale = 1.0;
 
-        self.perspective_matrix[0] = frustum_scale / (width as f32 / height as f32);
+        self.perspective_matrix[0] = frustum_scale / viewport.aspect_ratio();
         self.perspective_matrix[5] = frustum_scale;
 
-        self.program.set_used();
         self.program
-            .set_uniform(self.perspective_matrix_location, self.perspective_matrix);
-        self.program.set_unused();
+            .set_uniform_dsa(self.perspective_matrix_location, self.perspective_matrix);
 
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+        viewport.apply(&mut self.gl);
     }
 
     fn window(&self) -> &PWindow {
@@ -302,6 +299,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
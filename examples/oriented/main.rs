@@ -1,14 +1,14 @@
 #![forbid(unsafe_code)]
 use std::ffi::CString;
 
-use gl::types::GLsizei;
 use glam::{Mat4, Vec3, Vec4};
 use glfw::{Action, Key, Modifiers, PWindow};
-use opengl_rend::app::{run_app, Application};
+use opengl_rend::app::{run_app, AppError, Application};
 use opengl_rend::matrix_stack::{MatrixStack, PushStack};
 use opengl_rend::mesh::Mesh;
 use opengl_rend::opengl::{Capability, ClearFlags, CullMode, DepthFunc, FrontFace};
 use opengl_rend::program::{GLLocation, Shader, ShaderType};
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{opengl::OpenGl, program::Program};
 
 struct App {
@@ -32,22 +32,22 @@ impl App {
         if !self.draw_gimbals {
             return;
         }
-        let push = PushStack::new(stack);
+        let mut push = PushStack::new(stack);
         match axis {
             GimbalAxis::X => {}
             GimbalAxis::Y => {
-                push.stack.rotate_z(90.0);
-                push.stack.rotate_x(90.0);
+                push.rotate_z(90.0);
+                push.rotate_x(90.0);
             }
             GimbalAxis::Z => {
-                push.stack.rotate_y(90.0);
-                push.stack.rotate_x(90.0);
+                push.rotate_y(90.0);
+                push.rotate_x(90.0);
             }
         }
         self.program.set_used();
         self.program.set_uniform(self.base_color_uniform, color);
         self.program
-            .set_uniform(self.model_to_camera_uniform, push.stack.top());
+            .set_uniform(self.model_to_camera_uniform, push.top());
         match axis {
             GimbalAxis::X => self.large_gimbal.render(&mut self.gl),
             GimbalAxis::Y => self.medium_gimbal.render(&mut self.gl),
@@ -65,13 +65,13 @@ enum GimbalAxis {
 }
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let mut gl = OpenGl::new(&mut window);
 
         // initialize programs
         let vertex = CString::new(include_str!("pos_color_local_transform.vert")).unwrap();
         let fragment = CString::new(include_str!("color_mult_uniform.frag")).unwrap();
-        let mut program = Program::new(&[
+        let program = Program::new(&[
             Shader::new(&vertex, ShaderType::Vertex).unwrap(),
             Shader::new(&fragment, ShaderType::Fragment).unwrap(),
         ])
@@ -99,7 +99,7 @@ impl Application for App {
         let model_to_camera_uniform = program.get_uniform_location(c"modelToCamera").unwrap();
         let base_color_uniform = program.get_uniform_location(c"baseColor").unwrap();
 
-        Self {
+        Ok(Self {
             window,
             gl,
             program,
@@ -113,7 +113,7 @@ impl Application for App {
             ship_mesh,
             gimbal_angles: Vec3::ZERO,
             draw_gimbals: true,
-        }
+        })
     }
 
     fn display(&mut self) {
@@ -160,22 +160,16 @@ impl Application for App {
         }
     }
 
-    fn reshape(&mut self, width: i32, height: i32) {
+    fn reshape(&mut self, viewport: &Viewport) {
         const FOV: f32 = 20.0;
         const Z_NEAR: f32 = 1.0;
         const Z_FAR: f32 = 1500.0;
-        let matrix = Mat4::perspective_rh_gl(
-            f32::to_radians(FOV),
-            width as f32 / height as f32,
-            Z_NEAR,
-            Z_FAR,
-        );
-        self.program.set_used();
+        let matrix =
+            Mat4::perspective_rh_gl(f32::to_radians(FOV), viewport.aspect_ratio(), Z_NEAR, Z_FAR);
         self.program
-            .set_uniform(self.camera_to_clip_uniform, matrix);
-        self.program.set_unused();
+            .set_uniform_dsa(self.camera_to_clip_uniform, matrix);
 
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+        viewport.apply(&mut self.gl);
     }
 
     fn window(&self) -> &PWindow {
@@ -187,6 +181,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
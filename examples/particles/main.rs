@@ -0,0 +1,157 @@
+#![forbid(unsafe_code)]
+use glam::{Vec2, Vec3, Vec4};
+use glfw::{Key, PWindow};
+use opengl_rend::app::{
+    run_app_with_config, AppConfig, AppError, Application, ContextInfo, FrameStats,
+};
+use opengl_rend::camera::{Camera, OrbitCamera};
+use opengl_rend::debug_draw::DebugDraw;
+use opengl_rend::input::Input;
+use opengl_rend::opengl::{Capability, ClearFlags, DepthFunc, OpenGl};
+use opengl_rend::particles::{ParticleSystem, ParticleUpdate};
+use opengl_rend::text::TextRenderer;
+use opengl_rend::viewport::Viewport;
+
+const MIN_PARTICLES: usize = 1 << 10;
+const MAX_PARTICLES: usize = 1 << 22;
+const EMITTER_RADIUS: f32 = 2.0;
+
+struct App {
+    window: PWindow,
+    gl: OpenGl,
+    has_compute: bool,
+    method: ParticleUpdate,
+    particles: ParticleSystem,
+    camera: Camera,
+    orbit: OrbitCamera,
+    text: TextRenderer,
+    debug_draw: DebugDraw,
+    viewport: Viewport,
+    fps: f32,
+    // seconds since the start, the emitter goes around in a circle
+    time: f32,
+}
+
+impl App {
+    /// Replaces the system, keeping the emitter of the current one
+    fn rebuild(&mut self, count: usize) {
+        let mut particles = ParticleSystem::new(count, self.method).unwrap();
+        particles.emitter = self.particles.emitter;
+        self.particles = particles;
+    }
+
+    fn draw_overlay(&mut self) {
+        let help = format!(
+            "{} particles, {:?} update\n{:.0} fps\nUp/Down doubles/halves the particles{}",
+            self.particles.count(),
+            self.method,
+            self.fps,
+            if self.has_compute {
+                "\nM switches the update"
+            } else {
+                ""
+            },
+        );
+        self.text.queue(&help, Vec2::new(8.0, 8.0), Vec4::ONE);
+        let size = self.viewport.size();
+        self.text.draw(&mut self.gl, size.x as i32, size.y as i32);
+    }
+}
+
+impl Application for App {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
+        let has_compute = ContextInfo::from_window(&window).has_compute();
+        let mut gl = OpenGl::new(&mut window);
+
+        gl.enable(Capability::DepthTest);
+        gl.set_depth_mask(true);
+        gl.depth_func(DepthFunc::LessEqual);
+
+        let method = ParticleUpdate::TransformFeedback;
+        let mut particles = ParticleSystem::new(1 << 16, method).unwrap();
+        particles.emitter.speed = 8.0;
+        let text = TextRenderer::new(&mut gl).unwrap();
+        let debug_draw = DebugDraw::new(&mut gl).unwrap();
+
+        Ok(Self {
+            window,
+            gl,
+            has_compute,
+            method,
+            particles,
+            camera: Camera::new(60.0, 0.1, 200.0),
+            orbit: OrbitCamera {
+                min_pitch: 5.0,
+                ..OrbitCamera::new(Vec3::new(0.0, 2.0, 0.0), 90.0, 20.0, 15.0)
+            },
+            text,
+            debug_draw,
+            viewport: Viewport::new(1, 1),
+            fps: 0.0,
+            time: 0.0,
+        })
+    }
+
+    fn frame_stats(&mut self, stats: &FrameStats) {
+        // smoothed, the count changes the frame rate a lot
+        self.fps = self.fps.mul_add(0.95, stats.fps * 0.05);
+    }
+
+    fn update(&mut self, dt: f32, input: &Input) {
+        self.camera.update(&mut self.orbit, dt, input);
+        self.time += dt;
+        let (sin, cos) = self.time.sin_cos();
+        self.particles.emitter.position = Vec3::new(cos, 0.0, sin) * EMITTER_RADIUS;
+        self.particles.update(&mut self.gl, dt);
+
+        let count = self.particles.count();
+        if input.just_pressed(Key::Up) && count < MAX_PARTICLES {
+            self.rebuild(count * 2);
+        }
+        if input.just_pressed(Key::Down) && count > MIN_PARTICLES {
+            self.rebuild(count / 2);
+        }
+        if input.just_pressed(Key::M) && self.has_compute {
+            self.method = match self.method {
+                ParticleUpdate::TransformFeedback => ParticleUpdate::Compute,
+                ParticleUpdate::Compute => ParticleUpdate::TransformFeedback,
+            };
+            self.rebuild(count);
+        }
+    }
+
+    fn display(&mut self) {
+        self.gl.clear_color(0.02, 0.02, 0.05, 1.0);
+        self.gl.clear_depth(1.0);
+        self.gl.clear(ClearFlags::Color | ClearFlags::Depth);
+
+        self.debug_draw
+            .grid(Vec3::ZERO, 20.0, 20, Vec4::new(0.3, 0.3, 0.3, 1.0));
+        self.debug_draw
+            .flush(&mut self.gl, self.camera.view_projection());
+        self.particles.draw(&mut self.gl, &self.camera);
+        self.draw_overlay();
+    }
+
+    fn reshape(&mut self, viewport: &Viewport) {
+        self.camera.reshape(viewport);
+        viewport.apply(&mut self.gl);
+        self.viewport = *viewport;
+    }
+
+    fn window(&self) -> &PWindow {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    run_app_with_config::<App>(&AppConfig {
+        title: "Particles".to_owned(),
+        vsync: false,
+        ..AppConfig::default()
+    })
+}
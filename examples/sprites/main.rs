@@ -0,0 +1,192 @@
+#![forbid(unsafe_code)]
+use glam::{Vec2, Vec4};
+use glfw::{Key, PWindow};
+use opengl_rend::app::{run_app_with_config, AppConfig, AppError, Application};
+use opengl_rend::input::Input;
+use opengl_rend::opengl::{ClearFlags, OpenGl};
+use opengl_rend::sprite::{screen_projection, Sprite, SpriteBatch, TextureAtlas};
+use opengl_rend::text::TextRenderer;
+use opengl_rend::viewport::Viewport;
+
+/// Pixels on each side of a cell of the atlas
+const CELL: u32 = 16;
+/// Shapes in the atlas, side by side
+const SHAPES: u32 = 4;
+const SPRITE_SIZE: f32 = 32.0;
+const SHADOW_OFFSET: Vec2 = Vec2::new(4.0, 4.0);
+
+/// A circle, a diamond, a ring and a square, white so sprites can tint them
+fn atlas_pixels() -> Vec<u8> {
+    let width = CELL * SHAPES;
+    let mut pixels = vec![0; (width * CELL * 4) as usize];
+    for y in 0..CELL {
+        for x in 0..width {
+            let cell = x / CELL;
+            // from -1 to 1 across the cell
+            let p = (Vec2::new((x % CELL) as f32, y as f32) + 0.5) / CELL as f32 * 2.0 - 1.0;
+            let inside = match cell {
+                0 => p.length() < 1.0,
+                1 => p.x.abs() + p.y.abs() < 1.0,
+                2 => (0.6..1.0).contains(&p.length()),
+                _ => p.x.abs() < 0.8 && p.y.abs() < 0.8,
+            };
+            if inside {
+                let i = ((y * width + x) * 4) as usize;
+                pixels[i..i + 4].fill(u8::MAX);
+            }
+        }
+    }
+    pixels
+}
+
+/// Pseudo random numbers from 0 to 1, good enough to scatter sprites
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+struct Bouncer {
+    position: Vec2,
+    velocity: Vec2,
+    rotation: f32,
+    spin: f32,
+    shape: u32,
+    color: Vec4,
+}
+
+struct App {
+    window: PWindow,
+    gl: OpenGl,
+    atlas: TextureAtlas,
+    batch: SpriteBatch,
+    text: TextRenderer,
+    bouncers: Vec<Bouncer>,
+    random: Random,
+    viewport: Viewport,
+}
+
+impl App {
+    fn spawn(&mut self, count: usize) {
+        let size = self.viewport.size();
+        for _ in 0..count {
+            let random = &mut self.random;
+            let angle = random.next() * std::f32::consts::TAU;
+            self.bouncers.push(Bouncer {
+                position: Vec2::new(random.next(), random.next()) * size,
+                velocity: Vec2::from_angle(angle) * random.next().mul_add(200.0, 50.0),
+                rotation: 0.0,
+                spin: random.next().mul_add(4.0, -2.0),
+                shape: (random.next() * SHAPES as f32) as u32 % SHAPES,
+                color: Vec4::new(random.next(), random.next(), random.next(), 1.0),
+            });
+        }
+    }
+}
+
+impl Application for App {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
+        let mut gl = OpenGl::new(&mut window);
+        let atlas = TextureAtlas::from_rgba(CELL * SHAPES, CELL, &atlas_pixels(), false).unwrap();
+        let batch = SpriteBatch::new(&mut gl).unwrap();
+        let text = TextRenderer::new(&mut gl).unwrap();
+        let (width, height) = window.get_framebuffer_size();
+        let mut app = Self {
+            window,
+            gl,
+            atlas,
+            batch,
+            text,
+            bouncers: vec![],
+            random: Random(1),
+            viewport: Viewport::new(width, height),
+        };
+        app.spawn(100);
+        Ok(app)
+    }
+
+    fn update(&mut self, dt: f32, input: &Input) {
+        if input.just_pressed(Key::Space) {
+            self.spawn(100);
+        }
+        if input.just_pressed(Key::Backspace) {
+            self.bouncers.clear();
+        }
+        let size = self.viewport.size();
+        for bouncer in &mut self.bouncers {
+            bouncer.position += bouncer.velocity * dt;
+            bouncer.rotation += bouncer.spin * dt;
+            // bounce off the edges of the window
+            for axis in 0..2 {
+                if bouncer.position[axis] < 0.0 || bouncer.position[axis] > size[axis] {
+                    bouncer.velocity[axis] = -bouncer.velocity[axis];
+                    bouncer.position[axis] = bouncer.position[axis].clamp(0.0, size[axis]);
+                }
+            }
+        }
+    }
+
+    fn display(&mut self) {
+        self.gl.clear_color(0.2, 0.25, 0.3, 1.0);
+        self.gl.clear(ClearFlags::Color);
+
+        let sprite = |bouncer: &Bouncer| {
+            Sprite::new(bouncer.position, Vec2::splat(SPRITE_SIZE))
+                .with_rotation(bouncer.rotation, Vec2::splat(0.5))
+        };
+        // all the shadows then all the shapes, two draw calls whatever the count
+        for bouncer in &self.bouncers {
+            let mut shadow = sprite(bouncer).with_uv(self.atlas.cell(SHAPES, 1, bouncer.shape));
+            shadow.position += SHADOW_OFFSET;
+            self.batch.draw(
+                &self.atlas,
+                &shadow.with_color(Vec4::new(0.0, 0.0, 0.0, 0.4)),
+            );
+        }
+        for bouncer in &self.bouncers {
+            let shape = sprite(bouncer)
+                .with_uv(self.atlas.cell(SHAPES, 1, bouncer.shape))
+                .with_color(bouncer.color);
+            self.batch.draw(&self.atlas, &shape);
+        }
+
+        let help = format!(
+            "{} sprites in {} draw calls\nSpace adds 100, Backspace clears",
+            self.bouncers.len(),
+            self.batch.draw_calls(),
+        );
+        let panel_size = self.text.measure(&help) + Vec2::splat(16.0);
+        self.batch.draw_rect(
+            &Sprite::new(Vec2::ZERO, panel_size).with_color(Vec4::new(0.0, 0.0, 0.0, 0.6)),
+        );
+
+        let size = self.viewport.size();
+        self.batch.flush(&mut self.gl, screen_projection(size));
+        self.text.queue(&help, Vec2::splat(8.0), Vec4::ONE);
+        self.text.draw(&mut self.gl, size.x as i32, size.y as i32);
+    }
+
+    fn reshape(&mut self, viewport: &Viewport) {
+        viewport.apply(&mut self.gl);
+        self.viewport = *viewport;
+    }
+
+    fn window(&self) -> &PWindow {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+}
+
+fn main() -> Result<(), AppError> {
+    run_app_with_config::<App>(&AppConfig {
+        title: "Sprites".to_owned(),
+        size: (800, 600),
+        ..AppConfig::default()
+    })
+}
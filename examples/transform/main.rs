@@ -2,15 +2,20 @@
 use std::ffi::CString;
 
 use gl::types::GLsizei;
-use glam::{Mat4, Vec3};
+use glam::{EulerRot, Quat, Vec3};
 use glfw::{Action, Key, Modifiers, PWindow};
-use opengl_rend::app::{run_app, Application};
+use opengl_rend::app::{run_app, AppError, Application};
 use opengl_rend::buffer::{Target, Usage};
+use opengl_rend::input::Input;
+use opengl_rend::matrix_stack::MatrixStack;
+use opengl_rend::nodetree::{Channel, NodeId, NodeTree};
 use opengl_rend::opengl::{
     Capability, ClearFlags, CullMode, DepthFunc, FrontFace, IndexSize, Primitive,
 };
 use opengl_rend::program::{GLLocation, Shader, ShaderType};
+use opengl_rend::transform::Transform;
 use opengl_rend::vertex_attributes::{DataType, VertexAttribute};
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{
     buffer::Buffer, opengl::OpenGl, program::Program, vertex_attributes::VertexArrayObject,
 };
@@ -26,7 +31,8 @@ struct App {
     model_to_camera_matrix_location: GLLocation,
     perspective_matrix: [f32; 16],
     _depth_clamping: bool,
-    hierarchy: Hierarchy,
+    arm: NodeTree<Option<Transform>>,
+    joints: Joints,
 }
 
 const GREEN_COLOR: [f32; 4] = [0.75, 0.75, 1.0, 1.0];
@@ -135,362 +141,176 @@ fn calculate_frustum_scale(fov_degrees: f32) -> f32 {
     (fov_radians * 0.5).tan().recip()
 }
 
-struct MatrixStack {
-    current_matrix: Mat4,
-    stack: Vec<Mat4>,
+/// Rotation around z, then x, then y, in degrees
+fn euler(degrees: Vec3) -> Quat {
+    Quat::from_euler(
+        EulerRot::ZXY,
+        degrees.z.to_radians(),
+        degrees.x.to_radians(),
+        degrees.y.to_radians(),
+    )
 }
 
-impl MatrixStack {
-    const fn new() -> Self {
-        Self {
-            current_matrix: Mat4::IDENTITY,
-            stack: vec![],
-        }
-    }
-    const fn top(&self) -> glam::Mat4 {
-        self.current_matrix
-    }
-    fn push(&mut self) {
-        self.stack.push(self.current_matrix);
-    }
-    fn pop(&mut self) {
-        if let Some(new_matrix) = self.stack.pop() {
-            self.current_matrix = new_matrix;
-        }
-    }
-    fn rotate_x(&mut self, angle: f32) {
-        self.current_matrix *= Mat4::from_rotation_x(angle.to_radians());
-    }
-    fn rotate_y(&mut self, angle: f32) {
-        self.current_matrix *= Mat4::from_rotation_y(angle.to_radians());
-    }
-    fn rotate_z(&mut self, angle: f32) {
-        self.current_matrix *= Mat4::from_rotation_z(angle.to_radians());
-    }
-    fn scale(&mut self, scale: Vec3) {
-        self.current_matrix *= Mat4::from_scale(scale);
-    }
-    fn translate(&mut self, translation: Vec3) {
-        self.current_matrix *= Mat4::from_translation(translation);
-    }
+fn transform(position: Vec3, rotation: Vec3, scale: Vec3) -> Transform {
+    Transform::new(position, euler(rotation), scale)
 }
 
-struct Hierarchy {
-    stack: MatrixStack,
-    base_pos: Vec3,
-    base_ang: f32,
-    base_scale_z: f32,
-
-    base_left_pos: Vec3,
-    base_right_pos: Vec3,
-
-    upper_arm_ang: f32,
-    upper_arm_size: f32,
-    lower_arm_pos: Vec3,
-    lower_arm_ang: f32,
-    lower_arm_len: f32,
-    lower_arm_width: f32,
-
-    wrist_pos: Vec3,
-    wrist_roll_ang: f32,
-    wrist_pitch_ang: f32,
-    wrist_len: f32,
-    wrist_width: f32,
-
-    left_finger_pos: Vec3,
-    right_finger_pos: Vec3,
-    finger_open_ang: f32,
-    finger_len: f32,
-    finger_width: f32,
-    lower_finger_ang: f32,
+/// Places the cube mesh, spanning -1 to 1, as a box of `size` centered at `position`
+fn cube(position: Vec3, size: Vec3) -> Transform {
+    transform(position, Vec3::ZERO, size * 0.5)
 }
 
-struct DrawCtx<'a> {
-    gl: &'a mut OpenGl,
-    program: &'a mut Program,
-    matrix_location: GLLocation,
+/// The nodes of the arm moved from the keyboard
+struct Joints {
+    base: NodeId,
+    upper_arm: NodeId,
+    lower_arm: NodeId,
+    wrist_pitch: NodeId,
+    wrist_roll: NodeId,
+    left_finger: NodeId,
+    right_finger: NodeId,
 }
 
-impl<'a> DrawCtx<'a> {
-    fn new(gl: &'a mut OpenGl, program: &'a mut Program, matrix_location: GLLocation) -> Self {
-        Self {
-            gl,
-            program,
-            matrix_location,
-        }
-    }
-}
-#[derive(PartialEq, Clone, Copy)]
-struct Transform {
-    position: Vec3,
-    rotation: Vec3,
-    scale: Vec3,
-}
-
-impl Transform {
-    const fn new(position: Vec3, rotation: Vec3, scale: Vec3) -> Self {
-        Self {
-            position,
-            rotation,
-            scale,
-        }
-    }
-}
-
-impl Hierarchy {
+impl Joints {
     const STANDARD_ANGLE_INCREMENT: f32 = 11.25;
     const SMALL_ANGLE_INCREMENT: f32 = 9.0;
+    /// Degrees per second of the wrist while it spins
+    const WRIST_ROLL_SPEED: f32 = 90.0;
+}
 
-    const fn new() -> Self {
-        Self {
-            stack: MatrixStack::new(),
-            base_pos: Vec3::new(3.0, -5.0, -40.0),
-            base_ang: -45.0,
-            base_scale_z: 3.0,
-            base_left_pos: Vec3::new(2.0, 0.0, 0.0),
-            base_right_pos: Vec3::new(-2.0, 0.0, 0.0),
-            upper_arm_ang: -50.0,
-            upper_arm_size: 9.0,
-            lower_arm_pos: Vec3::new(0.0, 0.0, 8.0),
-            lower_arm_ang: 60.0,
-            lower_arm_len: 5.0,
-            lower_arm_width: 1.5,
-            wrist_pos: Vec3::new(0.0, 0.0, 5.0),
-            wrist_roll_ang: 0.0,
-            wrist_pitch_ang: 90.0,
-            wrist_len: 2.0,
-            wrist_width: 2.0,
-            left_finger_pos: Vec3::new(1.0, 0.0, 1.0),
-            right_finger_pos: Vec3::new(-1.0, 0.0, 1.0),
-            finger_open_ang: 70.0,
-            finger_len: 2.0,
-            finger_width: 0.5,
-            lower_finger_ang: 45.0,
-        }
-    }
-
-    fn increment_base_ang(&mut self, positive: bool) {
-        if positive {
-            self.base_ang += Self::STANDARD_ANGLE_INCREMENT;
-        } else {
-            self.base_ang -= Self::STANDARD_ANGLE_INCREMENT;
-        }
-        self.base_ang %= 360.0;
-    }
-    fn increment_upper_arm_ang(&mut self, positive: bool) {
-        if positive {
-            self.upper_arm_ang += Self::STANDARD_ANGLE_INCREMENT;
-        } else {
-            self.upper_arm_ang -= Self::STANDARD_ANGLE_INCREMENT;
-        }
-        self.upper_arm_ang = self.upper_arm_ang.clamp(-90.0, 0.0);
-    }
-    fn increment_lower_arm_ang(&mut self, positive: bool) {
-        if positive {
-            self.lower_arm_ang += Self::STANDARD_ANGLE_INCREMENT;
-        } else {
-            self.lower_arm_ang -= Self::STANDARD_ANGLE_INCREMENT;
-        }
-        self.lower_arm_ang = self.lower_arm_ang.clamp(0.0, 146.25);
-    }
-    fn increment_wrist_pitch(&mut self, positive: bool) {
-        if positive {
-            self.wrist_pitch_ang += Self::STANDARD_ANGLE_INCREMENT;
-        } else {
-            self.wrist_pitch_ang -= Self::STANDARD_ANGLE_INCREMENT;
-        }
-        self.wrist_pitch_ang %= 360.0;
-    }
-    fn increment_wrist_roll(&mut self, positive: bool) {
-        if positive {
-            self.wrist_roll_ang += Self::STANDARD_ANGLE_INCREMENT;
-        } else {
-            self.wrist_roll_ang -= Self::STANDARD_ANGLE_INCREMENT;
-        }
-        self.wrist_roll_ang %= 360.0;
-    }
-
-    fn increment_finger_ang(&mut self, positive: bool) {
-        if positive {
-            self.finger_open_ang += Self::SMALL_ANGLE_INCREMENT;
-        } else {
-            self.finger_open_ang -= Self::SMALL_ANGLE_INCREMENT;
-        }
-        self.finger_open_ang = self.finger_open_ang.clamp(9.0, 180.0);
-    }
-
-    fn set_transform(&mut self, transform: Transform) {
-        if transform.position != Vec3::ZERO {
-            self.stack.translate(transform.position);
-        }
-        if transform.rotation.z != 0.0 {
-            self.stack.rotate_z(transform.rotation.z);
-        }
-        if transform.rotation.x != 0.0 {
-            self.stack.rotate_x(transform.rotation.x);
-        }
-        if transform.rotation.y != 0.0 {
-            self.stack.rotate_y(transform.rotation.y);
-        }
-        if transform.scale != Vec3::ZERO {
-            self.stack.scale(transform.scale);
-        }
-    }
-
-    fn draw_cube(&mut self, ctx: &mut DrawCtx<'_>, transform: Transform) {
-        self.stack.push();
-        self.set_transform(transform);
-        ctx.program
-            .set_uniform(ctx.matrix_location, self.stack.top());
-        ctx.gl.draw_elements(
-            Primitive::Triangles,
-            INDEX_DATA.len() as GLsizei,
-            IndexSize::UnsignedInt,
-            0,
-        );
-        self.stack.pop();
-    }
-
-    fn draw(&mut self, gl: &mut OpenGl, program: &mut Program, matrix_location: GLLocation) {
-        self.stack = MatrixStack::new();
-        let mut ctx = DrawCtx::new(gl, program, matrix_location);
-        let base = Transform::new(self.base_pos, Vec3::Y * self.base_ang, Vec3::ONE);
-        self.set_transform(base);
-
-        let base_scale = Vec3::new(1.0, 1.0, self.base_scale_z);
-        let left_base = Transform::new(self.base_left_pos, Vec3::ZERO, base_scale);
-        self.draw_cube(&mut ctx, left_base);
-
-        let right_base = Transform::new(self.base_right_pos, Vec3::ZERO, base_scale);
-        self.draw_cube(&mut ctx, right_base);
-
-        self.draw_upper_arm(&mut ctx);
-    }
-    fn draw_upper_arm(&mut self, ctx: &mut DrawCtx<'_>) {
-        self.stack.push();
-
-        let upper_arm = Transform::new(Vec3::ZERO, Vec3::X * self.upper_arm_ang, Vec3::ONE);
-        self.set_transform(upper_arm);
-
-        let upper_arm_pos = Vec3::Z * (self.upper_arm_size / 2.0 - 1.0);
-        let upper_arm_scale = Vec3::new(1.0, 1.0, self.upper_arm_size / 2.0);
-
-        let upper_arm = Transform::new(upper_arm_pos, Vec3::ZERO, upper_arm_scale);
-        self.draw_cube(ctx, upper_arm);
-
-        self.draw_lower_arm(ctx);
-        self.stack.pop();
-    }
-
-    fn draw_lower_arm(&mut self, ctx: &mut DrawCtx<'_>) {
-        self.stack.push();
-
-        let lower_arm = Transform::new(self.lower_arm_pos, Vec3::X * self.lower_arm_ang, Vec3::ONE);
-
-        self.set_transform(lower_arm);
-
-        let lower_arm_pos = Vec3::Z * (self.lower_arm_len * 0.5);
-        let lower_arm_scale = Vec3::new(
-            self.lower_arm_width * 0.5,
-            self.lower_arm_width * 0.5,
-            self.lower_arm_len * 0.5,
-        );
-
-        let lower_arm = Transform::new(lower_arm_pos, Vec3::ZERO, lower_arm_scale);
-        self.draw_cube(ctx, lower_arm);
-
-        self.draw_wrist(ctx);
-        self.stack.pop();
-    }
-
-    fn draw_wrist(&mut self, ctx: &mut DrawCtx<'_>) {
-        self.stack.push();
-
-        let wrist = Transform::new(
-            self.wrist_pos,
-            Vec3::new(self.wrist_pitch_ang, 0.0, self.wrist_roll_ang),
-            Vec3::ONE,
-        );
-
-        self.set_transform(wrist);
-        let wrist_scale = Vec3::new(
-            self.wrist_width * 0.5,
-            self.wrist_width * 0.5,
-            self.wrist_len * 0.5,
-        );
-        let wrist = Transform::new(Vec3::ZERO, Vec3::ZERO, wrist_scale);
-        self.draw_cube(ctx, wrist);
-
-        self.draw_fingers(ctx);
-        self.stack.pop();
-    }
-
-    fn draw_fingers(&mut self, ctx: &mut DrawCtx<'_>) {
-        // draw left finger
-        self.stack.push();
+fn add_joint(
+    tree: &mut NodeTree<Option<Transform>>,
+    parent: NodeId,
+    name: &str,
+    joint: Transform,
+    channel: Option<Channel>,
+    cube: Option<Transform>,
+) -> NodeId {
+    let id = tree.add_child(parent, name, joint, cube).unwrap();
+    tree.get_mut(id).unwrap().channel = channel;
+    id
+}
 
-        let left_finger = Transform::new(
-            self.left_finger_pos,
-            Vec3::Y * self.finger_open_ang,
-            Vec3::ONE,
-        );
-        self.set_transform(left_finger);
-        let finger_pos = Vec3::Z * self.finger_len * 0.5;
-        let finger_scale = Vec3::new(
-            self.finger_width * 0.5,
-            self.finger_width * 0.5,
-            self.finger_len * 0.5,
+/// The left and right fingers on `wrist`, returning their upper joints
+fn add_fingers(tree: &mut NodeTree<Option<Transform>>, wrist: NodeId) -> [NodeId; 2] {
+    const FINGER_LEN: f32 = 2.0;
+    const FINGER_WIDTH: f32 = 0.5;
+    const LOWER_FINGER_ANG: f32 = 45.0;
+
+    let finger = cube(
+        Vec3::Z * (FINGER_LEN * 0.5),
+        Vec3::new(FINGER_WIDTH, FINGER_WIDTH, FINGER_LEN),
+    );
+    [("left", 1.0), ("right", -1.0)].map(|(name, side)| {
+        let upper_finger = add_joint(
+            tree,
+            wrist,
+            &format!("{name} finger"),
+            Transform::from_translation(Vec3::new(side, 0.0, 1.0)),
+            Some(Channel::new(Vec3::Y * side, 70.0).with_limits(9.0, 180.0)),
+            Some(finger),
         );
-        let finger = Transform::new(finger_pos, Vec3::ZERO, finger_scale);
-        self.draw_cube(ctx, finger);
-
-        {
-            let lower_finger = Transform::new(
-                Vec3::Z * self.finger_len,
-                Vec3::Y * -self.lower_finger_ang,
+        add_joint(
+            tree,
+            upper_finger,
+            &format!("{name} lower finger"),
+            transform(
+                Vec3::Z * FINGER_LEN,
+                Vec3::Y * (-side * LOWER_FINGER_ANG),
                 Vec3::ONE,
-            );
-            self.stack.push();
-
-            self.set_transform(lower_finger);
-
-            self.draw_cube(ctx, finger);
-
-            self.stack.pop();
-        }
-        self.stack.pop();
-
-        // draw right finger
-        self.stack.push();
-
-        let right_finger = Transform::new(
-            self.right_finger_pos,
-            Vec3::Y * -self.finger_open_ang,
-            Vec3::ONE,
+            ),
+            None,
+            Some(finger),
         );
-        self.set_transform(right_finger);
-
-        self.draw_cube(ctx, finger);
-
-        {
-            self.stack.push();
-            let lower_finger = Transform::new(
-                Vec3::Z * self.finger_len,
-                Vec3::Y * self.lower_finger_ang,
-                Vec3::ONE,
-            );
-            self.set_transform(lower_finger);
-
-            self.draw_cube(ctx, finger);
+        upper_finger
+    })
+}
 
-            self.stack.pop();
-        }
-        self.stack.pop();
-    }
+/// The joints of the arm, each with the box drawn relative to it
+fn arm() -> (NodeTree<Option<Transform>>, Joints) {
+    const UPPER_ARM_SIZE: f32 = 9.0;
+    const LOWER_ARM_LEN: f32 = 5.0;
+    const LOWER_ARM_WIDTH: f32 = 1.5;
+    const WRIST_LEN: f32 = 2.0;
+    const WRIST_WIDTH: f32 = 2.0;
+
+    let mut tree = NodeTree::new();
+    let base = tree.add_root(
+        "base",
+        Transform::from_translation(Vec3::new(3.0, -5.0, -40.0)),
+        None,
+    );
+    tree.get_mut(base).unwrap().channel = Some(Channel::new(Vec3::Y, -45.0));
+    let base_size = Vec3::new(2.0, 2.0, 6.0);
+    let mut add = |parent, name, joint, channel, cube| {
+        add_joint(&mut tree, parent, name, joint, channel, cube)
+    };
+    add(
+        base,
+        "left base",
+        Transform::from_translation(Vec3::X * 2.0),
+        None,
+        Some(cube(Vec3::ZERO, base_size)),
+    );
+    add(
+        base,
+        "right base",
+        Transform::from_translation(Vec3::X * -2.0),
+        None,
+        Some(cube(Vec3::ZERO, base_size)),
+    );
+    let upper_arm = add(
+        base,
+        "upper arm",
+        Transform::IDENTITY,
+        Some(Channel::new(Vec3::X, -50.0).with_limits(-90.0, 0.0)),
+        Some(cube(
+            Vec3::Z * (UPPER_ARM_SIZE / 2.0 - 1.0),
+            Vec3::new(2.0, 2.0, UPPER_ARM_SIZE),
+        )),
+    );
+    let lower_arm = add(
+        upper_arm,
+        "lower arm",
+        Transform::from_translation(Vec3::Z * 8.0),
+        Some(Channel::new(Vec3::X, 60.0).with_limits(0.0, 146.25)),
+        Some(cube(
+            Vec3::Z * (LOWER_ARM_LEN * 0.5),
+            Vec3::new(LOWER_ARM_WIDTH, LOWER_ARM_WIDTH, LOWER_ARM_LEN),
+        )),
+    );
+    let wrist_pitch = add(
+        lower_arm,
+        "wrist pitch",
+        Transform::from_translation(Vec3::Z * 5.0),
+        Some(Channel::new(Vec3::X, 90.0)),
+        None,
+    );
+    let wrist_roll = add(
+        wrist_pitch,
+        "wrist roll",
+        Transform::IDENTITY,
+        Some(Channel::new(Vec3::Z, 0.0)),
+        Some(cube(
+            Vec3::ZERO,
+            Vec3::new(WRIST_WIDTH, WRIST_WIDTH, WRIST_LEN),
+        )),
+    );
+    let [left_finger, right_finger] = add_fingers(&mut tree, wrist_roll);
+    let joints = Joints {
+        base,
+        upper_arm,
+        lower_arm,
+        wrist_pitch,
+        wrist_roll,
+        left_finger,
+        right_finger,
+    };
+    (tree, joints)
 }
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let mut gl = OpenGl::new(&mut window);
 
         // initialize program
@@ -498,7 +318,7 @@ impl Application for App {
         let frag_str = CString::new(include_str!("frag.frag")).unwrap();
         let vert_shader = Shader::new(&vert_str, ShaderType::Vertex).unwrap();
         let frag_shader = Shader::new(&frag_str, ShaderType::Fragment).unwrap();
-        let mut program = Program::new(&[vert_shader, frag_shader]).unwrap();
+        let program = Program::new(&[vert_shader, frag_shader]).unwrap();
 
         // initialize vertex buffer
         let mut vertex_buffer = Buffer::new(Target::ArrayBuffer);
@@ -550,11 +370,10 @@ impl Application for App {
         matrix[14] = (2.0 * z_far * z_near) / (z_near - z_far);
         matrix[11] = -1.0;
 
-        program.set_used();
-        program.set_uniform(camera_to_clip_location, matrix);
-        program.set_unused();
+        program.set_uniform_dsa(camera_to_clip_location, matrix);
 
-        Self {
+        let (arm, joints) = arm();
+        Ok(Self {
             gl,
             program,
             vertex_array_object,
@@ -565,8 +384,9 @@ impl Application for App {
             perspective_matrix: matrix,
             _depth_clamping: false,
             model_to_camera_matrix_location,
-            hierarchy: Hierarchy::new(),
-        }
+            arm,
+            joints,
+        })
     }
 
     fn display(&mut self) {
@@ -575,48 +395,83 @@ impl Application for App {
         self.gl.clear(ClearFlags::Color | ClearFlags::Depth);
         self.program.set_used();
         self.vertex_array_object.bind();
-        self.hierarchy.draw(
-            &mut self.gl,
-            &mut self.program,
-            self.model_to_camera_matrix_location,
-        );
+        let (gl, program) = (&mut self.gl, &mut self.program);
+        self.arm.draw(&mut MatrixStack::new(), &mut |joint, cube| {
+            if let Some(cube) = cube {
+                program.set_uniform(
+                    self.model_to_camera_matrix_location,
+                    *joint * cube.to_matrix(),
+                );
+                gl.draw_elements(
+                    Primitive::Triangles,
+                    INDEX_DATA.len() as GLsizei,
+                    IndexSize::UnsignedInt,
+                    0,
+                );
+            }
+        });
 
         self.vertex_array_object.unbind();
         self.program.set_unused();
     }
 
+    fn update(&mut self, dt: f32, _input: &Input) {
+        self.arm.animate(dt);
+    }
+
     fn keyboard(&mut self, key: Key, action: Action, _modifier: Modifiers) {
-        if action == Action::Press || action == Action::Repeat {
-            match key {
-                Key::A => self.hierarchy.increment_base_ang(true),
-                Key::D => self.hierarchy.increment_base_ang(false),
-                Key::W => self.hierarchy.increment_upper_arm_ang(true),
-                Key::S => self.hierarchy.increment_upper_arm_ang(false),
-                Key::R => self.hierarchy.increment_lower_arm_ang(true),
-                Key::F => self.hierarchy.increment_lower_arm_ang(false),
-                Key::T => self.hierarchy.increment_wrist_pitch(true),
-                Key::G => self.hierarchy.increment_wrist_pitch(false),
-                Key::Z => self.hierarchy.increment_wrist_roll(true),
-                Key::C => self.hierarchy.increment_wrist_roll(false),
-                Key::Q => self.hierarchy.increment_finger_ang(true),
-                Key::E => self.hierarchy.increment_finger_ang(false),
-                _ => {}
+        if action != Action::Press && action != Action::Repeat {
+            return;
+        }
+        let joints = &self.joints;
+        let (nodes, degrees): (&[NodeId], f32) = match key {
+            Key::A => (&[joints.base], Joints::STANDARD_ANGLE_INCREMENT),
+            Key::D => (&[joints.base], -Joints::STANDARD_ANGLE_INCREMENT),
+            Key::W => (&[joints.upper_arm], Joints::STANDARD_ANGLE_INCREMENT),
+            Key::S => (&[joints.upper_arm], -Joints::STANDARD_ANGLE_INCREMENT),
+            Key::R => (&[joints.lower_arm], Joints::STANDARD_ANGLE_INCREMENT),
+            Key::F => (&[joints.lower_arm], -Joints::STANDARD_ANGLE_INCREMENT),
+            Key::T => (&[joints.wrist_pitch], Joints::STANDARD_ANGLE_INCREMENT),
+            Key::G => (&[joints.wrist_pitch], -Joints::STANDARD_ANGLE_INCREMENT),
+            Key::Z => (&[joints.wrist_roll], Joints::STANDARD_ANGLE_INCREMENT),
+            Key::C => (&[joints.wrist_roll], -Joints::STANDARD_ANGLE_INCREMENT),
+            Key::Q => (
+                &[joints.left_finger, joints.right_finger],
+                Joints::SMALL_ANGLE_INCREMENT,
+            ),
+            Key::E => (
+                &[joints.left_finger, joints.right_finger],
+                -Joints::SMALL_ANGLE_INCREMENT,
+            ),
+            Key::Space if action == Action::Press => {
+                if let Some(roll) = self.arm.channel_mut(joints.wrist_roll) {
+                    roll.speed = if roll.speed == 0.0 {
+                        Joints::WRIST_ROLL_SPEED
+                    } else {
+                        0.0
+                    };
+                }
+                return;
+            }
+            _ => return,
+        };
+        for node in nodes {
+            if let Some(channel) = self.arm.channel_mut(*node) {
+                channel.rotate(degrees);
             }
         }
     }
 
-    fn reshape(&mut self, width: i32, height: i32) {
+    fn reshape(&mut self, viewport: &Viewport) {
         let frustum_scale = calculate_frustum_scale(45.0);
 
-        self.perspective_matrix[0] = frustum_scale / (width as f32 / height as f32);
+        self.perspective_matrix[0] = frustum_scale / viewport.aspect_ratio();
         self.perspective_matrix[5] = frustum_scale;
 
-        self.program.set_used();
         self.program
-            .set_uniform(self.camera_to_clip_location, self.perspective_matrix);
-        self.program.set_unused();
+            .set_uniform_dsa(self.camera_to_clip_location, self.perspective_matrix);
 
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+        viewport.apply(&mut self.gl);
     }
 
     fn window(&self) -> &PWindow {
@@ -628,6 +483,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
@@ -2,14 +2,14 @@
 
 use std::ffi::CString;
 
-use gl::types::GLsizei;
 use glfw::PWindow;
 use glfw::{Action, Key, Modifiers};
-use opengl_rend::app::{run_app, Application};
+use opengl_rend::app::{run_app, AppError, Application};
 use opengl_rend::buffer::{Target, Usage};
 use opengl_rend::opengl::{ClearFlags, Primitive};
 use opengl_rend::program::{Shader, ShaderType};
-use opengl_rend::vertex_attributes::{DataType, VertexAttribute};
+use opengl_rend::vertex_attributes::{DataType, VertexAttribute, VertexLayout};
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{
     buffer::Buffer, opengl::OpenGl, program::Program, vertex_attributes::VertexArrayObject,
 };
@@ -22,18 +22,16 @@ struct App {
     _vertex_buffer: Buffer<f32>,
 }
 
+// interleaved position and color
 #[rustfmt::skip]
 const VERTEX_DATA: [f32; 24] = [
-    0.0, 0.5, 0.0, 1.0, 
-    0.5, -0.366, 0.0, 1.0,
-    -0.5, -0.366, 0.0, 1.0,
-    1.0, 0.0, 0.0, 1.0,
-    0.0, 1.0, 0.0, 1.0,
-    0.0, 0.0, 1.0, 1.0,
+    0.0, 0.5, 0.0, 1.0,     1.0, 0.0, 0.0, 1.0,
+    0.5, -0.366, 0.0, 1.0,  0.0, 1.0, 0.0, 1.0,
+    -0.5, -0.366, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0,
 ];
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let gl = OpenGl::new(&mut window);
 
         let vert_str = CString::new(include_str!("vert.vert")).unwrap();
@@ -48,18 +46,18 @@ impl Application for App {
 
         let mut vertex_array_object = VertexArrayObject::new();
         let vec4 = VertexAttribute::new(4, DataType::Float, false);
+        let layout = VertexLayout::new().attribute(0, vec4).attribute(1, vec4);
 
         vertex_array_object.bind();
-        vertex_array_object.set_attribute(0, &vec4, 0, 0);
-        vertex_array_object.set_attribute(1, &vec4, 0, (vec4.size() * 3) as GLsizei);
+        vertex_array_object.set_layout(&layout);
         // gl.polygon_mode(opengl::PolygonMode::Line);
-        Self {
+        Ok(Self {
             gl,
             program,
             vertex_array_object,
             _vertex_buffer: vertex_buffer, // needs to be kept around if not it gets dropped
             window,
-        }
+        })
     }
 
     fn display(&mut self) {
@@ -76,8 +74,8 @@ impl Application for App {
 
     fn keyboard(&mut self, _key: Key, _action: Action, _modifier: Modifiers) {}
 
-    fn reshape(&mut self, width: i32, height: i32) {
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+    fn reshape(&mut self, viewport: &Viewport) {
+        viewport.apply(&mut self.gl);
     }
 
     fn window(&self) -> &PWindow {
@@ -89,6 +87,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
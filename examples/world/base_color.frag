@@ -1,9 +0,0 @@
-#version 330
-
-uniform vec4 baseColor;
-
-out vec4 outputColor;
-void main()
-{
-    outputColor = baseColor;
-}
@@ -0,0 +1,13 @@
+#version 330
+
+in vec4 shadowCoord;
+
+uniform vec4 baseColor;
+uniform sampler2DShadow shadowMap;
+
+out vec4 outputColor;
+void main()
+{
+    float lit = textureProj(shadowMap, shadowCoord);
+    outputColor = vec4(baseColor.rgb * mix(0.4, 1.0, lit), baseColor.a);
+}
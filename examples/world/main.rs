@@ -1,27 +1,43 @@
 #![forbid(unsafe_code)]
+use std::cell::RefCell;
 use std::ffi::CString;
+use std::rc::Rc;
 
 use gl::types::GLsizei;
-use glam::{Mat4, Vec3, Vec4};
-use glfw::{Action, Key, Modifiers, PWindow};
-use opengl_rend::app::{run_app, Application};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use glfw::{GamepadAxis, GamepadButton, Key, MouseButton, PWindow};
+use opengl_rend::app::{run_app, AppError, Application, ContextInfo, FrameStats};
+use opengl_rend::assets::MeshHandle;
 use opengl_rend::buffer::{Buffer, Target, Usage};
+use opengl_rend::camera::{Camera, CameraController, OrbitCamera};
+use opengl_rend::debug_draw::DebugDraw;
+use opengl_rend::frustum::Frustum;
+use opengl_rend::input::Input;
+use opengl_rend::material::{Material, MaterialHandle, ProgramHandle};
 use opengl_rend::matrix_stack::{MatrixStack, PushStack};
-use opengl_rend::mesh::Mesh;
-use opengl_rend::opengl::{Capability, ClearFlags, CullMode, DepthFunc, FrontFace};
-use opengl_rend::program::{GLBlockIndex, GLLocation, Shader, ShaderType};
+use opengl_rend::mesh::{Mesh, MeshLoader, PendingMesh};
+use opengl_rend::nodetree::NodeId;
+use opengl_rend::opengl::{
+    Capability, ClearFlags, ClipDepth, CullMode, DepthFunc, FrontFace, StateFlags,
+};
+use opengl_rend::program::{Shader, ShaderType};
+use opengl_rend::ray::Ray;
+use opengl_rend::scene::{Scene, SceneObject};
+use opengl_rend::shadow::ShadowMap;
+use opengl_rend::text::TextRenderer;
+use opengl_rend::transform::Transform;
+use opengl_rend::uniforms::UniformValue;
+use opengl_rend::vertex_attributes::VertexAttribute;
+use opengl_rend::viewport::Viewport;
 use opengl_rend::{opengl::OpenGl, program::Program};
 
-struct ProgramData {
-    program: Program,
-    model_to_world_matrix_uniform: GLLocation,
-    global_matrix_uniform: GLBlockIndex,
-    base_color_uniform: GLLocation,
-}
-
 const GLOBAL_MATRICES_BINDING_INDEX: u32 = 0;
+/// The text atlas is on unit 0
+const SHADOW_TEXTURE_UNIT: u32 = 1;
+const SUN_DIRECTION: Vec3 = Vec3::new(-1.0, -2.0, -0.5);
 
-fn load_program(vert: &str, frag: &str) -> ProgramData {
+/// Links the shaders and binds the `GlobalMatrices` block to the camera matrices
+fn load_program(vert: &str, frag: &str) -> ProgramHandle {
     let vert = CString::new(vert).unwrap();
     let frag = CString::new(frag).unwrap();
     let vert_shader = Shader::new(&vert, ShaderType::Vertex).unwrap();
@@ -30,12 +46,47 @@ fn load_program(vert: &str, frag: &str) -> ProgramData {
 
     let global_matrix_uniform = program.get_uniform_block_index(c"GlobalMatrices").unwrap();
     program.uniform_block_binding(global_matrix_uniform, GLOBAL_MATRICES_BINDING_INDEX);
-    ProgramData {
-        model_to_world_matrix_uniform: program.get_uniform_location(c"modelToWorld").unwrap(),
-        global_matrix_uniform,
-        base_color_uniform: program.get_uniform_location(c"baseColor").unwrap_or(-1),
-        program,
+    Rc::new(program)
+}
+
+/// Projection and view matrices shared by every program
+fn global_matrices_buffer() -> Buffer<Mat4> {
+    let mut buffer = Buffer::new(Target::UniformBuffer);
+    buffer.bind_scoped().reserve_data(2, Usage::StaticDraw);
+    buffer.bind_range_bytes(
+        GLOBAL_MATRICES_BINDING_INDEX,
+        0,
+        2 * std::mem::size_of::<Mat4>() as isize,
+    );
+    buffer
+}
+
+/// Depth of the forest and the building seen from the sun
+fn sun_shadow_map(reverse_z: bool) -> ShadowMap {
+    let mut shadow_map = ShadowMap::new(2048).unwrap();
+    if reverse_z {
+        shadow_map.clip_depth = ClipDepth::ZeroToOne;
     }
+    shadow_map.set_light(SUN_DIRECTION, Vec3::ZERO, 75.0);
+    shadow_map
+}
+
+/// Grass darkened where the shadow map is nearer to the sun
+fn ground_material(program: &ProgramHandle, shadow_map: &ShadowMap) -> MaterialHandle {
+    Rc::new(
+        material(program)
+            .with(c"baseColor", Vec4::new(0.302, 0.416, 0.0589, 1.0))
+            .with(c"worldToShadow", shadow_map.texture_matrix())
+            .with(
+                c"shadowMap",
+                UniformValue::Sampler(SHADOW_TEXTURE_UNIT as i32),
+            ),
+    )
+}
+
+/// Instanced programs take the model matrix as a vertex attribute and have no `modelToWorld`
+fn material(program: &ProgramHandle) -> Material {
+    Material::new(Rc::clone(program)).with_model_to_world(c"modelToWorld")
 }
 
 const FOREST: [[f32; 4]; 98] = [
@@ -142,276 +193,377 @@ const FOREST: [[f32; 4]; 98] = [
 struct App {
     window: PWindow,
     gl: OpenGl,
-    uniform_color: ProgramData,
-    object_color: ProgramData,
-    uniform_color_tint: ProgramData,
-    camera_target: Vec3,
-    camera_spherical_coords: Vec3,
-    plane_mesh: Mesh,
+    object_color: MaterialHandle,
+    trunk: Material,
+    treetop: Material,
+    camera: Camera,
+    orbit: OrbitCamera,
+    // the ground and the building
+    scene: Scene,
     cone_mesh: Mesh,
-    cube_color_mesh: Mesh,
-    cube_tint_mesh: Mesh,
-    cylinder_mesh: Mesh,
+    cube_color_mesh: MeshHandle,
+    cylinder_mesh: MeshHandle,
     look_at_point: bool,
     global_matrices_buffer: Buffer<Mat4>,
+    shadow_map: ShadowMap,
+    // per-instance model matrices, need to be kept around for the meshes to source them
+    culling: bool,
+    visible_trees: usize,
+    trunk_matrices: Vec<Mat4>,
+    treetop_matrices: Vec<Mat4>,
+    trunk_matrices_buffer: Buffer<Mat4>,
+    treetop_matrices_buffer: Buffer<Mat4>,
+    text: TextRenderer,
+    debug_draw: DebugDraw,
+    show_bounds: bool,
+    selected_tree: Option<usize>,
+    frame_stats: FrameStats,
+    viewport: Viewport,
 }
 
-const PARTHENON_COLUMN_HEIGHT: f32 = 5.0;
-const Z_NEAR: f32 = 0.1;
-const Z_FAR: f32 = 1000.0;
-const FOV: f32 = 100.0;
-impl App {
-    #[allow(clippy::too_many_lines)]
-    fn draw_parthenon(&mut self, stack: &mut MatrixStack) {
-        const PARTHENON_WIDTH: f32 = 14.0;
-        const PARTHENON_LENGTH: f32 = 20.0;
-        const PARTHENON_BASE_HEIGHT: f32 = 1.0;
-        const PARTHENON_TOP_HEIGHT: f32 = 2.0;
-        const FRONT_Z: f32 = PARTHENON_LENGTH * 0.5 - 1.0;
-        const RIGHT_X: f32 = PARTHENON_WIDTH * 0.5 - 1.0;
-        {
-            // draw base
-            let push = PushStack::new(stack);
-            push.stack.scale(Vec3::new(
-                PARTHENON_WIDTH,
-                PARTHENON_BASE_HEIGHT,
-                PARTHENON_LENGTH,
-            ));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.uniform_color_tint;
-
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program
-                .set_uniform(p.base_color_uniform, (0.9, 0.9, 0.9, 0.9));
-            self.cube_tint_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
-        {
-            // draw top
-            let push = PushStack::new(stack);
-            push.stack.translate(Vec3::new(
-                0.0,
-                PARTHENON_COLUMN_HEIGHT + PARTHENON_BASE_HEIGHT,
-                0.0,
-            ));
-
-            push.stack.scale(Vec3::new(
-                PARTHENON_WIDTH,
-                PARTHENON_TOP_HEIGHT,
-                PARTHENON_LENGTH,
-            ));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.uniform_color_tint;
-
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program
-                .set_uniform(p.base_color_uniform, (0.9, 0.9, 0.9, 0.9));
-            self.cube_tint_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
+const MODEL_TO_WORLD_ATTRIBUTE_LOCATION: u32 = 2;
+
+fn forest_matrices() -> (Vec<Mat4>, Vec<Mat4>) {
+    let mut trunks = Vec::with_capacity(FOREST.len());
+    let mut treetops = Vec::with_capacity(FOREST.len());
+    let mut stack = MatrixStack::new();
+    for [x_pos, z_pos, trunk_height, cone_height] in FOREST {
+        let mut push = PushStack::new(&mut stack);
+        push.translate(Vec3::new(x_pos, 0.0, z_pos));
+        trunks.push(push.with_pushed(|stack| {
+            stack.scale(Vec3::new(1.0, trunk_height, 1.0));
+            stack.translate(Vec3::new(0.0, 0.5, 0.0));
+            stack.top()
+        }));
+        treetops.push(push.with_pushed(|stack| {
+            stack.translate(Vec3::new(0.0, trunk_height, 0.0));
+            stack.scale(Vec3::new(3.0, cone_height, 3.0));
+            stack.top()
+        }));
+    }
+    (trunks, treetops)
+}
 
-        for i in 0..(PARTHENON_WIDTH / 2.0) as usize {
-            {
-                let push = PushStack::new(stack);
-                push.stack.translate(Vec3::new(
-                    2.0f32.mul_add(i as f32, -(PARTHENON_WIDTH / 2.0)) + 1.0,
-                    PARTHENON_BASE_HEIGHT,
-                    FRONT_Z,
-                ));
-                self.draw_column(push.stack, PARTHENON_COLUMN_HEIGHT);
-            }
-            {
-                let push = PushStack::new(stack);
-                push.stack.translate(Vec3::new(
-                    2.0f32.mul_add(i as f32, -(PARTHENON_WIDTH / 2.0)) + 1.0,
-                    PARTHENON_BASE_HEIGHT,
-                    -FRONT_Z,
-                ));
-                self.draw_column(push.stack, PARTHENON_COLUMN_HEIGHT);
-            }
-        }
-        for i in 1..((PARTHENON_LENGTH - 2.0) / 2.0) as usize {
-            {
-                let push = PushStack::new(stack);
-                push.stack.translate(Vec3::new(
-                    RIGHT_X,
-                    PARTHENON_BASE_HEIGHT,
-                    2.0f32.mul_add(i as f32, -(PARTHENON_LENGTH / 2.0)) + 1.0,
-                ));
-                self.draw_column(push.stack, PARTHENON_COLUMN_HEIGHT);
-            }
-            {
-                let push = PushStack::new(stack);
-                push.stack.translate(Vec3::new(
-                    -RIGHT_X,
-                    PARTHENON_BASE_HEIGHT,
-                    2.0f32.mul_add(i as f32, -(PARTHENON_LENGTH / 2.0)) + 1.0,
-                ));
-                self.draw_column(push.stack, PARTHENON_COLUMN_HEIGHT);
-            }
-        }
-        {
-            // draw interior
-            let push = PushStack::new(stack);
-            push.stack.translate(Vec3::Y);
-            push.stack.scale(Vec3::new(
+/// Bounding sphere of a tree in the forest
+fn tree_bounds([x_pos, z_pos, trunk_height, cone_height]: [f32; 4]) -> (Vec3, f32) {
+    let half_height = (trunk_height + cone_height) * 0.5;
+    let center = Vec3::new(x_pos, half_height, z_pos);
+    // the cone is scaled 3 times wider
+    (center, half_height.hypot(3.0))
+}
+
+/// The closest tree whose bounds `ray` hits
+fn pick_tree(ray: &Ray) -> Option<usize> {
+    FOREST
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, tree)| {
+            let (center, radius) = tree_bounds(tree);
+            ray.intersect_sphere(center, radius)
+                .map(|distance| (i, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+fn instance_matrices_buffer(mesh: &mut Mesh, matrices: &[Mat4]) -> Buffer<Mat4> {
+    let mut buffer = Buffer::new(Target::ArrayBuffer);
+    buffer
+        .bind_scoped()
+        .buffer_data(matrices, Usage::DynamicDraw);
+    mesh.set_instance_attribute(
+        &mut buffer,
+        MODEL_TO_WORLD_ATTRIBUTE_LOCATION,
+        &VertexAttribute::mat4(),
+        std::mem::size_of::<Mat4>() as GLsizei,
+        0,
+    );
+    buffer
+}
+
+/// What the ground and the building are made of
+struct SceneAssets {
+    ground: MaterialHandle,
+    stone: MaterialHandle,
+    white_stone: MaterialHandle,
+    object_color: MaterialHandle,
+    plane: MeshHandle,
+    cube_tint: MeshHandle,
+    cube_color: MeshHandle,
+    cylinder: MeshHandle,
+}
+
+/// The unit cube or cylinder scaled to `size`, with its bottom face centered at `position`
+fn standing(position: Vec3, size: Vec3) -> Transform {
+    Transform::new(position + Vec3::Y * (size.y * 0.5), Quat::IDENTITY, size)
+}
+
+fn add_object(
+    scene: &mut Scene,
+    parent: NodeId,
+    name: &str,
+    transform: Transform,
+    mesh: &MeshHandle,
+    material: &MaterialHandle,
+) {
+    let node = scene.nodes.add_child(parent, name, transform, ()).unwrap();
+    scene.add(SceneObject {
+        mesh: Rc::clone(mesh),
+        material: Rc::clone(material),
+        transform: node,
+    });
+}
+
+fn add_column(scene: &mut Scene, column: NodeId, height: f32, assets: &SceneAssets) {
+    const COLUMN_BASE_HEIGHT: f32 = 0.25;
+    let base_size = Vec3::new(1.0, COLUMN_BASE_HEIGHT, 1.0);
+    add_object(
+        scene,
+        column,
+        "bottom",
+        standing(Vec3::ZERO, base_size),
+        &assets.cube_tint,
+        &assets.white_stone,
+    );
+    add_object(
+        scene,
+        column,
+        "top",
+        standing(Vec3::Y * (height - COLUMN_BASE_HEIGHT), base_size),
+        &assets.cube_tint,
+        &assets.stone,
+    );
+    add_object(
+        scene,
+        column,
+        "shaft",
+        standing(
+            Vec3::Y * COLUMN_BASE_HEIGHT,
+            Vec3::new(0.8, COLUMN_BASE_HEIGHT.mul_add(-2.0, height), 0.8),
+        ),
+        &assets.cylinder,
+        &assets.stone,
+    );
+}
+
+fn add_parthenon(scene: &mut Scene, position: Vec3, assets: &SceneAssets) {
+    const PARTHENON_WIDTH: f32 = 14.0;
+    const PARTHENON_LENGTH: f32 = 20.0;
+    const PARTHENON_COLUMN_HEIGHT: f32 = 5.0;
+    const PARTHENON_BASE_HEIGHT: f32 = 1.0;
+    const PARTHENON_TOP_HEIGHT: f32 = 2.0;
+    const FRONT_Z: f32 = PARTHENON_LENGTH * 0.5 - 1.0;
+    const RIGHT_X: f32 = PARTHENON_WIDTH * 0.5 - 1.0;
+
+    let parthenon = scene
+        .nodes
+        .add_root("parthenon", Transform::from_translation(position), ());
+    add_object(
+        scene,
+        parthenon,
+        "base",
+        standing(
+            Vec3::ZERO,
+            Vec3::new(PARTHENON_WIDTH, PARTHENON_BASE_HEIGHT, PARTHENON_LENGTH),
+        ),
+        &assets.cube_tint,
+        &assets.stone,
+    );
+    add_object(
+        scene,
+        parthenon,
+        "top",
+        standing(
+            Vec3::Y * (PARTHENON_COLUMN_HEIGHT + PARTHENON_BASE_HEIGHT),
+            Vec3::new(PARTHENON_WIDTH, PARTHENON_TOP_HEIGHT, PARTHENON_LENGTH),
+        ),
+        &assets.cube_tint,
+        &assets.stone,
+    );
+
+    let front_and_back = (0..(PARTHENON_WIDTH / 2.0) as usize).flat_map(|i| {
+        let x = 2.0f32.mul_add(i as f32, -(PARTHENON_WIDTH / 2.0)) + 1.0;
+        [(x, FRONT_Z), (x, -FRONT_Z)]
+    });
+    let sides = (1..((PARTHENON_LENGTH - 2.0) / 2.0) as usize).flat_map(|i| {
+        let z = 2.0f32.mul_add(i as f32, -(PARTHENON_LENGTH / 2.0)) + 1.0;
+        [(RIGHT_X, z), (-RIGHT_X, z)]
+    });
+    for (x, z) in front_and_back.chain(sides) {
+        let column = scene
+            .nodes
+            .add_child(
+                parthenon,
+                "column",
+                Transform::from_translation(Vec3::new(x, PARTHENON_BASE_HEIGHT, z)),
+                (),
+            )
+            .unwrap();
+        add_column(scene, column, PARTHENON_COLUMN_HEIGHT, assets);
+    }
+
+    add_object(
+        scene,
+        parthenon,
+        "interior",
+        standing(
+            Vec3::Y,
+            Vec3::new(
                 PARTHENON_WIDTH - 6.0,
                 PARTHENON_COLUMN_HEIGHT,
                 PARTHENON_LENGTH - 6.0,
-            ));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.object_color;
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            self.cube_color_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
-        {
-            // draw headpiece
-            let push = PushStack::new(stack);
-            push.stack.translate(Vec3::new(
+            ),
+        ),
+        &assets.cube_color,
+        &assets.object_color,
+    );
+    add_object(
+        scene,
+        parthenon,
+        "headpiece",
+        Transform::new(
+            Vec3::new(
                 0.0,
                 PARTHENON_TOP_HEIGHT.mul_add(0.5, PARTHENON_COLUMN_HEIGHT + PARTHENON_BASE_HEIGHT),
                 PARTHENON_LENGTH * 0.5,
-            ));
-            push.stack.rotate_x(-135.0);
-            push.stack.rotate_y(45.0);
-
-            let p = &mut self.object_color;
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            self.cube_color_mesh.render(&mut self.gl);
-            p.program.set_unused();
+            ),
+            Quat::from_rotation_x((-135.0f32).to_radians())
+                * Quat::from_rotation_y(45.0f32.to_radians()),
+            Vec3::ONE,
+        ),
+        &assets.cube_color,
+        &assets.object_color,
+    );
+}
+
+fn build_scene(assets: &SceneAssets) -> Scene {
+    let mut scene = Scene::new();
+    let ground = scene.nodes.add_root(
+        "ground",
+        Transform::from_scale(Vec3::new(1000.0, 1.0, 1000.0)),
+        (),
+    );
+    scene.add(SceneObject {
+        mesh: Rc::clone(&assets.plane),
+        material: Rc::clone(&assets.ground),
+        transform: ground,
+    });
+    add_parthenon(&mut scene, Vec3::new(20.0, 0.0, -10.0), assets);
+    scene
+}
+
+const Z_NEAR: f32 = 0.1;
+const FOV: f32 = 100.0;
+impl App {
+    /// Uploads the matrices of the trees inside the frustum, packed at the start of the buffers
+    fn cull_forest(&mut self, frustum: &Frustum) {
+        let mut trunks = Vec::with_capacity(FOREST.len());
+        let mut treetops = Vec::with_capacity(FOREST.len());
+        for (i, tree) in FOREST.iter().enumerate() {
+            let (center, radius) = tree_bounds(*tree);
+            if frustum.intersects_sphere(center, radius) {
+                trunks.push(self.trunk_matrices[i]);
+                treetops.push(self.treetop_matrices[i]);
+            }
         }
+        self.trunk_matrices_buffer
+            .bind_scoped()
+            .update_data(&trunks, 0)
+            .unwrap();
+        self.treetop_matrices_buffer
+            .bind_scoped()
+            .update_data(&treetops, 0)
+            .unwrap();
+        self.visible_trees = trunks.len();
     }
 
-    fn draw_column(&mut self, stack: &mut MatrixStack, height: f32) {
-        const COLUMN_BASE_HEIGHT: f32 = 0.25;
-        {
-            // draw bottom
-            let push = PushStack::new(stack);
-            push.stack.scale(Vec3::new(1.0, COLUMN_BASE_HEIGHT, 1.0));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.uniform_color_tint;
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program.set_uniform(p.base_color_uniform, Vec4::ONE);
-            self.cube_tint_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
-        {
-            // draw top
-            let push = PushStack::new(stack);
-            push.stack
-                .translate(Vec3::new(0.0, height - COLUMN_BASE_HEIGHT, 0.0));
-            push.stack.scale(Vec3::new(1.0, COLUMN_BASE_HEIGHT, 1.0));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.uniform_color_tint;
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program
-                .set_uniform(p.base_color_uniform, (0.9, 0.9, 0.9, 0.9));
-            self.cube_tint_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
-        {
-            // draw main column
-            let push = PushStack::new(stack);
-            push.stack
-                .translate(Vec3::new(0.0, COLUMN_BASE_HEIGHT, 0.0));
-            push.stack.scale(Vec3::new(
-                0.8,
-                COLUMN_BASE_HEIGHT.mul_add(-2.0, height),
-                0.8,
-            ));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.uniform_color_tint;
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program
-                .set_uniform(p.base_color_uniform, (0.9, 0.9, 0.9, 0.9));
-            self.cylinder_mesh.render(&mut self.gl);
-            p.program.set_unused();
+    /// The culling bounds of the trees, the camera target and the ground grid
+    fn draw_bounds(&mut self) {
+        let color = Vec4::new(1.0, 0.5, 0.0, 1.0);
+        for tree in FOREST {
+            let (center, radius) = tree_bounds(tree);
+            self.debug_draw.sphere(center, radius, color);
         }
+        self.debug_draw
+            .axis(Mat4::from_translation(self.orbit.target), 5.0);
+        self.debug_draw
+            .grid(Vec3::ZERO, 100.0, 20, Vec4::new(0.5, 0.5, 0.5, 1.0));
     }
 
-    fn draw_tree(&mut self, stack: &mut MatrixStack, trunk_height: f32, cone_height: f32) {
-        {
-            // draw trunk
-            let push = PushStack::new(stack);
-            push.stack.scale(Vec3::new(1.0, trunk_height, 1.0));
-            push.stack.translate(Vec3::new(0.0, 0.5, 0.0));
-
-            let p = &mut self.uniform_color_tint;
-
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program
-                .set_uniform(p.base_color_uniform, (0.694, 0.4, 0.106, 1.0));
-            self.cylinder_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
-        {
-            // draw treetop
-            let push = PushStack::new(stack);
-            push.stack.translate(Vec3::new(0.0, trunk_height, 0.0));
-            push.stack.scale(Vec3::new(3.0, cone_height, 3.0));
-
-            let p = &mut self.uniform_color_tint;
-
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
-            p.program
-                .set_uniform(p.base_color_uniform, (0.0, 1.0, 0.0, 1.0));
-            self.cone_mesh.render(&mut self.gl);
-            p.program.set_unused();
-        }
+    fn draw_overlay(&mut self) {
+        let stats = format!(
+            "{:.0} fps  {:.2} ms cpu",
+            self.frame_stats.fps,
+            self.frame_stats.cpu_time.as_secs_f32() * 1000.0
+        );
+        let selected = self
+            .selected_tree
+            .map_or_else(|| "none".to_owned(), |tree| format!("tree {tree}"));
+        let camera = format!(
+            "target {:.1}\nlook at point {}\nculling {} ({} trees)\nselected {selected}",
+            self.orbit.target, self.look_at_point, self.culling, self.visible_trees
+        );
+        self.text
+            .queue(&stats, Vec2::new(8.0, 8.0), Vec4::new(1.0, 1.0, 0.0, 1.0));
+        self.text.queue(&camera, Vec2::new(8.0, 32.0), Vec4::ONE);
+        let size = self.viewport.size();
+        self.text.draw(&mut self.gl, size.x as i32, size.y as i32);
     }
-    fn draw_forest(&mut self, model_matrix: &mut MatrixStack) {
-        for [x_pos, z_pos, trunk_height, cone_height] in FOREST {
-            let push = PushStack::new(model_matrix);
-            push.stack.translate(Vec3::new(x_pos, 0.0, z_pos));
-            self.draw_tree(push.stack, trunk_height, cone_height);
-        }
+
+    /// Renders the depth of the trees and the building, with the trees culled from the
+    /// view left out
+    fn draw_shadows(&mut self) {
+        let visible_trees = self.visible_trees as GLsizei;
+        let (cylinder_mesh, cone_mesh, scene) =
+            (&self.cylinder_mesh, &mut self.cone_mesh, &self.scene);
+        self.shadow_map
+            .depth_pass(&mut self.gl, &self.viewport, |gl, shadow_map| {
+                shadow_map.cast_instanced(gl, &mut cylinder_mesh.borrow_mut(), visible_trees);
+                shadow_map.cast_instanced(gl, cone_mesh, visible_trees);
+                shadow_map.cast_scene(gl, scene);
+            });
+        self.shadow_map.bind_texture(SHADOW_TEXTURE_UNIT);
     }
 
-    fn calculate_camera_pos(&self) -> Vec3 {
-        let phi = self.camera_spherical_coords.x.to_radians();
-        let theta = (self.camera_spherical_coords.y + 90.0).to_radians();
+    fn draw_forest(&mut self) {
+        self.trunk.apply(&mut self.gl);
+        self.cylinder_mesh
+            .borrow_mut()
+            .render_instanced(&mut self.gl, self.visible_trees as GLsizei);
+        self.treetop.apply(&mut self.gl);
+        self.cone_mesh
+            .render_instanced(&mut self.gl, self.visible_trees as GLsizei);
+    }
 
-        let (sin_phi, cos_phi) = phi.sin_cos();
-        let (sin_theta, cos_theta) = theta.sin_cos();
-        Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi)
-            * self.camera_spherical_coords.z
-            + self.camera_target
+    fn uncull_forest(&mut self) {
+        self.trunk_matrices_buffer
+            .bind_scoped()
+            .update_data(&self.trunk_matrices, 0)
+            .unwrap();
+        self.treetop_matrices_buffer
+            .bind_scoped()
+            .update_data(&self.treetop_matrices, 0)
+            .unwrap();
+        self.visible_trees = FOREST.len();
     }
 }
 
 impl Application for App {
-    fn new(mut window: PWindow) -> Self {
+    fn new(mut window: PWindow) -> Result<Self, AppError> {
         let mut gl = OpenGl::new(&mut window);
 
+        // parse the meshes in the background while the programs compile
+        let loader = MeshLoader::default();
+        let cone_mesh = loader.load("examples/world/meshes/UnitConeTint.xml");
+        let cylinder_mesh = loader.load("examples/world/meshes/UnitCylinderTint.xml");
+        let cube_color_mesh = loader.load("examples/world/meshes/UnitCubeColor.xml");
+        let cube_tint_mesh = loader.load("examples/world/meshes/UnitCubeTint.xml");
+        let plane_mesh = loader.load("examples/world/meshes/UnitPlane.xml");
+
         // initialize programs
-        let uniform_color = load_program(
-            include_str!("only_pos_world_transformUBO.vert"),
-            include_str!("base_color.frag"),
+        let shadowed_color = load_program(
+            include_str!("only_pos_shadow_world_transformUBO.vert"),
+            include_str!("base_color_shadow.frag"),
         );
         let object_color = load_program(
             include_str!("pos_color_world_transformUBO.vert"),
@@ -421,16 +573,17 @@ impl Application for App {
             include_str!("pos_color_world_transformUBO.vert"),
             include_str!("base_vertex_color.frag"),
         );
-
-        let mut global_matrices_buffer = Buffer::new(Target::UniformBuffer);
-        global_matrices_buffer.bind();
-        global_matrices_buffer.reserve_data(2, Usage::StaticDraw);
-        global_matrices_buffer.unbind();
-        global_matrices_buffer.bind_range_bytes(
-            GLOBAL_MATRICES_BINDING_INDEX,
-            0,
-            2 * std::mem::size_of::<Mat4>() as isize,
+        let instanced_color_tint = load_program(
+            include_str!("pos_color_instanced_transformUBO.vert"),
+            include_str!("base_vertex_color.frag"),
         );
+        let object_color = Rc::new(material(&object_color));
+        let trunk =
+            material(&instanced_color_tint).with(c"baseColor", Vec4::new(0.694, 0.4, 0.106, 1.0));
+        let treetop =
+            material(&instanced_color_tint).with(c"baseColor", Vec4::new(0.0, 1.0, 0.0, 1.0));
+
+        let global_matrices_buffer = global_matrices_buffer();
 
         // enable backface culling
         gl.enable(Capability::CullFace);
@@ -438,142 +591,205 @@ impl Application for App {
         gl.front_face(FrontFace::CW);
         // gl.polygon_mode(PolygonMode::Line);
 
-        // enable depth test
+        // enable depth test, reversed with an infinite far plane for precision over the whole scene
+        let reverse_z = ContextInfo::from_window(&window).has_clip_control();
         gl.enable(Capability::DepthTest);
         gl.set_depth_mask(true);
-        gl.depth_func(DepthFunc::LessEqual);
+        if reverse_z {
+            gl.set_reverse_z(true);
+        } else {
+            gl.depth_func(DepthFunc::LessEqual);
+            gl.clear_depth(1.0);
+        }
         gl.depth_range(0.0, 1.0);
 
-        let cone_mesh = Mesh::new("examples/world/meshes/UnitConeTint.xml").unwrap();
-        let cylinder_mesh = Mesh::new("examples/world/meshes/UnitCylinderTint.xml").unwrap();
-        let cube_color_mesh = Mesh::new("examples/world/meshes/UnitCubeColor.xml").unwrap();
-        let cube_tint_mesh = Mesh::new("examples/world/meshes/UnitCubeTint.xml").unwrap();
-        let plane_mesh = Mesh::new("examples/world/meshes/UnitPlane.xml").unwrap();
-
-        Self {
+        let upload = |mesh: PendingMesh| Mesh::upload(&gl, mesh.wait().unwrap()).unwrap();
+        let shared = |mesh: PendingMesh| Rc::new(RefCell::new(upload(mesh)));
+        let mut cone_mesh = upload(cone_mesh);
+        let cylinder_mesh = shared(cylinder_mesh);
+        let cube_color_mesh = shared(cube_color_mesh);
+
+        let shadow_map = sun_shadow_map(reverse_z);
+        let scene = build_scene(&SceneAssets {
+            ground: ground_material(&shadowed_color, &shadow_map),
+            stone: Rc::new(material(&object_color_tint).with(c"baseColor", Vec4::splat(0.9))),
+            white_stone: Rc::new(material(&object_color_tint).with(c"baseColor", Vec4::ONE)),
+            object_color: Rc::clone(&object_color),
+            plane: shared(plane_mesh),
+            cube_tint: shared(cube_tint_mesh),
+            cube_color: Rc::clone(&cube_color_mesh),
+            cylinder: Rc::clone(&cylinder_mesh),
+        });
+
+        let text = TextRenderer::new(&mut gl).unwrap();
+        let debug_draw = DebugDraw::new(&mut gl).unwrap();
+
+        let mut camera = Camera::new(FOV, Z_NEAR, f32::INFINITY);
+        camera.reverse_z = reverse_z;
+
+        let (trunk_matrices, treetop_matrices) = forest_matrices();
+        let trunk_matrices_buffer =
+            instance_matrices_buffer(&mut cylinder_mesh.borrow_mut(), &trunk_matrices);
+        let treetop_matrices_buffer = instance_matrices_buffer(&mut cone_mesh, &treetop_matrices);
+
+        Ok(Self {
             gl,
             window,
-            uniform_color,
             object_color,
-            uniform_color_tint: object_color_tint,
-            camera_target: Vec3::new(0.0, 0.4, 0.0),
-            camera_spherical_coords: Vec3::new(67.5, -46.0, 150.0),
-            plane_mesh,
+            trunk,
+            treetop,
+            camera,
+            orbit: OrbitCamera {
+                min_pitch: 1.0,
+                max_pitch: 78.75,
+                min_distance: 5.0,
+                ..OrbitCamera::new(Vec3::new(0.0, 0.4, 0.0), 67.5, 46.0, 150.0)
+            },
+            scene,
             cone_mesh,
             cylinder_mesh,
-            cube_tint_mesh,
             cube_color_mesh,
             look_at_point: false,
             global_matrices_buffer,
-        }
+            shadow_map,
+            culling: false,
+            visible_trees: FOREST.len(),
+            trunk_matrices,
+            treetop_matrices,
+            trunk_matrices_buffer,
+            treetop_matrices_buffer,
+            text,
+            debug_draw,
+            show_bounds: false,
+            selected_tree: None,
+            frame_stats: FrameStats::default(),
+            viewport: Viewport::new(1, 1),
+        })
     }
 
     fn display(&mut self) {
         self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
-        self.gl.clear_depth(1.0);
         self.gl.clear(ClearFlags::Color | ClearFlags::Depth);
 
         // Draw
-        let camera_position = self.calculate_camera_pos();
-        let look_at = Mat4::look_at_rh(camera_position, self.camera_target, Vec3::Y);
-        self.global_matrices_buffer.bind();
-        self.global_matrices_buffer.update_data(&[look_at], 1);
-        self.global_matrices_buffer.unbind();
-
-        let mut model_matrix = MatrixStack::new();
-        {
-            // Draw ground
-            let push = PushStack::new(&mut model_matrix);
-            push.stack.scale(Vec3::new(1000.0, 1.0, 1000.0));
-            let program_data = &mut self.uniform_color;
-            program_data.program.set_used();
-            program_data
-                .program
-                .set_uniform(program_data.model_to_world_matrix_uniform, push.stack.top());
-            program_data
-                .program
-                .set_uniform(program_data.base_color_uniform, (0.302, 0.416, 0.0589, 1.0));
-            self.plane_mesh.render(&mut self.gl);
-            program_data.program.set_unused();
-        }
-        self.draw_forest(&mut model_matrix);
-        {
-            // Draw the building
-            let push = PushStack::new(&mut model_matrix);
-            push.stack.translate(Vec3::new(20.0, 0.0, -10.0));
-            self.draw_parthenon(push.stack);
+        self.global_matrices_buffer
+            .bind_scoped()
+            .update_data(&[self.camera.view()], 1)
+            .unwrap();
+
+        if self.culling {
+            self.cull_forest(&self.camera.frustum());
         }
+        self.draw_shadows();
+        self.scene.render(&mut self.gl, &self.camera);
+        self.draw_forest();
         if self.look_at_point {
-            self.gl.disable(Capability::DepthTest);
-
-            let push = PushStack::new(&mut model_matrix);
-            push.stack.translate(self.camera_target);
-            push.stack.scale(Vec3::ONE);
+            let mut gl = self.gl.save_state(StateFlags::Depth);
+            gl.disable(Capability::DepthTest);
 
-            let p = &mut self.object_color;
-            p.program.set_used();
-            p.program
-                .set_uniform(p.model_to_world_matrix_uniform, push.stack.top());
+            self.object_color.apply(&mut gl);
+            self.object_color
+                .set_model_to_world(Mat4::from_translation(self.orbit.target));
 
-            self.cube_color_mesh.render(&mut self.gl);
-            p.program.set_unused();
-
-            self.gl.enable(Capability::DepthTest);
+            self.cube_color_mesh.borrow_mut().render(&mut gl);
+        }
+        if self.show_bounds {
+            self.draw_bounds();
+        }
+        if let Some(tree) = self.selected_tree {
+            let (center, radius) = tree_bounds(FOREST[tree]);
+            self.debug_draw
+                .sphere(center, radius, Vec4::new(1.0, 1.0, 0.0, 1.0));
         }
+        self.debug_draw
+            .flush(&mut self.gl, self.camera.view_projection());
+        self.draw_overlay();
     }
 
-    fn keyboard(&mut self, key: Key, action: Action, modifier: Modifiers) {
-        let modifier = if modifier.contains(Modifiers::Shift) {
+    fn frame_stats(&mut self, stats: &FrameStats) {
+        self.frame_stats = *stats;
+    }
+
+    fn update(&mut self, dt: f32, input: &Input) {
+        let modifier = if input.pressed(Key::LeftShift) || input.pressed(Key::RightShift) {
             0.1
         } else {
             1.0
         };
-        if action == Action::Press || action == Action::Repeat {
-            match key {
-                Key::E => self.camera_target.y -= 4.0 * modifier,
-                Key::Q => self.camera_target.y += 4.0 * modifier,
-                Key::A => self.camera_target.x -= 4.0 * modifier,
-                Key::D => self.camera_target.x += 4.0 * modifier,
-                Key::W => self.camera_target.z -= 4.0 * modifier,
-                Key::S => self.camera_target.z += 4.0 * modifier,
-
-                Key::J => self.camera_spherical_coords.x -= 11.0 * modifier,
-                Key::L => self.camera_spherical_coords.x += 11.0 * modifier,
-                Key::I => self.camera_spherical_coords.y -= 11.0 * modifier,
-                Key::K => self.camera_spherical_coords.y += 11.0 * modifier,
-                Key::O => self.camera_spherical_coords.z -= 5.0 * modifier,
-                Key::U => self.camera_spherical_coords.z += 5.0 * modifier,
-                Key::Space => {
-                    self.look_at_point = !self.look_at_point;
-                    println!("look at point {}", self.look_at_point);
-                    println!("Target {}", self.camera_target);
-                }
-                _ => {}
+        // -1, 0 or 1 depending on which of the two keys is held
+        let axis = |negative: Key, positive: Key| {
+            f32::from(u8::from(input.pressed(positive)))
+                - f32::from(u8::from(input.pressed(negative)))
+        };
+
+        let gamepad = input.gamepad();
+        // Ignores small stick deflections, triggers rest at -1
+        let stick = |axis: GamepadAxis| {
+            let value = gamepad.axis(axis);
+            if value.abs() > 0.15 {
+                value
+            } else {
+                0.0
+            }
+        };
+        let trigger = |axis: GamepadAxis| (gamepad.axis(axis) + 1.0) * 0.5;
+        let button = |button: GamepadButton| f32::from(u8::from(gamepad.pressed(button)));
+
+        let speed = 40.0 * modifier * dt;
+        self.orbit.target += speed
+            * Vec3::new(
+                axis(Key::A, Key::D) + stick(GamepadAxis::AxisLeftX),
+                axis(Key::E, Key::Q) + trigger(GamepadAxis::AxisRightTrigger)
+                    - trigger(GamepadAxis::AxisLeftTrigger),
+                axis(Key::W, Key::S) + stick(GamepadAxis::AxisLeftY),
+            );
+        let angular_speed = 110.0 * modifier * dt;
+        self.orbit.orbit(
+            angular_speed * (axis(Key::J, Key::L) + stick(GamepadAxis::AxisRightX)),
+            angular_speed * (axis(Key::K, Key::I) - stick(GamepadAxis::AxisRightY)),
+        );
+        self.orbit.zoom(
+            50.0 * modifier
+                * dt
+                * (axis(Key::O, Key::U) + button(GamepadButton::ButtonRightBumper)
+                    - button(GamepadButton::ButtonLeftBumper)),
+        );
+
+        if input.just_pressed(Key::Space) || gamepad.just_pressed(GamepadButton::ButtonA) {
+            self.look_at_point = !self.look_at_point;
+        }
+        if input.just_pressed(Key::B) || gamepad.just_pressed(GamepadButton::ButtonX) {
+            self.show_bounds = !self.show_bounds;
+        }
+        if input.just_pressed(Key::C) || gamepad.just_pressed(GamepadButton::ButtonB) {
+            self.culling = !self.culling;
+            if !self.culling {
+                self.uncull_forest();
             }
-            self.camera_spherical_coords.y = self.camera_spherical_coords.y.clamp(-78.75, -1.0);
-            self.camera_spherical_coords.z = self.camera_spherical_coords.z.clamp(-5.0, 5.0);
-            self.camera_target.y = self.camera_target.y.max(0.0);
-            let position = self.calculate_camera_pos();
-            println!("Target {}", self.camera_target);
-            println!("Absolute Position {position}");
-            println!("Distance {}", self.camera_target.distance(position));
-            println!("Spherical coords {}", self.camera_spherical_coords);
         }
-    }
 
-    fn reshape(&mut self, width: i32, height: i32) {
-        let matrix = Mat4::perspective_rh_gl(
-            f32::to_radians(FOV),
-            width as f32 / height as f32,
-            Z_NEAR,
-            Z_FAR,
-        );
+        self.orbit.target.y = self.orbit.target.y.max(0.0);
+        self.camera.set_view(self.orbit.view());
+
+        // clicking selects the tree under the cursor
+        if input.mouse_just_pressed(MouseButton::Button1) {
+            let (x, y) = input.cursor();
+            let ray = self
+                .camera
+                .screen_to_ray(x as f32, y as f32, &self.viewport);
+            self.selected_tree = pick_tree(&ray);
+        }
+    }
 
-        self.global_matrices_buffer.bind();
-        self.global_matrices_buffer.update_data(&[matrix], 0);
-        self.global_matrices_buffer.unbind();
+    fn reshape(&mut self, viewport: &Viewport) {
+        self.camera.reshape(viewport);
+        self.global_matrices_buffer
+            .bind_scoped()
+            .update_data(&[self.camera.projection()], 0)
+            .unwrap();
 
-        self.gl.viewport(0, 0, width as GLsizei, height as GLsizei);
+        viewport.apply(&mut self.gl);
+        self.viewport = *viewport;
     }
 
     fn window(&self) -> &PWindow {
@@ -585,6 +801,6 @@ impl Application for App {
     }
 }
 
-fn main() {
-    run_app::<App>();
+fn main() -> Result<(), AppError> {
+    run_app::<App>()
 }
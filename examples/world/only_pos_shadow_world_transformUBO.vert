@@ -0,0 +1,21 @@
+#version 330
+
+layout(location = 0) in vec4 position;
+
+layout(std140) uniform GlobalMatrices {
+    mat4 cameraToClip;
+    mat4 worldToCamera;
+};
+
+uniform mat4 modelToWorld;
+uniform mat4 worldToShadow;
+
+out vec4 shadowCoord;
+
+void main()
+{
+    vec4 worldPos = modelToWorld * position;
+    shadowCoord = worldToShadow * worldPos;
+    vec4 cameraPos = worldToCamera * worldPos;
+    gl_Position = cameraToClip * cameraPos;
+}
@@ -2,17 +2,18 @@
 
 layout(location = 0) in vec4 position;
 layout(location = 1) in vec4 color;
+layout(location = 2) in mat4 modelToWorld;
 
 layout(std140) uniform GlobalMatrices {
     mat4 cameraToClip;
     mat4 worldToCamera;
 };
 
-uniform mat4 modelToWorld;
-
+smooth out vec4 fragColor;
 void main()
 {
     vec4 worldPos = modelToWorld * position;
     vec4 cameraPos = worldToCamera * worldPos;
     gl_Position = cameraToClip * cameraPos;
+    fragColor = color;
 }
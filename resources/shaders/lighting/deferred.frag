@@ -0,0 +1,32 @@
+in vec2 uv;
+
+uniform sampler2D albedoTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D depthTexture;
+uniform mat4 clipToWorld;
+// what to output, like `GBufferView` in src/gbuffer.rs
+uniform int view;
+
+out vec4 outputColor;
+
+void main() {
+    float depth = texture(depthTexture, uv).r;
+    // nothing was drawn here in the geometry pass
+    if (depth == 1.0) {
+        discard;
+    }
+    vec4 albedo = texture(albedoTexture, uv);
+    vec4 normal = texture(normalTexture, uv);
+    if (view == 1) {
+        outputColor = vec4(albedo.rgb, 1.0);
+    } else if (view == 2) {
+        outputColor = vec4(normal.xyz * 0.5 + 0.5, 1.0);
+    } else if (view == 3) {
+        // perspective depth bunches up close to 1
+        outputColor = vec4(vec3(pow(depth, 64.0)), 1.0);
+    } else {
+        vec4 world = clipToWorld * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+        vec3 color = shade(world.xyz / world.w, normalize(normal.xyz), albedo.rgb, vec3(albedo.a), normal.w);
+        outputColor = vec4(color, 1.0);
+    }
+}
@@ -0,0 +1,8 @@
+// Covers the screen with one triangle made from `gl_VertexID`
+
+out vec2 uv;
+
+void main() {
+    uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
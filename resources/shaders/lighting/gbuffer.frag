@@ -0,0 +1,12 @@
+in vec3 worldPosition;
+in vec3 worldNormal;
+
+// the attachments of `GBuffer` in src/gbuffer.rs
+layout(location = 0) out vec4 albedoSpecular;
+layout(location = 1) out vec4 normalShininess;
+
+void main() {
+    // the specular color is kept as a single intensity
+    albedoSpecular = vec4(diffuseColor.rgb, dot(specularColor.rgb, vec3(1.0 / 3.0)));
+    normalShininess = vec4(normalize(worldNormal), shininess);
+}
@@ -0,0 +1,7 @@
+in vec4 vertexColor;
+
+out vec4 outputColor;
+
+void main() {
+    outputColor = vertexColor;
+}
@@ -0,0 +1,14 @@
+layout(location = 0) in vec3 position;
+layout(location = 2) in vec3 normal;
+
+uniform mat4 modelToWorld;
+uniform mat4 worldToClip;
+
+out vec4 vertexColor;
+
+void main() {
+    vec4 world = modelToWorld * vec4(position, 1.0);
+    vec3 worldNormal = normalize(mat3(transpose(inverse(modelToWorld))) * normal);
+    vertexColor = vec4(shade(world.xyz, worldNormal, diffuseColor.rgb), diffuseColor.a);
+    gl_Position = worldToClip * world;
+}
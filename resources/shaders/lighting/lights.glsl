@@ -0,0 +1,73 @@
+// Lights shared by the lighting shaders, laid out like `LightBlock` in src/lighting.rs
+
+const int MAX_LIGHTS = 8;
+
+struct Light {
+    // w is 0 for directional lights
+    vec4 position;
+    // the direction the light shines to, w is the cosine of the outer cone angle
+    vec4 direction;
+    // premultiplied by the intensity, w is the range, 0 for no falloff
+    vec4 color;
+    // x is the cosine of the inner cone angle
+    vec4 cone;
+};
+
+layout(std140) uniform Lights {
+    vec4 ambient;
+    vec4 cameraPosition;
+    ivec4 lightCount;
+    Light lights[MAX_LIGHTS];
+};
+
+uniform vec4 diffuseColor;
+uniform vec4 specularColor;
+uniform float shininess;
+
+// Color of a surface at `position` in world space, with Blinn-Phong specular when BLINN is defined
+vec3 shade(vec3 position, vec3 normal, vec3 diffuse, vec3 specularTint, float exponent) {
+    vec3 toCamera = normalize(cameraPosition.xyz - position);
+    vec3 color = ambient.rgb * diffuse;
+    for (int i = 0; i < lightCount.x; i++) {
+        Light light = lights[i];
+        vec3 toLight;
+        float attenuation = 1.0;
+        if (light.position.w == 0.0) {
+            toLight = -light.direction.xyz;
+        } else {
+            vec3 offset = light.position.xyz - position;
+            float distance = length(offset);
+            toLight = offset / distance;
+            if (light.color.w > 0.0) {
+                float falloff = clamp(1.0 - distance / light.color.w, 0.0, 1.0);
+                attenuation = falloff * falloff;
+            }
+            float cosAngle = dot(-toLight, light.direction.xyz);
+            attenuation *= clamp(
+                (cosAngle - light.direction.w) / max(light.cone.x - light.direction.w, 0.0001),
+                0.0,
+                1.0
+            );
+        }
+
+        float cosIncidence = max(dot(normal, toLight), 0.0);
+        float specular = 0.0;
+        if (cosIncidence > 0.0) {
+#ifdef BLINN
+            vec3 halfAngle = normalize(toLight + toCamera);
+            specular = pow(max(dot(normal, halfAngle), 0.0), exponent);
+#else
+            vec3 reflected = reflect(-toLight, normal);
+            specular = pow(max(dot(reflected, toCamera), 0.0), exponent);
+#endif
+        }
+        color += light.color.rgb * attenuation
+            * (diffuse * cosIncidence + specularTint * specular);
+    }
+    return color;
+}
+
+// `shade` with the specular color and shininess of the material uniforms
+vec3 shade(vec3 position, vec3 normal, vec3 diffuse) {
+    return shade(position, normal, diffuse, specularColor.rgb, shininess);
+}
@@ -0,0 +1,9 @@
+in vec3 worldPosition;
+in vec3 worldNormal;
+
+out vec4 outputColor;
+
+void main() {
+    vec3 color = shade(worldPosition, normalize(worldNormal), diffuseColor.rgb);
+    outputColor = vec4(color, diffuseColor.a);
+}
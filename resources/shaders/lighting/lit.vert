@@ -0,0 +1,15 @@
+layout(location = 0) in vec3 position;
+layout(location = 2) in vec3 normal;
+
+uniform mat4 modelToWorld;
+uniform mat4 worldToClip;
+
+out vec3 worldPosition;
+out vec3 worldNormal;
+
+void main() {
+    vec4 world = modelToWorld * vec4(position, 1.0);
+    worldPosition = world.xyz;
+    worldNormal = mat3(transpose(inverse(modelToWorld))) * normal;
+    gl_Position = worldToClip * world;
+}
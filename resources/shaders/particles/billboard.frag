@@ -0,0 +1,17 @@
+in vec2 corner;
+in float age;
+
+uniform vec4 startColor;
+uniform vec4 endColor;
+
+out vec4 outputColor;
+
+void main() {
+    // soft round particles
+    float falloff = 1.0 - dot(corner, corner);
+    if (falloff <= 0.0) {
+        discard;
+    }
+    vec4 color = mix(startColor, endColor, age);
+    outputColor = vec4(color.rgb, color.a * falloff);
+}
@@ -0,0 +1,22 @@
+// one instance per particle, the corners of the quad come from gl_VertexID
+layout(location = 0) in vec4 positionLife;
+layout(location = 1) in vec4 velocityLifetime;
+
+uniform mat4 worldToCamera;
+uniform mat4 cameraToClip;
+uniform float size;
+
+out vec2 corner;
+// 0 when spawned to 1 when dead
+out float age;
+
+void main() {
+    corner = vec2(gl_VertexID & 1, gl_VertexID >> 1) * 2.0 - 1.0;
+    float lifetime = velocityLifetime.w;
+    bool alive = lifetime > 0.0 && positionLife.w > 0.0;
+    age = alive ? 1.0 - positionLife.w / lifetime : 1.0;
+    // dead and not yet spawned particles collapse to nothing
+    vec4 cameraPosition = worldToCamera * vec4(positionLife.xyz, 1.0);
+    cameraPosition.xy += corner * (alive ? size * 0.5 : 0.0);
+    gl_Position = cameraToClip * cameraPosition;
+}
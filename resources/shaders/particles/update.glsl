@@ -0,0 +1,42 @@
+// Particle update shared by the transform feedback and compute paths of `ParticleSystem`
+// in src/particles.rs. A particle is two vec4, laid out like `Particle`.
+
+uniform float deltaTime;
+// seconds since the system started, seeds the respawns
+uniform float time;
+uniform vec3 emitterPosition;
+uniform vec3 gravity;
+uniform float speed;
+// cosine of the half angle of the cone around +y particles are emitted in
+uniform float spreadCos;
+uniform float lifetime;
+
+// Integer hash mapped to 0..1
+float random(uint seed) {
+    seed ^= seed >> 16;
+    seed *= 0x7feb352du;
+    seed ^= seed >> 15;
+    seed *= 0x846ca68bu;
+    seed ^= seed >> 16;
+    return float(seed) / 4294967295.0;
+}
+
+// `positionLife.w` is the seconds left, `velocityLifetime.w` the seconds the particle
+// was spawned with, 0 until its first spawn
+void updateParticle(inout vec4 positionLife, inout vec4 velocityLifetime, uint index) {
+    positionLife.w -= deltaTime;
+    if (positionLife.w > 0.0) {
+        velocityLifetime.xyz += gravity * deltaTime;
+        positionLife.xyz += velocityLifetime.xyz * deltaTime;
+        return;
+    }
+    // respawn at the emitter in a random direction of the cone
+    uint seed = index * 4u + uint(time * 1000.0) * 7919u;
+    float angle = random(seed) * 6.2831853;
+    float cosTheta = mix(1.0, spreadCos, random(seed + 1u));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 direction = vec3(cos(angle) * sinTheta, cosTheta, sin(angle) * sinTheta);
+    float particleLifetime = lifetime * mix(0.5, 1.0, random(seed + 2u));
+    positionLife = vec4(emitterPosition, particleLifetime);
+    velocityLifetime = vec4(direction * speed * mix(0.7, 1.0, random(seed + 3u)), particleLifetime);
+}
@@ -0,0 +1,12 @@
+layout(location = 0) in vec4 positionLife;
+layout(location = 1) in vec4 velocityLifetime;
+
+// captured into the other buffer by transform feedback
+out vec4 outPositionLife;
+out vec4 outVelocityLifetime;
+
+void main() {
+    outPositionLife = positionLife;
+    outVelocityLifetime = velocityLifetime;
+    updateParticle(outPositionLife, outVelocityLifetime, uint(gl_VertexID));
+}
@@ -1,41 +1,402 @@
-use glfw::{fail_on_errors, Action, Context, Key, Modifiers, PWindow};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-pub trait Application {
-    fn new(window: PWindow) -> Self;
+use glfw::{
+    Action, Context, Glfw, GlfwReceiver, InitError, Key, Modifiers, OpenGlProfileHint, PWindow,
+    SwapInterval, Window, WindowEvent,
+};
+
+use crate::{
+    handle,
+    input::Input,
+    opengl::{OpenGl, SRGB_FRAMEBUFFER},
+    viewport::Viewport,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("Failed to initialize GLFW: {0}")]
+    InitError(#[from] InitError),
+    #[error("Failed to create a window with any of the OpenGL versions {0:?}")]
+    WindowError(Vec<(u32, u32)>),
+    /// Returned by [`Application::new`]
+    #[error("Application error: {0}")]
+    ApplicationError(#[from] Box<dyn std::error::Error>),
+}
+
+pub trait Application: Sized {
+    fn new(window: PWindow) -> Result<Self, AppError>;
+    /// Called before [`Application::display`] with the seconds since the last update
+    /// and the input state.
+    fn update(&mut self, _dt: f32, _input: &Input) {}
+    /// Calls [`Application::update`] with fixed steps of this many seconds instead,
+    /// as many times as the elapsed time allows. The `just_*` input of frames without a
+    /// step is kept for the next step, and only the first step of a frame sees it.
+    fn fixed_timestep(&self) -> Option<f32> {
+        None
+    }
+    /// Called before [`Application::update`] with the timings of the last frame.
+    fn frame_stats(&mut self, _stats: &FrameStats) {}
     fn display(&mut self) {}
     fn keyboard(&mut self, _key: Key, _action: Action, _modifier: Modifiers) {}
-    fn reshape(&mut self, _width: i32, _height: i32) {}
+    /// Called once after [`Application::new`] and whenever the framebuffer is resized.
+    fn reshape(&mut self, _viewport: &Viewport) {}
+    /// Unicode characters typed in the main window, after keyboard layout and modifiers.
+    fn text_input(&mut self, _ch: char) {}
+    /// Files dropped onto the main window.
+    fn file_dropped(&mut self, _paths: Vec<PathBuf>) {}
     fn window(&self) -> &PWindow;
     fn window_mut(&mut self) -> &mut PWindow;
+
+    /// Windows opened after [`Application::new`], with ids counting up from 1 in order.
+    /// Their contexts share buffers, textures and programs with the main one,
+    /// but not VAOs or any other state, see [`OpenGl::invalidate_cache`].
+    fn secondary_windows(&self) -> Vec<WindowConfig> {
+        vec![]
+    }
+    /// Renders a secondary window, whose context is current during the call.
+    fn display_for(&mut self, _window: WindowId) {}
+    fn keyboard_for(
+        &mut self,
+        _window: WindowId,
+        _key: Key,
+        _action: Action,
+        _modifier: Modifiers,
+    ) {
+    }
+    /// Called with the context of the secondary window current.
+    fn reshape_for(&mut self, _window: WindowId, _viewport: &Viewport) {}
+}
+
+/// Identifies the windows opened by [`run_app`], see [`Application::secondary_windows`]
+pub type WindowId = usize;
+/// The window given to [`Application::new`]
+pub const MAIN_WINDOW: WindowId = 0;
+
+/// A secondary window, see [`Application::secondary_windows`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: (u32, u32),
+}
+
+struct SecondaryWindow {
+    id: WindowId,
+    window: PWindow,
+    events: GlfwReceiver<(f64, WindowEvent)>,
+}
+
+fn open_secondary_windows<A: Application>(app: &mut A) -> Vec<SecondaryWindow> {
+    let mut windows = vec![];
+    for (i, config) in app.secondary_windows().into_iter().enumerate() {
+        let (width, height) = config.size;
+        let Some((mut window, events)) =
+            app.window()
+                .create_shared(width, height, &config.title, glfw::WindowMode::Windowed)
+        else {
+            log::error!("Failed to create the window {:?}", config.title);
+            continue;
+        };
+        window.set_key_polling(true);
+        window.set_framebuffer_size_polling(true);
+        let id = i + 1;
+        window.make_current();
+        let (width, height) = window.get_framebuffer_size();
+        app.reshape_for(id, &Viewport::new(width, height));
+        windows.push(SecondaryWindow { id, window, events });
+    }
+    app.window_mut().make_current();
+    windows
+}
+
+fn process_secondary_events<A: Application>(app: &mut A, windows: &mut Vec<SecondaryWindow>) {
+    for SecondaryWindow { id, window, events } in windows.iter_mut() {
+        for (_, event) in glfw::flush_messages(events) {
+            match event {
+                WindowEvent::Key(key, _, action, modifier) => {
+                    app.keyboard_for(*id, key, action, modifier);
+                }
+                WindowEvent::FramebufferSize(width, height) => {
+                    window.make_current();
+                    app.reshape_for(*id, &Viewport::new(width, height));
+                    app.window_mut().make_current();
+                }
+                _ => {}
+            }
+        }
+    }
+    let (closed, open) = std::mem::take(windows)
+        .into_iter()
+        .partition(|secondary| secondary.window.should_close());
+    *windows = open;
+    close_secondary_windows(app, closed);
+}
+
+/// Deletes what was dropped with their context current, before destroying them
+fn close_secondary_windows<A: Application>(app: &mut A, windows: Vec<SecondaryWindow>) {
+    if windows.is_empty() {
+        return;
+    }
+    for mut secondary in windows {
+        secondary.window.make_current();
+        handle::flush_deletions();
+        let context = secondary.window.window_ptr();
+        drop(secondary);
+        handle::forget_context(context);
+    }
+    app.window_mut().make_current();
+}
+
+fn display_secondary<A: Application>(app: &mut A, windows: &mut [SecondaryWindow]) {
+    if windows.is_empty() {
+        return;
+    }
+    for SecondaryWindow { id, window, .. } in windows {
+        window.make_current();
+        app.display_for(*id);
+        window.swap_buffers();
+        handle::flush_deletions();
+    }
+    app.window_mut().make_current();
+}
+
+/// Timings measured by [`run_app`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub fps: f32,
+    /// Time between the start of the last frame and the current one
+    pub frame_time: Duration,
+    /// Time spent updating and displaying the last frame, without waiting for the swap
+    pub cpu_time: Duration,
+}
+
+/// The window and context created by [`run_app_with_config`]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct AppConfig {
+    pub title: String,
+    /// Width and height in screen coordinates
+    pub size: (u32, u32),
+    pub resizable: bool,
+    /// Samples per pixel of the default framebuffer, `None` to disable multisampling
+    pub msaa_samples: Option<u32>,
+    pub vsync: bool,
+    /// Sleeps at the end of each frame to stay under this frame rate
+    pub max_fps: Option<f32>,
+    /// Tried in order until a context can be created
+    pub gl_version: Vec<(u32, u32)>,
+    pub profile: OpenGlProfileHint,
+    pub debug: bool,
+    /// Covers the primary monitor at `size` instead of opening a window
+    pub fullscreen: bool,
+    /// Letterboxes the main viewport to this width over height, see [`Viewport`]
+    pub aspect_ratio: Option<f32>,
+    /// Requests an sRGB default framebuffer and enables `FRAMEBUFFER_SRGB` in the
+    /// [`OpenGl`] created in [`Application::new`], so linear colors written by shaders are
+    /// gamma corrected on screen. Colors picked in sRGB then need converting, see
+    /// [`crate::color::srgb`], and color textures an sRGB format, see
+    /// [`crate::color::color_texture_format`].
+    pub srgb: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            title: "OpenGl".to_owned(),
+            size: (600, 600),
+            resizable: true,
+            msaa_samples: None,
+            vsync: true,
+            max_fps: None,
+            gl_version: vec![(4, 6), (4, 3), (3, 3)],
+            profile: OpenGlProfileHint::Core,
+            debug: true,
+            fullscreen: false,
+            aspect_ratio: None,
+            srgb: false,
+        }
+    }
+}
+
+/// The context that was actually created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextInfo {
+    pub version: (u32, u32),
+    pub profile: OpenGlProfileHint,
+    pub debug: bool,
+}
+
+impl ContextInfo {
+    #[must_use]
+    pub fn from_window(window: &Window) -> Self {
+        let version = window.get_context_version();
+        let profile = match window.get_opengl_profile() {
+            p if p == OpenGlProfileHint::Core as i32 => OpenGlProfileHint::Core,
+            p if p == OpenGlProfileHint::Compat as i32 => OpenGlProfileHint::Compat,
+            _ => OpenGlProfileHint::Any,
+        };
+        Self {
+            version: (
+                u32::try_from(version.major).unwrap_or_default(),
+                u32::try_from(version.minor).unwrap_or_default(),
+            ),
+            profile,
+            debug: window.is_opengl_debug_context(),
+        }
+    }
+
+    #[must_use]
+    pub fn supports(&self, major: u32, minor: u32) -> bool {
+        self.version >= (major, minor)
+    }
+    /// Debug output is core since 4.3
+    #[must_use]
+    pub fn has_debug_output(&self) -> bool {
+        self.debug && self.supports(4, 3)
+    }
+    /// Compute shaders are core since 4.3
+    #[must_use]
+    pub fn has_compute(&self) -> bool {
+        self.supports(4, 3)
+    }
+    /// Clip control, needed for [`OpenGl::set_reverse_z`], is core since 4.5
+    #[must_use]
+    pub fn has_clip_control(&self) -> bool {
+        self.supports(4, 5)
+    }
 }
 
-#[allow(clippy::unwrap_used)]
-#[allow(clippy::expect_used)]
-pub fn run_app<A: Application>() {
-    let mut glfw = glfw::init(fail_on_errors!()).unwrap();
+/// An invisible window whose context stays current while it's alive,
+/// created by [`OpenGl::new_headless`].
+pub struct HeadlessContext {
+    glfw: Glfw,
+    window: PWindow,
+}
+
+/// An invisible `width` by `height` window with a 4.3 core context, made current
+pub(crate) fn hidden_window(width: u32, height: u32) -> Result<(Glfw, PWindow), AppError> {
+    let mut glfw = glfw::init(|error, description| {
+        log::error!("GLFW error {error:?}: {description}");
+    })?;
+    glfw.window_hint(glfw::WindowHint::Visible(false));
     glfw.window_hint(glfw::WindowHint::ContextVersion(4, 3));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-        glfw::OpenGlProfileHint::Core,
-    ));
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
     glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));
+    let (mut window, _) = glfw
+        .create_window(width, height, "", glfw::WindowMode::Windowed)
+        .ok_or_else(|| AppError::WindowError(vec![(4, 3)]))?;
+    window.make_current();
+    Ok((glfw, window))
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        // the window is destroyed right after, taking the objects still queued in it
+        handle::forget_context(self.window.window_ptr());
+    }
+}
+
+impl HeadlessContext {
+    pub(crate) fn new() -> Result<Self, AppError> {
+        let (glfw, window) = hidden_window(1, 1)?;
+        Ok(Self { glfw, window })
+    }
+
+    #[must_use]
+    pub const fn window(&self) -> &PWindow {
+        &self.window
+    }
+    pub fn window_mut(&mut self) -> &mut PWindow {
+        &mut self.window
+    }
+
+    /// Finishes a frame, like [`run_app`] does after [`Application::display`].
+    pub fn swap_buffers(&mut self) {
+        self.window.swap_buffers();
+        self.glfw.poll_events();
+    }
+}
+
+/// Calls `frame` with the frame index `frames` times on a headless context.
+pub fn run_headless(
+    frames: usize,
+    mut frame: impl FnMut(&mut OpenGl, usize),
+) -> Result<(), AppError> {
+    let (mut gl, mut context) = OpenGl::new_headless()?;
+    for i in 0..frames {
+        frame(&mut gl, i);
+        context.swap_buffers();
+        gl.flush_deletions();
+    }
+    Ok(())
+}
+
+pub fn run_app<A: Application>() -> Result<(), AppError> {
+    run_app_with_config::<A>(&AppConfig::default())
+}
 
-    // Create a windowed mode window and its OpenGL context
-    let (mut window, events) = glfw
-        .create_window(600, 600, "OpenGl", glfw::WindowMode::Windowed)
-        .expect("Failed to create GLFW window.");
+pub fn run_app_with_config<A: Application>(config: &AppConfig) -> Result<(), AppError> {
+    // failing to create a context is expected while falling back to older versions
+    let mut glfw = glfw::init(|error, description| {
+        log::warn!("GLFW error {error:?}: {description}");
+    })?;
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(config.profile));
+    glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(config.debug));
+    glfw.window_hint(glfw::WindowHint::Resizable(config.resizable));
+    glfw.window_hint(glfw::WindowHint::Samples(config.msaa_samples));
+    glfw.window_hint(glfw::WindowHint::SRgbCapable(config.srgb));
+    SRGB_FRAMEBUFFER.set(config.srgb);
+
+    // Create a window and its OpenGL context
+    let (width, height) = config.size;
+    let (mut window, events) = config
+        .gl_version
+        .iter()
+        .find_map(|&(major, minor)| {
+            glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+            glfw.with_primary_monitor(|glfw, monitor| {
+                let mode = match monitor {
+                    Some(monitor) if config.fullscreen => glfw::WindowMode::FullScreen(monitor),
+                    _ => glfw::WindowMode::Windowed,
+                };
+                glfw.create_window(width, height, &config.title, mode)
+            })
+        })
+        .ok_or_else(|| AppError::WindowError(config.gl_version.clone()))?;
 
     // Make the window's context current
     window.make_current();
+    glfw.set_swap_interval(swap_interval(config.vsync));
+    log::info!("Created context {:?}", ContextInfo::from_window(&window));
     window.set_key_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_cursor_pos_polling(true);
     window.set_framebuffer_size_polling(true);
-    let (width, heigth) = window.get_size();
-    let mut app = A::new(window);
-    app.reshape(width, heigth);
+    window.set_drag_and_drop_polling(true);
+    window.set_char_polling(true);
+    let (width, height) = window.get_framebuffer_size();
+    let mut viewport = Viewport::new(width, height).with_fixed_aspect_ratio(config.aspect_ratio);
+    let mut app = A::new(window)?;
+    app.reshape(&viewport);
+    let mut secondary_windows = open_secondary_windows(&mut app);
 
+    let frame_target = config
+        .max_fps
+        .filter(|fps| *fps > 0.0)
+        .and_then(|fps| Duration::try_from_secs_f32(fps.recip()).ok());
+    let mut stats = FrameStats::default();
+    let mut input = Input::default();
+    let mut frame_start = Instant::now();
+    // time not yet consumed by fixed steps
+    let mut accumulator = 0.0;
     // Loop until the user closes the window
     while !app.window().should_close() {
         // process events
         for (_, event) in glfw::flush_messages(&events) {
+            input.handle_event(&event);
             match event {
                 glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
                     app.window_mut().set_should_close(true);
@@ -44,18 +405,86 @@ pub fn run_app<A: Application>() {
                     app.keyboard(key, action, modifier);
                 }
 
-                glfw::WindowEvent::FramebufferSize(width, height) => app.reshape(width, height),
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    viewport.resize(width, height);
+                    app.reshape(&viewport);
+                }
+                glfw::WindowEvent::Char(ch) => app.text_input(ch),
+                glfw::WindowEvent::FileDrop(paths) => app.file_dropped(paths),
                 _ => {}
             }
         }
+        process_secondary_events(&mut app, &mut secondary_windows);
+        input.poll_gamepad(&glfw);
+
+        let now = Instant::now();
+        stats.frame_time = now - frame_start;
+        stats.fps = stats.frame_time.as_secs_f32().recip();
+        frame_start = now;
+        app.frame_stats(&stats);
+
+        let dt = stats.frame_time.as_secs_f32();
+        if let Some(step) = app.fixed_timestep().filter(|step| *step > 0.0) {
+            accumulator += dt;
+            #[allow(clippy::while_float)]
+            while accumulator >= step {
+                app.update(step, &input);
+                accumulator -= step;
+                input.end_frame();
+            }
+        } else {
+            app.update(dt, &input);
+            input.end_frame();
+        }
 
         // render
         app.display();
+        stats.cpu_time = frame_start.elapsed();
 
         // Swap front and back buffers
         app.window_mut().swap_buffers();
+        display_secondary(&mut app, &mut secondary_windows);
+        // the application's `OpenGl` forgets its bindings of the deleted objects on next use
+        handle::flush_deletions();
+
+        if let Some(remaining) = frame_target.and_then(|t| t.checked_sub(frame_start.elapsed())) {
+            std::thread::sleep(remaining);
+        }
 
         // Poll for and process events
         glfw.poll_events();
     }
+    close_secondary_windows(&mut app, secondary_windows);
+    let context = app.window().window_ptr();
+    drop(app);
+    handle::forget_context(context);
+    Ok(())
+}
+
+pub(crate) const fn swap_interval(vsync: bool) -> SwapInterval {
+    if vsync {
+        SwapInterval::Sync(1)
+    } else {
+        SwapInterval::None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glfw::OpenGlProfileHint;
+
+    use super::ContextInfo;
+
+    #[test]
+    fn test_context_info() {
+        let info = ContextInfo {
+            version: (3, 3),
+            profile: OpenGlProfileHint::Core,
+            debug: true,
+        };
+        assert!(info.supports(3, 0));
+        assert!(!info.supports(4, 1));
+        assert!(!info.has_compute());
+        assert!(!info.has_debug_output());
+    }
 }
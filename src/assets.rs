@@ -0,0 +1,198 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CString, NulError},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use thiserror::Error;
+
+use crate::{
+    image::{ImageError, RgbaImage},
+    mesh::{Mesh, MeshCpuData, MeshError},
+    opengl::OpenGl,
+    program::{Shader, ShaderType},
+    texture::{Texture2D, TextureError, TextureParams},
+};
+
+type AssetResult<T> = Result<T, AssetError>;
+
+#[derive(Error, Debug)]
+pub enum AssetError {
+    #[error("Input error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Mesh error: {0}")]
+    MeshError(#[from] MeshError),
+    #[error("Shader source contains a nul byte: {0}")]
+    NulError(#[from] NulError),
+    #[error("Shader compile error in {0:?}: {1:?}")]
+    ShaderError(PathBuf, CString),
+    #[error("Texture error: {0}")]
+    TextureError(#[from] TextureError),
+    #[error("Image error: {0}")]
+    ImageError(#[from] ImageError),
+}
+
+pub type MeshHandle = Rc<RefCell<Mesh>>;
+pub type ShaderHandle = Rc<Shader>;
+pub type TextureHandle = Rc<Texture2D>;
+
+/// The same file sampled differently is a different texture
+struct CachedTexture {
+    srgb: bool,
+    params: TextureParams,
+    texture: TextureHandle,
+}
+
+/// Paths referring to the same file share the same key, even when written differently
+fn key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Loads each asset once and hands out shared handles to it. Invalidated assets are loaded
+/// again on the next request, handles given out before keep the old asset alive.
+#[derive(Default)]
+pub struct AssetCache {
+    meshes: HashMap<PathBuf, MeshHandle>,
+    /// The same file compiled as another stage is another shader
+    shaders: HashMap<(PathBuf, ShaderType), ShaderHandle>,
+    textures: HashMap<PathBuf, Vec<CachedTexture>>,
+}
+
+impl AssetCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a mesh in any format supported by [`MeshCpuData::load`].
+    pub fn mesh(&mut self, gl: &OpenGl, path: impl AsRef<Path>) -> AssetResult<MeshHandle> {
+        let key = key(path.as_ref());
+        if let Some(mesh) = self.meshes.get(&key) {
+            return Ok(Rc::clone(mesh));
+        }
+        let mesh = Mesh::upload(gl, MeshCpuData::load(&key)?)?;
+        let mesh = Rc::new(RefCell::new(mesh));
+        self.meshes.insert(key, Rc::clone(&mesh));
+        Ok(mesh)
+    }
+
+    pub fn shader(
+        &mut self,
+        path: impl AsRef<Path>,
+        shader_type: ShaderType,
+    ) -> AssetResult<ShaderHandle> {
+        let key = (key(path.as_ref()), shader_type);
+        if let Some(shader) = self.shaders.get(&key) {
+            return Ok(Rc::clone(shader));
+        }
+        let source = CString::new(std::fs::read(&key.0)?)?;
+        let shader = Shader::new(&source, shader_type)
+            .map_err(|error| AssetError::ShaderError(key.0.clone(), error))?;
+        let shader = Rc::new(shader);
+        self.shaders.insert(key, Rc::clone(&shader));
+        Ok(shader)
+    }
+
+    /// Loads a binary PAM image, see [`RgbaImage::read_pam`], or with the `ktx` feature a
+    /// `.ktx` or `.ktx2` file, whose format already tells whether it's sRGB.
+    pub fn texture(
+        &mut self,
+        path: impl AsRef<Path>,
+        srgb: bool,
+        params: &TextureParams,
+    ) -> AssetResult<TextureHandle> {
+        let key = key(path.as_ref());
+        let cached = self.textures.entry(key).or_default();
+        if let Some(cached) = cached
+            .iter()
+            .find(|cached| cached.srgb == srgb && cached.params == *params)
+        {
+            return Ok(Rc::clone(&cached.texture));
+        }
+        let texture = Rc::new(load_texture(path.as_ref(), srgb, params)?);
+        cached.push(CachedTexture {
+            srgb,
+            params: *params,
+            texture: Rc::clone(&texture),
+        });
+        Ok(texture)
+    }
+
+    #[must_use]
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        let key = key(path.as_ref());
+        self.meshes.contains_key(&key)
+            || self.shaders.keys().any(|(path, _)| *path == key)
+            || self
+                .textures
+                .get(&key)
+                .is_some_and(|cached| !cached.is_empty())
+    }
+
+    /// Forgets the asset at `path`, with every params its texture was loaded with and
+    /// every stage its shader was compiled as, returns whether there was one.
+    pub fn invalidate(&mut self, path: impl AsRef<Path>) -> bool {
+        let key = key(path.as_ref());
+        let mesh = self.meshes.remove(&key);
+        let shaders = self.shaders.len();
+        self.shaders.retain(|(path, _), _| *path != key);
+        let textures = self.textures.remove(&key);
+        mesh.is_some()
+            || self.shaders.len() < shaders
+            || textures.is_some_and(|cached| !cached.is_empty())
+    }
+
+    pub fn clear(&mut self) {
+        self.meshes.clear();
+        self.shaders.clear();
+        self.textures.clear();
+    }
+}
+
+fn load_texture(path: &Path, srgb: bool, params: &TextureParams) -> AssetResult<Texture2D> {
+    #[cfg(feature = "ktx")]
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "ktx" || extension == "ktx2")
+    {
+        return Ok(Texture2D::from_ktx(&std::fs::read(path)?, params)?);
+    }
+    let image = RgbaImage::load(path)?;
+    // the image rows are from the top, the texture's from the bottom
+    let row = image.width() as usize * 4;
+    let pixels: Vec<u8> = image
+        .pixels()
+        .chunks(row)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    Ok(Texture2D::from_rgba(
+        image.width(),
+        image.height(),
+        &pixels,
+        srgb,
+        params,
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::key;
+
+    #[test]
+    fn test_key() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/UnitCube.xml");
+        let roundabout = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/../resources/test/./UnitCube.xml"
+        );
+        assert_eq!(key(Path::new(path)), key(Path::new(roundabout)));
+        // missing files are kept as they are
+        assert_eq!(key(Path::new("missing.xml")), Path::new("missing.xml"));
+    }
+}
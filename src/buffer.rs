@@ -1,10 +1,27 @@
-use std::marker::PhantomData;
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
 
+use bytemuck::Pod;
 use gl::types::{GLenum, GLintptr, GLsizeiptr, GLuint};
+use glam::{Vec2, Vec3, Vec4};
+use thiserror::Error;
 
-use crate::{GLHandle, NULL_HANDLE};
+use crate::{
+    gl_api,
+    handle::{self, delete_later, BufferId, ContextBound, GlObject},
+    GLHandle, NULL_HANDLE,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Binds buffer `id` to `target` without [`crate::opengl::OpenGl`], which then forgets
+/// its cached buffer bindings
+pub(crate) fn bind_directly(target: GLenum, id: GLHandle) {
+    handle::bound_directly();
+    unsafe { gl::BindBuffer(target, id) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum Target {
     ArrayBuffer = gl::ARRAY_BUFFER,
@@ -24,7 +41,7 @@ pub enum Target {
     UniformBuffer = gl::UNIFORM_BUFFER,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum Usage {
     StaticDraw = gl::STATIC_DRAW,
@@ -39,15 +56,63 @@ pub enum Usage {
     DynamicCopy = gl::DYNAMIC_COPY,
 }
 
+type BufferResult<T> = Result<T, BufferError>;
+
+#[derive(Error, Debug)]
+pub enum BufferError {
+    #[error("Out of range access: offset {offset} size {size}, buffer size {buffer_size}")]
+    OutOfRange {
+        offset: isize,
+        size: usize,
+        buffer_size: usize,
+    },
+    #[error("Could not map buffer")]
+    MapFailed,
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+pub(crate) trait ClearValue: private::Sealed {
+    const INTERNAL_FORMAT: GLenum;
+    const FORMAT: GLenum;
+    const DATA_TYPE: GLenum;
+}
+
+macro_rules! impl_clear_value {
+    ($t:ty, $internal_format:expr, $format:expr, $data_type:expr) => {
+        impl private::Sealed for $t {}
+        impl ClearValue for $t {
+            const INTERNAL_FORMAT: GLenum = $internal_format;
+            const FORMAT: GLenum = $format;
+            const DATA_TYPE: GLenum = $data_type;
+        }
+    };
+}
+
+impl_clear_value!(u8, gl::R8UI, gl::RED_INTEGER, gl::UNSIGNED_BYTE);
+impl_clear_value!(u16, gl::R16UI, gl::RED_INTEGER, gl::UNSIGNED_SHORT);
+impl_clear_value!(u32, gl::R32UI, gl::RED_INTEGER, gl::UNSIGNED_INT);
+impl_clear_value!(i32, gl::R32I, gl::RED_INTEGER, gl::INT);
+impl_clear_value!(f32, gl::R32F, gl::RED, gl::FLOAT);
+impl_clear_value!(Vec2, gl::RG32F, gl::RG, gl::FLOAT);
+impl_clear_value!(Vec3, gl::RGB32F, gl::RGB, gl::FLOAT);
+impl_clear_value!(Vec4, gl::RGBA32F, gl::RGBA, gl::FLOAT);
+
 pub struct Buffer<T: Default> {
     id: GLHandle,
     target: Target,
+    // allocated size in bytes
+    size: usize,
+    usage: Option<Usage>,
     phantom: PhantomData<T>,
+    _context: ContextBound,
 }
 
 impl<T: Default> Drop for Buffer<T> {
     fn drop(&mut self) {
-        unsafe { gl::DeleteBuffers(1, &self.id) }
+        delete_later(GlObject::Buffer(self.id));
     }
 }
 
@@ -56,14 +121,60 @@ impl<T: Default> Buffer<T> {
     pub fn new(target: Target) -> Self {
         let mut id = NULL_HANDLE;
         unsafe { gl::GenBuffers(1, &mut id) };
+        gl_api::trace_call("glGenBuffers", || format!("{target:?} -> {id}"));
         Self {
             id,
             target,
+            size: 0,
+            usage: None,
             phantom: PhantomData,
+            _context: PhantomData,
         }
     }
+
+    #[must_use]
+    pub const fn id(&self) -> BufferId {
+        BufferId(self.id)
+    }
+
+    #[must_use]
+    pub const fn target(&self) -> Target {
+        self.target
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.size / std::mem::size_of::<T>()
+    }
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+    #[must_use]
+    pub const fn byte_len(&self) -> usize {
+        self.size
+    }
+    #[must_use]
+    pub const fn usage(&self) -> Option<Usage> {
+        self.usage
+    }
+
+    fn check_range(&self, offset: isize, size: usize) -> BufferResult<()> {
+        if offset < 0 || offset as usize + size > self.size {
+            return Err(BufferError::OutOfRange {
+                offset,
+                size,
+                buffer_size: self.size,
+            });
+        }
+        Ok(())
+    }
+
     pub fn reserve_data(&mut self, size: isize, usage: Usage) {
         let size_bytes = size * std::mem::size_of::<T>() as isize;
+        self.size = size_bytes as usize;
+        self.usage = Some(usage);
+        self.trace("glBufferData", || format!("{size_bytes}, null, {usage:?}"));
         unsafe {
             gl::BufferData(
                 self.target as GLenum,
@@ -74,6 +185,9 @@ impl<T: Default> Buffer<T> {
         };
     }
     pub fn reserve_data_bytes(&mut self, size: GLsizeiptr, usage: Usage) {
+        self.size = size as usize;
+        self.usage = Some(usage);
+        self.trace("glBufferData", || format!("{size}, null, {usage:?}"));
         unsafe {
             gl::BufferData(
                 self.target as GLenum,
@@ -85,6 +199,9 @@ impl<T: Default> Buffer<T> {
     }
 
     pub fn buffer_data(&mut self, data: &[T], usage: Usage) {
+        self.size = std::mem::size_of_val(data);
+        self.usage = Some(usage);
+        self.trace("glBufferData", || format!("{}, data, {usage:?}", self.size));
         unsafe {
             gl::BufferData(
                 self.target as GLenum,
@@ -94,15 +211,18 @@ impl<T: Default> Buffer<T> {
             );
         };
     }
-    pub fn get_data(&mut self, offset: isize, size: usize) -> Vec<T> {
-        let mut data: Vec<T> = vec![];
-        for _ in 0..size {
-            data.push(T::default());
-        }
-
+    pub fn get_data(&mut self, offset: isize, size: usize) -> BufferResult<Vec<T>>
+    where
+        T: Pod,
+    {
         let size_bytes = size * std::mem::size_of::<T>();
         let offset_bytes = offset * std::mem::size_of::<T>() as isize;
-        dbg!(size_bytes);
+        self.check_range(offset_bytes, size_bytes)?;
+
+        let mut data = vec![T::zeroed(); size];
+        self.trace("glGetBufferSubData", || {
+            format!("{offset_bytes}, {size_bytes}")
+        });
         unsafe {
             gl::GetBufferSubData(
                 self.target as GLenum,
@@ -111,11 +231,14 @@ impl<T: Default> Buffer<T> {
                 data.as_mut_ptr().cast(),
             );
         };
-        data
+        Ok(data)
     }
-    pub fn update_data(&mut self, data: &[T], offset: isize) {
+    pub fn update_data(&mut self, data: &[T], offset: isize) -> BufferResult<()> {
         let offset_bytes = offset * std::mem::size_of::<T>() as isize;
-
+        self.check_range(offset_bytes, std::mem::size_of_val(data))?;
+        self.trace("glBufferSubData", || {
+            format!("{offset_bytes}, {}", std::mem::size_of_val(data))
+        });
         unsafe {
             gl::BufferSubData(
                 self.target as GLenum,
@@ -124,12 +247,21 @@ impl<T: Default> Buffer<T> {
                 data.as_ptr().cast(),
             );
         };
+        Ok(())
     }
 
-    pub fn update_data_bytes(&mut self, data: &[u8], size: GLsizeiptr, offset: GLintptr) {
+    pub fn update_data_bytes(
+        &mut self,
+        data: &[u8],
+        size: GLsizeiptr,
+        offset: GLintptr,
+    ) -> BufferResult<()> {
+        self.check_range(offset, size as usize)?;
+        self.trace("glBufferSubData", || format!("{offset}, {size}"));
         unsafe {
             gl::BufferSubData(self.target as GLenum, offset, size, data.as_ptr().cast());
         };
+        Ok(())
     }
 
     pub fn bind_range(&mut self, binding_index: GLuint, offset: isize, size: usize) {
@@ -141,7 +273,11 @@ impl<T: Default> Buffer<T> {
         );
         let size_bytes = size * std::mem::size_of::<T>();
         let offset_bytes = offset * std::mem::size_of::<T>() as isize;
-
+        self.trace("glBindBufferRange", || {
+            format!("{binding_index}, {offset_bytes}, {size_bytes}")
+        });
+        // the generic binding of the target changes too
+        handle::bound_directly();
         unsafe {
             gl::BindBufferRange(
                 self.target as GLenum,
@@ -159,13 +295,254 @@ impl<T: Default> Buffer<T> {
                 || self.target == Target::UniformBuffer
                 || self.target == Target::ShaderStorageBuffer
         );
+        self.trace("glBindBufferRange", || {
+            format!("{binding_index}, {offset}, {size}")
+        });
+        handle::bound_directly();
         unsafe { gl::BindBufferRange(self.target as GLenum, binding_index, self.id, offset, size) };
     }
+    pub fn bind_base(&mut self, binding_index: GLuint) {
+        debug_assert!(
+            self.target == Target::AtomicCounterBuffer
+                || self.target == Target::TransformFeedbackBuffer
+                || self.target == Target::UniformBuffer
+                || self.target == Target::ShaderStorageBuffer
+        );
+        self.trace("glBindBufferBase", || binding_index.to_string());
+        handle::bound_directly();
+        unsafe { gl::BindBufferBase(self.target as GLenum, binding_index, self.id) };
+    }
+
+    /// Maps the whole buffer for reading, it gets unmapped when the guard is dropped.
+    pub fn map(&mut self) -> BufferResult<MappedBuffer<'_, T>>
+    where
+        T: Pod,
+    {
+        let ptr = self.map_range(gl::MAP_READ_BIT)?;
+        Ok(MappedBuffer { buffer: self, ptr })
+    }
+    /// Maps the whole buffer for reading and writing, it gets unmapped when the guard is dropped.
+    pub fn map_mut(&mut self) -> BufferResult<MappedBufferMut<'_, T>>
+    where
+        T: Pod,
+    {
+        let ptr = self.map_range(gl::MAP_READ_BIT | gl::MAP_WRITE_BIT)?;
+        Ok(MappedBufferMut { buffer: self, ptr })
+    }
+    // mapped through the copy write target, like the copies, to leave the bindings of
+    // `OpenGl` alone
+    fn map_range(&mut self, access: GLenum) -> BufferResult<*mut T> {
+        self.trace("glMapBufferRange", || {
+            format!("0, {}, {access:#x}", self.size)
+        });
+        let ptr = unsafe {
+            bind_directly(gl::COPY_WRITE_BUFFER, self.id);
+            let ptr = gl::MapBufferRange(gl::COPY_WRITE_BUFFER, 0, self.size as GLsizeiptr, access);
+            bind_directly(gl::COPY_WRITE_BUFFER, NULL_HANDLE);
+            ptr
+        };
+        if ptr.is_null() {
+            return Err(BufferError::MapFailed);
+        }
+        Ok(ptr.cast())
+    }
+    fn unmap(&mut self) {
+        self.trace("glUnmapBuffer", String::new);
+        unsafe {
+            bind_directly(gl::COPY_WRITE_BUFFER, self.id);
+            gl::UnmapBuffer(gl::COPY_WRITE_BUFFER);
+            bind_directly(gl::COPY_WRITE_BUFFER, NULL_HANDLE);
+        };
+    }
+
+    pub fn copy_to(
+        &mut self,
+        other: &mut Self,
+        src_offset: isize,
+        dst_offset: isize,
+        len: usize,
+    ) -> BufferResult<()> {
+        let size = std::mem::size_of::<T>();
+        self.copy_to_bytes(
+            other,
+            src_offset * size as isize,
+            dst_offset * size as isize,
+            (len * size) as GLsizeiptr,
+        )
+    }
+
+    pub fn copy_to_bytes<U: Default>(
+        &mut self,
+        other: &mut Buffer<U>,
+        src_offset: GLintptr,
+        dst_offset: GLintptr,
+        size: GLsizeiptr,
+    ) -> BufferResult<()> {
+        self.check_range(src_offset, size as usize)?;
+        other.check_range(dst_offset, size as usize)?;
+        self.trace("glCopyBufferSubData", || {
+            format!("{}, {src_offset}, {dst_offset}, {size}", other.id)
+        });
+        // the copy targets are only used for this, so binding to them leaves the rest of the state alone
+        unsafe {
+            bind_directly(gl::COPY_READ_BUFFER, self.id);
+            bind_directly(gl::COPY_WRITE_BUFFER, other.id);
+            gl::CopyBufferSubData(
+                gl::COPY_READ_BUFFER,
+                gl::COPY_WRITE_BUFFER,
+                src_offset,
+                dst_offset,
+                size,
+            );
+            bind_directly(gl::COPY_READ_BUFFER, NULL_HANDLE);
+            bind_directly(gl::COPY_WRITE_BUFFER, NULL_HANDLE);
+        };
+        Ok(())
+    }
+
+    #[allow(private_bounds)]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn clear<V: ClearValue>(&mut self, value: V) {
+        self.trace("glClearBufferData", String::new);
+        unsafe {
+            gl::ClearBufferData(
+                self.target as GLenum,
+                V::INTERNAL_FORMAT,
+                V::FORMAT,
+                V::DATA_TYPE,
+                std::ptr::from_ref(&value).cast(),
+            );
+        };
+    }
 
     pub fn bind(&mut self) {
-        unsafe { gl::BindBuffer(self.target as GLenum, self.id) };
+        self.trace("glBindBuffer", String::new);
+        bind_directly(self.target as GLenum, self.id);
     }
     pub fn unbind(&mut self) {
-        unsafe { gl::BindBuffer(self.target as GLenum, NULL_HANDLE) };
+        gl_api::trace_call("glBindBuffer", || format!("{:?}, 0", self.target));
+        bind_directly(self.target as GLenum, NULL_HANDLE);
+    }
+
+    /// Records a call on this buffer for [`crate::opengl::OpenGl::set_tracing`], after its
+    /// target and name
+    fn trace(&self, function: &'static str, args: impl FnOnce() -> String) {
+        gl_api::trace_call(function, || {
+            let args = args();
+            let separator = if args.is_empty() { "" } else { ", " };
+            format!("{:?}, {}{separator}{args}", self.target, self.id)
+        });
+    }
+    /// Binds the buffer and returns a guard that unbinds it when dropped.
+    pub fn bind_scoped(&mut self) -> BoundBuffer<'_, T> {
+        BoundBuffer::new(self)
+    }
+}
+
+pub struct BoundBuffer<'a, T: Default> {
+    pub buffer: &'a mut Buffer<T>,
+}
+
+impl<T: Default> Drop for BoundBuffer<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.unbind();
+    }
+}
+
+impl<'a, T: Default> BoundBuffer<'a, T> {
+    pub fn new(buffer: &'a mut Buffer<T>) -> Self {
+        buffer.bind();
+        Self { buffer }
+    }
+}
+
+impl<T: Default> Deref for BoundBuffer<'_, T> {
+    type Target = Buffer<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.buffer
+    }
+}
+
+impl<T: Default> DerefMut for BoundBuffer<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buffer
+    }
+}
+
+pub struct MappedBuffer<'a, T: Default + Pod> {
+    buffer: &'a mut Buffer<T>,
+    ptr: *mut T,
+}
+
+impl<T: Default + Pod> Drop for MappedBuffer<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.unmap();
+    }
+}
+
+impl<T: Default + Pod> Deref for MappedBuffer<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.buffer.len()) }
+    }
+}
+
+pub struct MappedBufferMut<'a, T: Default + Pod> {
+    buffer: &'a mut Buffer<T>,
+    ptr: *mut T,
+}
+
+impl<T: Default + Pod> Drop for MappedBufferMut<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.unmap();
+    }
+}
+
+impl<T: Default + Pod> Deref for MappedBufferMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.buffer.len()) }
+    }
+}
+
+impl<T: Default + Pod> DerefMut for MappedBufferMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.buffer.len()) }
+    }
+}
+
+pub struct ShaderStorageBuffer<T: Default> {
+    buffer: Buffer<T>,
+}
+
+impl<T: Default> ShaderStorageBuffer<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            buffer: Buffer::new(Target::ShaderStorageBuffer),
+        }
+    }
+}
+
+impl<T: Default> Default for ShaderStorageBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Default> Deref for ShaderStorageBuffer<T> {
+    type Target = Buffer<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl<T: Default> DerefMut for ShaderStorageBuffer<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
     }
 }
@@ -0,0 +1,379 @@
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use glfw::{Key, MouseButton};
+
+use crate::{frustum::Frustum, input::Input, ray::Ray, viewport::Viewport};
+
+/// Moves a camera from the input of each frame and provides its view matrix
+pub trait CameraController {
+    fn update(&mut self, dt: f32, input: &Input);
+    #[must_use]
+    fn position(&self) -> Vec3;
+    #[must_use]
+    fn view(&self) -> Mat4;
+}
+
+/// Right handed perspective projection with a vertical `fov` in radians.
+///
+/// An infinite `z_far` pushes the far plane to infinity. With `reverse_z` the near plane maps
+/// to depth 1 and the far plane to 0, which needs zero to one clip depth as set up by
+/// [`OpenGl::set_reverse_z`](crate::opengl::OpenGl::set_reverse_z); otherwise depth goes
+/// from -1 to 1 like the GL default.
+#[must_use]
+pub fn perspective(fov: f32, aspect_ratio: f32, z_near: f32, z_far: f32, reverse_z: bool) -> Mat4 {
+    match (reverse_z, z_far.is_finite()) {
+        (true, true) => Mat4::perspective_rh(fov, aspect_ratio, z_far, z_near),
+        (true, false) => Mat4::perspective_infinite_reverse_rh(fov, aspect_ratio, z_near),
+        (false, true) => Mat4::perspective_rh_gl(fov, aspect_ratio, z_near, z_far),
+        (false, false) => {
+            let f = 1.0 / (0.5 * fov).tan();
+            Mat4::from_cols(
+                Vec4::new(f / aspect_ratio, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, f, 0.0, 0.0),
+                Vec4::new(0.0, 0.0, -1.0, -1.0),
+                Vec4::new(0.0, 0.0, -2.0 * z_near, 0.0),
+            )
+        }
+    }
+}
+
+/// Perspective projection parameters and the view matrix of a controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    pub z_near: f32,
+    /// Can be [`f32::INFINITY`]
+    pub z_far: f32,
+    /// See [`perspective`]
+    pub reverse_z: bool,
+    aspect_ratio: f32,
+    view: Mat4,
+}
+
+impl Camera {
+    #[must_use]
+    pub const fn new(fov: f32, z_near: f32, z_far: f32) -> Self {
+        Self {
+            fov,
+            z_near,
+            z_far,
+            reverse_z: false,
+            aspect_ratio: 1.0,
+            view: Mat4::IDENTITY,
+        }
+    }
+
+    /// Keeps the aspect ratio of the projection in sync with the window
+    pub fn reshape(&mut self, viewport: &Viewport) {
+        self.aspect_ratio = viewport.aspect_ratio();
+    }
+    #[must_use]
+    pub const fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    /// Updates `controller` and takes its view matrix
+    pub fn update(&mut self, controller: &mut impl CameraController, dt: f32, input: &Input) {
+        controller.update(dt, input);
+        self.view = controller.view();
+    }
+    pub fn set_view(&mut self, view: Mat4) {
+        self.view = view;
+    }
+
+    #[must_use]
+    pub const fn view(&self) -> Mat4 {
+        self.view
+    }
+    /// Where the view looks from, in world space
+    #[must_use]
+    pub fn position(&self) -> Vec3 {
+        self.view.inverse().transform_point3(Vec3::ZERO)
+    }
+    #[must_use]
+    pub fn projection(&self) -> Mat4 {
+        perspective(
+            self.fov.to_radians(),
+            self.aspect_ratio,
+            self.z_near,
+            self.z_far,
+            self.reverse_z,
+        )
+    }
+    #[must_use]
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view
+    }
+    /// The visible volume in world space
+    #[must_use]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_projection())
+    }
+
+    /// The ray from the camera through the pixel at `x`, `y` from the top left of the
+    /// framebuffer, like the cursor position. Starts on the near plane.
+    #[must_use]
+    pub fn screen_to_ray(&self, x: f32, y: f32, viewport: &Viewport) -> Ray {
+        let point = viewport.to_area(x, y) / viewport.size();
+        let ndc = Vec2::new(point.x, 1.0 - point.y) * 2.0 - Vec2::ONE;
+        let near_depth = if self.reverse_z { 1.0 } else { -1.0 };
+        let near = self
+            .view_projection()
+            .inverse()
+            .project_point3(ndc.extend(near_depth));
+        Ray::new(near, near - self.position())
+    }
+}
+
+/// -1, 0 or 1 depending on which of the two keys is held
+fn key_axis(input: &Input, negative: Key, positive: Key) -> f32 {
+    f32::from(u8::from(input.pressed(positive))) - f32::from(u8::from(input.pressed(negative)))
+}
+
+/// Circles around `target` at `distance`, dragging with the left mouse button orbits
+/// and W/S move closer or further.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    /// Degrees around the y axis, 0 looks from +x
+    pub yaw: f32,
+    /// Degrees above the target
+    pub pitch: f32,
+    pub distance: f32,
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Degrees per pixel dragged
+    pub rotate_speed: f32,
+    /// Distance per second
+    pub zoom_speed: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 30.0,
+            distance: 10.0,
+            min_pitch: -89.0,
+            max_pitch: 89.0,
+            min_distance: 0.1,
+            max_distance: f32::INFINITY,
+            rotate_speed: 0.25,
+            zoom_speed: 10.0,
+        }
+    }
+}
+
+impl OrbitCamera {
+    #[must_use]
+    pub fn new(target: Vec3, yaw: f32, pitch: f32, distance: f32) -> Self {
+        Self {
+            target,
+            yaw,
+            pitch,
+            distance,
+            ..Self::default()
+        }
+    }
+
+    /// Rotates by the given degrees, keeping pitch within its limits
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(self.min_pitch, self.max_pitch);
+    }
+    /// Moves closer for positive `amount`, keeping the distance within its limits
+    pub fn zoom(&mut self, amount: f32) {
+        self.distance = (self.distance - amount).clamp(self.min_distance, self.max_distance);
+    }
+}
+
+impl CameraController for OrbitCamera {
+    fn update(&mut self, dt: f32, input: &Input) {
+        if input.mouse_pressed(MouseButton::Button1) {
+            let (dx, dy) = input.cursor_delta();
+            self.orbit(dx as f32 * self.rotate_speed, dy as f32 * self.rotate_speed);
+        }
+        self.zoom(key_axis(input, Key::S, Key::W) * self.zoom_speed * dt);
+    }
+
+    fn position(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.to_radians().sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.to_radians().sin_cos();
+        self.target + Vec3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw) * self.distance
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position(), self.target, Vec3::Y)
+    }
+}
+
+/// Free flying camera, WASD moves along the view, Space and left Control move up and down,
+/// left Shift speeds up and dragging with the right mouse button looks around.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    /// Degrees around the y axis, 0 looks towards -z
+    pub yaw: f32,
+    /// Degrees above the horizon
+    pub pitch: f32,
+    /// Distance per second
+    pub speed: f32,
+    /// Speed multiplier while left Shift is held
+    pub boost: f32,
+    /// Degrees per pixel dragged
+    pub look_speed: f32,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 10.0,
+            boost: 4.0,
+            look_speed: 0.2,
+        }
+    }
+}
+
+impl FlyCamera {
+    #[must_use]
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.to_radians().sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.to_radians().sin_cos();
+        Vec3::new(-sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+    #[must_use]
+    pub fn right(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.to_radians().sin_cos();
+        Vec3::new(cos_yaw, 0.0, -sin_yaw)
+    }
+}
+
+impl CameraController for FlyCamera {
+    fn update(&mut self, dt: f32, input: &Input) {
+        if input.mouse_pressed(MouseButton::Button2) {
+            let (dx, dy) = input.cursor_delta();
+            self.yaw = (dx as f32).mul_add(-self.look_speed, self.yaw);
+            self.pitch = (dy as f32)
+                .mul_add(-self.look_speed, self.pitch)
+                .clamp(-89.0, 89.0);
+        }
+        let direction = self.forward() * key_axis(input, Key::S, Key::W)
+            + self.right() * key_axis(input, Key::A, Key::D)
+            + Vec3::Y * key_axis(input, Key::LeftControl, Key::Space);
+        let speed = if input.pressed(Key::LeftShift) {
+            self.speed * self.boost
+        } else {
+            self.speed
+        };
+        self.position += direction.normalize_or_zero() * speed * dt;
+    }
+
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec3, Vec4};
+    use glfw::{Action, Key, Modifiers, WindowEvent};
+
+    use crate::{input::Input, viewport::Viewport};
+
+    use super::{perspective, Camera, CameraController, FlyCamera, OrbitCamera};
+
+    /// Depth after the perspective division of a point `distance` in front of the camera
+    fn depth(reverse_z: bool, z_far: f32, distance: f32) -> f32 {
+        let clip =
+            perspective(1.0, 1.0, 0.5, z_far, reverse_z) * Vec4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn test_perspective() {
+        assert!((depth(false, 100.0, 0.5) + 1.0).abs() < 1e-5);
+        assert!((depth(false, 100.0, 100.0) - 1.0).abs() < 1e-5);
+        assert!((depth(true, 100.0, 0.5) - 1.0).abs() < 1e-5);
+        assert!(depth(true, 100.0, 100.0).abs() < 1e-5);
+
+        assert!((depth(false, f32::INFINITY, 0.5) + 1.0).abs() < 1e-5);
+        assert!((depth(false, f32::INFINITY, 1e7) - 1.0).abs() < 1e-5);
+        assert!((depth(true, f32::INFINITY, 0.5) - 1.0).abs() < 1e-5);
+        assert!(depth(true, f32::INFINITY, 1e7).abs() < 1e-5);
+
+        let mut camera = Camera::new(90.0, 0.5, f32::INFINITY);
+        let viewport = Viewport::new(200, 100);
+        camera.reshape(&viewport);
+        camera.set_view(Mat4::look_at_rh(Vec3::Z * 10.0, Vec3::ZERO, Vec3::Y));
+        for reverse_z in [false, true] {
+            camera.reverse_z = reverse_z;
+            let frustum = camera.frustum();
+            assert!(frustum.contains_point(Vec3::new(0.0, 0.0, -1e6)));
+            assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 9.75)));
+
+            let center = camera.screen_to_ray(100.0, 50.0, &viewport);
+            assert!(center.origin.abs_diff_eq(Vec3::Z * 9.5, 1e-4));
+            assert!(center.direction.abs_diff_eq(Vec3::NEG_Z, 1e-5));
+            // the top right corner is 45 degrees up and further to the side
+            let corner = camera.screen_to_ray(200.0, 0.0, &viewport);
+            assert!(corner
+                .direction
+                .abs_diff_eq(Vec3::new(2.0, 1.0, -1.0).normalize(), 1e-5));
+        }
+    }
+
+    #[test]
+    fn test_orbit_camera() {
+        let mut orbit = OrbitCamera::new(Vec3::Y, 90.0, 0.0, 5.0);
+        assert!(orbit.position().abs_diff_eq(Vec3::new(0.0, 1.0, 5.0), 1e-5));
+        let view_target = orbit.view().transform_point3(orbit.target);
+        assert!(view_target.abs_diff_eq(Vec3::new(0.0, 0.0, -5.0), 1e-5));
+
+        orbit.orbit(0.0, 120.0);
+        assert!((orbit.pitch - orbit.max_pitch).abs() < f32::EPSILON);
+        orbit.zoom(100.0);
+        assert!((orbit.distance - orbit.min_distance).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_fly_camera() {
+        let mut fly = FlyCamera::new(Vec3::ZERO, 90.0, 0.0);
+        assert!(fly.forward().abs_diff_eq(Vec3::NEG_X, 1e-5));
+        assert!(fly.right().abs_diff_eq(Vec3::NEG_Z, 1e-5));
+
+        let mut input = Input::default();
+        input.handle_event(&WindowEvent::Key(
+            Key::W,
+            0,
+            Action::Press,
+            Modifiers::empty(),
+        ));
+        let mut camera = Camera::new(60.0, 0.1, 100.0);
+        camera.update(&mut fly, 0.5, &input);
+        assert!(fly.position().abs_diff_eq(Vec3::NEG_X * 5.0, 1e-5));
+        assert!(camera
+            .view()
+            .transform_point3(Vec3::NEG_X * 6.0)
+            .abs_diff_eq(Vec3::NEG_Z, 1e-5));
+    }
+}
@@ -0,0 +1,78 @@
+use gl::types::GLenum;
+use glam::{Vec3, Vec4};
+
+/// Converts a channel from the sRGB curve of color pickers and images to linear light
+#[must_use]
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear channel to the sRGB curve, what `FRAMEBUFFER_SRGB` does on write
+#[must_use]
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        value.powf(2.4_f32.recip()).mul_add(1.055, -0.055)
+    }
+}
+
+/// An sRGB color as the linear values shaders, lighting and blending work with.
+/// Alpha is already linear and kept as is.
+#[must_use]
+pub fn srgb(r: f32, g: f32, b: f32, a: f32) -> Vec4 {
+    srgb_rgb(Vec3::new(r, g, b)).extend(a)
+}
+
+/// [`srgb`] without alpha
+#[must_use]
+pub fn srgb_rgb(color: Vec3) -> Vec3 {
+    Vec3::new(
+        srgb_to_linear(color.x),
+        srgb_to_linear(color.y),
+        srgb_to_linear(color.z),
+    )
+}
+
+/// Internal format for 8 bit color textures, like images and albedo maps.
+///
+/// With `srgb` texels are decoded to linear when sampled, as colors in an sRGB pipeline
+/// must be. Data like normal maps or masks is linear already and stays `RGBA8`.
+#[must_use]
+pub const fn color_texture_format(srgb: bool) -> GLenum {
+    if srgb {
+        gl::SRGB8_ALPHA8
+    } else {
+        gl::RGBA8
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec4;
+
+    use super::{linear_to_srgb, srgb, srgb_to_linear};
+
+    #[test]
+    fn test_srgb_conversion() {
+        assert!(srgb_to_linear(0.0).abs() < 1e-6);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        // mid grey on screen is about a fifth of the light
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        for i in 0..=100 {
+            let value = i as f32 / 100.0;
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+        let color = srgb(1.0, 0.5, 0.0, 0.5);
+        assert!(
+            (color - Vec4::new(1.0, 0.214, 0.0, 0.5))
+                .abs()
+                .max_element()
+                < 1e-3
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::ffi::{CStr, CString};
+
+use gl::types::{GLint, GLsizei};
+use glam::{Mat4, Vec3, Vec4};
+use thiserror::Error;
+
+use crate::{
+    buffer::{Buffer, Target, Usage},
+    opengl::{OpenGl, Primitive},
+    program::{Program, Shader, ShaderType},
+    vertex_attributes::{DataType, VertexArrayObject, VertexAttribute, VertexLayout},
+};
+
+/// Segments in each of the circles making up a sphere
+const SPHERE_SEGMENTS: usize = 24;
+
+const VERTEX_SHADER: &CStr = c"#version 330
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+
+uniform mat4 view_projection;
+
+out vec4 line_color;
+
+void main() {
+    gl_Position = view_projection * vec4(position, 1.0);
+    line_color = color;
+}
+";
+
+const FRAGMENT_SHADER: &CStr = c"#version 330
+
+in vec4 line_color;
+
+out vec4 output_color;
+
+void main() {
+    output_color = line_color;
+}
+";
+
+#[derive(Error, Debug)]
+pub enum DebugDrawError {
+    #[error("Debug draw shader error: {0:?}")]
+    ShaderError(CString),
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct LineVertex {
+    position: Vec3,
+    color: Vec4,
+}
+
+/// The 12 edges of the box between `min` and `max`, as pairs of points
+fn aabb_lines(min: Vec3, max: Vec3) -> [Vec3; 24] {
+    let corner = |i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    };
+    // corners differing in a single bit share an edge
+    let edges = [
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+    let mut lines = [Vec3::ZERO; 24];
+    for (i, (a, b)) in edges.into_iter().enumerate() {
+        lines[2 * i] = corner(a);
+        lines[2 * i + 1] = corner(b);
+    }
+    lines
+}
+
+/// Points on the circle around `center` in the plane spanned by `u` and `v`, closing the loop
+fn circle_points(center: Vec3, u: Vec3, v: Vec3) -> impl Iterator<Item = Vec3> {
+    (0..=SPHERE_SEGMENTS).map(move |i| {
+        let angle = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+        center + u * angle.cos() + v * angle.sin()
+    })
+}
+
+/// Batches debug lines in world space and draws them all at once with [`DebugDraw::flush`].
+///
+/// Lines are depth tested against the scene with the current depth state.
+pub struct DebugDraw {
+    program: Program,
+    vao: VertexArrayObject,
+    vertex_buffer: Buffer<LineVertex>,
+    view_projection_location: GLint,
+    vertices: Vec<LineVertex>,
+}
+
+impl DebugDraw {
+    pub fn new(gl: &mut OpenGl) -> Result<Self, DebugDrawError> {
+        let vertex_shader =
+            Shader::new(VERTEX_SHADER, ShaderType::Vertex).map_err(DebugDrawError::ShaderError)?;
+        let fragment_shader = Shader::new(FRAGMENT_SHADER, ShaderType::Fragment)
+            .map_err(DebugDrawError::ShaderError)?;
+        let program =
+            Program::new(&[vertex_shader, fragment_shader]).map_err(DebugDrawError::ShaderError)?;
+        let view_projection_location = program
+            .get_uniform_location(c"view_projection")
+            .unwrap_or(-1);
+
+        let mut vao = VertexArrayObject::new();
+        let vertex_buffer = Buffer::new(Target::ArrayBuffer);
+        let layout = VertexLayout::new()
+            .attribute(0, VertexAttribute::new(3, DataType::Float, false))
+            .attribute(1, VertexAttribute::new(4, DataType::Float, false));
+        gl.bind_vertex_array(&vao);
+        gl.bind_buffer(&vertex_buffer);
+        vao.set_layout(&layout);
+        gl.unbind_vertex_array();
+        gl.unbind_buffer(Target::ArrayBuffer);
+
+        Ok(Self {
+            program,
+            vao,
+            vertex_buffer,
+            view_projection_location,
+            vertices: vec![],
+        })
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        self.vertices.extend([
+            LineVertex {
+                position: start,
+                color,
+            },
+            LineVertex {
+                position: end,
+                color,
+            },
+        ]);
+    }
+
+    /// Connects consecutive points
+    pub fn line_strip(&mut self, points: impl IntoIterator<Item = Vec3>, color: Vec4) {
+        let mut points = points.into_iter();
+        let Some(mut previous) = points.next() else {
+            return;
+        };
+        for point in points {
+            self.line(previous, point, color);
+            previous = point;
+        }
+    }
+
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        for edge in aabb_lines(min, max).chunks_exact(2) {
+            self.line(edge[0], edge[1], color);
+        }
+    }
+
+    /// Three circles around the main axes
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        let [x, y, z] = [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| axis * radius);
+        self.line_strip(circle_points(center, x, y), color);
+        self.line_strip(circle_points(center, y, z), color);
+        self.line_strip(circle_points(center, z, x), color);
+    }
+
+    /// The axes of `transform` from its origin, x red, y green and z blue
+    pub fn axis(&mut self, transform: Mat4, size: f32) {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        for (axis, color) in [
+            (Vec3::X, Vec4::new(1.0, 0.0, 0.0, 1.0)),
+            (Vec3::Y, Vec4::new(0.0, 1.0, 0.0, 1.0)),
+            (Vec3::Z, Vec4::new(0.0, 0.0, 1.0, 1.0)),
+        ] {
+            self.line(origin, transform.transform_point3(axis * size), color);
+        }
+    }
+
+    /// A square grid on the xz plane centered on `center`, with `divisions` cells per side
+    pub fn grid(&mut self, center: Vec3, size: f32, divisions: u32, color: Vec4) {
+        let divisions = divisions.max(1);
+        let half = size * 0.5;
+        for i in 0..=divisions {
+            let offset = (i as f32 / divisions as f32).mul_add(size, -half);
+            self.line(
+                center + Vec3::new(offset, 0.0, -half),
+                center + Vec3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                center + Vec3::new(-half, 0.0, offset),
+                center + Vec3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    /// Number of lines waiting for [`DebugDraw::flush`]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 2
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Discards the queued lines without drawing them
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    /// Draws every queued line in one call and empties the queue
+    pub fn flush(&mut self, gl: &mut OpenGl, view_projection: Mat4) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        gl.bind_buffer(&self.vertex_buffer);
+        self.vertex_buffer
+            .buffer_data(&self.vertices, Usage::StreamDraw);
+        gl.unbind_buffer(Target::ArrayBuffer);
+
+        let mut gl = gl.bind_program_scoped(&self.program);
+        gl.set_uniform(self.view_projection_location, view_projection);
+        gl.bind_vertex_array(&self.vao);
+        gl.draw_arrays(Primitive::Lines, 0, self.vertices.len() as GLsizei);
+        self.vertices.clear();
+        gl.unbind_vertex_array();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::{aabb_lines, circle_points, SPHERE_SEGMENTS};
+
+    #[test]
+    fn test_aabb_lines() {
+        let lines = aabb_lines(Vec3::ZERO, Vec3::ONE);
+        for edge in lines.chunks_exact(2) {
+            // every edge is axis aligned and as long as the box side
+            assert!(((edge[1] - edge[0]).length() - 1.0).abs() < 1e-6);
+        }
+        let mut edges = lines
+            .chunks_exact(2)
+            .map(|edge| (edge[0].to_array(), edge[1].to_array()))
+            .collect::<Vec<_>>();
+        edges.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        edges.dedup();
+        assert_eq!(edges.len(), 12);
+    }
+
+    #[test]
+    fn test_circle_points() {
+        let points = circle_points(Vec3::ONE, Vec3::X * 2.0, Vec3::Y * 2.0).collect::<Vec<_>>();
+        assert_eq!(points.len(), SPHERE_SEGMENTS + 1);
+        assert!(points
+            .iter()
+            .all(|p| ((*p - Vec3::ONE).length() - 2.0).abs() < 1e-5));
+        assert!((points[0] - points[SPHERE_SEGMENTS]).length() < 1e-5);
+    }
+}
@@ -0,0 +1,168 @@
+use std::marker::PhantomData;
+
+use gl::types::{GLenum, GLint};
+use thiserror::Error;
+
+use crate::{
+    handle::{delete_later, ContextBound, GlObject},
+    texture::ImageTexture,
+    GLHandle, NULL_HANDLE,
+};
+
+#[derive(Error, Debug)]
+pub enum FramebufferError {
+    #[error("Framebuffer incomplete, status {0:#x}")]
+    Incomplete(GLenum),
+    #[error("Layer {layer} out of the {layers} of the texture")]
+    LayerOutOfRange { layer: u32, layers: u32 },
+}
+
+/// Where a texture is attached to a [`Framebuffer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attachment {
+    Color(u32),
+    Depth,
+    DepthStencil,
+}
+
+impl Attachment {
+    const fn gl_enum(self) -> GLenum {
+        match self {
+            Self::Color(index) => gl::COLOR_ATTACHMENT0 + index,
+            Self::Depth => gl::DEPTH_ATTACHMENT,
+            Self::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
+        }
+    }
+}
+
+/// Renders into the textures attached to it instead of the window.
+///
+/// Attaching binds it to `GL_FRAMEBUFFER`, drawing to the window again needs
+/// [`Framebuffer::unbind`].
+pub struct Framebuffer {
+    id: GLHandle,
+    _context: ContextBound,
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        delete_later(GlObject::Framebuffer(self.id));
+    }
+}
+
+impl Framebuffer {
+    #[must_use]
+    pub fn new() -> Self {
+        let mut id = NULL_HANDLE;
+        unsafe { gl::GenFramebuffers(1, &mut id) };
+        Self {
+            id,
+            _context: PhantomData,
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.id) };
+    }
+    /// Binds the window back
+    pub fn unbind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE) };
+    }
+
+    /// Attaches mip `level` of `texture`. Every layer of arrays, volumes and cubemaps is
+    /// attached, a geometry shader picks the one drawn to with `gl_Layer`, like to render
+    /// the six faces of a point light shadow cube in a pass.
+    #[allow(private_bounds)]
+    pub fn attach(&mut self, attachment: Attachment, texture: &impl ImageTexture, level: u32) {
+        self.bind();
+        let image = texture.image();
+        unsafe {
+            gl::FramebufferTexture(
+                gl::FRAMEBUFFER,
+                attachment.gl_enum(),
+                image.id,
+                level as GLint,
+            );
+        };
+    }
+
+    /// Attaches `layer` of mip `level` of an array, a slice of a volume, or a face of a
+    /// cubemap, see [`crate::texture::CubeFace::layer`]
+    #[allow(private_bounds)]
+    pub fn attach_layer(
+        &mut self,
+        attachment: Attachment,
+        texture: &impl ImageTexture,
+        level: u32,
+        layer: u32,
+    ) -> Result<(), FramebufferError> {
+        let image = texture.image();
+        if layer >= image.layers {
+            return Err(FramebufferError::LayerOutOfRange {
+                layer,
+                layers: image.layers,
+            });
+        }
+        self.bind();
+        unsafe {
+            if image.target == gl::TEXTURE_CUBE_MAP {
+                // cubemap faces are layers only from 4.5 on
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    attachment.gl_enum(),
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+                    image.id,
+                    level as GLint,
+                );
+            } else if image.is_layered() {
+                gl::FramebufferTextureLayer(
+                    gl::FRAMEBUFFER,
+                    attachment.gl_enum(),
+                    image.id,
+                    level as GLint,
+                    layer as GLint,
+                );
+            } else {
+                gl::FramebufferTexture(
+                    gl::FRAMEBUFFER,
+                    attachment.gl_enum(),
+                    image.id,
+                    level as GLint,
+                );
+            }
+        };
+        Ok(())
+    }
+
+    /// Whether the attachments can be rendered to
+    pub fn check_status(&self) -> Result<(), FramebufferError> {
+        self.bind();
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(FramebufferError::Incomplete(status))
+        }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Attachment;
+
+    #[test]
+    fn test_attachment_gl_enum() {
+        assert_eq!(Attachment::Color(2).gl_enum(), gl::COLOR_ATTACHMENT2);
+        assert_eq!(Attachment::Depth.gl_enum(), gl::DEPTH_ATTACHMENT);
+        assert_eq!(
+            Attachment::DepthStencil.gl_enum(),
+            gl::DEPTH_STENCIL_ATTACHMENT
+        );
+    }
+}
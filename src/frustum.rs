@@ -0,0 +1,90 @@
+use glam::{Mat4, Vec3, Vec4};
+
+/// The volume visible through a projection * view matrix, as 6 planes facing inwards.
+/// Tests happen in the space the matrix transforms from, usually world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+fn distance(plane: Vec4, point: Vec3) -> f32 {
+    plane.truncate().dot(point) + plane.w
+}
+
+impl Frustum {
+    #[must_use]
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (x, y, z, w) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+        // left, right, bottom, top, near, far
+        // an infinite far plane has no normal and always passes
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+            let length = plane.truncate().length();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Self { planes }
+    }
+
+    #[must_use]
+    pub const fn planes(&self) -> &[Vec4; 6] {
+        &self.planes
+    }
+
+    #[must_use]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| distance(*plane, point) >= 0.0)
+    }
+
+    #[must_use]
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| distance(*plane, center) >= -radius)
+    }
+
+    /// Conservative test, boxes near the frustum corners may pass without being visible.
+    #[must_use]
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let corner = Vec3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            distance(*plane, corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec3};
+
+    use super::Frustum;
+
+    #[test]
+    fn test_frustum() {
+        let projection = Mat4::perspective_rh_gl(90f32.to_radians(), 1.0, 1.0, 100.0);
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, Vec3::Y);
+        let frustum = Frustum::from_matrix(projection * view);
+
+        assert!(frustum.contains_point(Vec3::ZERO));
+        // behind the camera and past the far plane
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 20.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -100.0)));
+        // a 90 degrees fov sees 10 units to the side at the origin
+        assert!(!frustum.contains_point(Vec3::new(11.0, 0.0, 0.0)));
+
+        assert!(frustum.intersects_sphere(Vec3::new(11.0, 0.0, 0.0), 2.0));
+        assert!(!frustum.intersects_sphere(Vec3::new(15.0, 0.0, 0.0), 2.0));
+
+        assert!(frustum.intersects_aabb(Vec3::new(9.0, -1.0, -1.0), Vec3::new(12.0, 1.0, 1.0)));
+        assert!(!frustum.intersects_aabb(Vec3::new(11.5, -1.0, -1.0), Vec3::new(12.0, 1.0, 1.0)));
+    }
+}
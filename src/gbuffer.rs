@@ -0,0 +1,312 @@
+use std::{
+    ffi::{CString, NulError},
+    marker::PhantomData,
+};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use glam::Mat4;
+use thiserror::Error;
+
+use crate::{
+    handle::{delete_later, ContextBound, GlObject},
+    lighting::{lighting_shader, LIT_VERTEX_SHADER},
+    opengl::{Capability, OpenGl, Primitive, StateFlags},
+    program::{Program, Shader, ShaderType},
+    vertex_attributes::VertexArrayObject,
+    viewport::Viewport,
+    GLHandle, NULL_HANDLE,
+};
+
+const GEOMETRY_FRAGMENT_SHADER: &str = lighting_shader!("gbuffer.frag");
+const LIGHT_VERTEX_SHADER: &str = lighting_shader!("deferred.vert");
+const LIGHT_FRAGMENT_SHADER: &str = lighting_shader!("deferred.frag", "#define BLINN");
+
+/// The color attachments written by the geometry pass, in fragment output order
+const DRAW_BUFFERS: [GLenum; 2] = [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1];
+
+#[derive(Error, Debug)]
+pub enum GBufferError {
+    #[error("Deferred shader error: {0:?}")]
+    ShaderError(CString),
+    #[error("Shader source contains a nul byte: {0}")]
+    NulError(#[from] NulError),
+    #[error("G-buffer framebuffer is incomplete, status {0:#x}")]
+    IncompleteFramebuffer(GLenum),
+}
+
+/// Surface attributes of what's on screen, drawn once by a geometry pass and lit afterwards
+/// by a [`DeferredLighting`] pass for every pixel.
+///
+/// - albedo: RGBA8 diffuse color, with the specular intensity in alpha
+/// - normal: RGBA16F world space normal, with the shininess in w
+/// - depth: 24 bit depth, the world position is reconstructed from it
+pub struct GBuffer {
+    framebuffer: GLHandle,
+    albedo_texture: GLHandle,
+    normal_texture: GLHandle,
+    depth_texture: GLHandle,
+    width: GLsizei,
+    height: GLsizei,
+    _context: ContextBound,
+}
+
+impl Drop for GBuffer {
+    fn drop(&mut self) {
+        delete_later(GlObject::Framebuffer(self.framebuffer));
+        for texture in [self.albedo_texture, self.normal_texture, self.depth_texture] {
+            delete_later(GlObject::Texture(texture));
+        }
+    }
+}
+
+/// An empty texture sampled without filtering, pixels of the G-buffer aren't blended
+fn attachment_texture() -> GLHandle {
+    let mut texture = NULL_HANDLE;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_S,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+    };
+    texture
+}
+
+impl GBuffer {
+    /// Attachments of `width` by `height` pixels
+    pub fn new(width: GLsizei, height: GLsizei) -> Result<Self, GBufferError> {
+        let mut gbuffer = Self {
+            framebuffer: NULL_HANDLE,
+            albedo_texture: attachment_texture(),
+            normal_texture: attachment_texture(),
+            depth_texture: attachment_texture(),
+            width: 0,
+            height: 0,
+            _context: PhantomData,
+        };
+        gbuffer.resize(width, height);
+        let status = unsafe {
+            gl::GenFramebuffers(1, &mut gbuffer.framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, gbuffer.framebuffer);
+            for (attachment, texture) in [
+                (gl::COLOR_ATTACHMENT0, gbuffer.albedo_texture),
+                (gl::COLOR_ATTACHMENT1, gbuffer.normal_texture),
+                (gl::DEPTH_ATTACHMENT, gbuffer.depth_texture),
+            ] {
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture, 0);
+            }
+            gl::DrawBuffers(DRAW_BUFFERS.len() as GLsizei, DRAW_BUFFERS.as_ptr());
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE);
+            status
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(GBufferError::IncompleteFramebuffer(status));
+        }
+        Ok(gbuffer)
+    }
+
+    /// Reallocates the attachments, their contents are lost. Sizes below 1 become 1.
+    pub fn resize(&mut self, width: GLsizei, height: GLsizei) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        for (texture, internal_format, format, data_type) in [
+            (self.albedo_texture, gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            (self.normal_texture, gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            (
+                self.depth_texture,
+                gl::DEPTH_COMPONENT24,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+            ),
+        ] {
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    internal_format as GLint,
+                    self.width,
+                    self.height,
+                    0,
+                    format,
+                    data_type,
+                    std::ptr::null(),
+                );
+            };
+        }
+        unsafe { gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE) };
+    }
+    #[must_use]
+    pub const fn size(&self) -> (GLsizei, GLsizei) {
+        (self.width, self.height)
+    }
+
+    /// Clears the attachments and renders what `draw` draws into them, over the whole size.
+    /// `draw` should use programs made by [`geometry_program`] with depth testing on.
+    ///
+    /// The default framebuffer is bound again afterwards, the viewport is left to the caller.
+    pub fn geometry_pass(&self, gl: &mut OpenGl, draw: impl FnOnce(&mut OpenGl)) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer) };
+        gl.viewport(0, 0, self.width, self.height);
+        for draw_buffer in 0..DRAW_BUFFERS.len() {
+            gl.clear_color_buffer(draw_buffer as GLint, [0.0; 4]);
+        }
+        gl.clear_depth_buffer(1.0);
+        draw(gl);
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE) };
+    }
+
+    /// Binds albedo, normal and depth to the texture units from `first_unit` on
+    pub fn bind_textures(&self, first_unit: GLuint) {
+        for (unit, texture) in
+            (first_unit..).zip([self.albedo_texture, self.normal_texture, self.depth_texture])
+        {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+            };
+        }
+    }
+}
+
+/// Links the program of the geometry pass, with the uniforms of
+/// [`crate::lighting::material`]. It takes position and normal at attribute locations 0 and 2.
+pub fn geometry_program() -> Result<Program, GBufferError> {
+    let vertex = Shader::new(&CString::new(LIT_VERTEX_SHADER)?, ShaderType::Vertex)
+        .map_err(GBufferError::ShaderError)?;
+    let fragment = Shader::new(
+        &CString::new(GEOMETRY_FRAGMENT_SHADER)?,
+        ShaderType::Fragment,
+    )
+    .map_err(GBufferError::ShaderError)?;
+    Program::new(&[vertex, fragment]).map_err(GBufferError::ShaderError)
+}
+
+/// What [`DeferredLighting::draw`] outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GBufferView {
+    /// The lights of the [`crate::lighting::LightBuffer`], with Blinn-Phong specular
+    #[default]
+    Lit,
+    Albedo,
+    /// World space normals mapped from -1..1 to colors
+    Normal,
+    Depth,
+}
+
+impl GBufferView {
+    #[must_use]
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Lit => Self::Albedo,
+            Self::Albedo => Self::Normal,
+            Self::Normal => Self::Depth,
+            Self::Depth => Self::Lit,
+        }
+    }
+}
+
+/// Light pass shading every pixel of a [`GBuffer`] with the lights of a
+/// [`crate::lighting::LightBuffer`], into the bound framebuffer
+pub struct DeferredLighting {
+    program: Program,
+    // draws without attributes still need a vertex array bound
+    vao: VertexArrayObject,
+    clip_to_world_location: GLint,
+    view_location: GLint,
+    sampler_locations: [GLint; 3],
+    pub view: GBufferView,
+}
+
+impl DeferredLighting {
+    /// Reads its lights from `binding_index`
+    pub fn new(binding_index: GLuint) -> Result<Self, GBufferError> {
+        let vertex = Shader::new(&CString::new(LIGHT_VERTEX_SHADER)?, ShaderType::Vertex)
+            .map_err(GBufferError::ShaderError)?;
+        let fragment = Shader::new(&CString::new(LIGHT_FRAGMENT_SHADER)?, ShaderType::Fragment)
+            .map_err(GBufferError::ShaderError)?;
+        let mut program = Program::new(&[vertex, fragment]).map_err(GBufferError::ShaderError)?;
+        if let Some(block) = program.get_uniform_block_index(c"Lights") {
+            program.uniform_block_binding(block, binding_index);
+        }
+        let sampler_locations = [c"albedoTexture", c"normalTexture", c"depthTexture"]
+            .map(|name| program.get_uniform_location(name).unwrap_or(-1));
+        Ok(Self {
+            clip_to_world_location: program.get_uniform_location(c"clipToWorld").unwrap_or(-1),
+            view_location: program.get_uniform_location(c"view").unwrap_or(-1),
+            sampler_locations,
+            program,
+            vao: VertexArrayObject::new(),
+            view: GBufferView::default(),
+        })
+    }
+
+    /// Lights `gbuffer` into `viewport`, with the `view_projection` of the geometry pass.
+    /// Pixels where nothing was drawn are left as they are. The depth is read as cleared to
+    /// 1 in the -1 to 1 clip range, not with reverse Z.
+    ///
+    /// Leaves the depth state as it was, the attachments stay bound to texture units 0 to 2.
+    pub fn draw(
+        &self,
+        gl: &mut OpenGl,
+        gbuffer: &GBuffer,
+        viewport: &Viewport,
+        view_projection: Mat4,
+    ) {
+        viewport.apply(gl);
+        let mut gl = gl.save_state(StateFlags::Depth);
+        gl.disable(Capability::DepthTest);
+
+        gl.bind_program(&self.program);
+        self.program
+            .set_uniform(self.clip_to_world_location, view_projection.inverse());
+        self.program
+            .set_uniform(self.view_location, self.view as i32);
+        for (unit, location) in (0..).zip(self.sampler_locations) {
+            self.program.set_uniform(location, unit);
+        }
+        gbuffer.bind_textures(0);
+        gl.bind_vertex_array(&self.vao);
+        gl.draw_arrays(Primitive::Triangles, 0, 3);
+        gl.unbind_vertex_array();
+        gl.unbind_program();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        GBufferView, GEOMETRY_FRAGMENT_SHADER, LIGHT_FRAGMENT_SHADER, LIGHT_VERTEX_SHADER,
+    };
+
+    #[test]
+    fn test_sources() {
+        for source in [
+            GEOMETRY_FRAGMENT_SHADER,
+            LIGHT_VERTEX_SHADER,
+            LIGHT_FRAGMENT_SHADER,
+        ] {
+            assert!(source.starts_with("#version 330\n"));
+        }
+        assert!(GEOMETRY_FRAGMENT_SHADER.contains("layout(location = 1) out"));
+        assert!(LIGHT_FRAGMENT_SHADER.contains("#define BLINN"));
+
+        let mut view = GBufferView::default();
+        for _ in 0..4 {
+            view = view.next();
+        }
+        assert_eq!(view, GBufferView::Lit);
+    }
+}
@@ -0,0 +1,507 @@
+//! The driver calls behind the state tracking of [`crate::opengl::OpenGl`], swappable with
+//! [`crate::opengl::OpenGl::with_api`] to test code drawing through it without a GPU or window.
+//!
+//! ```
+//! use opengl_rend::{gl_api::{GlCall, Recorder}, opengl::{Capability, OpenGl}};
+//!
+//! let recorder = Recorder::default();
+//! let mut gl = OpenGl::with_api(recorder.clone());
+//! gl.enable(Capability::DepthTest);
+//! gl.enable(Capability::DepthTest);
+//! assert_eq!(recorder.calls(), [GlCall::SetCapability(Capability::DepthTest, true)]);
+//! ```
+//!
+//! The api only covers the calls listed in [`GlCall`]: the bindings and state cached by
+//! [`crate::opengl::OpenGl`], clearing, the viewport and non-instanced draws. Its other
+//! methods, and objects like [`crate::buffer::Buffer`] or [`crate::mesh::Mesh`], call the
+//! loaded driver themselves. They need a context, so keep them away from an `OpenGl` given
+//! a [`Recorder`], whose other methods panic rather than call unloaded functions. Buffer,
+//! mesh and program logic can't be tested without a GPU yet, it needs those objects to go
+//! through the api too.
+//!
+//! [`crate::opengl::OpenGl::set_tracing`] keeps both the api calls and the driver calls of
+//! the `OpenGl` and of the objects used while its context is current, see [`TracedCall`].
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    rc::{Rc, Weak},
+};
+
+use gl::types::{GLenum, GLfloat, GLint, GLsizei};
+
+use crate::{
+    buffer::Target,
+    handle::{self, BufferId, Context, ProgramId, VaoId},
+    opengl::{BlendEquation, BlendFactor, Capability, ClearFlags, DepthFunc, IndexSize, Primitive},
+};
+
+/// Calls made by [`crate::opengl::OpenGl`], see [`Driver`] and [`Recorder`]
+pub trait GlApi {
+    /// Whether the GL functions are loaded, as the `OpenGl` methods outside the api call
+    /// them directly
+    fn calls_driver(&self) -> bool {
+        true
+    }
+    /// `glGetError`
+    fn get_error(&self) -> GLenum;
+    fn use_program(&self, program: ProgramId);
+    fn bind_vertex_array(&self, vao: VaoId);
+    fn bind_buffer(&self, target: Target, buffer: BufferId);
+    fn set_capability(&self, cap: Capability, enabled: bool);
+    fn depth_func(&self, func: DepthFunc);
+    fn depth_mask(&self, mask: bool);
+    fn blend_func(&self, src: BlendFactor, dst: BlendFactor);
+    /// `[src_rgb, dst_rgb, src_alpha, dst_alpha]`
+    fn blend_func_separate(&self, func: [BlendFactor; 4]);
+    fn blend_equation(&self, equation: BlendEquation);
+    fn blend_equation_separate(&self, rgb: BlendEquation, alpha: BlendEquation);
+    fn clear_color(&self, color: [GLfloat; 4]);
+    fn clear(&self, mask: ClearFlags);
+    fn viewport(&self, x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+    fn draw_arrays(&self, mode: Primitive, first: GLint, count: GLsizei);
+    fn draw_elements(&self, mode: Primitive, count: GLsizei, index_size: IndexSize, offset: usize);
+}
+
+/// Calls the loaded GL functions, the api of [`crate::opengl::OpenGl::new`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Driver;
+
+impl GlApi for Driver {
+    fn get_error(&self) -> GLenum {
+        unsafe { gl::GetError() }
+    }
+    fn use_program(&self, program: ProgramId) {
+        unsafe { gl::UseProgram(program.raw()) };
+    }
+    fn bind_vertex_array(&self, vao: VaoId) {
+        unsafe { gl::BindVertexArray(vao.raw()) };
+    }
+    fn bind_buffer(&self, target: Target, buffer: BufferId) {
+        unsafe { gl::BindBuffer(target as GLenum, buffer.raw()) };
+    }
+    fn set_capability(&self, cap: Capability, enabled: bool) {
+        if enabled {
+            unsafe { gl::Enable(cap as GLenum) };
+        } else {
+            unsafe { gl::Disable(cap as GLenum) };
+        }
+    }
+    fn depth_func(&self, func: DepthFunc) {
+        unsafe { gl::DepthFunc(func as GLenum) };
+    }
+    fn depth_mask(&self, mask: bool) {
+        unsafe { gl::DepthMask(if mask { gl::TRUE } else { gl::FALSE }) };
+    }
+    fn blend_func(&self, src: BlendFactor, dst: BlendFactor) {
+        unsafe { gl::BlendFunc(src as GLenum, dst as GLenum) };
+    }
+    fn blend_func_separate(&self, [src_rgb, dst_rgb, src_alpha, dst_alpha]: [BlendFactor; 4]) {
+        unsafe {
+            gl::BlendFuncSeparate(
+                src_rgb as GLenum,
+                dst_rgb as GLenum,
+                src_alpha as GLenum,
+                dst_alpha as GLenum,
+            );
+        };
+    }
+    fn blend_equation(&self, equation: BlendEquation) {
+        unsafe { gl::BlendEquation(equation as GLenum) };
+    }
+    fn blend_equation_separate(&self, rgb: BlendEquation, alpha: BlendEquation) {
+        unsafe { gl::BlendEquationSeparate(rgb as GLenum, alpha as GLenum) };
+    }
+    fn clear_color(&self, [red, green, blue, alpha]: [GLfloat; 4]) {
+        unsafe { gl::ClearColor(red, green, blue, alpha) };
+    }
+    fn clear(&self, mask: ClearFlags) {
+        unsafe { gl::Clear(mask.bits()) };
+    }
+    fn viewport(&self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        unsafe { gl::Viewport(x, y, width, height) };
+    }
+    fn draw_arrays(&self, mode: Primitive, first: GLint, count: GLsizei) {
+        unsafe { gl::DrawArrays(mode as GLenum, first, count) };
+    }
+    fn draw_elements(&self, mode: Primitive, count: GLsizei, index_size: IndexSize, offset: usize) {
+        unsafe {
+            gl::DrawElements(
+                mode as GLenum,
+                count,
+                index_size as GLenum,
+                offset as *const _,
+            );
+        };
+    }
+}
+
+/// A call made through a [`GlApi`], as logged by [`Recorder`] and traced by
+/// [`crate::opengl::OpenGl::set_tracing`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlCall {
+    UseProgram(ProgramId),
+    BindVertexArray(VaoId),
+    BindBuffer(Target, BufferId),
+    SetCapability(Capability, bool),
+    DepthFunc(DepthFunc),
+    DepthMask(bool),
+    BlendFunc(BlendFactor, BlendFactor),
+    BlendFuncSeparate([BlendFactor; 4]),
+    BlendEquation(BlendEquation),
+    BlendEquationSeparate(BlendEquation, BlendEquation),
+    ClearColor([GLfloat; 4]),
+    Clear(ClearFlags),
+    Viewport(GLint, GLint, GLsizei, GLsizei),
+    DrawArrays(Primitive, GLint, GLsizei),
+    DrawElements(Primitive, GLsizei, IndexSize, usize),
+}
+
+impl GlCall {
+    /// Makes the call through `api`
+    pub fn apply(self, api: &dyn GlApi) {
+        match self {
+            Self::UseProgram(program) => api.use_program(program),
+            Self::BindVertexArray(vao) => api.bind_vertex_array(vao),
+            Self::BindBuffer(target, buffer) => api.bind_buffer(target, buffer),
+            Self::SetCapability(cap, enabled) => api.set_capability(cap, enabled),
+            Self::DepthFunc(func) => api.depth_func(func),
+            Self::DepthMask(mask) => api.depth_mask(mask),
+            Self::BlendFunc(src, dst) => api.blend_func(src, dst),
+            Self::BlendFuncSeparate(func) => api.blend_func_separate(func),
+            Self::BlendEquation(equation) => api.blend_equation(equation),
+            Self::BlendEquationSeparate(rgb, alpha) => api.blend_equation_separate(rgb, alpha),
+            Self::ClearColor(color) => api.clear_color(color),
+            Self::Clear(mask) => api.clear(mask),
+            Self::Viewport(x, y, width, height) => api.viewport(x, y, width, height),
+            Self::DrawArrays(mode, first, count) => api.draw_arrays(mode, first, count),
+            Self::DrawElements(mode, count, index_size, offset) => {
+                api.draw_elements(mode, count, index_size, offset);
+            }
+        }
+    }
+}
+
+/// A call kept by [`crate::opengl::OpenGl::set_tracing`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TracedCall {
+    /// Made through the [`GlApi`]
+    Api(GlCall),
+    /// Made on the driver directly, by an `OpenGl` method or by an object like
+    /// [`crate::buffer::Buffer`], which also gives the arguments
+    Driver {
+        function: &'static str,
+        args: Option<String>,
+    },
+}
+
+impl fmt::Display for TracedCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api(call) => write!(f, "{call:?}"),
+            Self::Driver {
+                function,
+                args: None,
+            } => write!(f, "{function}"),
+            Self::Driver {
+                function,
+                args: Some(args),
+            } => write!(f, "{function}({args})"),
+        }
+    }
+}
+
+/// Ring buffer of the last calls of an `OpenGl`
+pub(crate) struct Trace {
+    calls: VecDeque<TracedCall>,
+    capacity: usize,
+}
+
+impl Trace {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            calls: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub(crate) fn push(&mut self, call: TracedCall) {
+        if self.calls.len() == self.capacity {
+            self.calls.pop_front();
+        }
+        self.calls.push_back(call);
+    }
+
+    pub(crate) fn calls(&self) -> Vec<TracedCall> {
+        self.calls.iter().cloned().collect()
+    }
+
+    /// Logs the calls, oldest first
+    pub(crate) fn dump(&self, reason: &str) {
+        if self.calls.is_empty() {
+            return;
+        }
+        log::error!("Last {} GL calls before {reason}:", self.calls.len());
+        for call in &self.calls {
+            log::error!("    {call}");
+        }
+    }
+}
+
+thread_local! {
+    // the trace of the `OpenGl` of each context, for the objects and the debug callback,
+    // which only know the context current on the thread
+    static TRACES: RefCell<Vec<(Context, Weak<RefCell<Trace>>)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Makes `trace` the one the objects record into while the current context is current
+pub(crate) fn register_trace(trace: &Rc<RefCell<Trace>>) {
+    let context = handle::current_context();
+    let _ = TRACES.try_with(|traces| {
+        let mut traces = traces.borrow_mut();
+        traces.retain(|(traced, trace)| *traced != context && trace.strong_count() > 0);
+        traces.push((context, Rc::downgrade(trace)));
+    });
+}
+
+fn with_current_trace(f: impl FnOnce(&mut Trace)) {
+    let _ = TRACES.try_with(|traces| {
+        let Ok(traces) = traces.try_borrow() else {
+            return;
+        };
+        // skips looking up the context while nothing is traced
+        if traces.is_empty() {
+            return;
+        }
+        let context = handle::current_context();
+        let Some(trace) = traces
+            .iter()
+            .find(|(traced, _)| *traced == context)
+            .and_then(|(_, trace)| trace.upgrade())
+        else {
+            return;
+        };
+        let Ok(mut trace) = trace.try_borrow_mut() else {
+            return;
+        };
+        f(&mut trace);
+    });
+}
+
+/// Records a driver call of an object in the trace of the current context, `args` is only
+/// formatted while tracing
+pub(crate) fn trace_call(function: &'static str, args: impl FnOnce() -> String) {
+    with_current_trace(|trace| {
+        trace.push(TracedCall::Driver {
+            function,
+            args: Some(args()),
+        });
+    });
+}
+
+/// Logs the trace of the current context, for the debug callback
+pub(crate) fn dump_current_trace(reason: &str) {
+    with_current_trace(|trace| trace.dump(reason));
+}
+
+/// Logs the calls instead of making them and never reports an error. Clones share the log,
+/// so keep one to inspect the calls of the [`crate::opengl::OpenGl`] given the other.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    calls: Rc<RefCell<Vec<GlCall>>>,
+}
+
+impl Recorder {
+    #[must_use]
+    pub fn calls(&self) -> Vec<GlCall> {
+        self.calls.borrow().clone()
+    }
+    /// Returns the calls logged so far and empties the log
+    #[must_use]
+    pub fn take(&self) -> Vec<GlCall> {
+        self.calls.take()
+    }
+
+    fn record(&self, call: GlCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl GlApi for Recorder {
+    fn calls_driver(&self) -> bool {
+        false
+    }
+    fn get_error(&self) -> GLenum {
+        gl::NO_ERROR
+    }
+    fn use_program(&self, program: ProgramId) {
+        self.record(GlCall::UseProgram(program));
+    }
+    fn bind_vertex_array(&self, vao: VaoId) {
+        self.record(GlCall::BindVertexArray(vao));
+    }
+    fn bind_buffer(&self, target: Target, buffer: BufferId) {
+        self.record(GlCall::BindBuffer(target, buffer));
+    }
+    fn set_capability(&self, cap: Capability, enabled: bool) {
+        self.record(GlCall::SetCapability(cap, enabled));
+    }
+    fn depth_func(&self, func: DepthFunc) {
+        self.record(GlCall::DepthFunc(func));
+    }
+    fn depth_mask(&self, mask: bool) {
+        self.record(GlCall::DepthMask(mask));
+    }
+    fn blend_func(&self, src: BlendFactor, dst: BlendFactor) {
+        self.record(GlCall::BlendFunc(src, dst));
+    }
+    fn blend_func_separate(&self, func: [BlendFactor; 4]) {
+        self.record(GlCall::BlendFuncSeparate(func));
+    }
+    fn blend_equation(&self, equation: BlendEquation) {
+        self.record(GlCall::BlendEquation(equation));
+    }
+    fn blend_equation_separate(&self, rgb: BlendEquation, alpha: BlendEquation) {
+        self.record(GlCall::BlendEquationSeparate(rgb, alpha));
+    }
+    fn clear_color(&self, color: [GLfloat; 4]) {
+        self.record(GlCall::ClearColor(color));
+    }
+    fn clear(&self, mask: ClearFlags) {
+        self.record(GlCall::Clear(mask));
+    }
+    fn viewport(&self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.record(GlCall::Viewport(x, y, width, height));
+    }
+    fn draw_arrays(&self, mode: Primitive, first: GLint, count: GLsizei) {
+        self.record(GlCall::DrawArrays(mode, first, count));
+    }
+    fn draw_elements(&self, mode: Primitive, count: GLsizei, index_size: IndexSize, offset: usize) {
+        self.record(GlCall::DrawElements(mode, count, index_size, offset));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{trace_call, GlCall, Recorder, TracedCall};
+    use crate::{
+        buffer::Target,
+        handle::{self, BufferId, ProgramId, VaoId},
+        opengl::{BlendFactor, ClearFlags, OpenGl, Primitive},
+    };
+
+    #[test]
+    fn test_redundant_calls_skipped() {
+        let recorder = Recorder::default();
+        let mut gl = OpenGl::with_api(recorder.clone());
+        gl.clear(ClearFlags::Color | ClearFlags::Depth);
+        gl.blend_func(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+        gl.blend_func(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+        gl.draw_arrays(Primitive::Triangles, 0, 3);
+        assert_eq!(
+            recorder.take(),
+            [
+                GlCall::Clear(ClearFlags::Color | ClearFlags::Depth),
+                GlCall::BlendFunc(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha),
+                GlCall::DrawArrays(Primitive::Triangles, 0, 3),
+            ]
+        );
+        gl.invalidate_cache();
+        gl.blend_func(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+        assert_eq!(recorder.take().len(), 1);
+    }
+
+    #[test]
+    fn test_vertex_array_forgets_index_buffer() {
+        let recorder = Recorder::default();
+        let mut gl = OpenGl::with_api(recorder.clone());
+        gl.unbind_buffer(Target::IndexBuffer);
+        gl.unbind_program();
+        gl.unbind_vertex_array();
+        gl.unbind_buffer(Target::IndexBuffer);
+        gl.unbind_program();
+        assert_eq!(
+            recorder.calls(),
+            [
+                GlCall::BindBuffer(Target::IndexBuffer, BufferId::NULL),
+                GlCall::UseProgram(ProgramId::NULL),
+                GlCall::BindVertexArray(VaoId::NULL),
+                GlCall::BindBuffer(Target::IndexBuffer, BufferId::NULL),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_direct_binds_forget_buffers() {
+        let recorder = Recorder::default();
+        let mut gl = OpenGl::with_api(recorder.clone());
+        gl.unbind_buffer(Target::ArrayBuffer);
+        gl.unbind_program();
+        // like `Buffer::bind` from a helper of the crate
+        handle::bound_directly();
+        gl.unbind_buffer(Target::ArrayBuffer);
+        gl.unbind_program();
+        assert_eq!(
+            recorder.calls(),
+            [
+                GlCall::BindBuffer(Target::ArrayBuffer, BufferId::NULL),
+                GlCall::UseProgram(ProgramId::NULL),
+                GlCall::BindBuffer(Target::ArrayBuffer, BufferId::NULL),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't part of GlApi")]
+    fn test_recorder_refuses_driver_calls() {
+        let mut gl = OpenGl::with_api(Recorder::default());
+        gl.primitive_restart_index(0xffff);
+    }
+
+    #[test]
+    fn test_tracing() {
+        let recorder = Recorder::default();
+        let mut gl = OpenGl::with_api(recorder.clone());
+        gl.draw_arrays(Primitive::Points, 0, 1);
+        assert!(gl.trace().is_empty());
+        gl.set_tracing(2);
+        gl.unbind_program();
+        gl.unbind_vertex_array();
+        gl.draw_arrays(Primitive::Triangles, 0, 3);
+        assert_eq!(
+            gl.trace(),
+            [
+                TracedCall::Api(GlCall::BindVertexArray(VaoId::NULL)),
+                TracedCall::Api(GlCall::DrawArrays(Primitive::Triangles, 0, 3)),
+            ]
+        );
+        // traced calls still reach the api
+        assert_eq!(recorder.calls().len(), 4);
+        gl.set_tracing(0);
+        gl.unbind_buffer(Target::ArrayBuffer);
+        assert!(gl.trace().is_empty());
+    }
+
+    #[test]
+    fn test_tracing_objects() {
+        let mut gl = OpenGl::with_api(Recorder::default());
+        gl.set_tracing(4);
+        trace_call("glBindBuffer", || "GL_ARRAY_BUFFER, 3".to_owned());
+        gl.draw_arrays(Primitive::Points, 0, 1);
+        // an untraced instance keeps its calls out of the trace of the other
+        let mut other = OpenGl::with_api(Recorder::default());
+        other.draw_arrays(Primitive::Lines, 0, 2);
+        let trace = gl.trace();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[0].to_string(), "glBindBuffer(GL_ARRAY_BUFFER, 3)");
+        assert_eq!(
+            trace[1],
+            TracedCall::Api(GlCall::DrawArrays(Primitive::Points, 0, 1))
+        );
+        drop(gl);
+        // nothing to record into once the traced instance is gone
+        trace_call("glBindBuffer", || unreachable!());
+    }
+}
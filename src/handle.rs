@@ -0,0 +1,265 @@
+//! Typed ids of the GL objects, so a buffer name can't be passed where a program is expected.
+//!
+//! The objects themselves delete their GL object when dropped. To share one between scene
+//! objects wrap it in an [`std::rc::Rc`], like [`crate::material::ProgramHandle`], and it's
+//! deleted once, when the last owner drops it.
+//!
+//! Dropping only queues the deletion, which happens in [`crate::opengl::OpenGl::flush_deletions`],
+//! called by [`crate::app::run_app`] after every frame. The deletion waits for the context
+//! current at the drop to be current again, objects whose window is destroyed first are
+//! forgotten, they went with its context.
+//!
+//! GL objects belong to the context current on the thread that created them, so the types
+//! owning them are neither `Send` nor `Sync`:
+//!
+//! ```compile_fail
+//! fn send<T: Send>(_: T) {}
+//! send(opengl_rend::buffer::Buffer::<f32>::new(opengl_rend::buffer::Target::ArrayBuffer));
+//! ```
+//!
+//! ```compile_fail
+//! fn sync<T: Sync>() {}
+//! sync::<opengl_rend::texture::Texture2D>();
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+};
+
+use gl::types::{GLint, GLsync, GLuint};
+use glfw::ffi::GLFWwindow;
+
+use crate::{GLHandle, NULL_HANDLE};
+
+/// Field keeping the object on the thread of its context, not `Send` nor `Sync`
+pub(crate) type ContextBound = PhantomData<*const ()>;
+
+/// The window whose context was current when an object was dropped
+pub(crate) type Context = *mut GLFWwindow;
+
+pub(crate) fn current_context() -> Context {
+    unsafe { glfw::ffi::glfwGetCurrentContext() }
+}
+
+/// Major and minor version of the current context, queried once per context
+pub(crate) fn context_version() -> (GLint, GLint) {
+    let context = current_context();
+    VERSIONS.with_borrow_mut(|versions| {
+        if let Some((_, version)) = versions.iter().find(|(queried, _)| *queried == context) {
+            return *version;
+        }
+        let mut version = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut version.0);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut version.1);
+        }
+        versions.push((context, version));
+        version
+    })
+}
+
+/// A GL object waiting in the deletion queue, see [`delete_later`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GlObject {
+    Buffer(GLuint),
+    Program(GLuint),
+    Shader(GLuint),
+    VertexArray(GLuint),
+    Texture(GLuint),
+    Framebuffer(GLuint),
+    Renderbuffer(GLuint),
+    Query(GLuint),
+    Sync(GLsync),
+}
+
+impl GlObject {
+    /// Needs the context that created the object current
+    unsafe fn delete(self) {
+        match self {
+            Self::Buffer(id) => gl::DeleteBuffers(1, &id),
+            Self::Program(id) => gl::DeleteProgram(id),
+            Self::Shader(id) => gl::DeleteShader(id),
+            Self::VertexArray(id) => gl::DeleteVertexArrays(1, &id),
+            Self::Texture(id) => gl::DeleteTextures(1, &id),
+            Self::Framebuffer(id) => gl::DeleteFramebuffers(1, &id),
+            Self::Renderbuffer(id) => gl::DeleteRenderbuffers(1, &id),
+            Self::Query(id) => gl::DeleteQueries(1, &id),
+            Self::Sync(sync) => gl::DeleteSync(sync),
+        }
+    }
+}
+
+thread_local! {
+    // GL objects can't leave the thread of their context, so neither does their queue
+    static DELETION_QUEUE: RefCell<Vec<(Context, GlObject)>> =
+        const { RefCell::new(Vec::new()) };
+    // counts the flushes that deleted something, their names can be handed out again
+    static FLUSHES: Cell<u64> = const { Cell::new(0) };
+    // versions of the contexts queried so far
+    static VERSIONS: RefCell<Vec<(Context, (GLint, GLint))>> = const { RefCell::new(Vec::new()) };
+    // counts the buffer and vertex array bindings changed without `OpenGl`
+    static DIRECT_BINDS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Queues the deletion of `object` in the current context, for the `Drop` of the types
+/// owning one. Without a current context it was destroyed along with it.
+pub(crate) fn delete_later(object: GlObject) {
+    let context = current_context();
+    if context.is_null() {
+        log::debug!("Forgot {object:?} dropped without a current context");
+        return;
+    }
+    // the queue is only gone while the thread exits, the context with it
+    let _ = DELETION_QUEUE.try_with(|queue| queue.borrow_mut().push((context, object)));
+}
+
+/// Deletes the objects queued in the current context and returns how many, the others
+/// wait for theirs.
+pub(crate) fn flush_deletions() -> usize {
+    let context = current_context();
+    if context.is_null() {
+        return 0;
+    }
+    let Ok(objects) = DELETION_QUEUE.try_with(|queue| {
+        let mut objects = vec![];
+        queue.borrow_mut().retain(|(queued_in, object)| {
+            let current = *queued_in == context;
+            if current {
+                objects.push(*object);
+            }
+            !current
+        });
+        objects
+    }) else {
+        return 0;
+    };
+    if objects.is_empty() {
+        return 0;
+    }
+    for object in &objects {
+        unsafe { object.delete() };
+    }
+    FLUSHES.set(FLUSHES.get() + 1);
+    objects.len()
+}
+
+/// Drops the objects queued in the context of `window` without deleting them, for when
+/// the window is destroyed and its objects with it.
+pub(crate) fn forget_context(window: Context) {
+    let _ = VERSIONS.try_with(|versions| {
+        versions
+            .borrow_mut()
+            .retain(|(context, _)| *context != window);
+    });
+    let _ = DELETION_QUEUE.try_with(|queue| {
+        let mut queue = queue.borrow_mut();
+        let queued = queue.len();
+        queue.retain(|(queued_in, _)| *queued_in != window);
+        if queue.len() < queued {
+            log::debug!(
+                "Forgot {} GL objects of a destroyed window",
+                queued - queue.len()
+            );
+        }
+    });
+}
+
+/// Changes whenever [`flush_deletions`] deletes objects
+pub(crate) fn flushes() -> u64 {
+    FLUSHES.get()
+}
+
+/// Notes a buffer or vertex array binding changed behind [`crate::opengl::OpenGl`], which
+/// forgets its cached ones
+pub(crate) fn bound_directly() {
+    DIRECT_BINDS.set(DIRECT_BINDS.get() + 1);
+}
+
+/// Changes whenever [`bound_directly`] is called
+pub(crate) fn direct_binds() -> u64 {
+    DIRECT_BINDS.get()
+}
+
+macro_rules! handle {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(transparent)]
+        pub struct $name(pub(crate) GLHandle);
+
+        impl $name {
+            /// No object, binding it unbinds the current one
+            pub const NULL: Self = Self(NULL_HANDLE);
+
+            /// The name GL knows the object by, for raw `gl` calls
+            #[must_use]
+            pub const fn raw(self) -> GLuint {
+                self.0
+            }
+            #[must_use]
+            pub const fn is_null(self) -> bool {
+                self.0 == NULL_HANDLE
+            }
+        }
+    };
+}
+
+handle!(
+    /// Id of a [`crate::buffer::Buffer`]
+    BufferId
+);
+handle!(
+    /// Id of a [`crate::program::Program`]
+    ProgramId
+);
+handle!(
+    /// Id of a [`crate::vertex_attributes::VertexArrayObject`]
+    VaoId
+);
+handle!(
+    /// Id of the textures of [`crate::texture`]
+    TextureId
+);
+
+#[cfg(test)]
+mod test {
+    use super::{
+        delete_later, flush_deletions, forget_context, BufferId, Context, GlObject, ProgramId,
+        DELETION_QUEUE,
+    };
+
+    #[test]
+    fn test_handles() {
+        assert!(BufferId::NULL.is_null());
+        assert_eq!(ProgramId(3).raw(), 3);
+        assert_eq!(format!("{:?}", ProgramId(3)), "ProgramId(3)");
+    }
+
+    #[test]
+    fn test_deletions_without_context() {
+        delete_later(GlObject::Buffer(7));
+        delete_later(GlObject::Program(3));
+        assert!(DELETION_QUEUE.with_borrow(Vec::is_empty));
+        assert_eq!(flush_deletions(), 0);
+    }
+
+    #[test]
+    fn test_forget_context() {
+        let (first, second) = (8 as Context, 16 as Context);
+        DELETION_QUEUE.with_borrow_mut(|queue| {
+            queue.push((first, GlObject::Buffer(7)));
+            queue.push((second, GlObject::Buffer(8)));
+            queue.push((first, GlObject::Program(3)));
+        });
+        // no context is current to delete them in
+        assert_eq!(flush_deletions(), 0);
+        forget_context(first);
+        assert_eq!(
+            DELETION_QUEUE.with_borrow(Vec::clone),
+            [(second, GlObject::Buffer(8))]
+        );
+        forget_context(second);
+        assert!(DELETION_QUEUE.with_borrow(Vec::is_empty));
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    ffi::{CStr, CString},
+    marker::PhantomData,
+};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use glam::Vec3;
+use thiserror::Error;
+
+use crate::{
+    handle::{delete_later, ContextBound, GlObject},
+    opengl::{Capability, OpenGl, Primitive, StateFlags},
+    program::{Program, Shader, ShaderType},
+    texture::Texture,
+    vertex_attributes::VertexArrayObject,
+    viewport::Viewport,
+    GLHandle, NULL_HANDLE,
+};
+
+/// Covers the screen with one triangle made from `gl_VertexID`
+const VERTEX_SHADER: &CStr = c"#version 330
+
+out vec2 uv;
+
+void main() {
+    uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}";
+
+const FRAGMENT_SHADER: &CStr = c"#version 330
+
+in vec2 uv;
+
+uniform sampler2D hdrColor;
+uniform float exposure;
+uniform int operator;
+
+out vec4 outputColor;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(hdrColor, uv).rgb * exposure;
+    vec3 mapped = operator == 0 ? color / (1.0 + color) : aces(color);
+    outputColor = vec4(mapped, 1.0);
+}";
+
+#[derive(Error, Debug)]
+pub enum HdrError {
+    #[error("Tone mapping shader error: {0:?}")]
+    ShaderError(CString),
+    #[error("HDR framebuffer is incomplete, status {0:#x}")]
+    IncompleteFramebuffer(GLenum),
+}
+
+/// Framebuffer with a half float color attachment, for lighting brighter than 1
+/// to be tone mapped by a [`ToneMapper`] instead of clipped.
+pub struct HdrTarget {
+    framebuffer: GLHandle,
+    color_texture: GLHandle,
+    depth_renderbuffer: GLHandle,
+    width: GLsizei,
+    height: GLsizei,
+    _context: ContextBound,
+}
+
+impl Drop for HdrTarget {
+    fn drop(&mut self) {
+        delete_later(GlObject::Framebuffer(self.framebuffer));
+        delete_later(GlObject::Texture(self.color_texture));
+        delete_later(GlObject::Renderbuffer(self.depth_renderbuffer));
+    }
+}
+
+impl HdrTarget {
+    /// An RGBA16F color and 24 bit depth target of `width` by `height` pixels
+    pub fn new(width: GLsizei, height: GLsizei) -> Result<Self, HdrError> {
+        let mut target = Self {
+            framebuffer: NULL_HANDLE,
+            color_texture: NULL_HANDLE,
+            depth_renderbuffer: NULL_HANDLE,
+            width: 0,
+            height: 0,
+            _context: PhantomData,
+        };
+        let status = unsafe {
+            gl::GenTextures(1, &mut target.color_texture);
+            gl::BindTexture(gl::TEXTURE_2D, target.color_texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+            gl::GenRenderbuffers(1, &mut target.depth_renderbuffer);
+            target.resize(width, height);
+
+            gl::GenFramebuffers(1, &mut target.framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.framebuffer);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                target.color_texture,
+                0,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                target.depth_renderbuffer,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE);
+            status
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(HdrError::IncompleteFramebuffer(status));
+        }
+        Ok(target)
+    }
+
+    /// Reallocates the attachments, their contents are lost. Sizes below 1 become 1.
+    pub fn resize(&mut self, width: GLsizei, height: GLsizei) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA16F as GLint,
+                self.width,
+                self.height,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_renderbuffer);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH_COMPONENT24,
+                self.width,
+                self.height,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, NULL_HANDLE);
+        };
+    }
+    #[must_use]
+    pub const fn size(&self) -> (GLsizei, GLsizei) {
+        (self.width, self.height)
+    }
+
+    /// Renders what `draw` draws into the target, over its whole size. The default
+    /// framebuffer is bound again afterwards, the viewport is left to the caller.
+    pub fn draw(&self, gl: &mut OpenGl, draw: impl FnOnce(&mut OpenGl)) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer) };
+        gl.viewport(0, 0, self.width, self.height);
+        draw(gl);
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE) };
+    }
+
+    /// Binds the color attachment to texture `unit`
+    pub fn bind_texture(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+        };
+    }
+}
+
+impl Texture for HdrTarget {
+    fn bind_texture(&self, unit: GLuint) {
+        Self::bind_texture(self, unit);
+    }
+}
+
+/// Curve mapping HDR colors into the zero to one range of the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    /// `c / (1 + c)`, gentle and never reaching white
+    #[default]
+    Reinhard,
+    /// Filmic curve with more contrast that saturates to white
+    Aces,
+}
+
+impl ToneMapping {
+    /// What the tone mapping pass does to `color`, already scaled by the exposure
+    #[must_use]
+    pub fn map(self, color: Vec3) -> Vec3 {
+        match self {
+            Self::Reinhard => color / (Vec3::ONE + color),
+            Self::Aces => {
+                let numerator = color * (color * 2.51 + Vec3::splat(0.03));
+                let denominator = color * (color * 2.43 + Vec3::splat(0.59)) + Vec3::splat(0.14);
+                (numerator / denominator).clamp(Vec3::ZERO, Vec3::ONE)
+            }
+        }
+    }
+    #[must_use]
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::Reinhard,
+        }
+    }
+}
+
+/// Post pass drawing an [`HdrTarget`] to the bound framebuffer with a [`ToneMapping`] curve
+pub struct ToneMapper {
+    program: Program,
+    // draws without attributes still need a vertex array bound
+    vao: VertexArrayObject,
+    exposure_location: GLint,
+    operator_location: GLint,
+    pub tone_mapping: ToneMapping,
+    /// Scale applied to the colors before the curve
+    pub exposure: f32,
+}
+
+impl ToneMapper {
+    pub fn new() -> Result<Self, HdrError> {
+        let vertex_shader =
+            Shader::new(VERTEX_SHADER, ShaderType::Vertex).map_err(HdrError::ShaderError)?;
+        let fragment_shader =
+            Shader::new(FRAGMENT_SHADER, ShaderType::Fragment).map_err(HdrError::ShaderError)?;
+        let program =
+            Program::new(&[vertex_shader, fragment_shader]).map_err(HdrError::ShaderError)?;
+        Ok(Self {
+            exposure_location: program.get_uniform_location(c"exposure").unwrap_or(-1),
+            operator_location: program.get_uniform_location(c"operator").unwrap_or(-1),
+            program,
+            vao: VertexArrayObject::new(),
+            tone_mapping: ToneMapping::default(),
+            exposure: 1.0,
+        })
+    }
+
+    /// Draws `target` tone mapped into `viewport` of the bound framebuffer.
+    ///
+    /// Leaves the depth state as it was, the color stays bound to texture unit 0.
+    pub fn draw(&self, gl: &mut OpenGl, target: &HdrTarget, viewport: &Viewport) {
+        viewport.apply(gl);
+        let mut gl = gl.save_state(StateFlags::Depth);
+        gl.disable(Capability::DepthTest);
+
+        let mut gl = gl.bind_program_scoped(&self.program);
+        gl.set_uniform(self.exposure_location, self.exposure);
+        gl.set_uniform(self.operator_location, self.tone_mapping as i32);
+        gl.bind_texture(c"hdrColor", target);
+        gl.bind_vertex_array(&self.vao);
+        gl.draw_arrays(Primitive::Triangles, 0, 3);
+        gl.unbind_vertex_array();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::ToneMapping;
+
+    #[test]
+    fn test_tone_mapping() {
+        for tone_mapping in [ToneMapping::Reinhard, ToneMapping::Aces] {
+            let mut previous = tone_mapping.map(Vec3::ZERO);
+            assert!(previous.x.abs() < 0.01);
+            // brighter inputs never map darker and stay displayable
+            for i in 1..100 {
+                let mapped = tone_mapping.map(Vec3::splat(i as f32 * 0.5));
+                assert!(mapped.x >= previous.x);
+                assert!(mapped.x <= 1.0);
+                previous = mapped;
+            }
+        }
+        assert_eq!(ToneMapping::Reinhard.map(Vec3::ONE), Vec3::splat(0.5));
+        assert!(ToneMapping::Aces.map(Vec3::splat(100.0)).x > 0.99);
+        assert_eq!(ToneMapping::Reinhard.next().next(), ToneMapping::Reinhard);
+    }
+}
@@ -0,0 +1,218 @@
+//! 8 bit RGBA images, read and written as binary PAM, the RGBA member of the Netpbm
+//! formats, for textures loaded by [`crate::assets::AssetCache::texture`] and the golden
+//! images of [`crate::testing`].
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Not a binary RGBA PAM image: {0}")]
+    InvalidImage(&'static str),
+}
+
+/// Bytes of `width` by `height` RGBA pixels, `None` when they can't be addressed
+pub(crate) fn byte_count(width: u32, height: u32) -> Option<usize> {
+    (width as usize)
+        .checked_mul(height as usize)?
+        .checked_mul(4)
+}
+
+/// 8 bit RGBA pixels, rows from the top
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// How two images differ, see [`RgbaImage::diff`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageDiff {
+    /// Pixels with a channel differing by more than the tolerance
+    pub differing_pixels: usize,
+    pub max_channel_difference: u8,
+}
+
+impl ImageDiff {
+    #[must_use]
+    pub const fn matches(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+impl RgbaImage {
+    /// `None` unless there are 4 bytes for each of the `width` by `height` pixels
+    #[must_use]
+    pub fn from_raw(width: u32, height: u32, pixels: Vec<u8>) -> Option<Self> {
+        (Some(pixels.len()) == byte_count(width, height)).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+    #[must_use]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+    #[must_use]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    #[must_use]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Compares the images channel by channel, `None` if their sizes differ
+    #[must_use]
+    pub fn diff(&self, other: &Self, tolerance: u8) -> Option<ImageDiff> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        let mut diff = ImageDiff::default();
+        for (a, b) in self.pixels.chunks(4).zip(other.pixels.chunks(4)) {
+            let difference = a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or_default();
+            diff.max_channel_difference = diff.max_channel_difference.max(difference);
+            if difference > tolerance {
+                diff.differing_pixels += 1;
+            }
+        }
+        Some(diff)
+    }
+
+    /// Writes a binary PAM, the RGBA member of the Netpbm formats
+    pub fn write_pam(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(
+            writer,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            self.width, self.height
+        )?;
+        writer.write_all(&self.pixels)
+    }
+
+    /// Reads a binary PAM written by [`RgbaImage::write_pam`]
+    pub fn read_pam(reader: impl Read) -> Result<Self, ImageError> {
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if line.trim_end() != "P7" {
+            return Err(ImageError::InvalidImage("missing P7 magic"));
+        }
+        let (mut width, mut height) = (None, None);
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(ImageError::InvalidImage("missing ENDHDR"));
+            }
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("ENDHDR"), _) => break,
+                (Some("WIDTH"), Some(value)) => width = value.parse().ok(),
+                (Some("HEIGHT"), Some(value)) => height = value.parse().ok(),
+                (Some("DEPTH"), Some(value)) if value != "4" => {
+                    return Err(ImageError::InvalidImage("not 4 channels"));
+                }
+                (Some("MAXVAL"), Some(value)) if value != "255" => {
+                    return Err(ImageError::InvalidImage("not 8 bit"));
+                }
+                _ => {}
+            }
+        }
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err(ImageError::InvalidImage("missing size"));
+        };
+        let size = byte_count(width, height).ok_or(ImageError::InvalidImage("too large"))?;
+        // read up to the size, a header claiming more than there is doesn't allocate it
+        let mut pixels = Vec::new();
+        reader.take(size as u64).read_to_end(&mut pixels)?;
+        if pixels.len() != size {
+            return Err(ImageError::InvalidImage("missing pixels"));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_pam(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        Self::read_pam(File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RgbaImage;
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        let pixels = (0..width * height)
+            .flat_map(|i| [i as u8, (i * 3) as u8, 255 - i as u8, 255])
+            .collect();
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_from_raw() {
+        assert!(RgbaImage::from_raw(2, 2, vec![0; 15]).is_none());
+        let image = gradient(3, 2);
+        assert_eq!(image.pixel(1, 1), [4, 12, 251, 255]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let image = gradient(4, 4);
+        let mut other = image.clone();
+        other.pixels[5] += 3;
+        other.pixels[9] += 1;
+        let diff = image.diff(&other, 2).unwrap();
+        assert_eq!(diff.differing_pixels, 1);
+        assert_eq!(diff.max_channel_difference, 3);
+        assert!(image.diff(&other, 3).unwrap().matches());
+        assert!(image.diff(&gradient(4, 3), 0).is_none());
+    }
+
+    #[test]
+    fn test_pam_roundtrip() {
+        let image = gradient(5, 3);
+        let mut bytes = vec![];
+        image.write_pam(&mut bytes).unwrap();
+        assert!(bytes.starts_with(b"P7\nWIDTH 5\n"));
+        assert_eq!(RgbaImage::read_pam(bytes.as_slice()).unwrap(), image);
+        assert!(RgbaImage::read_pam(&b"P6\n"[..]).is_err());
+        bytes.pop();
+        assert!(RgbaImage::read_pam(bytes.as_slice()).is_err());
+        let huge = b"P7\nWIDTH 4294967295\nHEIGHT 4294967295\nENDHDR\n";
+        assert!(RgbaImage::read_pam(&huge[..]).is_err());
+    }
+}
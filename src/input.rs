@@ -0,0 +1,344 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use glfw::{
+    Action, GamepadAxis, GamepadButton, Glfw, JoystickId, Key, Modifiers, MouseButton, WindowEvent,
+};
+
+const BUTTON_COUNT: usize = GamepadButton::ButtonDpadLeft as usize + 1;
+const AXIS_COUNT: usize = GamepadAxis::AxisRightTrigger as usize + 1;
+
+/// The first connected joystick with a gamepad mapping, polled once per frame. Like the
+/// keys, `just_*` compares with the buttons seen by the last update.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Gamepad {
+    name: Option<String>,
+    buttons: [bool; BUTTON_COUNT],
+    previous_buttons: [bool; BUTTON_COUNT],
+    axes: [f32; AXIS_COUNT],
+}
+
+impl Gamepad {
+    #[must_use]
+    pub const fn connected(&self) -> bool {
+        self.name.is_some()
+    }
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    #[must_use]
+    pub const fn pressed(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize]
+    }
+    #[must_use]
+    pub const fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize] && !self.previous_buttons[button as usize]
+    }
+    #[must_use]
+    pub const fn just_released(&self, button: GamepadButton) -> bool {
+        !self.buttons[button as usize] && self.previous_buttons[button as usize]
+    }
+    /// Sticks go from -1 to 1 with +y down, triggers from -1 released to 1
+    #[must_use]
+    pub const fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    fn poll(&mut self, glfw: &Glfw) {
+        let state = (0..=glfw::ffi::JOYSTICK_LAST)
+            .filter_map(JoystickId::from_i32)
+            .map(|id| glfw.get_joystick(id))
+            .filter(glfw::Joystick::is_gamepad)
+            .find_map(|joystick| {
+                Some((joystick.get_gamepad_name(), joystick.get_gamepad_state()?))
+            });
+        let Some((name, state)) = state else {
+            *self = Self::default();
+            return;
+        };
+        self.name = Some(name.unwrap_or_default());
+        for (i, pressed) in self.buttons.iter_mut().enumerate() {
+            *pressed = GamepadButton::from_i32(i as i32)
+                .is_some_and(|button| state.get_button_state(button) == Action::Press);
+        }
+        for (i, value) in self.axes.iter_mut().enumerate() {
+            *value = GamepadAxis::from_i32(i as i32).map_or(0.0, |axis| state.get_axis(axis));
+        }
+    }
+}
+
+/// Keyboard and mouse state maintained by [`crate::app::run_app`], the `just_*` queries
+/// refer to the events since the last [`crate::app::Application::update`].
+#[derive(Debug, Clone)]
+pub struct Input {
+    pressed_keys: HashSet<Key>,
+    just_pressed_keys: HashSet<Key>,
+    just_released_keys: HashSet<Key>,
+    pressed_buttons: HashSet<MouseButton>,
+    just_pressed_buttons: HashSet<MouseButton>,
+    just_released_buttons: HashSet<MouseButton>,
+    cursor: Option<(f64, f64)>,
+    cursor_delta: (f64, f64),
+    modifiers: Modifiers,
+    gamepad: Gamepad,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            pressed_keys: HashSet::new(),
+            just_pressed_keys: HashSet::new(),
+            just_released_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            just_pressed_buttons: HashSet::new(),
+            just_released_buttons: HashSet::new(),
+            cursor: None,
+            cursor_delta: (0.0, 0.0),
+            modifiers: Modifiers::empty(),
+            gamepad: Gamepad::default(),
+        }
+    }
+}
+
+impl Input {
+    #[must_use]
+    pub fn pressed(&self, key: Key) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+    #[must_use]
+    pub fn just_pressed(&self, key: Key) -> bool {
+        self.just_pressed_keys.contains(&key)
+    }
+    #[must_use]
+    pub fn just_released(&self, key: Key) -> bool {
+        self.just_released_keys.contains(&key)
+    }
+
+    #[must_use]
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+    #[must_use]
+    pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed_buttons.contains(&button)
+    }
+    #[must_use]
+    pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+        self.just_released_buttons.contains(&button)
+    }
+
+    /// Cursor position in screen coordinates from the top left of the window
+    #[must_use]
+    pub fn cursor(&self) -> (f64, f64) {
+        self.cursor.unwrap_or_default()
+    }
+    /// How much the cursor moved since the last frame
+    #[must_use]
+    pub const fn cursor_delta(&self) -> (f64, f64) {
+        self.cursor_delta
+    }
+    /// Modifiers held during the last key or mouse button event
+    #[must_use]
+    pub const fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    #[must_use]
+    pub const fn gamepad(&self) -> &Gamepad {
+        &self.gamepad
+    }
+
+    pub(crate) fn poll_gamepad(&mut self, glfw: &Glfw) {
+        self.gamepad.poll(glfw);
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Key(key, _, action, modifiers) => {
+                self.modifiers = modifiers;
+                Self::update(
+                    key,
+                    action,
+                    &mut self.pressed_keys,
+                    &mut self.just_pressed_keys,
+                    &mut self.just_released_keys,
+                );
+            }
+            WindowEvent::MouseButton(button, action, modifiers) => {
+                self.modifiers = modifiers;
+                Self::update(
+                    button,
+                    action,
+                    &mut self.pressed_buttons,
+                    &mut self.just_pressed_buttons,
+                    &mut self.just_released_buttons,
+                );
+            }
+            WindowEvent::CursorPos(x, y) => {
+                // the first position only tells where the cursor is
+                if let Some((last_x, last_y)) = self.cursor {
+                    self.cursor_delta.0 += x - last_x;
+                    self.cursor_delta.1 += y - last_y;
+                }
+                self.cursor = Some((x, y));
+            }
+            _ => {}
+        }
+    }
+
+    fn update<T: Hash + Eq + Copy>(
+        value: T,
+        action: Action,
+        pressed: &mut HashSet<T>,
+        just_pressed: &mut HashSet<T>,
+        just_released: &mut HashSet<T>,
+    ) {
+        match action {
+            Action::Press => {
+                pressed.insert(value);
+                just_pressed.insert(value);
+            }
+            Action::Release => {
+                pressed.remove(&value);
+                just_released.insert(value);
+            }
+            Action::Repeat => {}
+        }
+    }
+
+    /// Forgets the events an update has seen
+    pub(crate) fn end_frame(&mut self) {
+        self.gamepad.previous_buttons = self.gamepad.buttons;
+        self.just_pressed_keys.clear();
+        self.just_released_keys.clear();
+        self.just_pressed_buttons.clear();
+        self.just_released_buttons.clear();
+        self.cursor_delta = (0.0, 0.0);
+    }
+}
+
+/// Binds application defined actions to one or more keys.
+#[derive(Debug, Clone)]
+pub struct KeyMap<A> {
+    bindings: HashMap<A, Vec<Key>>,
+}
+
+impl<A> Default for KeyMap<A> {
+    fn default() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+impl<A: Hash + Eq> KeyMap<A> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn bind(mut self, action: A, key: Key) -> Self {
+        self.bindings.entry(action).or_default().push(key);
+        self
+    }
+
+    #[must_use]
+    pub fn keys(&self, action: &A) -> &[Key] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether any key bound to `action` is held
+    #[must_use]
+    pub fn pressed(&self, input: &Input, action: &A) -> bool {
+        self.keys(action).iter().any(|key| input.pressed(*key))
+    }
+    #[must_use]
+    pub fn just_pressed(&self, input: &Input, action: &A) -> bool {
+        self.keys(action).iter().any(|key| input.just_pressed(*key))
+    }
+    #[must_use]
+    pub fn just_released(&self, input: &Input, action: &A) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|key| input.just_released(*key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glfw::{Action, GamepadButton, Key, Modifiers, WindowEvent};
+
+    use super::{Gamepad, Input, KeyMap};
+
+    fn key_event(key: Key, action: Action) -> WindowEvent {
+        WindowEvent::Key(key, 0, action, Modifiers::empty())
+    }
+
+    #[test]
+    fn test_key_state() {
+        let mut input = Input::default();
+        input.handle_event(&key_event(Key::W, Action::Press));
+        assert!(input.pressed(Key::W));
+        assert!(input.just_pressed(Key::W));
+
+        input.end_frame();
+        input.handle_event(&key_event(Key::W, Action::Repeat));
+        assert!(input.pressed(Key::W));
+        assert!(!input.just_pressed(Key::W));
+
+        input.handle_event(&key_event(Key::W, Action::Release));
+        assert!(!input.pressed(Key::W));
+        assert!(input.just_released(Key::W));
+    }
+
+    #[test]
+    fn test_cursor_delta() {
+        let mut input = Input::default();
+        input.handle_event(&WindowEvent::CursorPos(10.0, 10.0));
+        assert_eq!(input.cursor_delta(), (0.0, 0.0));
+        input.handle_event(&WindowEvent::CursorPos(15.0, 8.0));
+        input.handle_event(&WindowEvent::CursorPos(16.0, 8.0));
+        assert_eq!(input.cursor(), (16.0, 8.0));
+        assert_eq!(input.cursor_delta(), (6.0, -2.0));
+        input.end_frame();
+        assert_eq!(input.cursor_delta(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_key_map() {
+        let map = KeyMap::new()
+            .bind("forward", Key::W)
+            .bind("forward", Key::Up);
+        let mut input = Input::default();
+        input.handle_event(&key_event(Key::Up, Action::Press));
+        assert!(map.pressed(&input, &"forward"));
+        assert!(!map.pressed(&input, &"back"));
+    }
+
+    #[test]
+    fn test_gamepad_buttons() {
+        let mut gamepad = Gamepad::default();
+        assert!(!gamepad.connected());
+        gamepad.buttons[GamepadButton::ButtonA as usize] = true;
+        assert!(gamepad.just_pressed(GamepadButton::ButtonA));
+
+        gamepad.previous_buttons = gamepad.buttons;
+        gamepad.buttons[GamepadButton::ButtonA as usize] = false;
+        assert!(!gamepad.pressed(GamepadButton::ButtonA));
+        assert!(gamepad.just_released(GamepadButton::ButtonA));
+    }
+
+    #[test]
+    fn test_gamepad_until_end_frame() {
+        let mut input = Input::default();
+        input.gamepad.buttons[GamepadButton::ButtonA as usize] = true;
+        assert!(input.gamepad().just_pressed(GamepadButton::ButtonA));
+        input.end_frame();
+        assert!(input.gamepad().pressed(GamepadButton::ButtonA));
+        assert!(!input.gamepad().just_pressed(GamepadButton::ButtonA));
+    }
+}
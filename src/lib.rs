@@ -1,12 +1,40 @@
 #![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 pub mod app;
+pub mod assets;
 pub mod buffer;
+pub mod camera;
+pub mod color;
+pub mod debug_draw;
+pub mod framebuffer;
+pub mod frustum;
+pub mod gbuffer;
+pub mod gl_api;
+pub mod handle;
+pub mod hdr;
+pub mod image;
+pub mod input;
+pub mod lighting;
+pub mod material;
 pub mod matrix_stack;
 pub mod mesh;
+pub mod nodetree;
 pub mod opengl;
+pub mod particles;
 pub mod program;
+pub mod query;
+pub mod ray;
+pub mod render_graph;
+pub mod scene;
+pub mod shadow;
+pub mod sprite;
+pub mod sync;
+pub mod testing;
+pub mod text;
+pub mod texture;
+pub mod transform;
 pub mod uniforms;
 pub mod vertex_attributes;
+pub mod viewport;
 
 const NULL_HANDLE: GLHandle = 0;
 
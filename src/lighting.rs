@@ -0,0 +1,333 @@
+use std::ffi::{CString, NulError};
+
+use gl::types::GLuint;
+use glam::{Vec3, Vec4};
+use thiserror::Error;
+
+use crate::{
+    buffer::{Buffer, BufferError, Target, Usage},
+    material::{Material, ProgramHandle},
+    program::{Program, Shader, ShaderType},
+};
+
+/// Lights a [`LightBlock`] holds, the size of the array in the shaders
+pub const MAX_LIGHTS: usize = 8;
+
+macro_rules! lighting_shader {
+    ($file:literal $(, $line:literal)*) => {
+        concat!(
+            "#version 330\n",
+            $($line, "\n",)*
+            include_str!("../resources/shaders/lighting/lights.glsl"),
+            include_str!(concat!("../resources/shaders/lighting/", $file)),
+        )
+    };
+}
+pub(crate) use lighting_shader;
+
+const GOURAUD_VERTEX_SHADER: &str = lighting_shader!("gouraud.vert");
+const GOURAUD_FRAGMENT_SHADER: &str = lighting_shader!("gouraud.frag");
+pub(crate) const LIT_VERTEX_SHADER: &str = lighting_shader!("lit.vert");
+const PHONG_FRAGMENT_SHADER: &str = lighting_shader!("lit.frag");
+const BLINN_FRAGMENT_SHADER: &str = lighting_shader!("lit.frag", "#define BLINN");
+
+#[derive(Error, Debug)]
+pub enum LightingError {
+    #[error("Lighting shader error: {0:?}")]
+    ShaderError(CString),
+    #[error("Shader source contains a nul byte: {0}")]
+    NulError(#[from] NulError),
+}
+
+/// Light from infinitely far away, like the sun
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    /// Where the light shines to
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+/// Light shining from `position` in every direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance at which the light fades out, 0 for no falloff
+    pub range: f32,
+}
+
+/// Light shining from `position` in a cone around `direction`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance at which the light fades out, 0 for no falloff
+    pub range: f32,
+    /// Degrees from `direction` within which the light is at full intensity
+    pub inner_angle: f32,
+    /// Degrees from `direction` past which there is no light
+    pub outer_angle: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Self::Directional(light)
+    }
+}
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Self::Point(light)
+    }
+}
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Self::Spot(light)
+    }
+}
+
+/// A light as the `Light` struct in the shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct GpuLight {
+    position: Vec4,
+    direction: Vec4,
+    color: Vec4,
+    cone: Vec4,
+}
+
+impl From<Light> for GpuLight {
+    fn from(light: Light) -> Self {
+        // without a cone every direction is past both cosines
+        const NO_CONE: (f32, f32) = (-2.0, -1.0);
+        match light {
+            Light::Directional(light) => Self {
+                position: Vec4::ZERO,
+                direction: light.direction.normalize().extend(NO_CONE.0),
+                color: (light.color * light.intensity).extend(0.0),
+                cone: Vec4::new(NO_CONE.1, 0.0, 0.0, 0.0),
+            },
+            Light::Point(light) => Self {
+                position: light.position.extend(1.0),
+                direction: Vec3::ZERO.extend(NO_CONE.0),
+                color: (light.color * light.intensity).extend(light.range),
+                cone: Vec4::new(NO_CONE.1, 0.0, 0.0, 0.0),
+            },
+            Light::Spot(light) => Self {
+                position: light.position.extend(1.0),
+                direction: light
+                    .direction
+                    .normalize()
+                    .extend(light.outer_angle.to_radians().cos()),
+                color: (light.color * light.intensity).extend(light.range),
+                cone: Vec4::new(light.inner_angle.to_radians().cos(), 0.0, 0.0, 0.0),
+            },
+        }
+    }
+}
+
+/// The `Lights` uniform block of the lighting shaders, in std140 layout
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LightBlock {
+    ambient: Vec4,
+    camera_position: Vec4,
+    /// x is the number of lights in use
+    light_count: [i32; 4],
+    lights: [GpuLight; MAX_LIGHTS],
+}
+
+impl LightBlock {
+    #[must_use]
+    pub fn new(ambient: Vec3) -> Self {
+        Self {
+            ambient: ambient.extend(1.0),
+            ..Self::default()
+        }
+    }
+
+    pub fn set_ambient(&mut self, ambient: Vec3) {
+        self.ambient = ambient.extend(1.0);
+    }
+    /// Where specular highlights are seen from
+    pub fn set_camera_position(&mut self, position: Vec3) {
+        self.camera_position = position.extend(1.0);
+    }
+
+    /// Adds a light, false when there are already [`MAX_LIGHTS`]
+    pub fn push(&mut self, light: impl Into<Light>) -> bool {
+        let len = self.len();
+        if len == MAX_LIGHTS {
+            return false;
+        }
+        self.lights[len] = light.into().into();
+        self.light_count[0] += 1;
+        true
+    }
+    pub fn clear(&mut self) {
+        self.light_count[0] = 0;
+    }
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.light_count[0] as usize
+    }
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.light_count[0] == 0
+    }
+}
+
+/// Uniform buffer with a [`LightBlock`], bound to a binding index for the programs
+/// made by [`ShadingModel::program`]
+pub struct LightBuffer {
+    buffer: Buffer<LightBlock>,
+}
+
+impl LightBuffer {
+    #[must_use]
+    pub fn new(binding_index: GLuint) -> Self {
+        let mut buffer = Buffer::new(Target::UniformBuffer);
+        buffer.bind_scoped().reserve_data(1, Usage::DynamicDraw);
+        buffer.bind_base(binding_index);
+        Self { buffer }
+    }
+
+    pub fn update(&mut self, block: &LightBlock) -> Result<(), BufferError> {
+        self.buffer
+            .bind_scoped()
+            .update_data(std::slice::from_ref(block), 0)
+    }
+}
+
+/// How the reference lighting programs shade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingModel {
+    /// Lighting computed per vertex and interpolated
+    Gouraud,
+    /// Per fragment with the reflection vector for specular
+    Phong,
+    /// Per fragment with the half angle vector for specular, which spreads wider than
+    /// Phong for the same shininess
+    Blinn,
+}
+
+impl ShadingModel {
+    /// Vertex and fragment shader sources, without the nul terminator
+    #[must_use]
+    pub const fn sources(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Gouraud => (GOURAUD_VERTEX_SHADER, GOURAUD_FRAGMENT_SHADER),
+            Self::Phong => (LIT_VERTEX_SHADER, PHONG_FRAGMENT_SHADER),
+            Self::Blinn => (LIT_VERTEX_SHADER, BLINN_FRAGMENT_SHADER),
+        }
+    }
+
+    /// Links the reference program, reading its lights from `binding_index`.
+    /// It takes position and normal at attribute locations 0 and 2.
+    pub fn program(self, binding_index: GLuint) -> Result<Program, LightingError> {
+        let (vertex, fragment) = self.sources();
+        let vertex = Shader::new(&CString::new(vertex)?, ShaderType::Vertex)
+            .map_err(LightingError::ShaderError)?;
+        let fragment = Shader::new(&CString::new(fragment)?, ShaderType::Fragment)
+            .map_err(LightingError::ShaderError)?;
+        let mut program = Program::new(&[vertex, fragment]).map_err(LightingError::ShaderError)?;
+        if let Some(block) = program.get_uniform_block_index(c"Lights") {
+            program.uniform_block_binding(block, binding_index);
+        }
+        Ok(program)
+    }
+}
+
+/// A material for the programs of [`ShadingModel::program`], `shininess` is the exponent
+/// of the specular highlight
+#[must_use]
+pub fn material(program: ProgramHandle, diffuse: Vec4, specular: Vec4, shininess: f32) -> Material {
+    Material::new(program)
+        .with_model_to_world(c"modelToWorld")
+        .with_view_projection(c"worldToClip")
+        .with(c"diffuseColor", diffuse)
+        .with(c"specularColor", specular)
+        .with(c"shininess", shininess)
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Vec3, Vec4};
+
+    use super::{
+        DirectionalLight, GpuLight, Light, LightBlock, PointLight, ShadingModel, SpotLight,
+        MAX_LIGHTS,
+    };
+
+    #[test]
+    fn test_light_block() {
+        // std140: three vec4 and an array of structs of four vec4
+        assert_eq!(std::mem::size_of::<LightBlock>(), 16 * 3 + 64 * MAX_LIGHTS);
+
+        let mut block = LightBlock::new(Vec3::splat(0.1));
+        assert!(block.is_empty());
+        let sun = DirectionalLight {
+            direction: Vec3::NEG_Y * 2.0,
+            color: Vec3::ONE,
+            intensity: 0.5,
+        };
+        for _ in 0..MAX_LIGHTS {
+            assert!(block.push(sun));
+        }
+        assert!(!block.push(sun));
+        assert_eq!(block.len(), MAX_LIGHTS);
+        block.clear();
+        assert!(block.is_empty());
+    }
+
+    #[test]
+    fn test_gpu_light() {
+        let point = GpuLight::from(Light::from(PointLight {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            color: Vec3::new(1.0, 0.5, 0.0),
+            intensity: 2.0,
+            range: 10.0,
+        }));
+        assert_eq!(point.position, Vec4::new(1.0, 2.0, 3.0, 1.0));
+        assert_eq!(point.color, Vec4::new(2.0, 1.0, 0.0, 10.0));
+
+        let spot = GpuLight::from(Light::from(SpotLight {
+            position: Vec3::ZERO,
+            direction: Vec3::NEG_Y * 5.0,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: 0.0,
+            inner_angle: 0.0,
+            outer_angle: 60.0,
+        }));
+        assert_eq!(spot.direction.truncate(), Vec3::NEG_Y);
+        assert!((spot.direction.w - 0.5).abs() < 1e-6);
+        assert!((spot.cone.x - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sources() {
+        for model in [
+            ShadingModel::Gouraud,
+            ShadingModel::Phong,
+            ShadingModel::Blinn,
+        ] {
+            let (vertex, fragment) = model.sources();
+            assert!(vertex.starts_with("#version 330\n"));
+            assert!(fragment.starts_with("#version 330\n"));
+            assert!(fragment.contains("uniform Lights"));
+        }
+        assert!(ShadingModel::Blinn.sources().1.contains("#define BLINN"));
+        assert!(!ShadingModel::Phong.sources().1.contains("#define BLINN"));
+    }
+}
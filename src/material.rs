@@ -0,0 +1,119 @@
+use std::{
+    ffi::{CStr, CString},
+    rc::Rc,
+};
+
+use glam::Mat4;
+
+use crate::{
+    opengl::OpenGl,
+    program::{GLLocation, Program},
+    uniforms::UniformValue,
+};
+
+pub type ProgramHandle = Rc<Program>;
+pub type MaterialHandle = Rc<Material>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Parameter {
+    name: CString,
+    /// `None` when the program doesn't use the uniform
+    location: Option<GLLocation>,
+    value: UniformValue,
+}
+
+/// A shared program with named uniform values, like colors, scalars and texture units,
+/// set together by [`Material::apply`]. Names the program doesn't use are kept but not set.
+pub struct Material {
+    program: ProgramHandle,
+    model_to_world: Option<GLLocation>,
+    view_projection: Option<GLLocation>,
+    parameters: Vec<Parameter>,
+}
+
+impl Material {
+    #[must_use]
+    pub const fn new(program: ProgramHandle) -> Self {
+        Self {
+            program,
+            model_to_world: None,
+            view_projection: None,
+            parameters: vec![],
+        }
+    }
+    /// The uniform the matrix of each object is set to, see [`Material::set_model_to_world`]
+    #[must_use]
+    pub fn with_model_to_world(mut self, name: &CStr) -> Self {
+        self.model_to_world = self.program.get_uniform_location(name);
+        self
+    }
+    /// The uniform the camera matrix is set to, see [`Material::set_view_projection`]
+    #[must_use]
+    pub fn with_view_projection(mut self, name: &CStr) -> Self {
+        self.view_projection = self.program.get_uniform_location(name);
+        self
+    }
+    #[must_use]
+    pub fn with(mut self, name: &CStr, value: impl Into<UniformValue>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    #[must_use]
+    pub const fn program(&self) -> &ProgramHandle {
+        &self.program
+    }
+
+    /// Changes the value of the parameter `name`, adding it if there isn't one.
+    /// It's sent to the program on the next [`Material::apply`].
+    pub fn set(&mut self, name: &CStr, value: impl Into<UniformValue>) {
+        let value = value.into();
+        if let Some(parameter) = self
+            .parameters
+            .iter_mut()
+            .find(|p| p.name.as_c_str() == name)
+        {
+            parameter.value = value;
+        } else {
+            self.parameters.push(Parameter {
+                name: name.to_owned(),
+                location: self.program.get_uniform_location(name),
+                value,
+            });
+        }
+    }
+    #[must_use]
+    pub fn get(&self, name: &CStr) -> Option<UniformValue> {
+        self.parameters
+            .iter()
+            .find(|p| p.name.as_c_str() == name)
+            .map(|p| p.value)
+    }
+    /// Whether the program uses the uniform `name`
+    #[must_use]
+    pub fn uses(&self, name: &CStr) -> bool {
+        self.program.get_uniform_location(name).is_some()
+    }
+
+    /// Binds the program and sets every parameter
+    pub fn apply(&self, gl: &mut OpenGl) {
+        gl.bind_program(&self.program);
+        for parameter in &self.parameters {
+            if let Some(location) = parameter.location {
+                self.program.set_uniform(location, parameter.value);
+            }
+        }
+    }
+    /// Sets the matrix of the object about to be drawn, the program has to be bound
+    pub fn set_model_to_world(&self, matrix: Mat4) {
+        if let Some(location) = self.model_to_world {
+            self.program.set_uniform(location, matrix);
+        }
+    }
+    /// Sets the camera matrix, the program has to be bound
+    pub fn set_view_projection(&self, matrix: Mat4) {
+        if let Some(location) = self.view_projection {
+            self.program.set_uniform(location, matrix);
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use std::ops::Mul;
+use std::ops::{Deref, DerefMut, Mul, MulAssign};
 
-use glam::{Mat4, Quat, Vec3};
+use glam::{Mat3, Mat4, Quat, Vec3};
+
+use crate::{camera::perspective, transform::Transform};
 
 pub struct MatrixStack {
     stack: Vec<Mat4>,
@@ -8,13 +10,15 @@ pub struct MatrixStack {
 }
 
 impl MatrixStack {
-    #[must_use] pub const fn new() -> Self {
+    #[must_use]
+    pub const fn new() -> Self {
         Self {
             stack: vec![],
             current_matrix: Mat4::IDENTITY,
         }
     }
-    #[must_use] pub const fn with_initial_matrix(mat: Mat4) -> Self {
+    #[must_use]
+    pub const fn with_initial_matrix(mat: Mat4) -> Self {
         Self {
             stack: vec![],
             current_matrix: mat,
@@ -33,7 +37,8 @@ impl MatrixStack {
             self.current_matrix = *value;
         }
     }
-    #[must_use] pub const fn top(&self) -> Mat4 {
+    #[must_use]
+    pub const fn top(&self) -> Mat4 {
         self.current_matrix
     }
     pub fn rotate_rad(&mut self, axis: Vec3, angle_rad: f32) {
@@ -69,6 +74,15 @@ impl MatrixStack {
     pub fn perspective(&mut self, fov: f32, aspect_ratio: f32, z_near: f32, z_far: f32) {
         self.current_matrix *= Mat4::perspective_rh_gl(fov, aspect_ratio, z_near, z_far);
     }
+    /// Perspective without a far plane
+    pub fn perspective_infinite(&mut self, fov: f32, aspect_ratio: f32, z_near: f32) {
+        self.current_matrix *= perspective(fov, aspect_ratio, z_near, f32::INFINITY, false);
+    }
+    /// Perspective mapping `z_near` to depth 1 and `z_far` to 0, which may be infinite.
+    /// See [`OpenGl::set_reverse_z`](crate::opengl::OpenGl::set_reverse_z).
+    pub fn perspective_reverse_z(&mut self, fov: f32, aspect_ratio: f32, z_near: f32, z_far: f32) {
+        self.current_matrix *= perspective(fov, aspect_ratio, z_near, z_far, true);
+    }
     pub fn orthographic(
         &mut self,
         left: f32,
@@ -83,12 +97,40 @@ impl MatrixStack {
     pub fn apply_matrix(&mut self, mat: Mat4) {
         self.current_matrix *= mat;
     }
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        self.current_matrix *= transform.to_matrix();
+    }
     pub fn set_matrix(&mut self, mat: Mat4) {
         self.current_matrix = mat;
     }
     pub fn set_identity(&mut self) {
         self.current_matrix = Mat4::IDENTITY;
     }
+    /// Inverse transpose of the upper 3x3 of the current matrix,
+    /// transforms normals so they stay perpendicular under non-uniform scaling.
+    #[must_use]
+    pub fn normal_matrix(&self) -> Mat3 {
+        Mat3::from_mat4(self.current_matrix).inverse().transpose()
+    }
+    #[must_use]
+    pub fn inverse_top(&self) -> Mat4 {
+        self.current_matrix.inverse()
+    }
+    /// Transforms a point by the current matrix, with perspective division.
+    #[must_use]
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.current_matrix.project_point3(point)
+    }
+    /// Transforms a direction by the current matrix, ignoring the translation.
+    #[must_use]
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.current_matrix.transform_vector3(vector)
+    }
+    /// Runs `f` with the current matrix pushed, it is popped once `f` returns.
+    pub fn with_pushed<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let mut push = PushStack::new(self);
+        f(&mut push)
+    }
 }
 
 pub struct PushStack<'a> {
@@ -108,16 +150,84 @@ impl<'a> PushStack<'a> {
     }
 }
 
-impl Mul<Mat4> for MatrixStack {
-    type Output = ();
+impl Deref for PushStack<'_> {
+    type Target = MatrixStack;
+
+    fn deref(&self) -> &Self::Target {
+        self.stack
+    }
+}
+
+impl DerefMut for PushStack<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.stack
+    }
+}
 
-    fn mul(mut self, rhs: Mat4) -> Self::Output {
+impl MulAssign<Mat4> for MatrixStack {
+    fn mul_assign(&mut self, rhs: Mat4) {
         self.current_matrix *= rhs;
     }
 }
 
+impl Mul<Mat4> for &MatrixStack {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        self.current_matrix * rhs
+    }
+}
+
 impl Default for MatrixStack {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat3, Mat4, Vec3};
+
+    use super::{MatrixStack, PushStack};
+
+    #[test]
+    fn test_push_stack() {
+        let mut stack = MatrixStack::new();
+        {
+            let mut push = PushStack::new(&mut stack);
+            push.translate(Vec3::X);
+            assert_eq!(push.top(), Mat4::from_translation(Vec3::X));
+        }
+        assert_eq!(stack.top(), Mat4::IDENTITY);
+
+        let top = stack.with_pushed(|stack| {
+            stack.scale(Vec3::splat(2.0));
+            stack.top()
+        });
+        assert_eq!(top, Mat4::from_scale(Vec3::splat(2.0)));
+        assert_eq!(stack.top(), Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn test_operators() {
+        let mut stack = MatrixStack::new();
+        stack *= Mat4::from_translation(Vec3::X);
+        assert_eq!(&stack * Mat4::IDENTITY, Mat4::from_translation(Vec3::X));
+
+        stack.uniform_scale(2.0);
+        assert_eq!(stack.transform_point(Vec3::Y), Vec3::new(1.0, 2.0, 0.0));
+        assert_eq!(stack.transform_vector(Vec3::Y), Vec3::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_normal_matrix() {
+        let mut stack = MatrixStack::new();
+        stack.translate(Vec3::new(1.0, 2.0, 3.0));
+        stack.scale(Vec3::new(2.0, 1.0, 1.0));
+        // translation doesn't affect normals, scaling by 2 along x halves them along x
+        assert!(stack
+            .normal_matrix()
+            .abs_diff_eq(Mat3::from_diagonal(Vec3::new(0.5, 1.0, 1.0)), 1e-6));
+        assert!((stack.top() * stack.inverse_top()).abs_diff_eq(Mat4::IDENTITY, 1e-6));
+    }
+}
@@ -1,17 +1,45 @@
-use std::{collections::HashMap, fs::File, io::BufReader, path::Path, str::FromStr};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
 
-use gl::types::{GLbyte, GLfloat, GLint, GLshort, GLsizeiptr, GLubyte, GLuint, GLushort};
-use glam::bool;
+use gl::types::{
+    GLbyte, GLdouble, GLfloat, GLint, GLshort, GLsizei, GLsizeiptr, GLubyte, GLuint, GLushort,
+};
+use glam::{bool, Mat3, Mat4, Vec3, Vec4};
 use thiserror::Error;
-use xml::{attribute::OwnedAttribute, reader::XmlEvent, EventReader};
+use xml::{attribute::OwnedAttribute, common::Position, reader::XmlEvent, EventReader};
 
 use crate::{
-    buffer::{Buffer, Target, Usage},
-    opengl::{IndexSize, OpenGl, Primitive},
+    buffer::{Buffer, BufferError, Target, Usage},
+    debug_draw::DebugDraw,
+    opengl::{Capability, IndexSize, OpenGl, PolygonMode, Primitive, StateFlags},
     vertex_attributes::{DataType, VertexArrayObject, VertexAttribute},
 };
+mod builder;
+mod debug;
+mod export;
+mod loader;
+mod obj;
+mod ply;
+mod stl;
+
+pub use builder::MeshBuilder;
+pub use debug::NormalLines;
+pub use loader::{MeshLoader, PendingMesh};
+
 type MeshResult<T> = Result<T, MeshError>;
 
+/// Attribute locations used by formats that don't specify their own
+pub const POSITION_ATTRIBUTE_LOCATION: GLuint = 0;
+pub const COLOR_ATTRIBUTE_LOCATION: GLuint = 1;
+pub const NORMAL_ATTRIBUTE_LOCATION: GLuint = 2;
+pub const TEXCOORD_ATTRIBUTE_LOCATION: GLuint = 5;
+pub const TANGENT_ATTRIBUTE_LOCATION: GLuint = 6;
+
 #[derive(Error, Debug)]
 pub enum MeshError {
     #[error("Input error: {0}")]
@@ -28,7 +56,7 @@ pub enum MeshError {
     #[error("Non existing attribute searched: {0}")]
     NonExistingAttribute(String),
     #[error("Parsing attribute data error: {0}")]
-    ParseAttributeDataError(Box<dyn std::error::Error>),
+    ParseAttributeDataError(Box<dyn std::error::Error + Send + Sync>),
     #[error("Attribute index must be between 0 and 16, found: {0}")]
     InvalidVertexAttributeLocation(GLuint),
     #[error("Attribute size must be between 1 and 5, found: {0}")]
@@ -57,11 +85,36 @@ pub enum MeshError {
     IntegralNormalizedError,
     #[error("cannot be both integral and floating point")]
     IntegralFloatingError,
+    #[error("Buffer error: {0}")]
+    BufferError(#[from] BufferError),
+    #[error("Invalid obj data at line {0}, file path:{1:?}")]
+    InvalidObjLine(usize, String),
+    #[error("Obj index {0} out of range at line {1}, file path:{2:?}")]
+    ObjIndexOutOfRange(isize, usize, String),
+    #[error("Index {0} out of range, vertex count {1}")]
+    IndexOutOfRange(GLuint, usize),
+    #[error("Missing float attribute at location {0}")]
+    MissingAttribute(GLuint),
+    #[error("Attribute at location {0} has {1} vertices, positions have {2}")]
+    AttributeLength(GLuint, usize, usize),
+    #[error("Unsupported primitive {0:?}, must be made of triangles")]
+    UnsupportedPrimitive(Primitive),
+    #[error("Mesh loader thread stopped before loading the mesh")]
+    LoaderDisconnected,
+    #[error("Invalid stl data, file path:{0:?}")]
+    InvalidStlData(String),
+    #[error("Invalid ply data: {0}, file path:{1:?}")]
+    InvalidPlyData(String, String),
+    #[error("Xml error at line {0}, column {1}: {msg}, file path:{3:?}", msg = .2.msg())]
+    XmlError(u64, u64, xml::reader::Error, String),
 }
 
 #[derive(Debug, PartialEq)]
 enum VertexAttributeValues {
+    Double(Vec<GLdouble>),
     Float(Vec<GLfloat>),
+    /// Half floats stored as their raw bits
+    Half(Vec<u16>),
     UnsignedInt(Vec<GLuint>),
     Int(Vec<GLint>),
     UnsignedShort(Vec<GLushort>),
@@ -73,10 +126,18 @@ enum VertexAttributeValues {
 impl VertexAttributeValues {
     fn parse_add(&mut self, word: &str) -> MeshResult<()> {
         match self {
+            Self::Double(items) => {
+                items.push(word.parse::<GLdouble>()?);
+                Ok(())
+            }
             Self::Float(items) => {
                 items.push(word.parse::<GLfloat>()?);
                 Ok(())
             }
+            Self::Half(items) => {
+                items.push(f32_to_f16_bits(word.parse::<GLfloat>()?));
+                Ok(())
+            }
             Self::UnsignedInt(items) => {
                 items.push(word.parse::<GLuint>()?);
                 Ok(())
@@ -105,10 +166,11 @@ impl VertexAttributeValues {
     }
     fn len(&self) -> usize {
         match self {
+            Self::Double(items) => items.len(),
             Self::Float(items) => items.len(),
             Self::UnsignedInt(items) => items.len(),
             Self::Int(items) => items.len(),
-            Self::UnsignedShort(items) => items.len(),
+            Self::UnsignedShort(items) | Self::Half(items) => items.len(),
             Self::Short(items) => items.len(),
             Self::UnsignedByte(items) => items.len(),
             Self::Byte(items) => items.len(),
@@ -117,22 +179,48 @@ impl VertexAttributeValues {
     #[allow(dead_code)]
     fn is_empty(&self) -> bool {
         match self {
+            Self::Double(items) => items.is_empty(),
             Self::Float(items) => items.is_empty(),
             Self::UnsignedInt(items) => items.is_empty(),
             Self::Int(items) => items.is_empty(),
-            Self::UnsignedShort(items) => items.is_empty(),
+            Self::UnsignedShort(items) | Self::Half(items) => items.is_empty(),
             Self::Short(items) => items.is_empty(),
             Self::UnsignedByte(items) => items.is_empty(),
             Self::Byte(items) => items.is_empty(),
         }
     }
 
+    /// Builds the values of the vertices at `indices`, each vertex made of `components` values
+    fn gather(&self, components: usize, indices: &[GLuint]) -> Self {
+        fn gather<T: Copy>(items: &[T], components: usize, indices: &[GLuint]) -> Vec<T> {
+            indices
+                .iter()
+                .flat_map(|i| {
+                    let start = *i as usize * components;
+                    items[start..start + components].iter().copied()
+                })
+                .collect()
+        }
+        match self {
+            Self::Double(items) => Self::Double(gather(items, components, indices)),
+            Self::Float(items) => Self::Float(gather(items, components, indices)),
+            Self::Half(items) => Self::Half(gather(items, components, indices)),
+            Self::UnsignedInt(items) => Self::UnsignedInt(gather(items, components, indices)),
+            Self::Int(items) => Self::Int(gather(items, components, indices)),
+            Self::UnsignedShort(items) => Self::UnsignedShort(gather(items, components, indices)),
+            Self::Short(items) => Self::Short(gather(items, components, indices)),
+            Self::UnsignedByte(items) => Self::UnsignedByte(gather(items, components, indices)),
+            Self::Byte(items) => Self::Byte(gather(items, components, indices)),
+        }
+    }
+
     fn get_bytes(&self) -> &[u8] {
         match self {
+            Self::Double(items) => bytemuck::cast_slice(items),
             Self::Float(items) => bytemuck::cast_slice(items),
             Self::UnsignedInt(items) => bytemuck::cast_slice(items),
             Self::Int(items) => bytemuck::cast_slice(items),
-            Self::UnsignedShort(items) => bytemuck::cast_slice(items),
+            Self::UnsignedShort(items) | Self::Half(items) => bytemuck::cast_slice(items),
             Self::Short(items) => bytemuck::cast_slice(items),
             Self::UnsignedByte(items) => bytemuck::cast_slice(items),
             Self::Byte(items) => bytemuck::cast_slice(items),
@@ -140,21 +228,94 @@ impl VertexAttributeValues {
     }
 }
 
-impl From<DataType> for VertexAttributeValues {
-    fn from(value: DataType) -> Self {
+impl TryFrom<DataType> for VertexAttributeValues {
+    type Error = MeshError;
+    fn try_from(value: DataType) -> MeshResult<Self> {
         match value {
-            DataType::Byte => Self::Byte(vec![]),
-            DataType::UnsignedByte => Self::UnsignedByte(vec![]),
-            DataType::Short => Self::Short(vec![]),
-            DataType::UnsignedShort => Self::UnsignedShort(vec![]),
-            DataType::Int => Self::Int(vec![]),
-            DataType::UnsignedInt => Self::UnsignedInt(vec![]),
-            DataType::Float => Self::Float(vec![]),
-            DataType::Fixed | DataType::Double => unimplemented!(),
+            DataType::Byte => Ok(Self::Byte(vec![])),
+            DataType::UnsignedByte => Ok(Self::UnsignedByte(vec![])),
+            DataType::Short => Ok(Self::Short(vec![])),
+            DataType::UnsignedShort => Ok(Self::UnsignedShort(vec![])),
+            DataType::Int => Ok(Self::Int(vec![])),
+            DataType::UnsignedInt => Ok(Self::UnsignedInt(vec![])),
+            DataType::Float => Ok(Self::Float(vec![])),
+            DataType::HalfFloat => Ok(Self::Half(vec![])),
+            DataType::Double => Ok(Self::Double(vec![])),
+            DataType::Fixed | DataType::Int2101010Rev | DataType::UnsignedInt2101010Rev => {
+                Err(MeshError::UnimplementedDataFormat(value))
+            }
         }
     }
 }
 
+/// Per vertex normals of a non indexed triangle list, every vertex gets the normal of its
+/// triangle, facing the side where the vertices wind counter clockwise.
+fn flat_normals(positions: &[GLfloat]) -> Vec<GLfloat> {
+    positions
+        .chunks_exact(9)
+        .flat_map(|triangle| {
+            let a = Vec3::new(triangle[0], triangle[1], triangle[2]);
+            let b = Vec3::new(triangle[3], triangle[4], triangle[5]);
+            let c = Vec3::new(triangle[6], triangle[7], triangle[8]);
+            let normal = (b - a).cross(c - a).normalize_or_zero().to_array();
+            [normal; 3].into_iter().flatten()
+        })
+        .collect()
+}
+
+/// Converts to the bits of the nearest half float, rounding ties to even.
+fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        // infinity or NaN
+        let nan = if mantissa == 0 { 0 } else { 0x0200 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        // too big, becomes infinity
+        return sign | 0x7c00;
+    }
+    let (half, mantissa, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            // too small, becomes zero
+            return sign;
+        }
+        // subnormal, the implicit leading bit becomes explicit
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        (mantissa >> shift, mantissa, shift)
+    } else {
+        (((exponent as u32) << 10) | (mantissa >> 13), mantissa, 13)
+    };
+    let round_bit = 1 << (shift - 1);
+    // round up when above the halfway point, or exactly on it with an odd result
+    let round_up = mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0;
+    // a carry out of the mantissa correctly bumps the exponent, up to infinity
+    sign | (half + u32::from(round_up)) as u16
+}
+
+/// Widens half float bits, every half float is exactly representable as a float.
+fn f16_bits_to_f32(bits: u16) -> f32 {
+    let exponent = u32::from(bits >> 10 & 0x1f);
+    let mantissa = u32::from(bits & 0x3ff);
+    let value = match exponent {
+        // zero and subnormals
+        0 => f32::from(bits & 0x3ff) * 2f32.powi(-24),
+        // infinity and NaN
+        0x1f => f32::from_bits(0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits((exponent + 112) << 23 | mantissa << 13),
+    };
+    if bits & 0x8000 == 0 {
+        value
+    } else {
+        -value
+    }
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq)]
 enum IndicesValues {
@@ -189,6 +350,13 @@ impl IndicesValues {
             Self::UnsignedByte(items) => items.len(),
         }
     }
+    fn to_indices(&self) -> Vec<GLuint> {
+        match self {
+            Self::UnsignedInt(items) => items.clone(),
+            Self::UnsignedShort(items) => items.iter().map(|i| GLuint::from(*i)).collect(),
+            Self::UnsignedByte(items) => items.iter().map(|i| GLuint::from(*i)).collect(),
+        }
+    }
     fn get_bytes(&self) -> &[u8] {
         match self {
             Self::UnsignedInt(items) => bytemuck::cast_slice(items),
@@ -209,7 +377,7 @@ impl From<IndexSize> for IndicesValues {
 }
 
 fn parse_attribute_values(data_type: DataType, s: &str) -> MeshResult<VertexAttributeValues> {
-    let mut data = VertexAttributeValues::from(data_type);
+    let mut data = VertexAttributeValues::try_from(data_type)?;
     for word in s.split_whitespace() {
         data.parse_add(word)?;
     }
@@ -231,8 +399,9 @@ struct Attribute {
 
 fn parse_data_type(s: &str) -> MeshResult<(DataType, bool)> {
     match s {
+        "double" => Ok((DataType::Double, false)),
         "float" => Ok((DataType::Float, false)),
-        "half" => Ok((DataType::Fixed, false)),
+        "half" => Ok((DataType::HalfFloat, false)),
         "int" => Ok((DataType::Int, false)),
         "uint" => Ok((DataType::UnsignedInt, false)),
         "norm-int" => Ok((DataType::Int, true)),
@@ -266,7 +435,7 @@ fn find_attribute_parse<T: FromStr>(
 ) -> Result<T, MeshError>
 // this makes the compiler happy
 where
-    <T as std::str::FromStr>::Err: std::error::Error + 'static,
+    <T as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
 {
     match find_attribute(attributes, name)?.parse::<T>() {
         Ok(attribute) => Ok(attribute),
@@ -314,8 +483,13 @@ impl Attribute {
         self.data.len() * self.vertex_attribute.data_type.size()
     }
 
-    fn setup_attribute_array(&self, vao: &mut VertexArrayObject, offset: GLint) {
-        vao.set_attribute(self.index, &self.vertex_attribute, 0, offset);
+    /// Size of the values of a single vertex
+    fn element_size(&self) -> usize {
+        self.vertex_attribute.components as usize * self.vertex_attribute.data_type.size()
+    }
+
+    fn setup_attribute_array(&self, vao: &mut VertexArrayObject, stride: GLsizei, offset: GLint) {
+        vao.set_attribute(self.index, &self.vertex_attribute, stride, offset);
     }
 }
 
@@ -352,6 +526,21 @@ fn parse_index_type(s: &str) -> MeshResult<(IndexSize, bool)> {
 }
 
 impl IndicesData {
+    /// Uses the smallest index size that can address every vertex.
+    fn from_indices(indices: Vec<GLuint>, vertex_count: usize) -> Self {
+        if vertex_count <= usize::from(u16::MAX) + 1 {
+            Self {
+                index_size: IndexSize::UnsignedShort,
+                data: IndicesValues::UnsignedShort(indices.iter().map(|i| *i as u16).collect()),
+            }
+        } else {
+            Self {
+                index_size: IndexSize::UnsignedInt,
+                data: IndicesValues::UnsignedInt(indices),
+            }
+        }
+    }
+
     fn new(attributes: &[OwnedAttribute], string_data: &str) -> MeshResult<Self> {
         let data_type = find_attribute(attributes, "type")?;
         let (index_size, _) = parse_index_type(&data_type)?;
@@ -364,6 +553,50 @@ impl IndicesData {
     fn byte_size(&self) -> usize {
         self.data.len() * self.index_size.size()
     }
+
+    /// Rewrites u32 indices with the smallest size that holds every index, keeping the
+    /// largest value of the new size free for `primitive_restart`.
+    /// Returns the restart index remapped to the new size.
+    fn downgrade(&mut self, primitive_restart: Option<GLuint>) -> Option<GLuint> {
+        let IndicesValues::UnsignedInt(items) = &self.data else {
+            return primitive_restart;
+        };
+        let max = items
+            .iter()
+            .filter(|i| Some(**i) != primitive_restart)
+            .max()
+            .copied()
+            .unwrap_or(0);
+        let fits = |size_max: GLuint| {
+            if primitive_restart.is_some() {
+                max < size_max
+            } else {
+                max <= size_max
+            }
+        };
+        let (index_size, restart) = if fits(u8::MAX.into()) {
+            (IndexSize::UnsignedByte, GLuint::from(u8::MAX))
+        } else if fits(u16::MAX.into()) {
+            (IndexSize::UnsignedShort, GLuint::from(u16::MAX))
+        } else {
+            return primitive_restart;
+        };
+        let remapped = items.iter().map(|i| {
+            if Some(*i) == primitive_restart {
+                restart
+            } else {
+                *i
+            }
+        });
+        self.data = match index_size {
+            IndexSize::UnsignedByte => {
+                IndicesValues::UnsignedByte(remapped.map(|i| i as u8).collect())
+            }
+            _ => IndicesValues::UnsignedShort(remapped.map(|i| i as u16).collect()),
+        };
+        self.index_size = index_size;
+        primitive_restart.map(|_| restart)
+    }
 }
 #[derive(Debug)]
 enum RenderCommand {
@@ -380,6 +613,16 @@ enum RenderCommand {
         start: GLint,
         count: GLint,
     },
+    /// Indexed draws sharing primitive, index size and restart index, issued as one multi-draw.
+    /// Only created by [`coalesce_commands`] once the offsets are known.
+    MultiIndexed {
+        indexes: Vec<IndicesData>,
+        primitive: Primitive,
+        index_size: IndexSize,
+        primitive_restart: Option<GLuint>,
+        counts: Vec<GLsizei>,
+        offsets: Vec<usize>,
+    },
 }
 
 fn parse_primitive(s: &str) -> MeshResult<Primitive> {
@@ -434,6 +677,47 @@ impl RenderCommand {
         })
     }
 
+    fn triangles(indexes: IndicesData) -> Self {
+        Self::Indexed {
+            primitive: Primitive::Triangles,
+            count: indexes.data.len() as i32,
+            index_size: indexes.index_size,
+            offset: 0,
+            primitive_restart: None,
+            indexes,
+        }
+    }
+
+    fn downgrade_indices(&mut self) {
+        if let Self::Indexed {
+            indexes,
+            index_size,
+            primitive_restart,
+            ..
+        } = self
+        {
+            *primitive_restart = indexes.downgrade(*primitive_restart);
+            *index_size = indexes.index_size;
+        }
+    }
+
+    /// Enables primitive restart when the command has a restart index, returns whether it
+    /// was enabled before for [`RenderCommand::end_restart`]
+    fn begin_restart(gl: &mut OpenGl, primitive_restart: Option<GLuint>) -> Option<bool> {
+        let index = primitive_restart?;
+        let enabled = gl.capability(Capability::PrimitiveRestart);
+        gl.enable(Capability::PrimitiveRestart);
+        gl.primitive_restart_index(index);
+        Some(enabled)
+    }
+
+    /// Disables primitive restart again unless it was enabled before the command
+    fn end_restart(gl: &mut OpenGl, enabled: Option<bool>) {
+        if enabled == Some(false) {
+            gl.disable(Capability::PrimitiveRestart);
+        }
+    }
+
     fn render(&mut self, gl: &mut OpenGl) {
         match self {
             Self::Indexed {
@@ -441,15 +725,226 @@ impl RenderCommand {
                 count,
                 index_size,
                 offset,
+                primitive_restart,
                 ..
-            } => gl.draw_elements(*primitive, *count, *index_size, *offset),
+            } => {
+                let restart = Self::begin_restart(gl, *primitive_restart);
+                gl.draw_elements(*primitive, *count, *index_size, *offset);
+                Self::end_restart(gl, restart);
+            }
             Self::Array {
                 primitive,
                 start,
                 count,
             } => gl.draw_arrays(*primitive, *start, *count),
+            Self::MultiIndexed {
+                primitive,
+                index_size,
+                primitive_restart,
+                counts,
+                offsets,
+                ..
+            } => {
+                let restart = Self::begin_restart(gl, *primitive_restart);
+                gl.multi_draw_elements(*primitive, counts, *index_size, offsets);
+                Self::end_restart(gl, restart);
+            }
+        }
+    }
+
+    /// Renders the `draw`th draw of a multi-draw, or the whole command otherwise
+    fn render_draw(&mut self, gl: &mut OpenGl, draw: usize) {
+        match self {
+            Self::MultiIndexed {
+                primitive,
+                index_size,
+                primitive_restart,
+                counts,
+                offsets,
+                ..
+            } => {
+                let (Some(count), Some(offset)) = (counts.get(draw), offsets.get(draw)) else {
+                    return;
+                };
+                let restart = Self::begin_restart(gl, *primitive_restart);
+                gl.draw_elements(*primitive, *count, *index_size, *offset);
+                Self::end_restart(gl, restart);
+            }
+            Self::Indexed { .. } | Self::Array { .. } => self.render(gl),
+        }
+    }
+
+    fn render_instanced(&mut self, gl: &mut OpenGl, instance_count: GLsizei) {
+        match self {
+            Self::Indexed {
+                primitive,
+                count,
+                index_size,
+                offset,
+                primitive_restart,
+                ..
+            } => {
+                let restart = Self::begin_restart(gl, *primitive_restart);
+                gl.draw_elements_instanced(
+                    *primitive,
+                    *count,
+                    *index_size,
+                    *offset,
+                    instance_count,
+                );
+                Self::end_restart(gl, restart);
+            }
+            Self::Array {
+                primitive,
+                start,
+                count,
+            } => gl.draw_arrays_instanced(*primitive, *start, *count, instance_count),
+            // there is no instanced multi-draw outside of indirect drawing
+            Self::MultiIndexed {
+                primitive,
+                index_size,
+                primitive_restart,
+                counts,
+                offsets,
+                ..
+            } => {
+                let restart = Self::begin_restart(gl, *primitive_restart);
+                for (count, offset) in counts.iter().zip(offsets.iter()) {
+                    gl.draw_elements_instanced(
+                        *primitive,
+                        *count,
+                        *index_size,
+                        *offset,
+                        instance_count,
+                    );
+                }
+                Self::end_restart(gl, restart);
+            }
+        }
+    }
+}
+
+/// Where each command from before [`coalesce_commands`] ended up, as the index of the
+/// merged command and of the draw in it.
+fn command_parts(commands: &[RenderCommand]) -> Vec<(usize, usize)> {
+    commands
+        .iter()
+        .enumerate()
+        .flat_map(|(i, cmd)| {
+            let draws = match cmd {
+                RenderCommand::MultiIndexed { counts, .. } => counts.len(),
+                RenderCommand::Indexed { .. } | RenderCommand::Array { .. } => 1,
+            };
+            (0..draws).map(move |draw| (i, draw))
+        })
+        .collect()
+}
+
+/// Merges consecutive indexed commands that can share a multi-draw.
+fn coalesce_commands(commands: Vec<RenderCommand>) -> Vec<RenderCommand> {
+    let mut coalesced: Vec<RenderCommand> = Vec::with_capacity(commands.len());
+    for command in commands {
+        match (coalesced.last_mut(), command) {
+            (
+                Some(RenderCommand::MultiIndexed {
+                    indexes: multi_indexes,
+                    primitive: multi_primitive,
+                    index_size: multi_index_size,
+                    primitive_restart: multi_primitive_restart,
+                    counts,
+                    offsets,
+                }),
+                RenderCommand::Indexed {
+                    indexes,
+                    primitive,
+                    count,
+                    index_size,
+                    offset,
+                    primitive_restart,
+                },
+            ) if (
+                *multi_primitive,
+                *multi_index_size,
+                *multi_primitive_restart,
+            ) == (primitive, index_size, primitive_restart) =>
+            {
+                multi_indexes.push(indexes);
+                counts.push(count);
+                offsets.push(offset);
+            }
+            (
+                _,
+                RenderCommand::Indexed {
+                    indexes,
+                    primitive,
+                    count,
+                    index_size,
+                    offset,
+                    primitive_restart,
+                },
+            ) => coalesced.push(RenderCommand::MultiIndexed {
+                indexes: vec![indexes],
+                primitive,
+                index_size,
+                primitive_restart,
+                counts: vec![count],
+                offsets: vec![offset],
+            }),
+            (_, command) => coalesced.push(command),
+        }
+    }
+    // a single draw doesn't need a multi-draw
+    coalesced
+        .into_iter()
+        .map(|command| match command {
+            RenderCommand::MultiIndexed {
+                mut indexes,
+                primitive,
+                index_size,
+                primitive_restart,
+                counts,
+                offsets,
+            } if counts.len() == 1 => RenderCommand::Indexed {
+                indexes: indexes.remove(0),
+                primitive,
+                count: counts[0],
+                index_size,
+                offset: offsets[0],
+                primitive_restart,
+            },
+            command => command,
+        })
+        .collect()
+}
+
+/// Puts the values of each vertex next to each other, every attribute aligned to 4 bytes.
+/// Returns the data, the vertex stride and the offset of each attribute inside a vertex.
+fn interleave(attribs: &[Attribute], num_elements: usize) -> (Vec<u8>, usize, Vec<usize>) {
+    let mut offsets = Vec::with_capacity(attribs.len());
+    let mut stride = 0;
+    for attrib in attribs {
+        offsets.push(stride);
+        stride += attrib.element_size().next_multiple_of(4);
+    }
+    let mut data = vec![0; stride * num_elements];
+    for (attrib, offset) in attribs.iter().zip(&offsets) {
+        let size = attrib.element_size();
+        for (i, values) in attrib.data.get_bytes().chunks_exact(size).enumerate() {
+            let start = i * stride + offset;
+            data[start..start + size].copy_from_slice(values);
         }
     }
+    (data, stride, offsets)
+}
+
+/// How the vertex attributes are placed in the attribute buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AttributeLayout {
+    /// The values of each vertex next to each other, faster to fetch
+    #[default]
+    Interleaved,
+    /// Every attribute in its own block
+    Separate,
 }
 
 struct MeshData {
@@ -458,6 +953,11 @@ struct MeshData {
     vao: VertexArrayObject,
     named_vaos: HashMap<String, VertexArrayObject>,
     commands: Vec<RenderCommand>,
+    /// For each command of the file, the command drawing it and its draw in there
+    command_parts: Vec<(usize, usize)>,
+    // kept for exporting
+    attribs: Vec<Attribute>,
+    named_vao_list: Vec<(String, Vec<GLuint>)>,
 }
 
 impl MeshData {
@@ -468,15 +968,76 @@ impl MeshData {
             vao: VertexArrayObject::new(),
             named_vaos: HashMap::new(),
             commands: Vec::new(),
+            command_parts: Vec::new(),
+            attribs: Vec::new(),
+            named_vao_list: Vec::new(),
+        }
+    }
+    /// Fills the bound attribute buffer and sets up the attributes of the bound VAO.
+    /// Returns the stride and offset of each attribute.
+    fn upload_attributes(
+        &mut self,
+        attribs: &[Attribute],
+        num_elements: usize,
+        layout: AttributeLayout,
+    ) -> MeshResult<Vec<(GLsizei, GLint)>> {
+        let placements = match layout {
+            AttributeLayout::Interleaved => {
+                let (data, stride, offsets) = interleave(attribs, num_elements);
+                self.attrib_array_buffer
+                    .reserve_data_bytes(data.len() as GLsizeiptr, Usage::StaticDraw);
+                self.attrib_array_buffer
+                    .update_data_bytes(&data, data.len() as isize, 0)?;
+                offsets
+                    .iter()
+                    .map(|offset| (stride as GLsizei, *offset as GLint))
+                    .collect::<Vec<_>>()
+            }
+            AttributeLayout::Separate => {
+                // this is trying to calculate how much they need to allocate for attributes
+                let mut attribute_buffer_size = 0;
+                let mut attribute_start_locs = Vec::with_capacity(attribs.len());
+                for attrib in attribs {
+                    attribute_buffer_size = if attribute_buffer_size % 16 != 0 {
+                        // i hate the c++ code i took this from. WTF
+                        // i guess it might be alignment?
+                        attribute_buffer_size + (16 - attribute_buffer_size % 16)
+                    } else {
+                        attribute_buffer_size
+                    };
+                    attribute_start_locs.push(attribute_buffer_size);
+                    attribute_buffer_size += attrib.byte_size();
+                }
+
+                self.attrib_array_buffer
+                    .reserve_data_bytes(attribute_buffer_size as GLsizeiptr, Usage::StaticDraw);
+                for (i, attrib) in attribs.iter().enumerate() {
+                    self.attrib_array_buffer.update_data_bytes(
+                        attrib.data.get_bytes(),
+                        attrib.byte_size() as isize,
+                        attribute_start_locs[i] as isize,
+                    )?;
+                }
+                attribute_start_locs
+                    .iter()
+                    .map(|offset| (0, *offset as GLint))
+                    .collect()
+            }
+        };
+        for (attrib, (stride, offset)) in attribs.iter().zip(&placements) {
+            attrib.setup_attribute_array(&mut self.vao, *stride, *offset);
         }
+        Ok(placements)
     }
+
     #[allow(dead_code)]
     fn indices(&self) -> Vec<&IndicesData> {
         self.commands
             .iter()
-            .filter_map(|cmd| match cmd {
-                RenderCommand::Indexed { indexes, .. } => Some(indexes),
-                RenderCommand::Array { .. } => None,
+            .flat_map(|cmd| match cmd {
+                RenderCommand::Indexed { indexes, .. } => vec![indexes],
+                RenderCommand::MultiIndexed { indexes, .. } => indexes.iter().collect(),
+                RenderCommand::Array { .. } => vec![],
             })
             .collect::<Vec<_>>()
     }
@@ -486,6 +1047,32 @@ pub struct Mesh {
     mesh_data: MeshData,
 }
 
+/// Mesh data parsed from a file and ready to be uploaded with [`Mesh::upload`].
+/// Loading needs no GL context, so it can happen on any thread.
+pub struct MeshCpuData {
+    parsed_data: ParsedData,
+    string_path: String,
+}
+
+impl MeshCpuData {
+    /// Loads a mesh, picking the format from the extension: `obj`, `stl`, `ply`, or XML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> MeshResult<Self> {
+        let path = path.as_ref();
+        let string_path = path.as_os_str().to_string_lossy().to_string();
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        let parsed_data = match extension.as_deref() {
+            Some("obj") => obj::parse_obj(BufReader::new(File::open(path)?), &string_path)?,
+            Some("stl") => stl::parse_stl(&std::fs::read(path)?, &string_path)?,
+            Some("ply") => ply::parse_ply(BufReader::new(File::open(path)?), &string_path)?,
+            _ => Mesh::parse_xml(path)?,
+        };
+        Ok(Self {
+            parsed_data,
+            string_path,
+        })
+    }
+}
+
 struct ParsedData {
     attribs: Vec<Attribute>,
     named_vao_list: Vec<(String, Vec<GLuint>)>,
@@ -493,13 +1080,40 @@ struct ParsedData {
 }
 
 impl ParsedData {
+    /// Sets up the named VAOs, the attribute buffer must be bound.
+    fn named_vaos(
+        &self,
+        placements: &[(GLsizei, GLint)],
+        string_path: &str,
+    ) -> MeshResult<HashMap<String, VertexArrayObject>> {
+        let mut named_vaos = HashMap::new();
+        for (name, source_list) in &self.named_vao_list {
+            let mut vao = VertexArrayObject::new();
+            vao.bind();
+            for &attrib in source_list {
+                let Some(position) = self.attribs.iter().position(|a| a.index == attrib) else {
+                    return Err(MeshError::VaoSourceInvalidIndex(
+                        attrib,
+                        name.clone(),
+                        string_path.to_owned(),
+                    ));
+                };
+
+                let (stride, offset) = placements[position];
+                self.attribs[position].setup_attribute_array(&mut vao, stride, offset);
+            }
+            named_vaos.insert(name.clone(), vao);
+        }
+        Ok(named_vaos)
+    }
+
     #[allow(dead_code)]
     fn indices(&self) -> std::vec::Vec<&IndicesData> {
         self.commands
             .iter()
             .filter_map(|cmd| match cmd {
                 RenderCommand::Indexed { indexes, .. } => Some(indexes),
-                RenderCommand::Array { .. } => None,
+                RenderCommand::Array { .. } | RenderCommand::MultiIndexed { .. } => None,
             })
             .collect::<Vec<_>>()
     }
@@ -640,7 +1254,16 @@ impl Mesh {
                     }
                     _ => {}
                 },
-                Err(err) => eprintln!("Error : {err}"),
+                Err(err) => {
+                    // rows and columns start from 0
+                    let position = err.position();
+                    return Err(MeshError::XmlError(
+                        position.row + 1,
+                        position.column + 1,
+                        err,
+                        string_path,
+                    ));
+                }
             }
         }
         Ok(ParsedData {
@@ -654,9 +1277,59 @@ impl Mesh {
         let string_path = path.as_ref().as_os_str().to_string_lossy().to_string();
 
         let parsed_data = Self::parse_xml(path)?;
+        Self::from_parsed(parsed_data, string_path, AttributeLayout::default())
+    }
+
+    /// Loads a Wavefront OBJ file, attributes are placed at [`POSITION_ATTRIBUTE_LOCATION`],
+    /// [`NORMAL_ATTRIBUTE_LOCATION`] and [`TEXCOORD_ATTRIBUTE_LOCATION`].
+    pub fn from_obj(path: impl AsRef<Path>) -> MeshResult<Self> {
+        let string_path = path.as_ref().as_os_str().to_string_lossy().to_string();
+
+        let file = BufReader::new(File::open(path)?);
+        let parsed_data = obj::parse_obj(file, &string_path)?;
+        Self::from_parsed(parsed_data, string_path, AttributeLayout::default())
+    }
+
+    /// Loads a binary or ASCII STL file, with positions and flat normals.
+    pub fn from_stl(path: impl AsRef<Path>) -> MeshResult<Self> {
+        let string_path = path.as_ref().as_os_str().to_string_lossy().to_string();
+
+        let data = std::fs::read(path)?;
+        let parsed_data = stl::parse_stl(&data, &string_path)?;
+        Self::from_parsed(parsed_data, string_path, AttributeLayout::default())
+    }
+
+    /// Loads an ASCII or binary PLY file, along with colors at [`COLOR_ATTRIBUTE_LOCATION`].
+    /// Flat normals are generated when the file has faces but no normals, and files without
+    /// faces are rendered as points.
+    pub fn from_ply(path: impl AsRef<Path>) -> MeshResult<Self> {
+        let string_path = path.as_ref().as_os_str().to_string_lossy().to_string();
+
+        let file = BufReader::new(File::open(path)?);
+        let parsed_data = ply::parse_ply(file, &string_path)?;
+        Self::from_parsed(parsed_data, string_path, AttributeLayout::default())
+    }
 
+    /// Uploads data loaded on another thread, must be called on the thread owning the context.
+    pub fn upload(gl: &OpenGl, cpu_data: MeshCpuData) -> MeshResult<Self> {
+        Self::upload_with_layout(gl, cpu_data, AttributeLayout::default())
+    }
+
+    pub fn upload_with_layout(
+        _gl: &OpenGl,
+        cpu_data: MeshCpuData,
+        layout: AttributeLayout,
+    ) -> MeshResult<Self> {
+        Self::from_parsed(cpu_data.parsed_data, cpu_data.string_path, layout)
+    }
+
+    fn from_parsed(
+        mut parsed_data: ParsedData,
+        string_path: String,
+        layout: AttributeLayout,
+    ) -> MeshResult<Self> {
         let mut mesh_data = MeshData::new();
-        mesh_data.commands = parsed_data.commands;
+        mesh_data.commands = std::mem::take(&mut parsed_data.commands);
 
         // checking if vertex attributes have all same sizes
         let mut num_elements = 0;
@@ -672,57 +1345,23 @@ impl Mesh {
             }
         }
 
-        // this is trying to calculate how much they need to allocate for attributes
-        let mut attribute_buffer_size = 0;
-        let mut attribute_start_locs = Vec::with_capacity(parsed_data.attribs.len());
-        for attrib in &parsed_data.attribs {
-            attribute_buffer_size = if attribute_buffer_size % 16 != 0 {
-                // i hate the c++ code i took this from. WTF
-                // i guess it might be alignment?
-                attribute_buffer_size + (16 - attribute_buffer_size % 16)
-            } else {
-                attribute_buffer_size
-            };
-            attribute_start_locs.push(attribute_buffer_size);
-            attribute_buffer_size += attrib.byte_size();
-        }
-
         mesh_data.vao.bind();
         mesh_data.attrib_array_buffer.bind();
-        mesh_data
-            .attrib_array_buffer
-            .reserve_data_bytes(attribute_buffer_size as GLsizeiptr, Usage::StaticDraw);
-
-        for (i, attrib) in parsed_data.attribs.iter().enumerate() {
-            let offset = attribute_start_locs[i];
-            mesh_data.attrib_array_buffer.update_data_bytes(
-                attrib.data.get_bytes(),
-                attrib.byte_size() as isize,
-                offset as isize,
-            );
-            attrib.setup_attribute_array(&mut mesh_data.vao, offset as GLint);
-        }
+        let placements = mesh_data.upload_attributes(&parsed_data.attribs, num_elements, layout)?;
 
         // fill named vaos
-        for (name, source_list) in parsed_data.named_vao_list {
-            let mut vao = VertexArrayObject::new();
-            vao.bind();
-            for attrib in source_list {
-                let Some(offset) = parsed_data.attribs.iter().position(|a| a.index == attrib)
-                else {
-                    return Err(MeshError::VaoSourceInvalidIndex(attrib, name, string_path));
-                };
+        mesh_data.named_vaos = parsed_data.named_vaos(&placements, &string_path)?;
+        mesh_data.vao.unbind();
 
-                parsed_data.attribs[offset].setup_attribute_array(&mut vao, offset as GLint);
-            }
-            mesh_data.named_vaos.insert(name, vao);
+        // smaller indices take less memory and bandwidth
+        for command in &mut mesh_data.commands {
+            command.downgrade_indices();
         }
-        mesh_data.vao.unbind();
 
         // calculate index buffer size
         let indices_list = mesh_data.commands.iter().filter_map(|cmd| match cmd {
             RenderCommand::Indexed { indexes, .. } => Some(indexes),
-            RenderCommand::Array { .. } => None,
+            RenderCommand::Array { .. } | RenderCommand::MultiIndexed { .. } => None,
         });
 
         let mut index_buffer_size = 0;
@@ -752,7 +1391,7 @@ impl Mesh {
                     data.data.get_bytes(),
                     data.byte_size() as isize,
                     offset as isize,
-                );
+                )?;
             }
             // fill in indexed rendering commands like said earlier
             // TODO: possibly merge commands and indices in a render pass struct or something like that
@@ -764,6 +1403,7 @@ impl Mesh {
                     i += 1;
                 }
             }
+            mesh_data.commands = coalesce_commands(std::mem::take(&mut mesh_data.commands));
 
             for vao in mesh_data.named_vaos.values_mut() {
                 vao.bind();
@@ -772,14 +1412,108 @@ impl Mesh {
             mesh_data.vao.unbind();
         }
 
+        mesh_data.command_parts = command_parts(&mesh_data.commands);
+        mesh_data.attribs = parsed_data.attribs;
+        mesh_data.named_vao_list = parsed_data.named_vao_list;
         Ok(Self { mesh_data })
     }
+    /// Saves the mesh in the XML format read by [`Mesh::new`].
+    pub fn write_xml(&self, path: impl AsRef<Path>) -> MeshResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        export::write_xml(
+            &mut writer,
+            &self.mesh_data.attribs,
+            &self.mesh_data.named_vao_list,
+            &self.mesh_data.commands,
+        )?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Saves the mesh as a Wavefront OBJ triangle mesh, with the positions, normals and
+    /// texture coordinates at the locations used by [`Mesh::from_obj`].
+    pub fn write_obj(&self, path: impl AsRef<Path>) -> MeshResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        export::write_obj(
+            &mut writer,
+            &self.mesh_data.attribs,
+            &self.mesh_data.commands,
+        )?;
+        writer.flush()?;
+        Ok(())
+    }
+
     pub fn render(&mut self, gl: &mut OpenGl) {
-        self.mesh_data.vao.bind();
+        gl.bind_vertex_array(&self.mesh_data.vao);
         for cmd in &mut self.mesh_data.commands {
             cmd.render(gl);
         }
+        gl.unbind_vertex_array();
+    }
+    /// Renders the edges of the triangles only, keeping the polygon mode set before.
+    pub fn render_wireframe(&mut self, gl: &mut OpenGl) {
+        let mut gl = gl.save_state(StateFlags::PolygonMode);
+        gl.polygon_mode(PolygonMode::Line);
+        self.render(&mut gl);
+    }
+
+    /// Lines `length` long along the normals, in model space. Vertex normals need the normal
+    /// attribute, face normals agree with it when there is one and are counter clockwise
+    /// otherwise.
+    pub fn normal_lines(&self, normals: NormalLines, length: f32) -> MeshResult<Vec<(Vec3, Vec3)>> {
+        debug::normal_lines(
+            &self.mesh_data.attribs,
+            &self.mesh_data.commands,
+            normals,
+            length,
+        )
+    }
+
+    /// Queues the [`Mesh::normal_lines`] of the mesh placed by `model_to_world`, the lines
+    /// are `length` long in world space.
+    pub fn draw_normals(
+        &self,
+        debug_draw: &mut DebugDraw,
+        model_to_world: Mat4,
+        normals: NormalLines,
+        length: f32,
+        color: Vec4,
+    ) -> MeshResult<()> {
+        let normal_matrix = Mat3::from_mat4(model_to_world).inverse().transpose();
+        for (start, end) in self.normal_lines(normals, 1.0)? {
+            let direction = (normal_matrix * (end - start)).normalize_or_zero();
+            let start = model_to_world.transform_point3(start);
+            debug_draw.line(start, start + direction * length, color);
+        }
+        Ok(())
+    }
+
+    pub fn render_instanced(&mut self, gl: &mut OpenGl, instance_count: GLsizei) {
+        gl.bind_vertex_array(&self.mesh_data.vao);
+        for cmd in &mut self.mesh_data.commands {
+            cmd.render_instanced(gl, instance_count);
+        }
+        gl.unbind_vertex_array();
+    }
+    /// Sources a per-instance attribute from `buffer`, advancing once per instance.
+    pub fn set_instance_attribute<T: Default>(
+        &mut self,
+        buffer: &mut Buffer<T>,
+        location: GLuint,
+        attribute: &VertexAttribute,
+        stride: GLsizei,
+        offset: GLint,
+    ) {
+        self.mesh_data.vao.bind();
+        buffer.bind();
+        self.mesh_data
+            .vao
+            .set_attribute(location, attribute, stride, offset);
+        self.mesh_data
+            .vao
+            .set_attribute_divisors(location, attribute, 1);
         self.mesh_data.vao.unbind();
+        buffer.unbind();
     }
     pub fn render_mesh(&mut self, mesh_name: &str, gl: &mut OpenGl) {
         let Some((_, vao)) = self
@@ -791,11 +1525,50 @@ impl Mesh {
             return;
         };
 
-        vao.bind();
+        gl.bind_vertex_array(vao);
         for cmd in &mut self.mesh_data.commands {
             cmd.render(gl);
         }
-        vao.unbind();
+        gl.unbind_vertex_array();
+    }
+
+    /// Names of the VAOs usable with [`Mesh::render_mesh`], in file order.
+    pub fn vao_names(&self) -> impl Iterator<Item = &str> {
+        self.mesh_data
+            .named_vao_list
+            .iter()
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Number of draw commands in the file. Consecutive indexed draws of the same primitive
+    /// are merged into a multi-draw on upload, the indices still count them one by one.
+    #[must_use]
+    pub fn command_count(&self) -> usize {
+        self.mesh_data.command_parts.len()
+    }
+
+    /// Renders only the command at `index` in the file, even when it was merged with
+    /// others. Does nothing if it is out of range.
+    pub fn render_command(&mut self, index: usize, gl: &mut OpenGl) {
+        let Some(&(cmd, draw)) = self.mesh_data.command_parts.get(index) else {
+            return;
+        };
+        gl.bind_vertex_array(&self.mesh_data.vao);
+        self.mesh_data.commands[cmd].render_draw(gl, draw);
+        gl.unbind_vertex_array();
+    }
+
+    /// Renders only the command at `index` in the file with the VAO named `mesh_name`.
+    pub fn render_mesh_command(&mut self, mesh_name: &str, index: usize, gl: &mut OpenGl) {
+        let (Some(vao), Some(&(cmd, draw))) = (
+            self.mesh_data.named_vaos.get_mut(mesh_name),
+            self.mesh_data.command_parts.get(index),
+        ) else {
+            return;
+        };
+        gl.bind_vertex_array(vao);
+        self.mesh_data.commands[cmd].render_draw(gl, draw);
+        gl.unbind_vertex_array();
     }
 }
 
@@ -804,7 +1577,6 @@ mod test {
     use std::path::Path;
 
     use gl::types::GLuint;
-    use glfw::{fail_on_errors, Context};
 
     use crate::{
         mesh::RenderCommand,
@@ -812,7 +1584,11 @@ mod test {
         vertex_attributes::{DataType, VertexAttribute},
     };
 
-    use super::{Attribute, IndicesData, IndicesValues, Mesh, VertexAttributeValues};
+    use super::{
+        coalesce_commands, command_parts, f16_bits_to_f32, f32_to_f16_bits, interleave,
+        parse_attribute_values, Attribute, IndicesData, IndicesValues, Mesh, MeshError,
+        VertexAttributeValues,
+    };
     macro_rules! test_case {
         ($fname:expr) => {
             concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/", $fname) // assumes Linux ('/')!
@@ -914,6 +1690,152 @@ mod test {
         test_commands(cmd, Primitive::Triangles);
     }
 
+    #[test]
+    fn test_interleave() {
+        let attribs = [
+            Attribute {
+                index: 0,
+                vertex_attribute: VertexAttribute::new(2, DataType::Float, false),
+                data: VertexAttributeValues::Float(vec![1.0, 2.0, 3.0, 4.0]),
+            },
+            Attribute {
+                index: 1,
+                vertex_attribute: VertexAttribute::new(3, DataType::UnsignedByte, true),
+                data: VertexAttributeValues::UnsignedByte(vec![5, 6, 7, 8, 9, 10]),
+            },
+        ];
+        let (data, stride, offsets) = interleave(&attribs, 2);
+        // the 3 bytes are padded to 4
+        assert_eq!(stride, 12);
+        assert_eq!(offsets, [0, 8]);
+        assert_eq!(data.len(), 24);
+        let floats: Vec<f32> = data
+            .chunks_exact(stride)
+            .flat_map(|vertex| bytemuck::pod_read_unaligned::<[f32; 2]>(&vertex[..8]))
+            .collect();
+        assert_eq!(floats, [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(&data[8..11], &[5, 6, 7]);
+        assert_eq!(&data[20..23], &[8, 9, 10]);
+    }
+
+    #[test]
+    fn test_malformed_parse() {
+        let file_path = Path::new(test_case!("Malformed.xml"));
+
+        let Err(MeshError::XmlError(line, _, _, _)) = Mesh::parse_xml(file_path) else {
+            panic!()
+        };
+        assert_eq!(line, 8);
+    }
+
+    #[test]
+    fn test_strip_restart_parse() {
+        let file_path = Path::new(test_case!("UnitStripRestart.xml"));
+
+        let parsed_xml = Mesh::parse_xml(file_path).unwrap();
+        assert_eq!(parsed_xml.attribs[0].num_elements(), 8);
+        assert_eq!(parsed_xml.commands.len(), 1);
+        let cmd = &parsed_xml.commands[0];
+        test_commands(cmd, Primitive::TriangleStrip);
+        let RenderCommand::Indexed {
+            primitive_restart,
+            indexes,
+            ..
+        } = cmd
+        else {
+            panic!()
+        };
+        assert_eq!(*primitive_restart, Some(65535));
+        test_indices(
+            indexes,
+            IndexSize::UnsignedShort,
+            &IndicesValues::UnsignedShort(vec![0, 1, 2, 3, 65535, 4, 5, 6, 7]),
+        );
+    }
+
+    #[test]
+    fn test_downgrade_indices() {
+        let mut indices = IndicesData::from_indices(vec![], 0);
+        indices.index_size = IndexSize::UnsignedInt;
+        indices.data = IndicesValues::UnsignedInt(vec![0, 1, 2, 254]);
+        assert_eq!(indices.downgrade(None), None);
+        test_indices(
+            &indices,
+            IndexSize::UnsignedByte,
+            &IndicesValues::UnsignedByte(vec![0, 1, 2, 254]),
+        );
+
+        // 255 is taken by the restart index
+        indices.index_size = IndexSize::UnsignedInt;
+        indices.data = IndicesValues::UnsignedInt(vec![0, 255, u32::MAX, 1]);
+        assert_eq!(indices.downgrade(Some(u32::MAX)), Some(65535));
+        test_indices(
+            &indices,
+            IndexSize::UnsignedShort,
+            &IndicesValues::UnsignedShort(vec![0, 255, 65535, 1]),
+        );
+
+        indices.index_size = IndexSize::UnsignedInt;
+        indices.data = IndicesValues::UnsignedInt(vec![0, 65536]);
+        assert_eq!(indices.downgrade(None), None);
+        assert_eq!(indices.index_size, IndexSize::UnsignedInt);
+
+        // smaller sizes are left alone
+        let mut indices = IndicesData::from_indices(vec![0, 1, 2], 3);
+        assert_eq!(indices.downgrade(Some(65535)), Some(65535));
+        assert_eq!(indices.index_size, IndexSize::UnsignedShort);
+    }
+
+    #[test]
+    fn test_coalesce_commands() {
+        let indexed = |primitive, offset| RenderCommand::Indexed {
+            indexes: IndicesData::from_indices(vec![0, 1, 2], 3),
+            primitive,
+            count: 3,
+            index_size: IndexSize::UnsignedShort,
+            offset,
+            primitive_restart: None,
+        };
+        let commands = coalesce_commands(vec![
+            indexed(Primitive::Triangles, 0),
+            indexed(Primitive::Triangles, 16),
+            indexed(Primitive::Triangles, 32),
+            RenderCommand::Array {
+                primitive: Primitive::Triangles,
+                start: 0,
+                count: 3,
+            },
+            indexed(Primitive::Triangles, 48),
+            indexed(Primitive::Lines, 64),
+        ]);
+        assert_eq!(commands.len(), 4);
+        assert_eq!(
+            command_parts(&commands),
+            [(0, 0), (0, 1), (0, 2), (1, 0), (2, 0), (3, 0)]
+        );
+        let RenderCommand::MultiIndexed {
+            counts, offsets, ..
+        } = &commands[0]
+        else {
+            panic!()
+        };
+        assert_eq!(counts, &[3, 3, 3]);
+        assert_eq!(offsets, &[0, 16, 32]);
+        assert!(matches!(commands[1], RenderCommand::Array { .. }));
+        // lone draws stay single draws
+        assert!(matches!(
+            commands[2],
+            RenderCommand::Indexed { offset: 48, .. }
+        ));
+        assert!(matches!(
+            commands[3],
+            RenderCommand::Indexed {
+                primitive: Primitive::Lines,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_cube_parse() {
         let file_path = Path::new(test_case!("UnitCube.xml"));
@@ -1402,26 +2324,10 @@ mod test {
 
     #[test]
     fn test_buffer_data() {
-        let mut glfw = glfw::init(fail_on_errors!()).unwrap();
-        glfw.window_hint(glfw::WindowHint::ContextVersion(4, 3));
-        glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-            glfw::OpenGlProfileHint::Core,
-        ));
-        glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));
-
-        // Create a windowed mode window and its OpenGL context
-        let (mut window, _) = glfw
-            .create_window(600, 600, "OpenGl", glfw::WindowMode::Windowed)
-            .expect("Failed to create GLFW window.");
-
-        // Make the window's context current
-        window.make_current();
-        window.set_key_polling(true);
-        window.set_framebuffer_size_polling(true);
-        let _ = OpenGl::new(&mut window);
+        let (_gl, _context) = OpenGl::new_headless().unwrap();
         let mut mesh = Mesh::new("resources/test/UnitPlane.xml").unwrap();
         mesh.mesh_data.attrib_array_buffer.bind();
-        let bytes = mesh.mesh_data.attrib_array_buffer.get_data(0, 48);
+        let bytes = mesh.mesh_data.attrib_array_buffer.get_data(0, 48).unwrap();
 
         let floats: &[f32] = bytemuck::cast_slice(&bytes);
         assert_eq!(
@@ -1429,8 +2335,44 @@ mod test {
             &[0.5, 0.0, -0.5, 0.5, 0.0, 0.5, -0.5, 0.0, 0.5, -0.5, 0.0, -0.5,]
         );
         mesh.mesh_data.index_buffer.bind();
-        let bytes = mesh.mesh_data.index_buffer.get_data(0, 24);
+        assert_eq!(mesh.mesh_data.index_buffer.byte_len(), 24);
+        assert!(mesh.mesh_data.index_buffer.get_data(0, 25).is_err());
+        let bytes = mesh.mesh_data.index_buffer.get_data(0, 24).unwrap();
         let indices: &[u16] = bytemuck::cast_slice(&bytes);
         assert_eq!(indices, &[0, 1, 2, 0, 2, 1, 2, 3, 0, 2, 0, 3]);
     }
+
+    #[test]
+    fn test_half_float() {
+        assert_eq!(f32_to_f16_bits(0.0), 0x0000);
+        assert_eq!(f32_to_f16_bits(-0.0), 0x8000);
+        assert_eq!(f32_to_f16_bits(1.0), 0x3c00);
+        assert_eq!(f32_to_f16_bits(-2.0), 0xc000);
+        assert_eq!(f32_to_f16_bits(0.5), 0x3800);
+        assert_eq!(f32_to_f16_bits(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16_bits(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16_bits(f32::NEG_INFINITY), 0xfc00);
+        // smallest subnormal
+        assert_eq!(f32_to_f16_bits(2.0f32.powi(-24)), 0x0001);
+        // 1 + 2^-11 is a tie, rounds to even
+        assert_eq!(f32_to_f16_bits(1.0 + 2.0f32.powi(-11)), 0x3c00);
+        assert_eq!(
+            f32_to_f16_bits(3.0f32.mul_add(2.0f32.powi(-11), 1.0)),
+            0x3c02
+        );
+        // every half float but NaN survives the round trip
+        for bits in 0..=u16::MAX {
+            let value = f16_bits_to_f32(bits);
+            if !value.is_nan() {
+                assert_eq!(f32_to_f16_bits(value), bits);
+            }
+        }
+
+        let data = parse_attribute_values(DataType::HalfFloat, "1.0 0.5").unwrap();
+        assert_eq!(data, VertexAttributeValues::Half(vec![0x3c00, 0x3800]));
+        assert!(matches!(
+            parse_attribute_values(DataType::Fixed, "1"),
+            Err(MeshError::UnimplementedDataFormat(DataType::Fixed))
+        ));
+    }
 }
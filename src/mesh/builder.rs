@@ -0,0 +1,577 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use gl::types::{GLfloat, GLint, GLuint};
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::{
+    opengl::Primitive,
+    vertex_attributes::{DataType, VertexAttribute},
+};
+
+use super::{
+    flat_normals, obj, Attribute, AttributeLayout, IndicesData, Mesh, MeshError, MeshResult,
+    ParsedData, RenderCommand, VertexAttributeValues, COLOR_ATTRIBUTE_LOCATION,
+    NORMAL_ATTRIBUTE_LOCATION, POSITION_ATTRIBUTE_LOCATION, TANGENT_ATTRIBUTE_LOCATION,
+    TEXCOORD_ATTRIBUTE_LOCATION,
+};
+
+mod private {
+    pub trait Sealed {}
+}
+
+trait AttributeValue: private::Sealed + Copy {
+    const DATA_TYPE: DataType;
+    fn values(data: &[Self]) -> VertexAttributeValues;
+}
+
+macro_rules! impl_attribute_value {
+    ($t:ty, $data_type:expr, $values:ident) => {
+        impl private::Sealed for $t {}
+        impl AttributeValue for $t {
+            const DATA_TYPE: DataType = $data_type;
+            fn values(data: &[Self]) -> VertexAttributeValues {
+                VertexAttributeValues::$values(data.to_vec())
+            }
+        }
+    };
+}
+
+impl_attribute_value!(f64, DataType::Double, Double);
+impl_attribute_value!(f32, DataType::Float, Float);
+impl_attribute_value!(u32, DataType::UnsignedInt, UnsignedInt);
+impl_attribute_value!(i32, DataType::Int, Int);
+impl_attribute_value!(u16, DataType::UnsignedShort, UnsignedShort);
+impl_attribute_value!(i16, DataType::Short, Short);
+impl_attribute_value!(u8, DataType::UnsignedByte, UnsignedByte);
+impl_attribute_value!(i8, DataType::Byte, Byte);
+
+/// Triangle list indices of the triangles drawn by `primitive`, `None` if it doesn't draw any
+fn triangulate(primitive: Primitive, indices: &[GLuint]) -> Option<Vec<GLuint>> {
+    match primitive {
+        Primitive::Triangles => Some(indices[..indices.len() / 3 * 3].to_vec()),
+        // every other triangle of a strip has its winding flipped
+        Primitive::TriangleStrip => Some(
+            indices
+                .windows(3)
+                .enumerate()
+                .flat_map(|(i, t)| {
+                    if i % 2 == 0 {
+                        [t[0], t[1], t[2]]
+                    } else {
+                        [t[1], t[0], t[2]]
+                    }
+                })
+                .collect(),
+        ),
+        Primitive::TriangleFan => Some(
+            (1..indices.len().saturating_sub(1))
+                .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Triangle list indices of everything drawn by `commands`
+pub(super) fn triangle_list(commands: &[RenderCommand]) -> MeshResult<Vec<GLuint>> {
+    let mut indices = vec![];
+    for command in commands {
+        let draws = match command {
+            RenderCommand::Indexed {
+                indexes,
+                primitive,
+                primitive_restart,
+                ..
+            } => vec![(*primitive, *primitive_restart, indexes.data.to_indices())],
+            RenderCommand::MultiIndexed {
+                indexes,
+                primitive,
+                primitive_restart,
+                ..
+            } => indexes
+                .iter()
+                .map(|indexes| (*primitive, *primitive_restart, indexes.data.to_indices()))
+                .collect(),
+            RenderCommand::Array {
+                primitive,
+                start,
+                count,
+            } => vec![(
+                *primitive,
+                None,
+                (*start as GLuint..(*start + *count) as GLuint).collect(),
+            )],
+        };
+        for (primitive, primitive_restart, all) in draws {
+            for part in all.split(|i| Some(*i) == primitive_restart) {
+                let triangles = triangulate(primitive, part)
+                    .ok_or(MeshError::UnsupportedPrimitive(primitive))?;
+                indices.extend(triangles);
+            }
+        }
+    }
+    Ok(indices)
+}
+
+/// Builds a [`Mesh`] from vertex data in memory. Without indices the vertices are drawn in order.
+pub struct MeshBuilder {
+    primitive: Primitive,
+    attribs: Vec<Attribute>,
+    indices: Option<Vec<GLuint>>,
+    /// Generate normals when building, smooth or flat
+    normals: Option<bool>,
+    tangents: bool,
+}
+
+impl MeshBuilder {
+    #[must_use]
+    pub const fn new(primitive: Primitive) -> Self {
+        Self {
+            primitive,
+            attribs: vec![],
+            indices: None,
+            normals: None,
+            tangents: false,
+        }
+    }
+
+    /// Loads an XML mesh to be modified, all its commands are merged in a single triangle list.
+    /// Named VAOs are not kept.
+    pub fn from_xml(path: impl AsRef<Path>) -> MeshResult<Self> {
+        Self::from_parsed(Mesh::parse_xml(path)?)
+    }
+
+    pub fn from_obj(path: impl AsRef<Path>) -> MeshResult<Self> {
+        let string_path = path.as_ref().as_os_str().to_string_lossy().to_string();
+
+        let file = BufReader::new(File::open(path)?);
+        Self::from_parsed(obj::parse_obj(file, &string_path)?)
+    }
+
+    fn from_parsed(parsed_data: ParsedData) -> MeshResult<Self> {
+        let indices = triangle_list(&parsed_data.commands)?;
+        Ok(Self {
+            primitive: Primitive::Triangles,
+            attribs: parsed_data.attribs,
+            indices: Some(indices),
+            normals: None,
+            tangents: false,
+        })
+    }
+
+    /// Adds an attribute of `components` values per vertex at `location`.
+    #[must_use]
+    #[allow(private_bounds)]
+    pub fn attribute<T: AttributeValue>(
+        mut self,
+        location: GLuint,
+        components: GLint,
+        normalized: bool,
+        data: &[T],
+    ) -> Self {
+        // replace attributes at the same location
+        self.attribs.retain(|a| a.index != location);
+        self.attribs.push(Attribute {
+            index: location,
+            vertex_attribute: VertexAttribute::new(components, T::DATA_TYPE, normalized),
+            data: T::values(data),
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn positions(self, positions: &[Vec3]) -> Self {
+        let data = positions
+            .iter()
+            .flat_map(Vec3::to_array)
+            .collect::<Vec<_>>();
+        self.attribute(POSITION_ATTRIBUTE_LOCATION, 3, false, &data)
+    }
+
+    #[must_use]
+    pub fn colors(self, colors: &[Vec4]) -> Self {
+        let data = colors.iter().flat_map(Vec4::to_array).collect::<Vec<_>>();
+        self.attribute(COLOR_ATTRIBUTE_LOCATION, 4, false, &data)
+    }
+
+    #[must_use]
+    pub fn normals(self, normals: &[Vec3]) -> Self {
+        let data = normals.iter().flat_map(Vec3::to_array).collect::<Vec<_>>();
+        self.attribute(NORMAL_ATTRIBUTE_LOCATION, 3, false, &data)
+    }
+
+    #[must_use]
+    pub fn tex_coords(self, tex_coords: &[Vec2]) -> Self {
+        let data = tex_coords
+            .iter()
+            .flat_map(Vec2::to_array)
+            .collect::<Vec<_>>();
+        self.attribute(TEXCOORD_ATTRIBUTE_LOCATION, 2, false, &data)
+    }
+
+    #[must_use]
+    pub fn indices(mut self, indices: &[GLuint]) -> Self {
+        self.indices = Some(indices.to_vec());
+        self
+    }
+
+    /// Generates normals from the triangles when building, replacing any existing ones.
+    /// Smooth normals average the faces around each position, flat normals give every triangle
+    /// its own vertices.
+    #[must_use]
+    pub const fn compute_normals(mut self, smooth: bool) -> Self {
+        self.normals = Some(smooth);
+        self
+    }
+
+    /// Generates tangents at [`TANGENT_ATTRIBUTE_LOCATION`] when building, from the normals and
+    /// texture coordinates. The w component holds the handedness of the bitangent.
+    #[must_use]
+    pub const fn compute_tangents(mut self) -> Self {
+        self.tangents = true;
+        self
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.attribs.first().map_or(0, Attribute::num_elements)
+    }
+
+    fn float_attribute(&self, location: GLuint, components: GLint) -> MeshResult<&[GLfloat]> {
+        self.attribs
+            .iter()
+            .find(|a| a.index == location && a.vertex_attribute.components == components)
+            .and_then(|a| match &a.data {
+                VertexAttributeValues::Float(data) => Some(data.as_slice()),
+                _ => None,
+            })
+            .ok_or(MeshError::MissingAttribute(location))
+    }
+
+    fn set_float_attribute(&mut self, location: GLuint, components: GLint, data: Vec<GLfloat>) {
+        self.attribs.retain(|a| a.index != location);
+        self.attribs.push(Attribute {
+            index: location,
+            vertex_attribute: VertexAttribute::new(components, DataType::Float, false),
+            data: VertexAttributeValues::Float(data),
+        });
+    }
+
+    fn triangles(&self) -> MeshResult<Vec<GLuint>> {
+        let indices = self
+            .indices
+            .clone()
+            .unwrap_or_else(|| (0..self.vertex_count() as GLuint).collect());
+        triangulate(self.primitive, &indices).ok_or(MeshError::UnsupportedPrimitive(self.primitive))
+    }
+
+    fn generate_normals(&mut self, smooth: bool) -> MeshResult<()> {
+        let triangles = self.triangles()?;
+        if !smooth {
+            // split the vertices so every triangle can have its own normal
+            for attrib in &mut self.attribs {
+                let components = attrib.vertex_attribute.components as usize
+                    * attrib.vertex_attribute.columns as usize;
+                attrib.data = attrib.data.gather(components, &triangles);
+            }
+            self.primitive = Primitive::Triangles;
+            self.indices = None;
+            let normals = flat_normals(self.float_attribute(POSITION_ATTRIBUTE_LOCATION, 3)?);
+            self.set_float_attribute(NORMAL_ATTRIBUTE_LOCATION, 3, normals);
+            return Ok(());
+        }
+
+        let positions = self
+            .float_attribute(POSITION_ATTRIBUTE_LOCATION, 3)?
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect::<Vec<_>>();
+        // vertices at the same position are smoothed together, even across uv seams
+        let key = |p: Vec3| p.to_array().map(f32::to_bits);
+        let mut sums: HashMap<[u32; 3], Vec3> = HashMap::new();
+        for triangle in triangles.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+            // not normalized, so bigger triangles weigh more
+            let normal = (b - a).cross(c - a);
+            for p in [a, b, c] {
+                *sums.entry(key(p)).or_insert(Vec3::ZERO) += normal;
+            }
+        }
+        let normals = positions
+            .iter()
+            .flat_map(|p| {
+                sums.get(&key(*p))
+                    .map_or(Vec3::ZERO, |n| n.normalize_or_zero())
+                    .to_array()
+            })
+            .collect();
+        self.set_float_attribute(NORMAL_ATTRIBUTE_LOCATION, 3, normals);
+        Ok(())
+    }
+
+    fn generate_tangents(&mut self) -> MeshResult<()> {
+        let triangles = self.triangles()?;
+        let positions = self.float_attribute(POSITION_ATTRIBUTE_LOCATION, 3)?;
+        let normals = self.float_attribute(NORMAL_ATTRIBUTE_LOCATION, 3)?;
+        let uvs = self.float_attribute(TEXCOORD_ATTRIBUTE_LOCATION, 2)?;
+        let position = |i: GLuint| Vec3::from_slice(&positions[i as usize * 3..]);
+        let uv = |i: GLuint| Vec2::from_slice(&uvs[i as usize * 2..]);
+
+        let vertex_count = self.vertex_count();
+        let mut tangents = vec![Vec3::ZERO; vertex_count];
+        let mut bitangents = vec![Vec3::ZERO; vertex_count];
+        for triangle in triangles.chunks_exact(3) {
+            let edge1 = position(triangle[1]) - position(triangle[0]);
+            let edge2 = position(triangle[2]) - position(triangle[0]);
+            let delta1 = uv(triangle[1]) - uv(triangle[0]);
+            let delta2 = uv(triangle[2]) - uv(triangle[0]);
+            let determinant = delta1.perp_dot(delta2);
+            if determinant.abs() < f32::EPSILON {
+                // degenerate texture mapping
+                continue;
+            }
+            let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+            let bitangent = (edge2 * delta1.x - edge1 * delta2.x) / determinant;
+            for i in triangle {
+                tangents[*i as usize] += tangent;
+                bitangents[*i as usize] += bitangent;
+            }
+        }
+
+        let tangents = tangents
+            .iter()
+            .zip(&bitangents)
+            .zip(normals.chunks_exact(3).map(Vec3::from_slice))
+            .flat_map(|((tangent, bitangent), normal)| {
+                // Gram-Schmidt orthogonalize against the normal
+                let tangent = (*tangent - normal * normal.dot(*tangent)).normalize_or_zero();
+                let handedness = if normal.cross(tangent).dot(*bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                tangent.extend(handedness).to_array()
+            })
+            .collect();
+        self.set_float_attribute(TANGENT_ATTRIBUTE_LOCATION, 4, tangents);
+        Ok(())
+    }
+
+    pub(super) fn into_parsed(mut self) -> MeshResult<ParsedData> {
+        let Some(first) = self.attribs.first() else {
+            return Err(MeshError::NoVertexAttributes(String::new()));
+        };
+        let vertex_count = first.num_elements();
+        if let Some(i) = self
+            .attribs
+            .iter()
+            .position(|a| a.num_elements() != vertex_count)
+        {
+            return Err(MeshError::VertexAttributesArrayWithDifferentSize(
+                i,
+                String::new(),
+            ));
+        }
+        if let Some(index) = self
+            .indices
+            .iter()
+            .flatten()
+            .find(|i| **i as usize >= vertex_count)
+        {
+            return Err(MeshError::IndexOutOfRange(*index, vertex_count));
+        }
+
+        if let Some(smooth) = self.normals {
+            self.generate_normals(smooth)?;
+        }
+        if self.tangents {
+            self.generate_tangents()?;
+        }
+        let vertex_count = self.vertex_count();
+
+        let command = match self.indices {
+            Some(indices) => {
+                let indexes = IndicesData::from_indices(indices, vertex_count);
+                RenderCommand::Indexed {
+                    primitive: self.primitive,
+                    count: indexes.data.len() as i32,
+                    index_size: indexes.index_size,
+                    offset: 0,
+                    primitive_restart: None,
+                    indexes,
+                }
+            }
+            None => RenderCommand::Array {
+                primitive: self.primitive,
+                start: 0,
+                count: vertex_count as i32,
+            },
+        };
+
+        Ok(ParsedData {
+            attribs: self.attribs,
+            named_vao_list: vec![],
+            commands: vec![command],
+        })
+    }
+
+    pub fn build(self) -> MeshResult<Mesh> {
+        Mesh::from_parsed(
+            self.into_parsed()?,
+            String::new(),
+            AttributeLayout::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Vec2, Vec3, Vec4};
+
+    use crate::{
+        mesh::{
+            IndicesValues, MeshError, RenderCommand, VertexAttributeValues,
+            NORMAL_ATTRIBUTE_LOCATION, TANGENT_ATTRIBUTE_LOCATION,
+        },
+        opengl::Primitive,
+    };
+
+    use super::{triangulate, MeshBuilder};
+
+    const TRIANGLE: [Vec3; 3] = [Vec3::ZERO, Vec3::X, Vec3::Y];
+
+    #[test]
+    fn test_builder() {
+        let parsed = MeshBuilder::new(Primitive::Triangles)
+            .positions(&TRIANGLE)
+            .colors(&[Vec4::ONE; 3])
+            .attribute(3, 1, true, &[0u8, 128, 255])
+            .indices(&[0, 1, 2])
+            .into_parsed()
+            .unwrap();
+        assert_eq!(parsed.attribs.len(), 3);
+        assert_eq!(
+            parsed.attribs[2].data,
+            VertexAttributeValues::UnsignedByte(vec![0, 128, 255])
+        );
+        assert_eq!(
+            parsed.indices()[0].data,
+            IndicesValues::UnsignedShort(vec![0, 1, 2])
+        );
+
+        let parsed = MeshBuilder::new(Primitive::Points)
+            .positions(&TRIANGLE)
+            .into_parsed()
+            .unwrap();
+        assert!(matches!(
+            parsed.commands[0],
+            RenderCommand::Array { count: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        assert!(matches!(
+            MeshBuilder::new(Primitive::Triangles).into_parsed(),
+            Err(MeshError::NoVertexAttributes(_))
+        ));
+        assert!(matches!(
+            MeshBuilder::new(Primitive::Triangles)
+                .positions(&TRIANGLE)
+                .colors(&[Vec4::ONE; 2])
+                .into_parsed(),
+            Err(MeshError::VertexAttributesArrayWithDifferentSize(1, _))
+        ));
+        assert!(matches!(
+            MeshBuilder::new(Primitive::Triangles)
+                .positions(&TRIANGLE)
+                .indices(&[0, 1, 3])
+                .into_parsed(),
+            Err(MeshError::IndexOutOfRange(3, 3))
+        ));
+    }
+
+    #[test]
+    fn test_triangulate() {
+        assert_eq!(
+            triangulate(Primitive::TriangleStrip, &[0, 1, 2, 3]),
+            Some(vec![0, 1, 2, 2, 1, 3])
+        );
+        assert_eq!(
+            triangulate(Primitive::TriangleFan, &[0, 1, 2, 3]),
+            Some(vec![0, 1, 2, 0, 2, 3])
+        );
+        assert_eq!(triangulate(Primitive::Lines, &[0, 1]), None);
+    }
+
+    #[test]
+    fn test_compute_normals() {
+        const QUAD: [Vec3; 4] = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        let builder = MeshBuilder::new(Primitive::TriangleFan).positions(&QUAD);
+
+        let mut smooth = builder.compute_normals(true);
+        smooth.generate_normals(true).unwrap();
+        let normals = smooth
+            .float_attribute(NORMAL_ATTRIBUTE_LOCATION, 3)
+            .unwrap();
+        assert_eq!(normals, [0.0, 0.0, 1.0].repeat(4));
+
+        let parsed = smooth.compute_normals(false).into_parsed().unwrap();
+        // flat normals split the fan into 2 separate triangles
+        assert!(matches!(
+            parsed.commands[0],
+            RenderCommand::Array { count: 6, .. }
+        ));
+        assert!(matches!(
+            MeshBuilder::new(Primitive::Lines)
+                .positions(&QUAD)
+                .compute_normals(true)
+                .into_parsed(),
+            Err(MeshError::UnsupportedPrimitive(Primitive::Lines))
+        ));
+    }
+
+    #[test]
+    fn test_compute_tangents() {
+        const QUAD: [Vec3; 4] = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        const UVS: [Vec2; 4] = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+        let parsed = MeshBuilder::new(Primitive::Triangles)
+            .positions(&QUAD)
+            .tex_coords(&UVS)
+            .indices(&[0, 1, 2, 0, 2, 3])
+            .compute_normals(true)
+            .compute_tangents()
+            .into_parsed()
+            .unwrap();
+        let tangents = parsed
+            .attribs
+            .iter()
+            .find(|a| a.index == TANGENT_ATTRIBUTE_LOCATION)
+            .unwrap();
+        assert_eq!(
+            tangents.data,
+            VertexAttributeValues::Float([1.0, 0.0, 0.0, 1.0].repeat(4))
+        );
+
+        // mirrored uvs flip the handedness
+        let mirrored = UVS.map(|uv| Vec2::new(1.0 - uv.x, uv.y));
+        let parsed = MeshBuilder::new(Primitive::Triangles)
+            .positions(&QUAD)
+            .tex_coords(&mirrored)
+            .indices(&[0, 1, 2, 0, 2, 3])
+            .compute_normals(true)
+            .compute_tangents()
+            .into_parsed()
+            .unwrap();
+        assert_eq!(
+            parsed.attribs.last().unwrap().data,
+            VertexAttributeValues::Float([-1.0, 0.0, 0.0, -1.0].repeat(4))
+        );
+        assert!(matches!(
+            MeshBuilder::new(Primitive::Triangles)
+                .positions(&QUAD)
+                .compute_tangents()
+                .into_parsed(),
+            Err(MeshError::MissingAttribute(NORMAL_ATTRIBUTE_LOCATION))
+        ));
+    }
+}
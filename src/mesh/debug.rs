@@ -0,0 +1,162 @@
+use gl::types::GLuint;
+use glam::Vec3;
+
+use super::{
+    builder::triangle_list, export::float_attribute, Attribute, MeshError, MeshResult,
+    RenderCommand, NORMAL_ATTRIBUTE_LOCATION, POSITION_ATTRIBUTE_LOCATION,
+};
+
+/// Which normals [`super::Mesh::normal_lines`] shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalLines {
+    /// The normal attribute, from each vertex
+    Vertex,
+    /// The normal of each triangle, from its center
+    Face,
+}
+
+impl NormalLines {
+    /// Cycles through no normals, vertex and face normals
+    #[must_use]
+    pub const fn next(normals: Option<Self>) -> Option<Self> {
+        match normals {
+            None => Some(Self::Vertex),
+            Some(Self::Vertex) => Some(Self::Face),
+            Some(Self::Face) => None,
+        }
+    }
+}
+
+/// The float attribute at `location` as vectors, missing components are 0
+fn vectors(attribs: &[Attribute], location: GLuint) -> MeshResult<Vec<Vec3>> {
+    let (values, components) =
+        float_attribute(attribs, location).ok_or(MeshError::MissingAttribute(location))?;
+    Ok(values
+        .chunks_exact(components)
+        .map(|v| Vec3::from_array([0, 1, 2].map(|i| v.get(i).copied().unwrap_or_default())))
+        .collect())
+}
+
+/// Start and end of a line `length` long along each normal, in model space
+pub(super) fn normal_lines(
+    attribs: &[Attribute],
+    commands: &[RenderCommand],
+    normals: NormalLines,
+    length: f32,
+) -> MeshResult<Vec<(Vec3, Vec3)>> {
+    let positions = vectors(attribs, POSITION_ATTRIBUTE_LOCATION)?;
+    let vertex_normals = vectors(attribs, NORMAL_ATTRIBUTE_LOCATION).and_then(|normals| {
+        if normals.len() == positions.len() {
+            Ok(normals)
+        } else {
+            Err(MeshError::AttributeLength(
+                NORMAL_ATTRIBUTE_LOCATION,
+                normals.len(),
+                positions.len(),
+            ))
+        }
+    });
+    if normals == NormalLines::Vertex {
+        return Ok(positions
+            .into_iter()
+            .zip(vertex_normals?)
+            .map(|(position, normal)| (position, position + normal.normalize_or_zero() * length))
+            .collect());
+    }
+
+    let vertex_normals = match vertex_normals {
+        Err(MeshError::MissingAttribute(_)) => None,
+        normals => Some(normals?),
+    };
+    let indices = triangle_list(commands)?;
+    let mut lines = Vec::with_capacity(indices.len() / 3);
+    for triangle in indices.chunks_exact(3) {
+        if let Some(i) = triangle.iter().find(|i| **i as usize >= positions.len()) {
+            return Err(MeshError::IndexOutOfRange(*i, positions.len()));
+        }
+        let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+        // counter clockwise unless the vertex normals say otherwise
+        let mut normal = (b - a).cross(c - a).normalize_or_zero();
+        if let Some(vertex_normals) = &vertex_normals {
+            let sum = triangle
+                .iter()
+                .map(|i| vertex_normals[*i as usize])
+                .sum::<Vec3>();
+            if sum.dot(normal) < 0.0 {
+                normal = -normal;
+            }
+        }
+        // degenerate triangles have no normal
+        if normal != Vec3::ZERO {
+            let center = (a + b + c) / 3.0;
+            lines.push((center, center + normal * length));
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use crate::{
+        mesh::{MeshBuilder, MeshError, VertexAttributeValues, NORMAL_ATTRIBUTE_LOCATION},
+        opengl::Primitive,
+    };
+
+    use super::{normal_lines, NormalLines};
+
+    const TRIANGLE: [Vec3; 3] = [Vec3::ZERO, Vec3::X, Vec3::Y];
+
+    #[test]
+    fn test_normal_lines() {
+        let parsed = MeshBuilder::new(Primitive::Triangles)
+            .positions(&TRIANGLE)
+            .into_parsed()
+            .unwrap();
+        assert!(matches!(
+            normal_lines(&parsed.attribs, &parsed.commands, NormalLines::Vertex, 1.0),
+            Err(MeshError::MissingAttribute(NORMAL_ATTRIBUTE_LOCATION))
+        ));
+        let lines =
+            normal_lines(&parsed.attribs, &parsed.commands, NormalLines::Face, 2.0).unwrap();
+        let center = Vec3::new(1.0, 1.0, 0.0) / 3.0;
+        assert_eq!(lines, vec![(center, center + Vec3::Z * 2.0)]);
+
+        // vertex normals pointing the other way flip the face normal
+        let parsed = MeshBuilder::new(Primitive::Triangles)
+            .positions(&TRIANGLE)
+            .normals(&[Vec3::NEG_Z * 3.0; 3])
+            .into_parsed()
+            .unwrap();
+        let lines =
+            normal_lines(&parsed.attribs, &parsed.commands, NormalLines::Vertex, 0.5).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], (Vec3::X, Vec3::new(1.0, 0.0, -0.5)));
+        let lines =
+            normal_lines(&parsed.attribs, &parsed.commands, NormalLines::Face, 1.0).unwrap();
+        assert_eq!(lines, vec![(center, center - Vec3::Z)]);
+    }
+
+    #[test]
+    fn test_normal_lines_length() {
+        let mut parsed = MeshBuilder::new(Primitive::Triangles)
+            .positions(&TRIANGLE)
+            .normals(&[Vec3::Z; 3])
+            .into_parsed()
+            .unwrap();
+        for attrib in &mut parsed.attribs {
+            if let (NORMAL_ATTRIBUTE_LOCATION, VertexAttributeValues::Float(values)) =
+                (attrib.index, &mut attrib.data)
+            {
+                values.truncate(3);
+            }
+        }
+        for normals in [NormalLines::Vertex, NormalLines::Face] {
+            assert!(matches!(
+                normal_lines(&parsed.attribs, &parsed.commands, normals, 1.0),
+                Err(MeshError::AttributeLength(NORMAL_ATTRIBUTE_LOCATION, 1, 3))
+            ));
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::{fmt::Display, io::Write};
+
+use gl::types::{GLfloat, GLuint};
+
+use crate::{
+    opengl::{IndexSize, Primitive},
+    vertex_attributes::DataType,
+};
+
+use super::{
+    builder::triangle_list, f16_bits_to_f32, Attribute, IndicesData, IndicesValues, MeshError,
+    MeshResult, RenderCommand, VertexAttributeValues, NORMAL_ATTRIBUTE_LOCATION,
+    POSITION_ATTRIBUTE_LOCATION, TEXCOORD_ATTRIBUTE_LOCATION,
+};
+
+/// The inverse of `parse_data_type`
+fn data_type_name(data_type: DataType, normalized: bool) -> MeshResult<&'static str> {
+    let name = match (data_type, normalized) {
+        (DataType::Double, _) => "double",
+        (DataType::Float, _) => "float",
+        (DataType::HalfFloat, _) => "half",
+        (DataType::Int, false) => "int",
+        (DataType::UnsignedInt, false) => "uint",
+        (DataType::Int, true) => "norm-int",
+        (DataType::UnsignedInt, true) => "norm-uint",
+        (DataType::Short, false) => "short",
+        (DataType::UnsignedShort, false) => "ushort",
+        (DataType::Short, true) => "norm-short",
+        (DataType::UnsignedShort, true) => "norm-ushort",
+        (DataType::Byte, false) => "byte",
+        (DataType::UnsignedByte, false) => "ubyte",
+        (DataType::Byte, true) => "norm-byte",
+        (DataType::UnsignedByte, true) => "norm-ubyte",
+        (DataType::Fixed | DataType::Int2101010Rev | DataType::UnsignedInt2101010Rev, _) => {
+            return Err(MeshError::UnimplementedDataFormat(data_type))
+        }
+    };
+    Ok(name)
+}
+
+const fn index_type_name(index_size: IndexSize) -> &'static str {
+    match index_size {
+        IndexSize::UnsignedByte => "ubyte",
+        IndexSize::UnsignedShort => "ushort",
+        IndexSize::UnsignedInt => "uint",
+    }
+}
+
+/// The inverse of `parse_primitive`
+const fn primitive_name(primitive: Primitive) -> MeshResult<&'static str> {
+    match primitive {
+        Primitive::Lines => Ok("lines"),
+        Primitive::Triangles => Ok("triangles"),
+        Primitive::TriangleStrip => Ok("tri-strip"),
+        Primitive::TriangleFan => Ok("tri-fan"),
+        Primitive::LineStrip => Ok("line-strip"),
+        Primitive::LineLoop => Ok("line-loop"),
+        Primitive::Points => Ok("points"),
+        _ => Err(MeshError::UnsupportedPrimitive(primitive)),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes `row` values per line
+fn write_rows<T: Display>(writer: &mut impl Write, items: &[T], row: usize) -> MeshResult<()> {
+    for values in items.chunks(row.max(1)) {
+        write!(writer, "\n\t\t")?;
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                write!(writer, " ")?;
+            }
+            write!(writer, "{value}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_values(
+    writer: &mut impl Write,
+    values: &VertexAttributeValues,
+    row: usize,
+) -> MeshResult<()> {
+    match values {
+        VertexAttributeValues::Double(items) => write_rows(writer, items, row),
+        VertexAttributeValues::Float(items) => write_rows(writer, items, row),
+        VertexAttributeValues::Half(items) => {
+            let floats = items
+                .iter()
+                .map(|h| f16_bits_to_f32(*h))
+                .collect::<Vec<_>>();
+            write_rows(writer, &floats, row)
+        }
+        VertexAttributeValues::UnsignedInt(items) => write_rows(writer, items, row),
+        VertexAttributeValues::Int(items) => write_rows(writer, items, row),
+        VertexAttributeValues::UnsignedShort(items) => write_rows(writer, items, row),
+        VertexAttributeValues::Short(items) => write_rows(writer, items, row),
+        VertexAttributeValues::UnsignedByte(items) => write_rows(writer, items, row),
+        VertexAttributeValues::Byte(items) => write_rows(writer, items, row),
+    }
+}
+
+fn write_indices(
+    writer: &mut impl Write,
+    indexes: &IndicesData,
+    primitive: Primitive,
+    primitive_restart: Option<GLuint>,
+) -> MeshResult<()> {
+    write!(
+        writer,
+        "\t<indices cmd=\"{}\" type=\"{}\" ",
+        primitive_name(primitive)?,
+        index_type_name(indexes.index_size)
+    )?;
+    if let Some(restart) = primitive_restart {
+        write!(writer, "prim-restart=\"{restart}\" ")?;
+    }
+    write!(writer, ">")?;
+    let row = if primitive == Primitive::Triangles {
+        3
+    } else {
+        16
+    };
+    match &indexes.data {
+        IndicesValues::UnsignedInt(items) => write_rows(writer, items, row)?,
+        IndicesValues::UnsignedShort(items) => write_rows(writer, items, row)?,
+        IndicesValues::UnsignedByte(items) => write_rows(writer, items, row)?,
+    }
+    writeln!(writer, "</indices>")?;
+    Ok(())
+}
+
+/// Writes the XML mesh format read by [`super::Mesh::new`].
+pub(super) fn write_xml(
+    writer: &mut impl Write,
+    attribs: &[Attribute],
+    named_vao_list: &[(String, Vec<GLuint>)],
+    commands: &[RenderCommand],
+) -> MeshResult<()> {
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        writer,
+        "<mesh xmlns=\"http://www.arcsynthesis.com/gltut/mesh\" >"
+    )?;
+    for attrib in attribs {
+        let vertex_attribute = &attrib.vertex_attribute;
+        write!(
+            writer,
+            "\t<attribute index=\"{}\" type=\"{}\" size=\"{}\" >",
+            attrib.index,
+            data_type_name(vertex_attribute.data_type, vertex_attribute.normalized)?,
+            vertex_attribute.components
+        )?;
+        write_values(writer, &attrib.data, vertex_attribute.components as usize)?;
+        writeln!(writer, "</attribute>")?;
+    }
+    for (name, sources) in named_vao_list {
+        writeln!(writer, "\t<vao name=\"{}\" >", escape(name))?;
+        for source in sources {
+            writeln!(writer, "\t\t<source attrib=\"{source}\" />")?;
+        }
+        writeln!(writer, "\t</vao>")?;
+    }
+    for command in commands {
+        match command {
+            RenderCommand::Indexed {
+                indexes,
+                primitive,
+                primitive_restart,
+                ..
+            } => write_indices(writer, indexes, *primitive, *primitive_restart)?,
+            RenderCommand::MultiIndexed {
+                indexes,
+                primitive,
+                primitive_restart,
+                ..
+            } => {
+                for indexes in indexes {
+                    write_indices(writer, indexes, *primitive, *primitive_restart)?;
+                }
+            }
+            RenderCommand::Array {
+                primitive,
+                start,
+                count,
+            } => writeln!(
+                writer,
+                "\t<arrays cmd=\"{}\" start=\"{start}\" count=\"{count}\" />",
+                primitive_name(*primitive)?
+            )?,
+        }
+    }
+    writeln!(writer, "</mesh>")?;
+    Ok(())
+}
+
+/// The values of a float attribute at `location`, if there is one
+pub(super) fn float_attribute(
+    attribs: &[Attribute],
+    location: GLuint,
+) -> Option<(&[GLfloat], usize)> {
+    attribs.iter().find_map(|attrib| match &attrib.data {
+        VertexAttributeValues::Float(items) if attrib.index == location => Some((
+            items.as_slice(),
+            attrib.vertex_attribute.components as usize,
+        )),
+        _ => None,
+    })
+}
+
+/// Writes positions, normals and texture coordinates as a Wavefront OBJ triangle mesh,
+/// from the float attributes at the default locations.
+pub(super) fn write_obj(
+    writer: &mut impl Write,
+    attribs: &[Attribute],
+    commands: &[RenderCommand],
+) -> MeshResult<()> {
+    let (positions, components) = float_attribute(attribs, POSITION_ATTRIBUTE_LOCATION)
+        .ok_or(MeshError::MissingAttribute(POSITION_ATTRIBUTE_LOCATION))?;
+    let normals = float_attribute(attribs, NORMAL_ATTRIBUTE_LOCATION);
+    let uvs = float_attribute(attribs, TEXCOORD_ATTRIBUTE_LOCATION);
+
+    for position in positions.chunks_exact(components) {
+        let [x, y, z] = [0, 1, 2].map(|i| position.get(i).copied().unwrap_or_default());
+        writeln!(writer, "v {x} {y} {z}")?;
+    }
+    if let Some((uvs, components)) = uvs {
+        for uv in uvs.chunks_exact(components) {
+            let [u, v] = [0, 1].map(|i| uv.get(i).copied().unwrap_or_default());
+            writeln!(writer, "vt {u} {v}")?;
+        }
+    }
+    if let Some((normals, components)) = normals {
+        for normal in normals.chunks_exact(components) {
+            let [x, y, z] = [0, 1, 2].map(|i| normal.get(i).copied().unwrap_or_default());
+            writeln!(writer, "vn {x} {y} {z}")?;
+        }
+    }
+
+    let vertex_count = positions.len() / components;
+    let indices = triangle_list(commands)?;
+    // OBJ indices start from 1
+    let vertex = |i: GLuint| match (uvs.is_some(), normals.is_some()) {
+        (false, false) => format!("{}", i + 1),
+        (true, false) => format!("{0}/{0}", i + 1),
+        (false, true) => format!("{0}//{0}", i + 1),
+        (true, true) => format!("{0}/{0}/{0}", i + 1),
+    };
+    for triangle in indices.chunks_exact(3) {
+        if let Some(i) = triangle.iter().find(|i| **i as usize >= vertex_count) {
+            return Err(MeshError::IndexOutOfRange(*i, vertex_count));
+        }
+        writeln!(
+            writer,
+            "f {} {} {}",
+            vertex(triangle[0]),
+            vertex(triangle[1]),
+            vertex(triangle[2])
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::mesh::{obj::parse_obj, Mesh};
+
+    use super::{write_obj, write_xml};
+
+    macro_rules! test_case {
+        ($fname:expr) => {
+            concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/", $fname)
+        };
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let parsed = Mesh::parse_xml(test_case!("UnitSphere.xml")).unwrap();
+        let mut xml = vec![];
+        write_xml(
+            &mut xml,
+            &parsed.attribs,
+            &parsed.named_vao_list,
+            &parsed.commands,
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join("opengl_rend_export_round_trip.xml");
+        std::fs::write(&path, &xml).unwrap();
+        let reparsed = Mesh::parse_xml(Path::new(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reparsed.attribs.len(), parsed.attribs.len());
+        for (lhs, rhs) in reparsed.attribs.iter().zip(&parsed.attribs) {
+            assert_eq!(lhs.index, rhs.index);
+            assert_eq!(lhs.data, rhs.data);
+        }
+        assert_eq!(reparsed.named_vao_list, parsed.named_vao_list);
+        assert_eq!(reparsed.indices().len(), parsed.indices().len());
+        for (lhs, rhs) in reparsed.indices().iter().zip(parsed.indices()) {
+            assert_eq!(lhs.data, rhs.data);
+        }
+    }
+
+    #[test]
+    fn test_obj_round_trip() {
+        let parsed = Mesh::parse_xml(test_case!("UnitPlane.xml")).unwrap();
+        let mut obj = vec![];
+        write_obj(&mut obj, &parsed.attribs, &parsed.commands).unwrap();
+
+        let reparsed = parse_obj(obj.as_slice(), "").unwrap();
+        assert_eq!(reparsed.attribs[0].data, parsed.attribs[0].data);
+        assert_eq!(
+            reparsed.indices()[0].data.to_indices(),
+            parsed.indices()[0].data.to_indices()
+        );
+    }
+}
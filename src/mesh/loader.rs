@@ -0,0 +1,124 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use super::{MeshCpuData, MeshError, MeshResult};
+
+type Job = (PathBuf, Sender<MeshResult<MeshCpuData>>);
+
+/// Parses meshes on a pool of worker threads, the results are uploaded with
+/// [`super::Mesh::upload`] on the render thread.
+pub struct MeshLoader {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl MeshLoader {
+    /// Starts `threads` workers, at least one.
+    #[must_use]
+    pub fn new(threads: usize) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                std::thread::spawn(move || loop {
+                    // the lock is released before parsing
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    let Ok((path, result)) = job else {
+                        // the loader was dropped
+                        return;
+                    };
+                    // nobody might be waiting anymore
+                    let _ = result.send(MeshCpuData::load(path));
+                })
+            })
+            .collect();
+        Self {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    #[must_use]
+    pub fn load(&self, path: impl Into<PathBuf>) -> PendingMesh {
+        let (sender, receiver) = mpsc::channel();
+        if let Some(jobs) = &self.jobs {
+            // if every worker is gone the pending mesh reports the disconnection
+            let _ = jobs.send((path.into(), sender));
+        }
+        PendingMesh { receiver }
+    }
+}
+
+impl Default for MeshLoader {
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, std::num::NonZero::get))
+    }
+}
+
+impl Drop for MeshLoader {
+    fn drop(&mut self) {
+        // closing the channel stops the workers once the queued jobs are done
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+pub struct PendingMesh {
+    receiver: Receiver<MeshResult<MeshCpuData>>,
+}
+
+impl PendingMesh {
+    /// Blocks until the mesh is loaded.
+    pub fn wait(self) -> MeshResult<MeshCpuData> {
+        self.receiver
+            .recv()
+            .map_err(|_| MeshError::LoaderDisconnected)?
+    }
+
+    /// Returns the mesh if it finished loading, without blocking.
+    #[must_use]
+    pub fn try_get(&self) -> Option<MeshResult<MeshCpuData>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(MeshError::LoaderDisconnected)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mesh::MeshError;
+
+    use super::MeshLoader;
+
+    macro_rules! test_case {
+        ($fname:expr) => {
+            concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/", $fname)
+        };
+    }
+
+    #[test]
+    fn test_loader() {
+        let loader = MeshLoader::new(2);
+        let cube = loader.load(test_case!("UnitCube.xml"));
+        let cone = loader.load(test_case!("UnitCone.xml"));
+        let missing = loader.load(test_case!("Missing.xml"));
+
+        assert!(!cube.wait().unwrap().parsed_data.attribs.is_empty());
+        assert!(!cone.wait().unwrap().parsed_data.attribs.is_empty());
+        assert!(matches!(missing.wait(), Err(MeshError::IOError(_))));
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashMap, io::BufRead, str::SplitWhitespace};
+
+use gl::types::{GLfloat, GLuint};
+
+use crate::vertex_attributes::{DataType, VertexAttribute};
+
+use super::{
+    Attribute, IndicesData, MeshError, MeshResult, ParsedData, RenderCommand,
+    VertexAttributeValues, NORMAL_ATTRIBUTE_LOCATION, POSITION_ATTRIBUTE_LOCATION,
+    TEXCOORD_ATTRIBUTE_LOCATION,
+};
+
+/// Indices into the position, texture coordinate and normal lists of a face vertex
+type ObjVertex = (usize, Option<usize>, Option<usize>);
+
+fn parse_floats<const N: usize>(
+    words: &mut SplitWhitespace,
+    line: usize,
+    string_path: &str,
+) -> MeshResult<[GLfloat; N]> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let word = words
+            .next()
+            .ok_or_else(|| MeshError::InvalidObjLine(line, string_path.to_owned()))?;
+        *value = word.parse()?;
+    }
+    Ok(values)
+}
+
+/// OBJ indices start from 1, negative ones are relative to the end of the list
+fn parse_index(word: &str, count: usize, line: usize, string_path: &str) -> MeshResult<usize> {
+    let index = word.parse::<isize>()?;
+    let resolved = if index < 0 {
+        count as isize + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved as usize >= count {
+        return Err(MeshError::ObjIndexOutOfRange(
+            index,
+            line,
+            string_path.to_owned(),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, `counts` are the lengths of the lists
+fn parse_face_vertex(
+    word: &str,
+    counts: (usize, usize, usize),
+    line: usize,
+    string_path: &str,
+) -> MeshResult<ObjVertex> {
+    let mut parts = word.split('/');
+    let position = parse_index(
+        parts.next().unwrap_or_default(),
+        counts.0,
+        line,
+        string_path,
+    )?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(parse_index(uv, counts.1, line, string_path)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => {
+            Some(parse_index(normal, counts.2, line, string_path)?)
+        }
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+pub(super) fn parse_obj(reader: impl BufRead, string_path: &str) -> MeshResult<ParsedData> {
+    let mut positions: Vec<[GLfloat; 3]> = vec![];
+    let mut uvs: Vec<[GLfloat; 2]> = vec![];
+    let mut normals: Vec<[GLfloat; 3]> = vec![];
+
+    // each distinct position/uv/normal combination becomes a single vertex
+    let mut vertices: Vec<ObjVertex> = vec![];
+    let mut vertex_indices: HashMap<ObjVertex, GLuint> = HashMap::new();
+    let mut indices: Vec<GLuint> = vec![];
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_number + 1;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => positions.push(parse_floats(&mut words, line_number, string_path)?),
+            Some("vt") => uvs.push(parse_floats(&mut words, line_number, string_path)?),
+            Some("vn") => normals.push(parse_floats(&mut words, line_number, string_path)?),
+            Some("f") => {
+                let mut face = vec![];
+                for word in words {
+                    let vertex = parse_face_vertex(
+                        word,
+                        (positions.len(), uvs.len(), normals.len()),
+                        line_number,
+                        string_path,
+                    )?;
+                    let index = *vertex_indices.entry(vertex).or_insert_with(|| {
+                        vertices.push(vertex);
+                        (vertices.len() - 1) as GLuint
+                    });
+                    face.push(index);
+                }
+                if face.len() < 3 {
+                    return Err(MeshError::InvalidObjLine(
+                        line_number,
+                        string_path.to_owned(),
+                    ));
+                }
+                // polygons are triangulated as a fan
+                for i in 1..face.len() - 1 {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            // groups, materials and smoothing are not supported
+            _ => {}
+        }
+    }
+
+    let mut attribs = vec![Attribute {
+        index: POSITION_ATTRIBUTE_LOCATION,
+        vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+        data: VertexAttributeValues::Float(
+            vertices
+                .iter()
+                .flat_map(|(position, _, _)| positions[*position])
+                .collect(),
+        ),
+    }];
+    if vertices.iter().any(|(_, _, normal)| normal.is_some()) {
+        attribs.push(Attribute {
+            index: NORMAL_ATTRIBUTE_LOCATION,
+            vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+            data: VertexAttributeValues::Float(
+                vertices
+                    .iter()
+                    .flat_map(|(_, _, normal)| normal.map_or([0.0; 3], |n| normals[n]))
+                    .collect(),
+            ),
+        });
+    }
+    if vertices.iter().any(|(_, uv, _)| uv.is_some()) {
+        attribs.push(Attribute {
+            index: TEXCOORD_ATTRIBUTE_LOCATION,
+            vertex_attribute: VertexAttribute::new(2, DataType::Float, false),
+            data: VertexAttributeValues::Float(
+                vertices
+                    .iter()
+                    .flat_map(|(_, uv, _)| uv.map_or([0.0; 2], |uv| uvs[uv]))
+                    .collect(),
+            ),
+        });
+    }
+
+    let commands = vec![RenderCommand::triangles(IndicesData::from_indices(
+        indices,
+        vertices.len(),
+    ))];
+
+    Ok(ParsedData {
+        attribs,
+        named_vao_list: vec![],
+        commands,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mesh::{
+        IndicesValues, MeshError, RenderCommand, VertexAttributeValues, NORMAL_ATTRIBUTE_LOCATION,
+        POSITION_ATTRIBUTE_LOCATION, TEXCOORD_ATTRIBUTE_LOCATION,
+    };
+
+    use super::parse_obj;
+
+    const QUAD: &str = "
+# unit quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1
+f -4/-4/-1 -2/-2/-1 -1/-1/-1
+";
+
+    #[test]
+    fn test_obj_parse() {
+        let parsed = parse_obj(QUAD.as_bytes(), "quad.obj").unwrap();
+        assert_eq!(parsed.attribs.len(), 3);
+        assert_eq!(parsed.attribs[0].index, POSITION_ATTRIBUTE_LOCATION);
+        assert_eq!(parsed.attribs[1].index, NORMAL_ATTRIBUTE_LOCATION);
+        assert_eq!(parsed.attribs[2].index, TEXCOORD_ATTRIBUTE_LOCATION);
+        // shared corners are deduplicated
+        assert_eq!(parsed.attribs[0].num_elements(), 4);
+        assert_eq!(
+            parsed.attribs[2].data,
+            VertexAttributeValues::Float(vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0])
+        );
+
+        let indices = parsed.indices();
+        assert_eq!(
+            indices[0].data,
+            IndicesValues::UnsignedShort(vec![0, 1, 2, 0, 2, 3])
+        );
+        assert!(matches!(
+            parsed.commands[0],
+            RenderCommand::Indexed { count: 6, .. }
+        ));
+    }
+
+    #[test]
+    fn test_obj_polygon() {
+        let parsed = parse_obj(
+            b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n".as_slice(),
+            "",
+        )
+        .unwrap();
+        // only positions
+        assert_eq!(parsed.attribs.len(), 1);
+        assert_eq!(
+            parsed.indices()[0].data,
+            IndicesValues::UnsignedShort(vec![0, 1, 2, 0, 2, 3])
+        );
+    }
+
+    #[test]
+    fn test_obj_errors() {
+        assert!(matches!(
+            parse_obj(b"v 0 0 0\nf 1 2 3\n".as_slice(), ""),
+            Err(MeshError::ObjIndexOutOfRange(2, 2, _))
+        ));
+        assert!(matches!(
+            parse_obj(b"v 0 0\n".as_slice(), ""),
+            Err(MeshError::InvalidObjLine(1, _))
+        ));
+    }
+}
@@ -0,0 +1,576 @@
+use std::{collections::HashMap, io::BufRead, str::SplitWhitespace};
+
+use gl::types::{GLfloat, GLubyte, GLuint};
+
+use crate::{
+    opengl::Primitive,
+    vertex_attributes::{DataType, VertexAttribute},
+};
+
+use super::{
+    flat_normals, Attribute, IndicesData, MeshError, MeshResult, ParsedData, RenderCommand,
+    VertexAttributeValues, COLOR_ATTRIBUTE_LOCATION, NORMAL_ATTRIBUTE_LOCATION,
+    POSITION_ATTRIBUTE_LOCATION, TEXCOORD_ATTRIBUTE_LOCATION,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PropertyType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl PropertyType {
+    fn parse(s: &str, string_path: &str) -> MeshResult<Self> {
+        match s {
+            "char" | "int8" => Ok(Self::Char),
+            "uchar" | "uint8" => Ok(Self::UChar),
+            "short" | "int16" => Ok(Self::Short),
+            "ushort" | "uint16" => Ok(Self::UShort),
+            "int" | "int32" => Ok(Self::Int),
+            "uint" | "uint32" => Ok(Self::UInt),
+            "float" | "float32" => Ok(Self::Float),
+            "double" | "float64" => Ok(Self::Double),
+            _ => Err(invalid(&format!("unknown type {s}"), string_path)),
+        }
+    }
+
+    const fn size(self) -> usize {
+        match self {
+            Self::Char | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt | Self::Float => 4,
+            Self::Double => 8,
+        }
+    }
+
+    const fn is_floating_point(self) -> bool {
+        matches!(self, Self::Float | Self::Double)
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, PropertyType),
+    List {
+        name: String,
+        count_type: PropertyType,
+        item_type: PropertyType,
+    },
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(reason: &str, string_path: &str) -> MeshError {
+    MeshError::InvalidPlyData(reason.to_owned(), string_path.to_owned())
+}
+
+fn parse_header(
+    reader: &mut impl BufRead,
+    string_path: &str,
+) -> MeshResult<(Format, Vec<Element>)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut line = String::new();
+    let mut first = true;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("missing end_header", string_path));
+        }
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if first {
+            if keyword != Some("ply") {
+                return Err(invalid("missing ply magic number", string_path));
+            }
+            first = false;
+            continue;
+        }
+        match keyword {
+            Some("format") => {
+                format = Some(match words.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => return Err(invalid("unknown format", string_path)),
+                });
+            }
+            Some("element") => {
+                let (Some(name), Some(count)) = (words.next(), words.next()) else {
+                    return Err(invalid("bad element", string_path));
+                };
+                elements.push(Element {
+                    name: name.to_owned(),
+                    count: count.parse()?,
+                    properties: vec![],
+                });
+            }
+            Some("property") => {
+                let Some(element) = elements.last_mut() else {
+                    return Err(invalid("property outside of element", string_path));
+                };
+                let property = match (words.next(), words.next(), words.next(), words.next()) {
+                    (Some("list"), Some(count_type), Some(item_type), Some(name)) => {
+                        Property::List {
+                            name: name.to_owned(),
+                            count_type: PropertyType::parse(count_type, string_path)?,
+                            item_type: PropertyType::parse(item_type, string_path)?,
+                        }
+                    }
+                    (Some(data_type), Some(name), None, None) => Property::Scalar(
+                        name.to_owned(),
+                        PropertyType::parse(data_type, string_path)?,
+                    ),
+                    _ => return Err(invalid("bad property", string_path)),
+                };
+                element.properties.push(property);
+            }
+            Some("end_header") => break,
+            // comments and obj_info
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| invalid("missing format", string_path))?;
+    Ok((format, elements))
+}
+
+/// Reads the values in the body one at a time, whatever the format
+enum Body<'a> {
+    Ascii(SplitWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    fn read(&mut self, data_type: PropertyType, string_path: &str) -> MeshResult<f64> {
+        match self {
+            Self::Ascii(words) => {
+                let word = words
+                    .next()
+                    .ok_or_else(|| invalid("unexpected end of data", string_path))?;
+                Ok(word.parse()?)
+            }
+            Self::Binary { bytes, big_endian } => {
+                let size = data_type.size();
+                if bytes.len() < size {
+                    return Err(invalid("unexpected end of data", string_path));
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+                // convert everything to little endian
+                let mut b = [0; 8];
+                b[..size].copy_from_slice(value);
+                if *big_endian {
+                    b[..size].reverse();
+                }
+                Ok(match data_type {
+                    PropertyType::Char => f64::from(b[0] as i8),
+                    PropertyType::UChar => f64::from(b[0]),
+                    PropertyType::Short => f64::from(i16::from_le_bytes([b[0], b[1]])),
+                    PropertyType::UShort => f64::from(u16::from_le_bytes([b[0], b[1]])),
+                    PropertyType::Int => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    PropertyType::UInt => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    PropertyType::Float => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    PropertyType::Double => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+}
+
+/// Vertex data read from the `vertex` element, and triangulated `face` indices
+#[derive(Default)]
+struct PlyData {
+    positions: Vec<GLfloat>,
+    normals: Vec<GLfloat>,
+    colors: Vec<GLubyte>,
+    uvs: Vec<GLfloat>,
+    indices: Vec<GLuint>,
+}
+
+/// Finds the first of the `names` in `columns` for each component
+fn find_columns<const N: usize>(
+    columns: &HashMap<&str, (usize, PropertyType)>,
+    names: [&[&str]; N],
+) -> Option<[(usize, PropertyType); N]> {
+    let mut found = [(0, PropertyType::Float); N];
+    for (found, names) in found.iter_mut().zip(names) {
+        *found = names.iter().find_map(|name| columns.get(name).copied())?;
+    }
+    Some(found)
+}
+
+fn read_vertices(
+    element: &Element,
+    body: &mut Body,
+    data: &mut PlyData,
+    string_path: &str,
+) -> MeshResult<()> {
+    let columns = element
+        .properties
+        .iter()
+        .enumerate()
+        .filter_map(|(i, property)| match property {
+            Property::Scalar(name, data_type) => Some((name.as_str(), (i, *data_type))),
+            Property::List { .. } => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let positions = find_columns(&columns, [&["x"], &["y"], &["z"]])
+        .ok_or_else(|| invalid("vertices have no position", string_path))?;
+    let normals = find_columns(&columns, [&["nx"], &["ny"], &["nz"]]);
+    let colors = find_columns(
+        &columns,
+        [
+            &["red", "r"],
+            &["green", "g"],
+            &["blue", "b"],
+            &["alpha", "a"],
+        ],
+    )
+    .or_else(|| {
+        find_columns(&columns, [&["red", "r"], &["green", "g"], &["blue", "b"]])
+            .map(|[r, g, b]| [r, g, b, (usize::MAX, PropertyType::UChar)])
+    });
+    let uvs = find_columns(
+        &columns,
+        [&["s", "u", "texture_u"], &["t", "v", "texture_v"]],
+    );
+
+    let mut values = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            match property {
+                Property::Scalar(_, data_type) => *value = body.read(*data_type, string_path)?,
+                Property::List {
+                    count_type,
+                    item_type,
+                    ..
+                } => {
+                    let count = body.read(*count_type, string_path)? as usize;
+                    for _ in 0..count {
+                        body.read(*item_type, string_path)?;
+                    }
+                }
+            }
+        }
+        data.positions
+            .extend(positions.map(|(i, _)| values[i] as GLfloat));
+        if let Some(normals) = normals {
+            data.normals
+                .extend(normals.map(|(i, _)| values[i] as GLfloat));
+        }
+        if let Some(colors) = colors {
+            data.colors
+                .extend(colors.map(|(i, data_type)| match values.get(i) {
+                    // no alpha
+                    None => GLubyte::MAX,
+                    Some(value) if data_type.is_floating_point() => (value * 255.0) as GLubyte,
+                    Some(value) => *value as GLubyte,
+                }));
+        }
+        if let Some(uvs) = uvs {
+            data.uvs.extend(uvs.map(|(i, _)| values[i] as GLfloat));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(
+    element: &Element,
+    body: &mut Body,
+    data: &mut PlyData,
+    vertex_count: usize,
+    string_path: &str,
+) -> MeshResult<()> {
+    let mut face = vec![];
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::Scalar(_, data_type) => {
+                    body.read(*data_type, string_path)?;
+                }
+                Property::List {
+                    name,
+                    count_type,
+                    item_type,
+                } => {
+                    face.clear();
+                    let count = body.read(*count_type, string_path)? as usize;
+                    for _ in 0..count {
+                        face.push(body.read(*item_type, string_path)? as GLuint);
+                    }
+                    if name != "vertex_indices" && name != "vertex_index" {
+                        continue;
+                    }
+                    if face.iter().any(|i| *i as usize >= vertex_count) {
+                        return Err(invalid("face index out of range", string_path));
+                    }
+                    // polygons are triangulated as a fan
+                    for i in 1..face.len().saturating_sub(1) {
+                        data.indices
+                            .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn skip_element(element: &Element, body: &mut Body, string_path: &str) -> MeshResult<()> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::Scalar(_, data_type) => {
+                    body.read(*data_type, string_path)?;
+                }
+                Property::List {
+                    count_type,
+                    item_type,
+                    ..
+                } => {
+                    let count = body.read(*count_type, string_path)? as usize;
+                    for _ in 0..count {
+                        body.read(*item_type, string_path)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses ASCII or binary PLY. Meshes without faces are drawn as points, meshes with faces
+/// but without normals get flat normals, which needs a vertex per triangle corner.
+pub(super) fn parse_ply(mut reader: impl BufRead, string_path: &str) -> MeshResult<ParsedData> {
+    let (format, elements) = parse_header(&mut reader, string_path)?;
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let mut body = match format {
+        Format::Ascii => Body::Ascii(
+            std::str::from_utf8(&bytes)
+                .map_err(|_| invalid("ascii data is not valid utf8", string_path))?
+                .split_whitespace(),
+        ),
+        Format::BinaryLittleEndian => Body::Binary {
+            bytes: &bytes,
+            big_endian: false,
+        },
+        Format::BinaryBigEndian => Body::Binary {
+            bytes: &bytes,
+            big_endian: true,
+        },
+    };
+
+    let mut data = PlyData::default();
+    let mut vertex_count = 0;
+    let mut has_faces = false;
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                read_vertices(element, &mut body, &mut data, string_path)?;
+                vertex_count = element.count;
+            }
+            "face" => {
+                read_faces(element, &mut body, &mut data, vertex_count, string_path)?;
+                has_faces = true;
+            }
+            _ => skip_element(element, &mut body, string_path)?,
+        }
+    }
+
+    let mut attribs = vec![Attribute {
+        index: POSITION_ATTRIBUTE_LOCATION,
+        vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+        data: VertexAttributeValues::Float(data.positions),
+    }];
+    if !data.colors.is_empty() {
+        attribs.push(Attribute {
+            index: COLOR_ATTRIBUTE_LOCATION,
+            vertex_attribute: VertexAttribute::new(4, DataType::UnsignedByte, true),
+            data: VertexAttributeValues::UnsignedByte(data.colors),
+        });
+    }
+    if !data.uvs.is_empty() {
+        attribs.push(Attribute {
+            index: TEXCOORD_ATTRIBUTE_LOCATION,
+            vertex_attribute: VertexAttribute::new(2, DataType::Float, false),
+            data: VertexAttributeValues::Float(data.uvs),
+        });
+    }
+    let has_normals = !data.normals.is_empty();
+    if has_normals {
+        attribs.push(Attribute {
+            index: NORMAL_ATTRIBUTE_LOCATION,
+            vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+            data: VertexAttributeValues::Float(data.normals),
+        });
+    }
+
+    let commands = if !has_faces {
+        vec![RenderCommand::Array {
+            primitive: Primitive::Points,
+            start: 0,
+            count: vertex_count as i32,
+        }]
+    } else if has_normals {
+        vec![RenderCommand::triangles(IndicesData::from_indices(
+            data.indices,
+            vertex_count,
+        ))]
+    } else {
+        // split the vertices so every triangle can have its own normal
+        for attrib in &mut attribs {
+            let components = attrib.vertex_attribute.components as usize;
+            attrib.data = attrib.data.gather(components, &data.indices);
+        }
+        if let VertexAttributeValues::Float(positions) = &attribs[0].data {
+            let normals = flat_normals(positions);
+            attribs.push(Attribute {
+                index: NORMAL_ATTRIBUTE_LOCATION,
+                vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+                data: VertexAttributeValues::Float(normals),
+            });
+        }
+        vec![RenderCommand::Array {
+            primitive: Primitive::Triangles,
+            start: 0,
+            count: data.indices.len() as i32,
+        }]
+    };
+
+    Ok(ParsedData {
+        attribs,
+        named_vao_list: vec![],
+        commands,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        mesh::{IndicesValues, MeshError, RenderCommand, VertexAttributeValues},
+        opengl::Primitive,
+    };
+
+    use super::parse_ply;
+
+    const QUAD: &str = "ply
+format ascii 1.0
+comment unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    #[test]
+    fn test_ply_ascii() {
+        let parsed = parse_ply(QUAD.as_bytes(), "").unwrap();
+        // two triangles split into 6 vertices for flat normals
+        assert!(matches!(
+            parsed.commands[0],
+            RenderCommand::Array { count: 6, .. }
+        ));
+        assert_eq!(
+            parsed.attribs[1].data,
+            VertexAttributeValues::UnsignedByte(vec![
+                255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255, 0, 0, 255, 255,
+                255, 255, 255, 255
+            ])
+        );
+        assert_eq!(
+            parsed.attribs[2].data,
+            VertexAttributeValues::Float([0.0, 0.0, 1.0].repeat(6))
+        );
+    }
+
+    #[test]
+    fn test_ply_binary() {
+        let mut data = b"ply
+format binary_big_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+element face 1
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+        for vertex in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for value in vertex.iter().chain(&[0.0, 0.0, 1.0]) {
+                data.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        data.push(3);
+        for index in [0u32, 1, 2] {
+            data.extend_from_slice(&index.to_be_bytes());
+        }
+
+        let parsed = parse_ply(data.as_slice(), "").unwrap();
+        assert_eq!(
+            parsed.attribs[0].data,
+            VertexAttributeValues::Float(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+        );
+        assert_eq!(
+            parsed.indices()[0].data,
+            IndicesValues::UnsignedShort(vec![0, 1, 2])
+        );
+
+        // truncated
+        assert!(matches!(
+            parse_ply(&data[..data.len() - 1], ""),
+            Err(MeshError::InvalidPlyData(..))
+        ));
+    }
+
+    #[test]
+    fn test_ply_points() {
+        let parsed = parse_ply(
+            b"ply\nformat ascii 1.0\nelement vertex 2\nproperty double x\nproperty double y\nproperty double z\nend_header\n0 0 0\n1 2 3\n"
+                .as_slice(),
+            "",
+        )
+        .unwrap();
+        assert!(matches!(
+            parsed.commands[0],
+            RenderCommand::Array {
+                primitive: Primitive::Points,
+                count: 2,
+                ..
+            }
+        ));
+    }
+}
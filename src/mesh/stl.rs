@@ -0,0 +1,176 @@
+use gl::types::GLfloat;
+
+use crate::{
+    opengl::Primitive,
+    vertex_attributes::{DataType, VertexAttribute},
+};
+
+use super::{
+    flat_normals, Attribute, MeshError, MeshResult, ParsedData, RenderCommand,
+    VertexAttributeValues, NORMAL_ATTRIBUTE_LOCATION, POSITION_ATTRIBUTE_LOCATION,
+};
+
+const HEADER_SIZE: usize = 80;
+/// Normal, 3 vertices and a 2 byte attribute count
+const TRIANGLE_SIZE: usize = 12 * 4 + 2;
+
+/// Positions and facet normals, 9 and 3 floats per triangle
+struct Triangles {
+    positions: Vec<GLfloat>,
+    normals: Vec<GLfloat>,
+}
+
+fn parse_binary(data: &[u8]) -> Triangles {
+    let mut triangles = Triangles {
+        positions: vec![],
+        normals: vec![],
+    };
+    for triangle in data[HEADER_SIZE + 4..].chunks_exact(TRIANGLE_SIZE) {
+        let mut floats = triangle[..48]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        triangles.normals.extend(floats.by_ref().take(3));
+        triangles.positions.extend(floats);
+    }
+    triangles
+}
+
+fn parse_ascii(data: &str, string_path: &str) -> MeshResult<Triangles> {
+    let mut triangles = Triangles {
+        positions: vec![],
+        normals: vec![],
+    };
+    let mut words = data.split_whitespace();
+    while let Some(word) = words.next() {
+        let values = match word {
+            "normal" => &mut triangles.normals,
+            "vertex" => &mut triangles.positions,
+            _ => continue,
+        };
+        for _ in 0..3 {
+            let value = words
+                .next()
+                .ok_or_else(|| MeshError::InvalidStlData(string_path.to_owned()))?;
+            values.push(value.parse()?);
+        }
+    }
+    if triangles.positions.len() != triangles.normals.len() * 3 {
+        return Err(MeshError::InvalidStlData(string_path.to_owned()));
+    }
+    Ok(triangles)
+}
+
+/// Parses binary or ASCII STL, facet normals that are missing (zero) are computed from the
+/// triangle winding.
+pub(super) fn parse_stl(data: &[u8], string_path: &str) -> MeshResult<ParsedData> {
+    let count = data
+        .get(HEADER_SIZE..HEADER_SIZE + 4)
+        .map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize);
+    // binary files may start with "solid" too, the size is more reliable
+    let triangles =
+        if count.is_some_and(|count| data.len() == HEADER_SIZE + 4 + count * TRIANGLE_SIZE) {
+            parse_binary(data)
+        } else if data.starts_with(b"solid") {
+            let data = std::str::from_utf8(data)
+                .map_err(|_| MeshError::InvalidStlData(string_path.to_owned()))?;
+            parse_ascii(data, string_path)?
+        } else {
+            return Err(MeshError::InvalidStlData(string_path.to_owned()));
+        };
+
+    let computed = flat_normals(&triangles.positions);
+    let normals = triangles
+        .normals
+        .chunks_exact(3)
+        .zip(computed.chunks_exact(9))
+        .flat_map(|(normal, computed)| {
+            if normal.iter().all(|n| *n == 0.0) {
+                [computed[0], computed[1], computed[2]]
+            } else {
+                [normal[0], normal[1], normal[2]]
+            }
+            .repeat(3)
+        })
+        .collect::<Vec<_>>();
+
+    let count = (triangles.positions.len() / 3) as i32;
+    Ok(ParsedData {
+        attribs: vec![
+            Attribute {
+                index: POSITION_ATTRIBUTE_LOCATION,
+                vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+                data: VertexAttributeValues::Float(triangles.positions),
+            },
+            Attribute {
+                index: NORMAL_ATTRIBUTE_LOCATION,
+                vertex_attribute: VertexAttribute::new(3, DataType::Float, false),
+                data: VertexAttributeValues::Float(normals),
+            },
+        ],
+        named_vao_list: vec![],
+        commands: vec![RenderCommand::Array {
+            primitive: Primitive::Triangles,
+            start: 0,
+            count,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mesh::{MeshError, RenderCommand, VertexAttributeValues};
+
+    use super::parse_stl;
+
+    const ASCII: &str = "solid triangle
+facet normal 0 0 0
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid triangle
+";
+
+    #[test]
+    fn test_stl_ascii() {
+        let parsed = parse_stl(ASCII.as_bytes(), "").unwrap();
+        assert!(matches!(
+            parsed.commands[0],
+            RenderCommand::Array { count: 3, .. }
+        ));
+        // the missing normal is computed
+        assert_eq!(
+            parsed.attribs[1].data,
+            VertexAttributeValues::Float([0.0, 0.0, 1.0].repeat(3))
+        );
+    }
+
+    #[test]
+    fn test_stl_binary() {
+        let mut data = b"solid but actually binary".to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for value in [
+            0.0f32, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[0, 0]);
+
+        let parsed = parse_stl(&data, "").unwrap();
+        assert_eq!(
+            parsed.attribs[0].data,
+            VertexAttributeValues::Float(vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            parsed.attribs[1].data,
+            VertexAttributeValues::Float([0.0, 0.0, -1.0].repeat(3))
+        );
+        assert!(matches!(
+            parse_stl(&[0; 90], ""),
+            Err(MeshError::InvalidStlData(_))
+        ));
+    }
+}
@@ -0,0 +1,437 @@
+use glam::{Mat4, Quat, Vec3};
+
+use crate::{matrix_stack::MatrixStack, transform::Transform};
+
+/// Handle to a node in its [`NodeTree`], stays valid until the node is removed.
+/// Handles of removed nodes never refer to nodes added later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+/// Rotation of a node around `axis` on top of its transform, like a joint.
+/// [`NodeTree::animate`] turns it at `speed`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Channel {
+    pub axis: Vec3,
+    /// Degrees
+    pub angle: f32,
+    /// Degrees per second
+    pub speed: f32,
+    /// Degrees, the angle stays between them and animations bounce back at the ends
+    pub limits: Option<(f32, f32)>,
+}
+
+impl Channel {
+    #[must_use]
+    pub const fn new(axis: Vec3, angle: f32) -> Self {
+        Self {
+            axis,
+            angle,
+            speed: 0.0,
+            limits: None,
+        }
+    }
+    #[must_use]
+    pub const fn with_limits(mut self, min: f32, max: f32) -> Self {
+        self.limits = Some((min, max));
+        self
+    }
+    #[must_use]
+    pub const fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Adds `degrees` to the angle, keeping it within the limits
+    pub fn rotate(&mut self, degrees: f32) {
+        self.angle = match self.limits {
+            Some((min, max)) => (self.angle + degrees).clamp(min, max),
+            None => (self.angle + degrees) % 360.0,
+        };
+    }
+    /// Turns at `speed` for `dt` seconds, reversing at the limits
+    pub fn advance(&mut self, dt: f32) {
+        self.rotate(self.speed * dt);
+        if let Some((min, max)) = self.limits {
+            if (self.angle >= max && self.speed > 0.0) || (self.angle <= min && self.speed < 0.0) {
+                self.speed = -self.speed;
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn rotation(&self) -> Quat {
+        Quat::from_axis_angle(self.axis.normalize(), self.angle.to_radians())
+    }
+}
+
+/// A named node, its transform is relative to the parent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<T> {
+    pub name: String,
+    pub transform: Transform,
+    pub channel: Option<Channel>,
+    pub value: T,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl<T> Node<T> {
+    #[must_use]
+    pub const fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+    #[must_use]
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// The transform with the rotation of the channel applied after its own
+    #[must_use]
+    pub fn local_transform(&self) -> Transform {
+        self.channel.map_or(self.transform, |channel| Transform {
+            rotation: self.transform.rotation * channel.rotation(),
+            ..self.transform
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Slot<T> {
+    generation: u32,
+    node: Option<Node<T>>,
+}
+
+/// Hierarchy of transforms with a value each, stored in a single `Vec`.
+/// Nodes without a parent are roots, there can be any number of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeTree<T> {
+    slots: Vec<Slot<T>>,
+    /// Indices of empty slots
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> Default for NodeTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> NodeTree<T> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+            len: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn add_root(&mut self, name: &str, transform: Transform, value: T) -> NodeId {
+        self.insert(None, name, transform, value)
+    }
+    /// `None` if `parent` doesn't exist
+    pub fn add_child(
+        &mut self,
+        parent: NodeId,
+        name: &str,
+        transform: Transform,
+        value: T,
+    ) -> Option<NodeId> {
+        self.get(parent)?;
+        let id = self.insert(Some(parent), name, transform, value);
+        self.get_mut(parent)?.children.push(id);
+        Some(id)
+    }
+    fn insert(
+        &mut self,
+        parent: Option<NodeId>,
+        name: &str,
+        transform: Transform,
+        value: T,
+    ) -> NodeId {
+        let node = Node {
+            name: name.to_owned(),
+            transform,
+            channel: None,
+            value,
+            parent,
+            children: vec![],
+        };
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.node = Some(node);
+            NodeId {
+                index,
+                generation: slot.generation,
+            }
+        } else {
+            self.slots.push(Slot {
+                generation: 0,
+                node: Some(node),
+            });
+            NodeId {
+                index: self.slots.len() - 1,
+                generation: 0,
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, id: NodeId) -> Option<&Node<T>> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)?
+            .node
+            .as_ref()
+    }
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<T>> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)?
+            .node
+            .as_mut()
+    }
+    #[must_use]
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+    pub fn channel_mut(&mut self, id: NodeId) -> Option<&mut Channel> {
+        self.get_mut(id)?.channel.as_mut()
+    }
+
+    /// Every node in no particular order
+    fn nodes(&self) -> impl Iterator<Item = (NodeId, &Node<T>)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = NodeId {
+                index,
+                generation: slot.generation,
+            };
+            slot.node.as_ref().map(|node| (id, node))
+        })
+    }
+
+    /// The first node called `name` in depth first order, see [`NodeTree::iter`]
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter()
+            .find(|(_, node)| node.name == name)
+            .map(|(id, _)| id)
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(id, _)| id)
+    }
+
+    /// Every node depth first, parents before their children
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node<T>)> {
+        let mut stack = self.roots().collect::<Vec<_>>();
+        stack.reverse();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let node = self.get(id)?;
+            stack.extend(node.children.iter().rev());
+            Some((id, node))
+        })
+    }
+
+    /// Transform from the space of `id` to the space of its root
+    #[must_use]
+    pub fn world_matrix(&self, id: NodeId) -> Option<Mat4> {
+        let mut node = self.get(id)?;
+        let mut matrix = node.local_transform().to_matrix();
+        while let Some(parent) = node.parent {
+            node = self.get(parent)?;
+            matrix = node.local_transform().to_matrix() * matrix;
+        }
+        Some(matrix)
+    }
+
+    /// Visits every node depth first with the top of `stack` times its world matrix.
+    /// The stack is pushed for each node and popped after its children.
+    pub fn draw(&self, stack: &mut MatrixStack, visit: &mut impl FnMut(&Mat4, &T)) {
+        for root in self.roots() {
+            self.draw_node(root, stack, visit);
+        }
+    }
+    fn draw_node(&self, id: NodeId, stack: &mut MatrixStack, visit: &mut impl FnMut(&Mat4, &T)) {
+        let Some(node) = self.get(id) else {
+            return;
+        };
+        stack.with_pushed(|stack| {
+            stack.apply_transform(&node.local_transform());
+            visit(&stack.top(), &node.value);
+            for child in &node.children {
+                self.draw_node(*child, stack, visit);
+            }
+        });
+    }
+
+    /// Advances every channel by `dt` seconds
+    pub fn animate(&mut self, dt: f32) {
+        for slot in &mut self.slots {
+            if let Some(channel) = slot.node.as_mut().and_then(|node| node.channel.as_mut()) {
+                channel.advance(dt);
+            }
+        }
+    }
+
+    /// Removes `id` and its descendants, returning its value
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        let parent = self.get(id)?.parent;
+        if let Some(parent) = parent.and_then(|parent| self.get_mut(parent)) {
+            parent.children.retain(|child| *child != id);
+        }
+        let mut value = None;
+        let mut stack = vec![id];
+        while let Some(removed) = stack.pop() {
+            let slot = &mut self.slots[removed.index];
+            let Some(node) = slot.node.take() else {
+                continue;
+            };
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(removed.index);
+            self.len -= 1;
+            stack.extend(node.children);
+            if removed == id {
+                value = Some(node.value);
+            }
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Quat, Vec3};
+
+    use crate::{matrix_stack::MatrixStack, transform::Transform};
+
+    use super::{Channel, NodeTree};
+
+    /// A base with an arm of two segments and a lamp on the base
+    fn arm() -> NodeTree<u32> {
+        let mut tree = NodeTree::new();
+        let base = tree.add_root("base", Transform::from_translation(Vec3::X), 0);
+        let upper = tree
+            .add_child(base, "upper", Transform::from_translation(Vec3::Y), 1)
+            .unwrap();
+        tree.add_child(base, "lamp", Transform::IDENTITY, 2)
+            .unwrap();
+        tree.add_child(
+            upper,
+            "lower",
+            Transform::from_translation(Vec3::Y * 2.0),
+            3,
+        )
+        .unwrap();
+        tree
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let tree = arm();
+        let lower = tree.find("lower").unwrap();
+        let upper = tree.get(lower).unwrap().parent().unwrap();
+        assert_eq!(tree.get(upper).unwrap().name, "upper");
+        assert_eq!(tree.find("missing"), None);
+
+        let order = tree.iter().map(|(_, node)| node.value).collect::<Vec<_>>();
+        assert_eq!(order, [0, 1, 3, 2]);
+
+        let matrix = tree.world_matrix(lower).unwrap();
+        assert!(matrix.abs_diff_eq(Mat4::from_translation(Vec3::new(1.0, 3.0, 0.0)), 1e-6));
+    }
+
+    #[test]
+    fn test_draw() {
+        let tree = arm();
+        let camera = Mat4::from_translation(Vec3::NEG_Z * 10.0);
+        let mut stack = MatrixStack::with_initial_matrix(camera);
+        let mut visited = vec![];
+        tree.draw(&mut stack, &mut |matrix, value| {
+            visited.push((*matrix, *value));
+        });
+
+        assert_eq!(visited.len(), tree.len());
+        for (matrix, value) in visited {
+            let id = tree.iter().find(|(_, node)| node.value == value).unwrap().0;
+            let expected = camera * tree.world_matrix(id).unwrap();
+            assert!(matrix.abs_diff_eq(expected, 1e-6));
+        }
+        assert_eq!(stack.top(), camera);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = arm();
+        let base = tree.find("base").unwrap();
+        let upper = tree.find("upper").unwrap();
+        let lower = tree.find("lower").unwrap();
+        let lamp = tree.find("lamp").unwrap();
+        assert_eq!(tree.remove(upper), Some(1));
+        assert_eq!(tree.len(), 2);
+        assert!(!tree.contains(lower));
+        assert_eq!(tree.remove(upper), None);
+
+        // the remaining ids still refer to the same nodes
+        assert_eq!(tree.get(lamp).unwrap().parent(), Some(base));
+        assert_eq!(tree.get(base).unwrap().children(), [lamp]);
+        assert_eq!(tree.roots().collect::<Vec<_>>(), [base]);
+
+        // new nodes reuse the slots without reviving the old ids
+        let new = tree.add_child(base, "new", Transform::IDENTITY, 4).unwrap();
+        assert_ne!(new, upper);
+        assert!(tree.get(upper).is_none());
+        assert_eq!(
+            tree.add_child(upper, "orphan", Transform::IDENTITY, 5),
+            None
+        );
+    }
+
+    #[test]
+    fn test_channel() {
+        let mut tree = arm();
+        let upper = tree.find("upper").unwrap();
+        let lower = tree.find("lower").unwrap();
+        tree.get_mut(upper).unwrap().channel = Some(
+            Channel::new(Vec3::Z, 0.0)
+                .with_limits(0.0, 90.0)
+                .with_speed(60.0),
+        );
+
+        tree.animate(1.0);
+        assert!((tree.channel_mut(upper).unwrap().angle - 60.0).abs() < 1e-4);
+        // the lower segment swings around the joint of the upper one
+        let expected = Mat4::from_translation(Vec3::new(1.0, 1.0, 0.0))
+            * Mat4::from_quat(Quat::from_rotation_z(60f32.to_radians()))
+            * Mat4::from_translation(Vec3::Y * 2.0);
+        assert!(tree
+            .world_matrix(lower)
+            .unwrap()
+            .abs_diff_eq(expected, 1e-5));
+
+        // stops at the limit and comes back
+        tree.animate(1.0);
+        let channel = tree.channel_mut(upper).unwrap();
+        assert!((channel.angle - 90.0).abs() < 1e-4);
+        assert!(channel.speed < 0.0);
+        tree.animate(0.5);
+        assert!((tree.channel_mut(upper).unwrap().angle - 60.0).abs() < 1e-4);
+    }
+}
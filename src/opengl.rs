@@ -1,11 +1,77 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
     ffi::{c_void, CStr},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
     ptr,
+    rc::Rc,
 };
 
-use gl::types::{GLchar, GLenum, GLfloat, GLint, GLsizei, GLuint};
+use gl::types::{GLboolean, GLchar, GLdouble, GLenum, GLfloat, GLint, GLsizei, GLuint};
 use glfw::Window;
-pub struct OpenGl;
+use thiserror::Error;
+
+use crate::{
+    app::{swap_interval, AppError, HeadlessContext},
+    buffer::{Buffer, Target},
+    gl_api::{self, Driver, GlApi, GlCall, Trace, TracedCall},
+    handle::{self, BufferId, ContextBound, ProgramId, VaoId},
+    program::{GLLocation, Program, UniformRef},
+    query::{ConditionalRenderMode, Query},
+    sync::{Fence, PendingPixels},
+    texture::{ImageTexture, Texture, TextureFormat, MAX_TEXTURE_MAX_ANISOTROPY},
+    uniforms::SetUniform,
+    vertex_attributes::VertexArrayObject,
+};
+
+/// Tracks the state set through it and skips the GL calls that wouldn't change it.
+///
+/// Buffers and vertex arrays bound by their own `bind` methods, or by the crate's helpers,
+/// make it forget its cached buffer and vertex array bindings. The cache goes stale when
+/// other state is changed behind its back, with raw `gl` calls, [`Program::set_used`], or
+/// by deleting a bound object with raw `gl` calls; call [`OpenGl::invalidate_cache`]
+/// afterwards.
+///
+/// Draws use the program, vertex array and buffers bound last. Binding a program with
+/// [`OpenGl::bind_program_scoped`] instead of [`OpenGl::bind_program`] ties setting its
+/// uniforms and drawing with it to the [`BoundProgram`], which unbinds it when dropped.
+///
+/// Dropped GL objects are deleted by [`OpenGl::flush_deletions`], and by dropping the
+/// `OpenGl` while its context is current.
+///
+/// GL errors reach the debug callback with a debug context, without one turn on
+/// [`OpenGl::set_error_checking`] and call [`OpenGl::check_error`].
+pub struct OpenGl {
+    api: Box<dyn GlApi>,
+    cache: StateCache,
+    error_checking: bool,
+    /// First error found by [`OpenGl::set_error_checking`], returned by the next
+    /// [`OpenGl::check_error`]
+    error: Option<GlError>,
+    /// Kept by [`OpenGl::set_tracing`]
+    trace: Option<Rc<RefCell<Trace>>>,
+    _context: ContextBound,
+}
+
+/// Last values set through [`OpenGl`], missing when unknown
+#[derive(Default)]
+struct StateCache {
+    program: Option<ProgramId>,
+    vertex_array: Option<VaoId>,
+    buffers: HashMap<Target, BufferId>,
+    capabilities: HashMap<Capability, bool>,
+    depth_func: Option<DepthFunc>,
+    depth_mask: Option<bool>,
+    /// `[src_rgb, dst_rgb, src_alpha, dst_alpha]`
+    blend_func: Option<[BlendFactor; 4]>,
+    /// `[rgb, alpha]`
+    blend_equation: Option<[BlendEquation; 2]>,
+    /// [`handle::flushes`] when the bindings were cached
+    flushes: u64,
+    /// [`handle::direct_binds`] when the buffer and vertex array bindings were cached
+    direct_binds: u64,
+}
 
 #[derive(Clone, Copy)]
 #[repr(u32)]
@@ -14,7 +80,15 @@ pub enum PolygonMode {
     Line = gl::LINE,
     Fill = gl::FILL,
 }
-#[derive(Clone, Copy)]
+/// How shaders access an image bound with [`OpenGl::bind_image_texture`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ImageAccess {
+    ReadOnly = gl::READ_ONLY,
+    WriteOnly = gl::WRITE_ONLY,
+    ReadWrite = gl::READ_WRITE,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum Capability {
     Blend = gl::BLEND,
@@ -38,6 +112,7 @@ pub enum Capability {
     MULTISAMPLE = gl::MULTISAMPLE,
     PolygonOffsetFill = gl::POLYGON_OFFSET_FILL,
     PolygonOffsetLine = gl::POLYGON_OFFSET_LINE,
+    PolygonOffsetPoint = gl::POLYGON_OFFSET_POINT,
     PolygonSmooth = gl::POLYGON_SMOOTH,
     PrimitiveRestart = gl::PRIMITIVE_RESTART,
     PrimitiveRestartFixedIndex = gl::PRIMITIVE_RESTART_FIXED_INDEX,
@@ -53,6 +128,36 @@ pub enum Capability {
     ProgramPointSize = gl::PROGRAM_POINT_SIZE,
 }
 
+/// Implementation limits read with [`OpenGl::get_integer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Parameter {
+    MajorVersion = gl::MAJOR_VERSION,
+    MinorVersion = gl::MINOR_VERSION,
+    NumExtensions = gl::NUM_EXTENSIONS,
+    MaxTextureSize = gl::MAX_TEXTURE_SIZE,
+    Max3DTextureSize = gl::MAX_3D_TEXTURE_SIZE,
+    MaxCubeMapTextureSize = gl::MAX_CUBE_MAP_TEXTURE_SIZE,
+    MaxArrayTextureLayers = gl::MAX_ARRAY_TEXTURE_LAYERS,
+    MaxTextureImageUnits = gl::MAX_TEXTURE_IMAGE_UNITS,
+    MaxCombinedTextureImageUnits = gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS,
+    MaxRenderbufferSize = gl::MAX_RENDERBUFFER_SIZE,
+    MaxColorAttachments = gl::MAX_COLOR_ATTACHMENTS,
+    MaxDrawBuffers = gl::MAX_DRAW_BUFFERS,
+    MaxSamples = gl::MAX_SAMPLES,
+    MaxViewports = gl::MAX_VIEWPORTS,
+    MaxVertexAttribs = gl::MAX_VERTEX_ATTRIBS,
+    MaxUniformBufferBindings = gl::MAX_UNIFORM_BUFFER_BINDINGS,
+    MaxUniformBlockSize = gl::MAX_UNIFORM_BLOCK_SIZE,
+    UniformBufferOffsetAlignment = gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT,
+    MaxShaderStorageBufferBindings = gl::MAX_SHADER_STORAGE_BUFFER_BINDINGS,
+    ShaderStorageBufferOffsetAlignment = gl::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT,
+    MaxComputeWorkGroupInvocations = gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS,
+    MaxComputeSharedMemorySize = gl::MAX_COMPUTE_SHARED_MEMORY_SIZE,
+    MaxElementsIndices = gl::MAX_ELEMENTS_INDICES,
+    MaxElementsVertices = gl::MAX_ELEMENTS_VERTICES,
+}
+
 #[derive(Clone, Copy)]
 #[repr(u32)]
 pub enum CullMode {
@@ -104,7 +209,7 @@ impl IndexSize {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum DepthFunc {
     Never = gl::NEVER,
@@ -117,6 +222,256 @@ pub enum DepthFunc {
     Always = gl::ALWAYS,
 }
 
+impl DepthFunc {
+    const fn from_gl(func: GLenum) -> Option<Self> {
+        match func {
+            gl::NEVER => Some(Self::Never),
+            gl::LESS => Some(Self::Less),
+            gl::EQUAL => Some(Self::Equal),
+            gl::LEQUAL => Some(Self::LessEqual),
+            gl::GREATER => Some(Self::Greater),
+            gl::NOTEQUAL => Some(Self::NotEqual),
+            gl::GEQUAL => Some(Self::GreaterEqual),
+            gl::ALWAYS => Some(Self::Always),
+            _ => None,
+        }
+    }
+
+    /// The function giving the same ordering when depth values are flipped, as with reverse-Z
+    #[must_use]
+    pub const fn reversed(self) -> Self {
+        match self {
+            Self::Less => Self::Greater,
+            Self::LessEqual => Self::GreaterEqual,
+            Self::Greater => Self::Less,
+            Self::GreaterEqual => Self::LessEqual,
+            func => func,
+        }
+    }
+}
+
+/// Where the window origin of clip space maps to, see [`OpenGl::clip_control`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ClipOrigin {
+    LowerLeft = gl::LOWER_LEFT,
+    UpperLeft = gl::UPPER_LEFT,
+}
+
+/// Range of the clip space depth mapped to the depth range, see [`OpenGl::clip_control`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ClipDepth {
+    NegativeOneToOne = gl::NEGATIVE_ONE_TO_ONE,
+    ZeroToOne = gl::ZERO_TO_ONE,
+}
+
+/// Every piece of depth buffer state, see [`OpenGl::depth_state`] and
+/// [`OpenGl::set_depth_state`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthState {
+    pub test: bool,
+    pub mask: bool,
+    pub func: DepthFunc,
+    pub range: (GLdouble, GLdouble),
+    pub clear_depth: GLdouble,
+}
+
+impl Default for DepthState {
+    /// The initial state of a context
+    fn default() -> Self {
+        Self {
+            test: false,
+            mask: true,
+            func: DepthFunc::Less,
+            range: (0.0, 1.0),
+            clear_depth: 1.0,
+        }
+    }
+}
+
+struct BlendSnapshot {
+    enabled: bool,
+    /// `[src_rgb, dst_rgb, src_alpha, dst_alpha]`
+    func: [GLint; 4],
+    /// `[rgb, alpha]`
+    equation: [GLint; 2],
+}
+
+struct CullSnapshot {
+    enabled: bool,
+    mode: GLint,
+    front_face: GLint,
+}
+
+#[derive(Default)]
+struct SavedState {
+    depth: Option<DepthState>,
+    blend: Option<BlendSnapshot>,
+    cull: Option<CullSnapshot>,
+    polygon_mode: Option<GLint>,
+}
+
+/// Restores the state saved by [`OpenGl::save_state`] when dropped.
+pub struct StateGuard<'a> {
+    gl: &'a mut OpenGl,
+    saved: SavedState,
+}
+
+impl Drop for StateGuard<'_> {
+    fn drop(&mut self) {
+        self.gl.restore_state(&self.saved);
+    }
+}
+
+impl Deref for StateGuard<'_> {
+    type Target = OpenGl;
+
+    fn deref(&self) -> &Self::Target {
+        self.gl
+    }
+}
+
+impl DerefMut for StateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.gl
+    }
+}
+
+/// Methods of [`BoundProgram`] calling the [`OpenGl`] method of the same name, the ones
+/// leaving the bound program alone
+macro_rules! forward {
+    ($(fn $name:ident($($arg:ident: $type:ty),* $(,)?);)*) => {
+        $(
+            #[doc = concat!("See [`OpenGl::", stringify!($name), "`]")]
+            pub fn $name(&mut self, $($arg: $type),*) {
+                self.gl.$name($($arg),*);
+            }
+        )*
+    };
+}
+
+/// The program bound by [`OpenGl::bind_program_scoped`], which unbinds it when dropped.
+///
+/// Draws through it use the program, and uniforms are set on it. It only lends out the
+/// [`OpenGl`] for reading, so the program can't be swapped for another while it's alive.
+pub struct BoundProgram<'a> {
+    gl: &'a mut OpenGl,
+    program: &'a Program,
+}
+
+impl Drop for BoundProgram<'_> {
+    fn drop(&mut self) {
+        self.gl.unbind_program();
+    }
+}
+
+impl Deref for BoundProgram<'_> {
+    type Target = OpenGl;
+
+    fn deref(&self) -> &Self::Target {
+        self.gl
+    }
+}
+
+impl<'a> BoundProgram<'a> {
+    #[must_use]
+    pub const fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    /// Sets a uniform of the bound program, see [`Program::set_uniform`]
+    #[allow(private_bounds)]
+    pub fn set_uniform<T: SetUniform>(&self, location: GLLocation, value: T) {
+        self.program.set_uniform(location, value);
+    }
+
+    /// See [`Program::bind_texture`]
+    pub fn bind_texture<'n>(
+        &self,
+        sampler: impl Into<UniformRef<'n>>,
+        texture: &impl Texture,
+    ) -> Option<GLuint> {
+        self.program.bind_texture(sampler, texture)
+    }
+
+    pub fn bind_buffer<T: Default>(&mut self, buffer: &Buffer<T>) {
+        self.gl.bind_buffer(buffer);
+    }
+
+    forward! {
+        fn unbind_buffer(target: Target);
+        fn bind_vertex_array(vao: &VertexArrayObject);
+        fn unbind_vertex_array();
+        fn enable(cap: Capability);
+        fn disable(cap: Capability);
+        fn draw_arrays(mode: Primitive, first: GLint, count: GLsizei);
+        fn draw_arrays_instanced(
+            mode: Primitive,
+            first: GLint,
+            count: GLsizei,
+            instance_count: GLsizei,
+        );
+        fn draw_elements(mode: Primitive, count: GLint, index_size: IndexSize, offset: usize);
+        fn draw_elements_instanced(
+            mode: Primitive,
+            count: GLint,
+            index_size: IndexSize,
+            offset: usize,
+            instance_count: GLsizei,
+        );
+        fn draw_elements_base_vertex(
+            mode: Primitive,
+            count: GLint,
+            index_size: IndexSize,
+            offset: usize,
+            base_vertex: GLsizei,
+        );
+        fn multi_draw_elements(
+            mode: Primitive,
+            counts: &[GLsizei],
+            index_size: IndexSize,
+            offsets: &[usize],
+        );
+        fn dispatch_compute(groups_x: GLuint, groups_y: GLuint, groups_z: GLuint);
+        fn memory_barrier(barriers: BarrierFlags);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum BlendFactor {
+    Zero = gl::ZERO,
+    One = gl::ONE,
+    SrcColor = gl::SRC_COLOR,
+    OneMinusSrcColor = gl::ONE_MINUS_SRC_COLOR,
+    DstColor = gl::DST_COLOR,
+    OneMinusDstColor = gl::ONE_MINUS_DST_COLOR,
+    SrcAlpha = gl::SRC_ALPHA,
+    OneMinusSrcAlpha = gl::ONE_MINUS_SRC_ALPHA,
+    DstAlpha = gl::DST_ALPHA,
+    OneMinusDstAlpha = gl::ONE_MINUS_DST_ALPHA,
+    ConstantColor = gl::CONSTANT_COLOR,
+    OneMinusConstantColor = gl::ONE_MINUS_CONSTANT_COLOR,
+    ConstantAlpha = gl::CONSTANT_ALPHA,
+    OneMinusConstantAlpha = gl::ONE_MINUS_CONSTANT_ALPHA,
+    SrcAlphaSaturate = gl::SRC_ALPHA_SATURATE,
+    Src1Color = gl::SRC1_COLOR,
+    OneMinusSrc1Color = gl::ONE_MINUS_SRC1_COLOR,
+    Src1Alpha = gl::SRC1_ALPHA,
+    OneMinusSrc1Alpha = gl::ONE_MINUS_SRC1_ALPHA,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum BlendEquation {
+    Add = gl::FUNC_ADD,
+    Subtract = gl::FUNC_SUBTRACT,
+    ReverseSubtract = gl::FUNC_REVERSE_SUBTRACT,
+    Min = gl::MIN,
+    Max = gl::MAX,
+}
+
 use bitflags::bitflags;
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -127,6 +482,46 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// The state saved by [`OpenGl::save_state`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct StateFlags : u32 {
+       /// Every field of [`DepthState`]
+       const Depth = 1;
+       /// Enabled, factors and equations
+       const Blend = 1 << 1;
+       /// Enabled, culled face and front face
+       const Cull = 1 << 2;
+       const PolygonMode = 1 << 3;
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct BarrierFlags : u32 {
+       const VertexAttribArray = gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT;
+       const ElementArray = gl::ELEMENT_ARRAY_BARRIER_BIT;
+       const Uniform = gl::UNIFORM_BARRIER_BIT;
+       /// Sampling textures written as images
+       const TextureFetch = gl::TEXTURE_FETCH_BARRIER_BIT;
+       /// Image loads and stores after images were written
+       const ShaderImageAccess = gl::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+       const Command = gl::COMMAND_BARRIER_BIT;
+       const PixelBuffer = gl::PIXEL_BUFFER_BARRIER_BIT;
+       /// Uploads and reads of textures written as images
+       const TextureUpdate = gl::TEXTURE_UPDATE_BARRIER_BIT;
+       const BufferUpdate = gl::BUFFER_UPDATE_BARRIER_BIT;
+       /// Rendering to or reading from framebuffer attachments written as images
+       const Framebuffer = gl::FRAMEBUFFER_BARRIER_BIT;
+       const TransformFeedback = gl::TRANSFORM_FEEDBACK_BARRIER_BIT;
+       const AtomicCounter = gl::ATOMIC_COUNTER_BARRIER_BIT;
+       const ShaderStorage = gl::SHADER_STORAGE_BARRIER_BIT;
+       const ClientMappedBuffer = gl::CLIENT_MAPPED_BUFFER_BARRIER_BIT;
+       const QueryBuffer = gl::QUERY_BUFFER_BARRIER_BIT;
+       const All = gl::ALL_BARRIER_BITS;
+    }
+}
+
 mod private {
     pub trait Sealed {}
 }
@@ -162,7 +557,120 @@ impl AsFloat for f64 {
     }
 }
 
-#[allow(clippy::unreadable_literal)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugSource {
+    Api,
+    WindowSystem,
+    ShaderCompiler,
+    ThirdParty,
+    Application,
+    Other,
+}
+
+impl DebugSource {
+    const fn from_gl(source: GLenum) -> Self {
+        match source {
+            gl::DEBUG_SOURCE_API => Self::Api,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => Self::WindowSystem,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => Self::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY => Self::ThirdParty,
+            gl::DEBUG_SOURCE_APPLICATION => Self::Application,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugType {
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+
+impl DebugType {
+    const fn from_gl(type_: GLenum) -> Self {
+        match type_ {
+            gl::DEBUG_TYPE_ERROR => Self::Error,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => Self::DeprecatedBehavior,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => Self::UndefinedBehavior,
+            gl::DEBUG_TYPE_PORTABILITY => Self::Portability,
+            gl::DEBUG_TYPE_PERFORMANCE => Self::Performance,
+            gl::DEBUG_TYPE_MARKER => Self::Marker,
+            gl::DEBUG_TYPE_PUSH_GROUP => Self::PushGroup,
+            gl::DEBUG_TYPE_POP_GROUP => Self::PopGroup,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl DebugSeverity {
+    const fn from_gl(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => Self::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Self::Medium,
+            gl::DEBUG_SEVERITY_LOW => Self::Low,
+            _ => Self::Notification,
+        }
+    }
+}
+
+/// A message from the debug output, see [`OpenGl::set_debug_callback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugMessage {
+    pub source: DebugSource,
+    pub ty: DebugType,
+    pub severity: DebugSeverity,
+    pub id: GLuint,
+    pub text: String,
+}
+
+/// The default debug callback, sends messages to the `log` crate with a level matching
+/// their severity.
+#[allow(clippy::unreadable_literal, clippy::needless_pass_by_value)]
+pub fn log_debug_message(message: DebugMessage) {
+    // noisy driver notifications, like buffer placement details
+    if matches!(message.id, 131169 | 131185 | 131218 | 131204) {
+        return;
+    }
+    let level = match message.severity {
+        DebugSeverity::High => log::Level::Error,
+        DebugSeverity::Medium => log::Level::Warn,
+        DebugSeverity::Low => log::Level::Info,
+        DebugSeverity::Notification => log::Level::Debug,
+    };
+    log::log!(
+        level,
+        "OpenGl debug message ({}) {:?} {:?}: {}",
+        message.id,
+        message.source,
+        message.ty,
+        message.text
+    );
+}
+
+type DebugCallback = Box<dyn Fn(DebugMessage)>;
+
+thread_local! {
+    // debug output is synchronous, so messages arrive on the thread owning the context
+    static DEBUG_CALLBACK: RefCell<Option<DebugCallback>> = const { RefCell::new(None) };
+    // set by `run_app_with_config` before the application creates its `OpenGl`
+    pub(crate) static SRGB_FRAMEBUFFER: Cell<bool> = const { Cell::new(false) };
+}
+
 extern "system" fn gl_debug_output(
     source: GLenum,
     type_: GLenum,
@@ -172,69 +680,473 @@ extern "system" fn gl_debug_output(
     message: *const GLchar,
     _user_param: *mut c_void,
 ) {
-    if id == 131169 || id == 131185 || id == 131218 || id == 131204 {
-        return;
+    let message = DebugMessage {
+        source: DebugSource::from_gl(source),
+        ty: DebugType::from_gl(type_),
+        severity: DebugSeverity::from_gl(severity),
+        id,
+        text: unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned(),
+    };
+    if message.ty == DebugType::Error || message.severity == DebugSeverity::High {
+        gl_api::dump_current_trace(&message.text);
     }
-    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
-
-    println!("------------");
-    println!("Debug message ({id}) : {message:?} ");
-
-    match source {
-        gl::DEBUG_SOURCE_API => println!("Source: API"),
-        gl::DEBUG_SOURCE_WINDOW_SYSTEM => println!("Source: Window System"),
-        gl::DEBUG_SOURCE_SHADER_COMPILER => println!("Source: Shader Compiler"),
-        gl::DEBUG_SOURCE_THIRD_PARTY => println!("Source: Third Party"),
-        gl::DEBUG_SOURCE_APPLICATION => println!("Source: Application"),
-        gl::DEBUG_SOURCE_OTHER => println!("Source: Other"),
-        _ => {}
-    }
-    match type_ {
-        gl::DEBUG_TYPE_ERROR => println!("Type: Error"),
-        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => println!("Type: Deprecated Behaviour"),
-        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => println!("Type: Undefined Behaviour"),
-        gl::DEBUG_TYPE_PORTABILITY => println!("Type: Portability"),
-        gl::DEBUG_TYPE_PERFORMANCE => println!("Type: Performance"),
-        gl::DEBUG_TYPE_MARKER => println!("Type: Marker"),
-        gl::DEBUG_TYPE_PUSH_GROUP => println!("Type: Push Group"),
-        gl::DEBUG_TYPE_POP_GROUP => println!("Type: Pop Group"),
-        gl::DEBUG_TYPE_OTHER => println!("Type: Other"),
-        _ => {}
-    }
-    match severity {
-        gl::DEBUG_SEVERITY_HIGH => println!("Severity: high"),
-        gl::DEBUG_SEVERITY_MEDIUM => println!("Severity: medium"),
-        gl::DEBUG_SEVERITY_LOW => println!("Severity: low"),
-        gl::DEBUG_SEVERITY_NOTIFICATION => println!("Severity: notification"),
-        _ => {}
+    // the callback is busy if it caused the message itself
+    let _ = DEBUG_CALLBACK.try_with(|callback| match callback.try_borrow().as_deref() {
+        Ok(Some(callback)) => callback(message),
+        Ok(None) => log_debug_message(message),
+        Err(_) => {}
+    });
+}
+
+/// Error flags reported by `glGetError`
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlError {
+    #[error("Invalid enum")]
+    InvalidEnum,
+    #[error("Invalid value")]
+    InvalidValue,
+    #[error("Invalid operation")]
+    InvalidOperation,
+    #[error("Invalid framebuffer operation")]
+    InvalidFramebufferOperation,
+    #[error("Out of memory")]
+    OutOfMemory,
+    #[error("Stack underflow")]
+    StackUnderflow,
+    #[error("Stack overflow")]
+    StackOverflow,
+    #[error("Unknown error: {0:#x}")]
+    Unknown(GLenum),
+}
+
+impl GlError {
+    const fn from_code(code: GLenum) -> Option<Self> {
+        match code {
+            gl::NO_ERROR => None,
+            gl::INVALID_ENUM => Some(Self::InvalidEnum),
+            gl::INVALID_VALUE => Some(Self::InvalidValue),
+            gl::INVALID_OPERATION => Some(Self::InvalidOperation),
+            gl::INVALID_FRAMEBUFFER_OPERATION => Some(Self::InvalidFramebufferOperation),
+            gl::OUT_OF_MEMORY => Some(Self::OutOfMemory),
+            gl::STACK_UNDERFLOW => Some(Self::StackUnderflow),
+            gl::STACK_OVERFLOW => Some(Self::StackOverflow),
+            _ => Some(Self::Unknown(code)),
+        }
+    }
+}
+
+impl Drop for OpenGl {
+    fn drop(&mut self) {
+        self.flush_deletions();
     }
 }
 
 impl OpenGl {
     pub fn new(window: &mut Window) -> Self {
         gl::load_with(|symbol| window.get_proc_address(symbol).cast());
-        let mut gl = Self;
+        let mut gl = Self::with_api(Driver);
         gl.setup_debug_context();
+        if SRGB_FRAMEBUFFER.get() {
+            // the window asked for an sRGB default framebuffer, see `AppConfig::srgb`
+            unsafe { gl::Enable(Capability::FramebufferSrgb as GLenum) };
+            gl.cache
+                .capabilities
+                .insert(Capability::FramebufferSrgb, true);
+        }
         gl
     }
 
+    /// Makes the calls of [`GlApi`] through `api` instead of the driver. See
+    /// [`crate::gl_api::Recorder`] to test without a context, the methods calling the driver
+    /// directly then panic.
+    pub fn with_api(api: impl GlApi + 'static) -> Self {
+        Self {
+            api: Box::new(api),
+            cache: StateCache::default(),
+            error_checking: false,
+            error: None,
+            trace: None,
+            _context: PhantomData,
+        }
+    }
+
+    /// Loads GL through an invisible window, which must outlive every GL call.
+    pub fn new_headless() -> Result<(Self, HeadlessContext), AppError> {
+        let mut context = HeadlessContext::new()?;
+        let gl = Self::new(context.window_mut());
+        Ok((gl, context))
+    }
+
+    /// Waits for the vertical blank before swapping the buffers of `window`.
+    pub fn set_vsync(&mut self, window: &mut Window, on: bool) {
+        window.glfw.set_swap_interval(swap_interval(on));
+    }
+
+    /// Replaces the handler of debug messages, [`log_debug_message`] by default.
+    /// Messages only arrive with a debug context.
+    pub fn set_debug_callback(&mut self, callback: impl Fn(DebugMessage) + 'static) {
+        DEBUG_CALLBACK.set(Some(Box::new(callback)));
+    }
+
+    /// Returns the oldest error flag and clears it, call until `Ok` to clear every flag.
+    /// Useful on drivers without `KHR_debug`, where the debug callback stays silent.
+    ///
+    /// With [`OpenGl::set_error_checking`] the first error found by a wrapper since the last
+    /// check is returned first.
+    pub fn check_error(&mut self) -> Result<(), GlError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let error = GlError::from_code(self.api.get_error());
+        if let Some(error) = error {
+            self.dump_trace(&error.to_string());
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Polls `glGetError` after every wrapper call and logs the errors, for drivers without
+    /// `KHR_debug`. The first one is kept for [`OpenGl::check_error`]. Off by default, each
+    /// poll can stall the driver.
+    pub fn set_error_checking(&mut self, on: bool) {
+        self.error_checking = on;
+    }
+    #[must_use]
+    pub const fn error_checking(&self) -> bool {
+        self.error_checking
+    }
+
+    /// Makes a call to the GL `function` outside the [`GlApi`], polling its error if checking
+    #[track_caller]
+    fn checked<T>(&mut self, function: &'static str, call: impl FnOnce() -> T) -> T {
+        assert!(
+            self.api.calls_driver(),
+            "{function} isn't part of GlApi and needs the GL driver loaded"
+        );
+        self.record(TracedCall::Driver {
+            function,
+            args: None,
+        });
+        let value = call();
+        if self.error_checking {
+            self.poll_error();
+        }
+        value
+    }
+    fn poll_error(&mut self) {
+        if let Some(error) = GlError::from_code(self.api.get_error()) {
+            log::error!("OpenGl error: {error}");
+            self.dump_trace(&error.to_string());
+            self.error.get_or_insert(error);
+        }
+    }
+
+    /// Keeps the last `capacity` calls, logged as errors when [`OpenGl::check_error`] finds
+    /// an error or a debug message reports one. Besides its own calls, the buffers,
+    /// textures, programs and vertex arrays used while its context is current record theirs.
+    /// 0 stops tracing.
+    pub fn set_tracing(&mut self, capacity: usize) {
+        self.trace = (capacity > 0).then(|| {
+            let trace = Rc::new(RefCell::new(Trace::new(capacity)));
+            gl_api::register_trace(&trace);
+            trace
+        });
+    }
+    /// The calls kept by [`OpenGl::set_tracing`], oldest first
+    #[must_use]
+    pub fn trace(&self) -> Vec<TracedCall> {
+        self.trace
+            .as_ref()
+            .map(|trace| trace.borrow().calls())
+            .unwrap_or_default()
+    }
+    fn record(&self, call: TracedCall) {
+        if let Some(trace) = &self.trace {
+            trace.borrow_mut().push(call);
+        }
+    }
+    fn dump_trace(&self, reason: &str) {
+        if let Some(trace) = &self.trace {
+            trace.borrow().dump(reason);
+        }
+    }
+
+    fn call(&mut self, call: GlCall) {
+        self.record(TracedCall::Api(call));
+        call.apply(&*self.api);
+        if self.error_checking {
+            self.poll_error();
+        }
+    }
+
+    fn get_integers<const N: usize>(&mut self, parameter: GLenum) -> [GLint; N] {
+        let mut values = [0; N];
+        self.checked("glGetIntegerv", || unsafe {
+            gl::GetIntegerv(parameter, values.as_mut_ptr());
+        });
+        values
+    }
+
+    /// Saves the state selected by `flags`, restored when the returned guard is dropped.
+    pub fn save_state(&mut self, flags: StateFlags) -> StateGuard<'_> {
+        let mut saved = SavedState::default();
+        if flags.contains(StateFlags::Depth) {
+            saved.depth = Some(self.depth_state());
+        }
+        if flags.contains(StateFlags::Blend) {
+            let [src_rgb] = self.get_integers(gl::BLEND_SRC_RGB);
+            let [dst_rgb] = self.get_integers(gl::BLEND_DST_RGB);
+            let [src_alpha] = self.get_integers(gl::BLEND_SRC_ALPHA);
+            let [dst_alpha] = self.get_integers(gl::BLEND_DST_ALPHA);
+            let [rgb] = self.get_integers(gl::BLEND_EQUATION_RGB);
+            let [alpha] = self.get_integers(gl::BLEND_EQUATION_ALPHA);
+            saved.blend = Some(BlendSnapshot {
+                enabled: self.is_enabled(Capability::Blend),
+                func: [src_rgb, dst_rgb, src_alpha, dst_alpha],
+                equation: [rgb, alpha],
+            });
+        }
+        if flags.contains(StateFlags::Cull) {
+            let [mode] = self.get_integers(gl::CULL_FACE_MODE);
+            let [front_face] = self.get_integers(gl::FRONT_FACE);
+            saved.cull = Some(CullSnapshot {
+                enabled: self.is_enabled(Capability::CullFace),
+                mode,
+                front_face,
+            });
+        }
+        if flags.contains(StateFlags::PolygonMode) {
+            // older contexts return the front and back modes
+            let [mode, _] = self.get_integers::<2>(gl::POLYGON_MODE);
+            saved.polygon_mode = Some(mode);
+        }
+        StateGuard { gl: self, saved }
+    }
+
+    fn restore_state(&mut self, saved: &SavedState) {
+        if let Some(depth) = &saved.depth {
+            self.set_depth_state(depth);
+        }
+        if let Some(blend) = &saved.blend {
+            let [src_rgb, dst_rgb, src_alpha, dst_alpha] = blend.func.map(|f| f as GLenum);
+            let [rgb, alpha] = blend.equation.map(|e| e as GLenum);
+            self.checked("glBlendFuncSeparate", || unsafe {
+                gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            });
+            self.checked("glBlendEquationSeparate", || unsafe {
+                gl::BlendEquationSeparate(rgb, alpha);
+            });
+            self.cache.blend_func = None;
+            self.cache.blend_equation = None;
+            self.set_capability(Capability::Blend, blend.enabled);
+        }
+        if let Some(cull) = &saved.cull {
+            self.checked("glCullFace", || unsafe {
+                gl::CullFace(cull.mode as GLenum);
+            });
+            self.checked("glFrontFace", || unsafe {
+                gl::FrontFace(cull.front_face as GLenum);
+            });
+            self.set_capability(Capability::CullFace, cull.enabled);
+        }
+        if let Some(mode) = saved.polygon_mode {
+            self.checked("glPolygonMode", || unsafe {
+                gl::PolygonMode(gl::FRONT_AND_BACK, mode as GLenum);
+            });
+        }
+    }
+
+    fn set_capability(&mut self, cap: Capability, enabled: bool) {
+        if enabled {
+            self.enable(cap);
+        } else {
+            self.disable(cap);
+        }
+    }
+
+    /// Forgets every cached value, so the next calls reach GL.
+    pub fn invalidate_cache(&mut self) {
+        self.cache = StateCache::default();
+    }
+
+    /// Deletes the GL objects dropped in the current context since the last flush and returns
+    /// how many. [`crate::app::run_app`] flushes after every frame.
+    ///
+    /// Objects dropped in another context wait for it to be current. Objects dropped without
+    /// a current context, or whose window was destroyed first, are forgotten, they went with
+    /// their context.
+    pub fn flush_deletions(&mut self) -> usize {
+        let deleted = handle::flush_deletions();
+        self.forget_stale_bindings();
+        deleted
+    }
+
+    /// Forgets the cached bindings if objects were deleted since, their names can be reused,
+    /// and the buffer and vertex array ones if they were bound without it
+    fn forget_stale_bindings(&mut self) {
+        let flushes = handle::flushes();
+        if self.cache.flushes != flushes {
+            self.cache.program = None;
+            self.cache.vertex_array = None;
+            self.cache.buffers.clear();
+            self.cache.flushes = flushes;
+        }
+        // bound by the objects' own methods
+        let direct_binds = handle::direct_binds();
+        if self.cache.direct_binds != direct_binds {
+            self.cache.vertex_array = None;
+            self.cache.buffers.clear();
+            self.cache.direct_binds = direct_binds;
+        }
+    }
+
+    pub fn bind_program(&mut self, program: &Program) {
+        self.use_program(program.id());
+    }
+    pub fn unbind_program(&mut self) {
+        self.use_program(ProgramId::NULL);
+    }
+    /// Binds `program` until the returned [`BoundProgram`] is dropped
+    pub fn bind_program_scoped<'a>(&'a mut self, program: &'a Program) -> BoundProgram<'a> {
+        self.bind_program(program);
+        BoundProgram { gl: self, program }
+    }
+    fn use_program(&mut self, id: ProgramId) {
+        self.forget_stale_bindings();
+        if self.cache.program == Some(id) {
+            return;
+        }
+        self.call(GlCall::UseProgram(id));
+        self.cache.program = Some(id);
+    }
+
+    pub fn bind_vertex_array(&mut self, vao: &VertexArrayObject) {
+        self.use_vertex_array(vao.id());
+    }
+    pub fn unbind_vertex_array(&mut self) {
+        self.use_vertex_array(VaoId::NULL);
+    }
+    fn use_vertex_array(&mut self, id: VaoId) {
+        self.forget_stale_bindings();
+        if self.cache.vertex_array == Some(id) {
+            return;
+        }
+        self.call(GlCall::BindVertexArray(id));
+        self.cache.vertex_array = Some(id);
+        // the index buffer binding is part of the VAO
+        self.cache.buffers.remove(&Target::IndexBuffer);
+    }
+
+    pub fn bind_buffer<T: Default>(&mut self, buffer: &Buffer<T>) {
+        self.use_buffer(buffer.target(), buffer.id());
+    }
+    pub fn unbind_buffer(&mut self, target: Target) {
+        self.use_buffer(target, BufferId::NULL);
+    }
+    fn use_buffer(&mut self, target: Target, id: BufferId) {
+        self.forget_stale_bindings();
+        if self.cache.buffers.get(&target) == Some(&id) {
+            return;
+        }
+        self.call(GlCall::BindBuffer(target, id));
+        self.cache.buffers.insert(target, id);
+    }
+
     pub fn enable(&mut self, cap: Capability) {
-        unsafe { gl::Enable(cap as GLenum) };
+        if self.cache.capabilities.get(&cap) == Some(&true) {
+            return;
+        }
+        self.call(GlCall::SetCapability(cap, true));
+        self.cache.capabilities.insert(cap, true);
     }
     pub fn disable(&mut self, cap: Capability) {
-        unsafe { gl::Disable(cap as GLenum) };
+        if self.cache.capabilities.get(&cap) == Some(&false) {
+            return;
+        }
+        self.call(GlCall::SetCapability(cap, false));
+        self.cache.capabilities.insert(cap, false);
+    }
+    /// Enables an indexed capability like [`Capability::ScissorTest`] for a single viewport.
+    pub fn enable_indexed(&mut self, cap: Capability, index: GLuint) {
+        self.cache.capabilities.remove(&cap);
+        self.checked("glEnablei", || unsafe { gl::Enablei(cap as GLenum, index) });
+    }
+    pub fn disable_indexed(&mut self, cap: Capability, index: GLuint) {
+        self.cache.capabilities.remove(&cap);
+        self.checked("glDisablei", || unsafe {
+            gl::Disablei(cap as GLenum, index);
+        });
+    }
+    /// Whether `cap` is enabled, from the cache when it's known
+    pub(crate) fn capability(&mut self, cap: Capability) -> bool {
+        if let Some(&enabled) = self.cache.capabilities.get(&cap) {
+            return enabled;
+        }
+        let enabled = self.is_enabled(cap);
+        self.cache.capabilities.insert(cap, enabled);
+        enabled
     }
+
     pub fn is_enabled(&mut self, cap: Capability) -> bool {
-        (unsafe { gl::IsEnabled(cap as GLenum) } != gl::FALSE)
+        self.checked(
+            "glIsEnabled",
+            || unsafe { gl::IsEnabled(cap as GLenum) } != gl::FALSE,
+        )
+    }
+
+    pub fn get_integer(&mut self, parameter: Parameter) -> GLint {
+        let mut value = 0;
+        self.checked("glGetIntegerv", || unsafe {
+            gl::GetIntegerv(parameter as GLenum, &mut value);
+        });
+        value
+    }
+    /// Highest [`crate::texture::TextureParams::anisotropy`], 1 when not supported
+    pub fn max_anisotropy(&mut self) -> GLfloat {
+        let mut value = 1.0;
+        self.checked("glGetFloatv", || unsafe {
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut value);
+        });
+        value
+    }
+    /// The `(major, minor)` version of the current context
+    pub fn version(&mut self) -> (GLint, GLint) {
+        let major = self.get_integer(Parameter::MajorVersion);
+        let minor = self.get_integer(Parameter::MinorVersion);
+        (major, minor)
+    }
+    pub fn extensions(&mut self) -> Vec<String> {
+        let count = self.get_integer(Parameter::NumExtensions);
+        self.checked("glGetStringi", || {
+            (0..count.max(0) as GLuint)
+                .filter_map(|i| {
+                    let name = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+                    (!name.is_null()).then(|| {
+                        unsafe { CStr::from_ptr(name.cast()) }
+                            .to_string_lossy()
+                            .into_owned()
+                    })
+                })
+                .collect()
+        })
+    }
+    pub fn has_extension(&mut self, name: &str) -> bool {
+        let extensions = self.extensions();
+        extensions.iter().any(|extension| extension == name)
     }
 
     pub fn setup_debug_context(&mut self) {
         let mut flags = 0;
-        unsafe { gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags) };
-        if (flags as GLenum & gl::CONTEXT_FLAG_DEBUG_BIT) != 0 {
+        self.checked("glGetIntegerv", || unsafe {
+            gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+        });
+        // debug output is only core since 4.3
+        let supported = gl::DebugMessageCallback::is_loaded();
+        if supported && (flags as GLenum & gl::CONTEXT_FLAG_DEBUG_BIT) != 0 {
             // initialize debug output
-            self.enable(Capability::DebugOutput);
-            self.enable(Capability::DebugOutputSync);
+            unsafe { gl::Enable(Capability::DebugOutput as GLenum) };
+            unsafe { gl::Enable(Capability::DebugOutputSync as GLenum) };
             unsafe { gl::DebugMessageCallback(Some(gl_debug_output), ptr::null()) }
             unsafe {
                 gl::DebugMessageControl(
@@ -250,13 +1162,65 @@ impl OpenGl {
     }
 
     pub fn clear_color(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
-        unsafe { gl::ClearColor(red, green, blue, alpha) };
+        self.call(GlCall::ClearColor([red, green, blue, alpha]));
     }
     pub fn clear(&mut self, mask: ClearFlags) {
-        unsafe { gl::Clear(mask.bits()) };
+        self.call(GlCall::Clear(mask));
+    }
+    /// Clears a single color attachment of the bound draw framebuffer, ignoring the clear color.
+    pub fn clear_color_buffer(&mut self, draw_buffer: GLint, color: [GLfloat; 4]) {
+        self.checked("glClearBufferfv", || unsafe {
+            gl::ClearBufferfv(gl::COLOR, draw_buffer, color.as_ptr());
+        });
+    }
+    pub fn clear_depth_buffer(&mut self, value: GLfloat) {
+        self.checked("glClearBufferfv", || unsafe {
+            gl::ClearBufferfv(gl::DEPTH, 0, &value);
+        });
+    }
+    pub fn clear_stencil_buffer(&mut self, value: GLint) {
+        self.checked("glClearBufferiv", || unsafe {
+            gl::ClearBufferiv(gl::STENCIL, 0, &value);
+        });
     }
     pub fn draw_arrays(&mut self, mode: Primitive, first: GLint, count: GLsizei) {
-        unsafe { gl::DrawArrays(mode as GLenum, first, count) };
+        self.call(GlCall::DrawArrays(mode, first, count));
+    }
+    pub fn draw_arrays_instanced(
+        &mut self,
+        mode: Primitive,
+        first: GLint,
+        count: GLsizei,
+        instance_count: GLsizei,
+    ) {
+        self.checked("glDrawArraysInstanced", || unsafe {
+            gl::DrawArraysInstanced(mode as GLenum, first, count, instance_count);
+        });
+    }
+    /// `base_instance` offsets the instance index used to fetch instanced attributes.
+    pub fn draw_arrays_instanced_base_instance(
+        &mut self,
+        mode: Primitive,
+        first: GLint,
+        count: GLsizei,
+        instance_count: GLsizei,
+        base_instance: GLuint,
+    ) {
+        self.checked("glDrawArraysInstancedBaseInstance", || unsafe {
+            gl::DrawArraysInstancedBaseInstance(
+                mode as GLenum,
+                first,
+                count,
+                instance_count,
+                base_instance,
+            );
+        });
+    }
+    /// Index that ends the current strip or fan when [`Capability::PrimitiveRestart`] is enabled.
+    pub fn primitive_restart_index(&mut self, index: GLuint) {
+        self.checked("glPrimitiveRestartIndex", || unsafe {
+            gl::PrimitiveRestartIndex(index);
+        });
     }
     pub fn draw_elements(
         &mut self,
@@ -265,14 +1229,72 @@ impl OpenGl {
         index_size: IndexSize,
         offset: usize,
     ) {
-        unsafe {
-            gl::DrawElements(
+        self.call(GlCall::DrawElements(mode, count, index_size, offset));
+    }
+
+    /// Like [`OpenGl::draw_elements`], `start` and `end` are the lowest and highest index
+    /// values in the drawn range.
+    pub fn draw_elements_range(
+        &mut self,
+        mode: Primitive,
+        start: GLuint,
+        end: GLuint,
+        count: GLint,
+        index_size: IndexSize,
+        offset: usize,
+    ) {
+        self.checked("glDrawRangeElements", || unsafe {
+            gl::DrawRangeElements(
                 mode as GLenum,
+                start,
+                end,
                 count,
                 index_size as GLenum,
                 offset as *const _,
             );
-        };
+        });
+    }
+
+    pub fn draw_elements_instanced(
+        &mut self,
+        mode: Primitive,
+        count: GLint,
+        index_size: IndexSize,
+        offset: usize,
+        instance_count: GLsizei,
+    ) {
+        self.checked("glDrawElementsInstanced", || unsafe {
+            gl::DrawElementsInstanced(
+                mode as GLenum,
+                count,
+                index_size as GLenum,
+                offset as *const _,
+                instance_count,
+            );
+        });
+    }
+
+    /// Issues one draw per pair of `counts` and `offsets` with a single call.
+    pub fn multi_draw_elements(
+        &mut self,
+        mode: Primitive,
+        counts: &[GLsizei],
+        index_size: IndexSize,
+        offsets: &[usize],
+    ) {
+        let offsets = offsets
+            .iter()
+            .map(|offset| *offset as *const c_void)
+            .collect::<Vec<_>>();
+        self.checked("glMultiDrawElements", || unsafe {
+            gl::MultiDrawElements(
+                mode as GLenum,
+                counts.as_ptr(),
+                index_size as GLenum,
+                offsets.as_ptr(),
+                counts.len().min(offsets.len()) as GLsizei,
+            );
+        });
     }
 
     pub fn draw_elements_base_vertex(
@@ -283,7 +1305,7 @@ impl OpenGl {
         offset: usize,
         base_vertex: GLsizei,
     ) {
-        unsafe {
+        self.checked("glDrawElementsBaseVertex", || unsafe {
             gl::DrawElementsBaseVertex(
                 mode as GLenum,
                 count,
@@ -291,41 +1313,368 @@ impl OpenGl {
                 offset as *const _,
                 base_vertex,
             );
-        };
+        });
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_elements_instanced_base_vertex_base_instance(
+        &mut self,
+        mode: Primitive,
+        count: GLint,
+        index_size: IndexSize,
+        offset: usize,
+        instance_count: GLsizei,
+        base_vertex: GLint,
+        base_instance: GLuint,
+    ) {
+        self.checked("glDrawElementsInstancedBaseVertexBaseInstance", || unsafe {
+            gl::DrawElementsInstancedBaseVertexBaseInstance(
+                mode as GLenum,
+                count,
+                index_size as GLenum,
+                offset as *const _,
+                instance_count,
+                base_vertex,
+                base_instance,
+            );
+        });
+    }
+
+    /// Needs a 4.3 context, see [`crate::app::ContextInfo::has_compute`].
+    pub fn dispatch_compute(&mut self, groups_x: GLuint, groups_y: GLuint, groups_z: GLuint) {
+        self.checked("glDispatchCompute", || unsafe {
+            gl::DispatchCompute(groups_x, groups_y, groups_z);
+        });
+    }
+    pub fn memory_barrier(&mut self, barriers: BarrierFlags) {
+        self.checked("glMemoryBarrier", || unsafe {
+            gl::MemoryBarrier(barriers.bits());
+        });
+    }
+    /// Only orders the accesses of fragment shaders to the same pixels, cheaper than
+    /// [`OpenGl::memory_barrier`] between passes writing images per pixel.
+    pub fn memory_barrier_by_region(&mut self, barriers: BarrierFlags) {
+        self.checked("glMemoryBarrierByRegion", || unsafe {
+            gl::MemoryBarrierByRegion(barriers.bits());
+        });
+    }
+    /// Binds mip `level` of `texture` to image `unit` for `image2D` like uniforms,
+    /// every layer of arrays, volumes and cubemaps. Shaders read and write its texels as `format`,
+    /// which can't be sRGB or depth. Writes need a [`OpenGl::memory_barrier`] before
+    /// being read by later commands.
+    #[allow(private_bounds)]
+    pub fn bind_image_texture(
+        &mut self,
+        unit: GLuint,
+        texture: &impl ImageTexture,
+        level: GLint,
+        access: ImageAccess,
+        format: TextureFormat,
+    ) {
+        debug_assert!(!matches!(
+            format,
+            TextureFormat::Srgb8Alpha8 | TextureFormat::Depth32F
+        ));
+        let image = texture.image();
+        self.checked("glBindImageTexture", || unsafe {
+            gl::BindImageTexture(
+                unit,
+                image.id,
+                level,
+                GLboolean::from(image.is_layered()),
+                0,
+                access as GLenum,
+                format.internal_format(),
+            );
+        });
+    }
+    /// Captures the outputs of the draws until [`OpenGl::end_transform_feedback`], see
+    /// [`Program::with_transform_feedback`]. `mode` is `Points`, `Lines` or `Triangles`.
+    pub fn begin_transform_feedback(&mut self, mode: Primitive) {
+        self.checked("glBeginTransformFeedback", || unsafe {
+            gl::BeginTransformFeedback(mode as GLenum);
+        });
+    }
+    pub fn end_transform_feedback(&mut self) {
+        self.checked("glEndTransformFeedback", || unsafe {
+            gl::EndTransformFeedback();
+        });
+    }
+    /// Skips the draw calls until [`OpenGl::end_conditional_render`] if `query`,
+    /// an occlusion query, found no samples passing.
+    pub fn begin_conditional_render(&mut self, query: &Query, mode: ConditionalRenderMode) {
+        self.checked("glBeginConditionalRender", || unsafe {
+            gl::BeginConditionalRender(query.id(), mode as GLenum);
+        });
+    }
+    pub fn end_conditional_render(&mut self) {
+        self.checked("glEndConditionalRender", || unsafe {
+            gl::EndConditionalRender();
+        });
+    }
+    /// Inserts a fence after every command issued so far.
+    pub fn fence_sync(&mut self) -> Fence {
+        self.checked("glFenceSync", Fence::new)
+    }
+    /// Starts reading the `width` by `height` pixels from `x`, `y` of the read framebuffer
+    /// as `format` into a pixel buffer, without waiting for the GPU to draw them.
+    pub fn read_pixels_async(
+        &mut self,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: TextureFormat,
+    ) -> PendingPixels {
+        self.checked("glReadPixels", || {
+            PendingPixels::new(x, y, width, height, format)
+        })
     }
 
     pub fn depth_func(&mut self, mode: DepthFunc) {
-        unsafe { gl::DepthFunc(mode as GLenum) };
+        if self.cache.depth_func == Some(mode) {
+            return;
+        }
+        self.call(GlCall::DepthFunc(mode));
+        self.cache.depth_func = Some(mode);
     }
     pub fn set_depth_mask(&mut self, value: bool) {
-        if value {
-            unsafe { gl::DepthMask(gl::TRUE) };
-        } else {
-            unsafe { gl::DepthMask(gl::FALSE) };
+        if self.cache.depth_mask == Some(value) {
+            return;
         }
+        self.call(GlCall::DepthMask(value));
+        self.cache.depth_mask = Some(value);
     }
     pub fn depth_range<T: AsFloat>(&mut self, near: T, far: T) {
-        T::set_depth_range(near, far);
+        self.checked("glDepthRange", || T::set_depth_range(near, far));
     }
 
     pub fn clear_depth<T: AsFloat>(&mut self, value: T) {
-        value.clear_depth();
+        self.checked("glClearDepth", || value.clear_depth());
     }
 
-    pub fn viewport(&mut self, x: GLsizei, y: GLsizei, width: GLsizei, height: GLsizei) {
-        unsafe {
-            gl::Viewport(x, y, width, height);
+    pub fn depth_mask(&mut self) -> bool {
+        let mut value = gl::FALSE;
+        self.checked("glGetBooleanv", || unsafe {
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut value);
+        });
+        value != gl::FALSE
+    }
+    pub fn get_depth_func(&mut self) -> DepthFunc {
+        let mut value = 0;
+        self.checked("glGetIntegerv", || unsafe {
+            gl::GetIntegerv(gl::DEPTH_FUNC, &mut value);
+        });
+        // the context only ever holds valid functions
+        DepthFunc::from_gl(value as GLenum).unwrap_or(DepthFunc::Less)
+    }
+    pub fn get_depth_range(&mut self) -> (GLdouble, GLdouble) {
+        let mut value = [0.0; 2];
+        self.checked("glGetDoublev", || unsafe {
+            gl::GetDoublev(gl::DEPTH_RANGE, value.as_mut_ptr());
+        });
+        value.into()
+    }
+    pub fn get_clear_depth(&mut self) -> GLdouble {
+        let mut value = 0.0;
+        self.checked("glGetDoublev", || unsafe {
+            gl::GetDoublev(gl::DEPTH_CLEAR_VALUE, &mut value);
+        });
+        value
+    }
+
+    /// Sets where the clip space origin is and which clip space depths map to the depth
+    /// range. Needs GL 4.5 or `ARB_clip_control`, see
+    /// [`crate::app::ContextInfo::has_clip_control`].
+    pub fn clip_control(&mut self, origin: ClipOrigin, depth: ClipDepth) {
+        self.checked("glClipControl", || unsafe {
+            gl::ClipControl(origin as GLenum, depth as GLenum);
+        });
+    }
+    /// Switches between reverse-Z and the default depth setup: zero to one clip depth,
+    /// clearing to 0 and [`DepthFunc::GreaterEqual`], or the GL defaults with
+    /// [`DepthFunc::LessEqual`]. Projections must match, see [`crate::camera::perspective`].
+    pub fn set_reverse_z(&mut self, enabled: bool) {
+        let (depth, clear, func) = if enabled {
+            (ClipDepth::ZeroToOne, 0.0, DepthFunc::GreaterEqual)
+        } else {
+            (ClipDepth::NegativeOneToOne, 1.0, DepthFunc::LessEqual)
+        };
+        self.clip_control(ClipOrigin::LowerLeft, depth);
+        self.clear_depth(clear);
+        self.depth_func(func);
+    }
+
+    /// Captures the depth state, to be restored later with [`OpenGl::set_depth_state`].
+    pub fn depth_state(&mut self) -> DepthState {
+        DepthState {
+            test: self.is_enabled(Capability::DepthTest),
+            mask: self.depth_mask(),
+            func: self.get_depth_func(),
+            range: self.get_depth_range(),
+            clear_depth: self.get_clear_depth(),
         }
     }
+    pub fn set_depth_state(&mut self, state: &DepthState) {
+        self.set_capability(Capability::DepthTest, state.test);
+        self.set_depth_mask(state.mask);
+        self.depth_func(state.func);
+        self.depth_range(state.range.0, state.range.1);
+        self.clear_depth(state.clear_depth);
+    }
+
+    pub fn viewport(&mut self, x: GLsizei, y: GLsizei, width: GLsizei, height: GLsizei) {
+        self.call(GlCall::Viewport(x, y, width, height));
+    }
+    /// Sets one of the viewports a geometry shader can pick with `gl_ViewportIndex`.
+    pub fn viewport_indexed(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        width: GLfloat,
+        height: GLfloat,
+    ) {
+        self.checked("glViewportIndexedf", || unsafe {
+            gl::ViewportIndexedf(index, x, y, width, height);
+        });
+    }
+    /// Only the pixels inside the box are drawn with [`Capability::ScissorTest`] enabled.
+    pub fn scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.checked("glScissor", || unsafe { gl::Scissor(x, y, width, height) });
+    }
+    /// Scissor box of the viewport at `index`.
+    pub fn scissor_indexed(
+        &mut self,
+        index: GLuint,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        self.checked("glScissorIndexed", || unsafe {
+            gl::ScissorIndexed(index, x, y, width, height);
+        });
+    }
     pub fn polygon_mode(&mut self, mode: PolygonMode) {
-        unsafe { gl::PolygonMode(gl::FRONT_AND_BACK, mode as GLenum) };
+        self.checked("glPolygonMode", || unsafe {
+            gl::PolygonMode(gl::FRONT_AND_BACK, mode as GLenum);
+        });
     }
 
     pub fn cull_face(&mut self, mode: CullMode) {
-        unsafe { gl::CullFace(mode as GLenum) };
+        self.checked("glCullFace", || unsafe { gl::CullFace(mode as GLenum) });
     }
 
     pub fn front_face(&mut self, front_face: FrontFace) {
-        unsafe { gl::FrontFace(front_face as GLenum) };
+        self.checked("glFrontFace", || unsafe {
+            gl::FrontFace(front_face as GLenum);
+        });
+    }
+
+    /// Only applies to the polygon modes whose [`Capability::PolygonOffsetFill`],
+    /// [`Capability::PolygonOffsetLine`] or [`Capability::PolygonOffsetPoint`] is enabled.
+    pub fn polygon_offset(&mut self, factor: GLfloat, units: GLfloat) {
+        self.checked("glPolygonOffset", || unsafe {
+            gl::PolygonOffset(factor, units);
+        });
+    }
+
+    pub fn line_width(&mut self, width: GLfloat) {
+        self.checked("glLineWidth", || unsafe { gl::LineWidth(width) });
+    }
+
+    /// Ignored while [`Capability::ProgramPointSize`] is enabled, then the shader writes `gl_PointSize`.
+    pub fn point_size(&mut self, size: GLfloat) {
+        self.checked("glPointSize", || unsafe { gl::PointSize(size) });
+    }
+
+    /// Blending only happens with [`Capability::Blend`] enabled.
+    pub fn blend_func(&mut self, src: BlendFactor, dst: BlendFactor) {
+        let func = [src, dst, src, dst];
+        if self.cache.blend_func == Some(func) {
+            return;
+        }
+        self.call(GlCall::BlendFunc(src, dst));
+        self.cache.blend_func = Some(func);
+    }
+    pub fn blend_func_separate(
+        &mut self,
+        src_rgb: BlendFactor,
+        dst_rgb: BlendFactor,
+        src_alpha: BlendFactor,
+        dst_alpha: BlendFactor,
+    ) {
+        let func = [src_rgb, dst_rgb, src_alpha, dst_alpha];
+        if self.cache.blend_func == Some(func) {
+            return;
+        }
+        self.call(GlCall::BlendFuncSeparate(func));
+        self.cache.blend_func = Some(func);
+    }
+    /// Blend factors of a single draw buffer.
+    pub fn blend_func_i(&mut self, draw_buffer: GLuint, src: BlendFactor, dst: BlendFactor) {
+        // the draw buffers no longer share the same factors
+        self.cache.blend_func = None;
+        self.checked("glBlendFunci", || unsafe {
+            gl::BlendFunci(draw_buffer, src as GLenum, dst as GLenum);
+        });
+    }
+    pub fn blend_equation(&mut self, equation: BlendEquation) {
+        if self.cache.blend_equation == Some([equation; 2]) {
+            return;
+        }
+        self.call(GlCall::BlendEquation(equation));
+        self.cache.blend_equation = Some([equation; 2]);
+    }
+    pub fn blend_equation_separate(&mut self, rgb: BlendEquation, alpha: BlendEquation) {
+        if self.cache.blend_equation == Some([rgb, alpha]) {
+            return;
+        }
+        self.call(GlCall::BlendEquationSeparate(rgb, alpha));
+        self.cache.blend_equation = Some([rgb, alpha]);
+    }
+    /// Color used by the constant blend factors.
+    pub fn blend_color(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+        self.checked("glBlendColor", || unsafe {
+            gl::BlendColor(red, green, blue, alpha);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DebugSeverity, DebugSource, DebugType, DepthFunc, GlError};
+
+    #[test]
+    fn test_debug_enums() {
+        assert_eq!(
+            DebugSeverity::from_gl(gl::DEBUG_SEVERITY_HIGH),
+            DebugSeverity::High
+        );
+        assert!(DebugSeverity::High > DebugSeverity::Notification);
+        assert_eq!(
+            DebugSource::from_gl(gl::DEBUG_SOURCE_SHADER_COMPILER),
+            DebugSource::ShaderCompiler
+        );
+        assert_eq!(DebugType::from_gl(0), DebugType::Other);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(GlError::from_code(gl::NO_ERROR), None);
+        assert_eq!(
+            GlError::from_code(gl::INVALID_OPERATION),
+            Some(GlError::InvalidOperation)
+        );
+        assert_eq!(GlError::from_code(0x1234), Some(GlError::Unknown(0x1234)));
+    }
+
+    #[test]
+    fn test_depth_func() {
+        for func in [DepthFunc::Never, DepthFunc::LessEqual, DepthFunc::Always] {
+            assert_eq!(DepthFunc::from_gl(func as u32), Some(func));
+        }
+        assert_eq!(DepthFunc::from_gl(gl::BLEND), None);
     }
 }
@@ -0,0 +1,346 @@
+use std::{
+    ffi::{CString, NulError},
+    mem::offset_of,
+    rc::Rc,
+};
+
+use gl::types::{GLint, GLsizei, GLuint};
+use glam::{Vec3, Vec4};
+use thiserror::Error;
+
+use crate::{
+    buffer::{bind_directly, Buffer, Target, Usage},
+    camera::Camera,
+    material::Material,
+    opengl::{BarrierFlags, BlendFactor, Capability, OpenGl, Primitive, StateFlags},
+    program::{Program, Shader, ShaderType},
+    vertex_attributes::{DataType, VertexArrayObject, VertexAttribute},
+    NULL_HANDLE,
+};
+
+/// Shader storage binding the compute update reads the particles from
+pub const PARTICLES_BINDING_INDEX: GLuint = 0;
+/// Attribute locations of the two halves of a [`Particle`]
+const POSITION_LIFE_LOCATION: GLuint = 0;
+const VELOCITY_LIFETIME_LOCATION: GLuint = 1;
+/// `local_size_x` of update.comp
+const WORK_GROUP_SIZE: usize = 64;
+
+macro_rules! update_shader {
+    ($version:literal, $file:literal) => {
+        concat!(
+            "#version ",
+            $version,
+            "\n",
+            include_str!("../resources/shaders/particles/update.glsl"),
+            include_str!(concat!("../resources/shaders/particles/", $file)),
+        )
+    };
+}
+
+const UPDATE_VERTEX_SHADER: &str = update_shader!("330", "update.vert");
+const UPDATE_COMPUTE_SHADER: &str = update_shader!("430", "update.comp");
+const BILLBOARD_VERTEX_SHADER: &str = concat!(
+    "#version 330\n",
+    include_str!("../resources/shaders/particles/billboard.vert")
+);
+const BILLBOARD_FRAGMENT_SHADER: &str = concat!(
+    "#version 330\n",
+    include_str!("../resources/shaders/particles/billboard.frag")
+);
+
+#[derive(Error, Debug)]
+pub enum ParticleError {
+    #[error("Particle shader error: {0:?}")]
+    ShaderError(CString),
+    #[error("Shader source contains a nul byte: {0}")]
+    NulError(#[from] NulError),
+}
+
+/// A particle as stored in the buffers, two `vec4` in the shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Particle {
+    pub position: Vec3,
+    /// Seconds left, 0 or less once dead
+    pub life: f32,
+    pub velocity: Vec3,
+    /// Seconds it was spawned with, 0 before its first spawn
+    pub lifetime: f32,
+}
+
+/// How [`ParticleSystem::update`] runs on the GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticleUpdate {
+    /// A vertex shader captured into a second buffer, the two are swapped every update
+    TransformFeedback,
+    /// A compute shader updating the buffer in place, see
+    /// [`crate::app::ContextInfo::has_compute`]
+    Compute,
+}
+
+/// Where and how particles respawn once dead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emitter {
+    pub position: Vec3,
+    /// Acceleration of every particle
+    pub gravity: Vec3,
+    /// Initial speed, particles get 70 to 100% of it
+    pub speed: f32,
+    /// Degrees from +y particles are emitted within
+    pub spread: f32,
+    /// Seconds particles live, they get 50 to 100% of it
+    pub lifetime: f32,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            speed: 5.0,
+            spread: 20.0,
+            lifetime: 2.0,
+        }
+    }
+}
+
+/// Particles waiting for their first spawn, staggered over one `lifetime`
+/// so the emission starts evenly
+fn initial_particles(count: usize, lifetime: f32) -> Vec<Particle> {
+    (0..count)
+        .map(|i| Particle {
+            life: lifetime * i as f32 / count as f32,
+            ..Particle::default()
+        })
+        .collect()
+}
+
+/// Sources the particle attributes of `vao` from `buffer`, every vertex with a `divisor`
+/// of 0 or every instance with 1
+fn set_particle_attributes(
+    vao: &mut VertexArrayObject,
+    buffer: &Buffer<Particle>,
+    divisor: GLuint,
+) {
+    let attribute = VertexAttribute::new(4, DataType::Float, false);
+    let stride = std::mem::size_of::<Particle>() as GLsizei;
+    vao.bind();
+    // the buffer has the target of the update, attributes are read from the array buffer
+    bind_directly(gl::ARRAY_BUFFER, buffer.id().raw());
+    for (location, offset) in [
+        (POSITION_LIFE_LOCATION, offset_of!(Particle, position)),
+        (VELOCITY_LIFETIME_LOCATION, offset_of!(Particle, velocity)),
+    ] {
+        vao.set_attribute(location, &attribute, stride, offset as GLint);
+        vao.set_attribute_divisor(location, divisor);
+    }
+    vao.unbind();
+    bind_directly(gl::ARRAY_BUFFER, NULL_HANDLE);
+}
+
+/// A buffer of particles and the vertex arrays reading it
+struct ParticleBuffer {
+    buffer: Buffer<Particle>,
+    // every vertex a particle, for the transform feedback update only
+    update_vao: Option<VertexArrayObject>,
+    // every instance a particle
+    render_vao: VertexArrayObject,
+}
+
+impl ParticleBuffer {
+    fn new(target: Target, particles: &[Particle], update: ParticleUpdate) -> Self {
+        let mut buffer = Buffer::new(target);
+        buffer
+            .bind_scoped()
+            .buffer_data(particles, Usage::DynamicCopy);
+        let update_vao = (update == ParticleUpdate::TransformFeedback).then(|| {
+            let mut vao = VertexArrayObject::new();
+            set_particle_attributes(&mut vao, &buffer, 0);
+            vao
+        });
+        let mut render_vao = VertexArrayObject::new();
+        set_particle_attributes(&mut render_vao, &buffer, 1);
+        Self {
+            buffer,
+            update_vao,
+            render_vao,
+        }
+    }
+}
+
+/// Particles living in GPU buffers, updated and drawn as camera facing quads
+/// without going through the CPU
+pub struct ParticleSystem {
+    update: ParticleUpdate,
+    count: usize,
+    // one, or two that transform feedback writes in turn
+    buffers: Vec<ParticleBuffer>,
+    current: usize,
+    update_material: Material,
+    render_material: Material,
+    // seconds since the start, seeds the respawns
+    time: f32,
+    pub emitter: Emitter,
+    /// Width of the quads in world units
+    pub size: f32,
+    /// Color of the particles when spawned, faded to `end_color` as they age
+    pub start_color: Vec4,
+    pub end_color: Vec4,
+}
+
+impl ParticleSystem {
+    pub fn new(count: usize, update: ParticleUpdate) -> Result<Self, ParticleError> {
+        let update_program = match update {
+            ParticleUpdate::TransformFeedback => {
+                let vertex = Shader::new(&CString::new(UPDATE_VERTEX_SHADER)?, ShaderType::Vertex)
+                    .map_err(ParticleError::ShaderError)?;
+                Program::with_transform_feedback(
+                    &[vertex],
+                    &[c"outPositionLife", c"outVelocityLifetime"],
+                )
+                .map_err(ParticleError::ShaderError)?
+            }
+            ParticleUpdate::Compute => {
+                let compute =
+                    Shader::new(&CString::new(UPDATE_COMPUTE_SHADER)?, ShaderType::Compute)
+                        .map_err(ParticleError::ShaderError)?;
+                let mut program = Program::new(&[compute]).map_err(ParticleError::ShaderError)?;
+                if let Some(block) = program.get_shader_storage_block_index(c"Particles") {
+                    program.shader_storage_block_binding(block, PARTICLES_BINDING_INDEX);
+                }
+                program
+            }
+        };
+        let vertex = Shader::new(&CString::new(BILLBOARD_VERTEX_SHADER)?, ShaderType::Vertex)
+            .map_err(ParticleError::ShaderError)?;
+        let fragment = Shader::new(
+            &CString::new(BILLBOARD_FRAGMENT_SHADER)?,
+            ShaderType::Fragment,
+        )
+        .map_err(ParticleError::ShaderError)?;
+        let render_program =
+            Program::new(&[vertex, fragment]).map_err(ParticleError::ShaderError)?;
+
+        let emitter = Emitter::default();
+        let target = match update {
+            ParticleUpdate::TransformFeedback => Target::TransformFeedbackBuffer,
+            ParticleUpdate::Compute => Target::ShaderStorageBuffer,
+        };
+        let particles = initial_particles(count, emitter.lifetime);
+        let buffer_count = match update {
+            ParticleUpdate::TransformFeedback => 2,
+            ParticleUpdate::Compute => 1,
+        };
+        let buffers = (0..buffer_count)
+            .map(|_| ParticleBuffer::new(target, &particles, update))
+            .collect();
+
+        Ok(Self {
+            update,
+            count,
+            buffers,
+            current: 0,
+            update_material: Material::new(Rc::new(update_program)),
+            render_material: Material::new(Rc::new(render_program)),
+            time: 0.0,
+            emitter,
+            size: 0.2,
+            start_color: Vec4::new(1.0, 0.8, 0.3, 1.0),
+            end_color: Vec4::new(0.8, 0.1, 0.0, 0.0),
+        })
+    }
+
+    #[must_use]
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Moves the particles `dt` seconds forward, respawning the dead ones at the emitter
+    pub fn update(&mut self, gl: &mut OpenGl, dt: f32) {
+        self.time += dt;
+        let emitter = self.emitter;
+        let material = &mut self.update_material;
+        material.set(c"deltaTime", dt);
+        material.set(c"time", self.time);
+        material.set(c"emitterPosition", emitter.position);
+        material.set(c"gravity", emitter.gravity);
+        material.set(c"speed", emitter.speed);
+        material.set(c"spreadCos", emitter.spread.to_radians().cos());
+        material.set(c"lifetime", emitter.lifetime);
+        material.apply(gl);
+
+        match self.update {
+            ParticleUpdate::TransformFeedback => {
+                let next = 1 - self.current;
+                self.buffers[next].buffer.bind_base(0);
+                gl.enable(Capability::RasterizerDiscard);
+                if let Some(vao) = &self.buffers[self.current].update_vao {
+                    gl.bind_vertex_array(vao);
+                }
+                gl.begin_transform_feedback(Primitive::Points);
+                gl.draw_arrays(Primitive::Points, 0, self.count as GLsizei);
+                gl.end_transform_feedback();
+                gl.unbind_vertex_array();
+                gl.disable(Capability::RasterizerDiscard);
+                self.current = next;
+            }
+            ParticleUpdate::Compute => {
+                self.buffers[0].buffer.bind_base(PARTICLES_BINDING_INDEX);
+                let groups = self.count.div_ceil(WORK_GROUP_SIZE) as GLuint;
+                gl.dispatch_compute(groups, 1, 1);
+                // drawing reads the particles as vertex attributes
+                gl.memory_barrier(BarrierFlags::VertexAttribArray);
+            }
+        }
+        gl.unbind_program();
+    }
+
+    /// Draws the particles as quads facing `camera`, blended additively over what's drawn
+    /// and depth tested without writing depth. Leaves the depth and blend state as it was.
+    pub fn draw(&mut self, gl: &mut OpenGl, camera: &Camera) {
+        let material = &mut self.render_material;
+        material.set(c"worldToCamera", camera.view());
+        material.set(c"cameraToClip", camera.projection());
+        material.set(c"size", self.size);
+        material.set(c"startColor", self.start_color);
+        material.set(c"endColor", self.end_color);
+
+        let mut gl = gl.save_state(StateFlags::Depth | StateFlags::Blend);
+        gl.enable(Capability::Blend);
+        gl.blend_func(BlendFactor::SrcAlpha, BlendFactor::One);
+        gl.set_depth_mask(false);
+        material.apply(&mut gl);
+        gl.bind_vertex_array(&self.buffers[self.current].render_vao);
+        gl.draw_arrays_instanced(Primitive::TriangleStrip, 0, 4, self.count as GLsizei);
+        gl.unbind_vertex_array();
+        gl.unbind_program();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem::offset_of;
+
+    use super::{initial_particles, Particle, UPDATE_COMPUTE_SHADER, UPDATE_VERTEX_SHADER};
+
+    #[test]
+    fn test_particle_layout() {
+        // two vec4 in the shaders
+        assert_eq!(std::mem::size_of::<Particle>(), 32);
+        assert_eq!(offset_of!(Particle, velocity), 16);
+
+        assert!(UPDATE_VERTEX_SHADER.starts_with("#version 330\n"));
+        assert!(UPDATE_COMPUTE_SHADER.starts_with("#version 430\n"));
+        assert!(UPDATE_COMPUTE_SHADER.contains("void updateParticle"));
+    }
+
+    #[test]
+    fn test_initial_particles() {
+        let particles = initial_particles(4, 2.0);
+        let delays: Vec<_> = particles.iter().map(|particle| particle.life).collect();
+        assert_eq!(delays, [0.0, 0.5, 1.0, 1.5]);
+        // none spawned yet
+        assert!(particles.iter().all(|particle| particle.lifetime == 0.0));
+    }
+}
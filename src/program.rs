@@ -1,38 +1,104 @@
 use std::{
+    borrow::Borrow,
+    cell::RefCell,
     ffi::{CStr, CString},
+    marker::PhantomData,
     ptr,
 };
 
-use gl::types::{GLenum, GLint, GLuint};
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use glam::{Mat4, Vec4};
 
-use crate::{uniforms::SetUniform, GLHandle};
+use crate::{
+    gl_api,
+    handle::{delete_later, ContextBound, GlObject, ProgramId},
+    texture::Texture,
+    uniforms::{has_program_uniforms, SetUniform},
+    GLHandle,
+};
 
 pub type GLLocation = GLint;
 pub type GLBlockIndex = GLuint;
 
 pub struct Program {
     id: GLHandle,
+    /// Sampler locations by the texture unit given to them by [`Program::bind_texture`]
+    texture_units: RefCell<Vec<GLLocation>>,
+    _context: ContextBound,
+}
+
+/// A uniform by name, or by the location found with [`Program::get_uniform_location`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformRef<'a> {
+    Name(&'a CStr),
+    Location(GLLocation),
+}
+
+impl<'a> From<&'a CStr> for UniformRef<'a> {
+    fn from(name: &'a CStr) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<GLLocation> for UniformRef<'_> {
+    fn from(location: GLLocation) -> Self {
+        Self::Location(location)
+    }
 }
 
 impl Drop for Program {
     fn drop(&mut self) {
-        unsafe { gl::DeleteProgram(self.id) }
+        delete_later(GlObject::Program(self.id));
     }
 }
 
 impl Program {
-    pub fn new(shaders: &[Shader]) -> Result<Self, CString> {
+    /// Links the shaders, which can be owned or shared handles such as `Rc<Shader>`.
+    pub fn new<S: Borrow<Shader>>(shaders: &[S]) -> Result<Self, CString> {
+        Self::link(shaders, |_| {})
+    }
+
+    /// Links the shaders capturing the `varyings` outputs, interleaved in order into the
+    /// buffer bound to transform feedback binding 0, see [`OpenGl::begin_transform_feedback`].
+    ///
+    /// [`OpenGl::begin_transform_feedback`]: crate::opengl::OpenGl::begin_transform_feedback
+    pub fn with_transform_feedback<S: Borrow<Shader>>(
+        shaders: &[S],
+        varyings: &[&CStr],
+    ) -> Result<Self, CString> {
+        let varyings: Vec<_> = varyings.iter().map(|name| name.as_ptr()).collect();
+        Self::link(shaders, |id| unsafe {
+            gl::TransformFeedbackVaryings(
+                id,
+                varyings.len() as GLsizei,
+                varyings.as_ptr(),
+                gl::INTERLEAVED_ATTRIBS,
+            );
+        })
+    }
+
+    /// `before_link` sets what has to be known at link time
+    fn link<S: Borrow<Shader>>(
+        shaders: &[S],
+        before_link: impl FnOnce(GLHandle),
+    ) -> Result<Self, CString> {
         let id = unsafe { gl::CreateProgram() };
         for shader in shaders {
-            unsafe { gl::AttachShader(id, shader.id) };
+            unsafe { gl::AttachShader(id, shader.borrow().id) };
         }
+        before_link(id);
+        gl_api::trace_call("glLinkProgram", || id.to_string());
         unsafe { gl::LinkProgram(id) };
-        let mut program = Self { id };
+        let mut program = Self {
+            id,
+            texture_units: RefCell::new(vec![]),
+            _context: PhantomData,
+        };
         if let Some(error) = program.get_link_error() {
             return Err(error);
         }
         for shader in shaders {
-            unsafe { gl::DetachShader(id, shader.id) };
+            unsafe { gl::DetachShader(id, shader.borrow().id) };
         }
         Ok(program)
     }
@@ -60,14 +126,22 @@ impl Program {
         None
     }
 
+    #[must_use]
+    pub const fn id(&self) -> ProgramId {
+        ProgramId(self.id)
+    }
+
     pub fn set_used(&mut self) {
+        gl_api::trace_call("glUseProgram", || self.id.to_string());
         unsafe { gl::UseProgram(self.id) };
     }
     pub fn set_unused(&mut self) {
+        gl_api::trace_call("glUseProgram", || "0".to_owned());
         unsafe { gl::UseProgram(0) };
     }
 
-    pub fn get_uniform_location(&mut self, name: &CStr) -> Option<GLLocation> {
+    #[must_use]
+    pub fn get_uniform_location(&self, name: &CStr) -> Option<GLLocation> {
         let loc = unsafe { gl::GetUniformLocation(self.id, name.as_ptr()) };
         if loc == -1 {
             return None;
@@ -82,21 +156,141 @@ impl Program {
         Some(loc)
     }
     pub fn uniform_block_binding(&mut self, block_index: GLBlockIndex, binding_index: GLuint) {
+        gl_api::trace_call("glUniformBlockBinding", || {
+            format!("{}, {block_index}, {binding_index}", self.id)
+        });
         unsafe { gl::UniformBlockBinding(self.id, block_index, binding_index) };
     }
+    pub fn get_shader_storage_block_index(&mut self, name: &CStr) -> Option<GLBlockIndex> {
+        let loc = unsafe {
+            gl::GetProgramResourceIndex(self.id, gl::SHADER_STORAGE_BLOCK, name.as_ptr())
+        };
+        if loc == gl::INVALID_INDEX {
+            return None;
+        }
+        Some(loc)
+    }
+    pub fn shader_storage_block_binding(
+        &mut self,
+        block_index: GLBlockIndex,
+        binding_index: GLuint,
+    ) {
+        gl_api::trace_call("glShaderStorageBlockBinding", || {
+            format!("{}, {block_index}, {binding_index}", self.id)
+        });
+        unsafe { gl::ShaderStorageBlockBinding(self.id, block_index, binding_index) };
+    }
+
+    /// Binds `texture` for the sampler uniform `sampler`, returning the texture unit used.
+    /// Each sampler gets its own unit the first time it is bound, and keeps it afterwards.
+    /// Returns `None` if the program has no such uniform.
+    pub fn bind_texture<'a>(
+        &self,
+        sampler: impl Into<UniformRef<'a>>,
+        texture: &impl Texture,
+    ) -> Option<GLuint> {
+        let location = match sampler.into() {
+            UniformRef::Name(name) => self.get_uniform_location(name)?,
+            UniformRef::Location(-1) => return None,
+            UniformRef::Location(location) => location,
+        };
+        let mut units = self.texture_units.borrow_mut();
+        let unit = units
+            .iter()
+            .position(|l| *l == location)
+            .unwrap_or_else(|| {
+                units.push(location);
+                self.set_uniform_dsa(location, (units.len() - 1) as GLint);
+                units.len() - 1
+            });
+        texture.bind_texture(unit as GLuint);
+        Some(unit as GLuint)
+    }
+
+    /// Whether this is the program in use
+    #[must_use]
+    pub fn is_current(&self) -> bool {
+        let mut current = 0;
+        unsafe { gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut current) };
+        current as GLuint == self.id
+    }
 
+    /// Sets a uniform of this program, which has to be in use,
+    /// see [`Program::set_uniform_dsa`] otherwise
     #[allow(private_bounds)]
     #[allow(clippy::needless_pass_by_value)]
-    pub fn set_uniform<T: SetUniform>(&mut self, location: GLint, value: T) {
+    pub fn set_uniform<T: SetUniform>(&self, location: GLint, value: T) {
+        debug_assert!(
+            self.is_current(),
+            "setting a uniform of program {} which isn't in use",
+            self.id
+        );
+        gl_api::trace_call("glUniform", || format!("{location}, {value:?}"));
         value.set_uniform(location);
     }
+
+    /// Sets a uniform of this program whether it is in use or not. Contexts older than 4.1
+    /// lack `glProgramUniform*`, there the program is put in use for the call and the one
+    /// in use before is restored.
+    #[allow(private_bounds)]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn set_uniform_dsa<T: SetUniform>(&self, location: GLint, value: T) {
+        gl_api::trace_call("glProgramUniform", || {
+            format!("{}, {location}, {value:?}", self.id)
+        });
+        if has_program_uniforms() {
+            value.set_program_uniform(self.id, location);
+            return;
+        }
+        let mut previous = 0;
+        unsafe {
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut previous);
+            gl::UseProgram(self.id);
+        }
+        value.set_uniform(location);
+        unsafe { gl::UseProgram(previous as GLuint) };
+    }
+
+    /// Up to a 4x4 matrix of floats, the values past the size of the uniform are left at 0
+    fn get_uniform_floats(&self, location: GLLocation) -> [f32; 16] {
+        let mut values = [0.0; 16];
+        unsafe { gl::GetUniformfv(self.id, location, values.as_mut_ptr()) };
+        values
+    }
+
+    /// Reads back a float uniform, for debugging
+    #[must_use]
+    pub fn get_uniform_f32(&self, location: GLLocation) -> f32 {
+        self.get_uniform_floats(location)[0]
+    }
+
+    /// Reads back an int or sampler uniform, for debugging
+    #[must_use]
+    pub fn get_uniform_i32(&self, location: GLLocation) -> i32 {
+        let mut values = [0; 16];
+        unsafe { gl::GetUniformiv(self.id, location, values.as_mut_ptr()) };
+        values[0]
+    }
+
+    /// Reads back a `vec4` uniform, smaller vectors leave the rest at 0
+    #[must_use]
+    pub fn get_uniform_vec4(&self, location: GLLocation) -> Vec4 {
+        Vec4::from_slice(&self.get_uniform_floats(location))
+    }
+
+    /// Reads back a `mat4` uniform, for debugging
+    #[must_use]
+    pub fn get_uniform_mat4(&self, location: GLLocation) -> Mat4 {
+        Mat4::from_cols_array(&self.get_uniform_floats(location))
+    }
 }
 
 pub struct Shader {
     id: GLHandle,
+    _context: ContextBound,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum ShaderType {
     Compute = gl::COMPUTE_SHADER,
@@ -109,7 +303,7 @@ pub enum ShaderType {
 
 impl Drop for Shader {
     fn drop(&mut self) {
-        unsafe { gl::DeleteShader(self.id) }
+        delete_later(GlObject::Shader(self.id));
     }
 }
 
@@ -138,7 +332,10 @@ impl Shader {
     }
     pub fn new(text: &CStr, shader_type: ShaderType) -> Result<Self, CString> {
         let id = unsafe { gl::CreateShader(shader_type as GLenum) };
-        let mut shader = Self { id };
+        let mut shader = Self {
+            id,
+            _context: PhantomData,
+        };
 
         unsafe { gl::ShaderSource(shader.id, 1, &text.as_ptr(), ptr::null()) };
 
@@ -159,3 +356,60 @@ fn create_whitespace_cstring_with_len(len: usize) -> CString {
     // convert buffer to CString
     unsafe { CString::from_vec_unchecked(buffer) }
 }
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec4};
+
+    use crate::{
+        opengl::OpenGl,
+        texture::{Texture2D, TextureParams},
+    };
+
+    use super::{Program, Shader, ShaderType};
+
+    #[test]
+    fn test_uniform_round_trip() {
+        let (_gl, _context) = OpenGl::new_headless().unwrap();
+        let vertex = Shader::new(
+            c"#version 330
+            uniform float scale;
+            uniform mat4 matrix;
+            void main() { gl_Position = matrix * vec4(scale); }",
+            ShaderType::Vertex,
+        )
+        .unwrap();
+        let fragment = Shader::new(
+            c"#version 330
+            uniform vec4 color;
+            uniform sampler2D first;
+            uniform sampler2D second;
+            out vec4 outColor;
+            void main() {
+                outColor = color * texture(first, vec2(0.0)) * texture(second, vec2(0.0));
+            }",
+            ShaderType::Fragment,
+        )
+        .unwrap();
+        let program = Program::new(&[vertex, fragment]).unwrap();
+        let location = |name| program.get_uniform_location(name).unwrap();
+
+        program.set_uniform_dsa(location(c"scale"), 2.5);
+        assert!((program.get_uniform_f32(location(c"scale")) - 2.5).abs() < f32::EPSILON);
+        let color = Vec4::new(0.1, 0.2, 0.3, 0.4);
+        program.set_uniform_dsa(location(c"color"), color);
+        assert_eq!(program.get_uniform_vec4(location(c"color")), color);
+        let matrix = Mat4::from_cols_array(&std::array::from_fn(|i| i as f32));
+        program.set_uniform_dsa(location(c"matrix"), matrix);
+        assert_eq!(program.get_uniform_mat4(location(c"matrix")), matrix);
+
+        // samplers keep the unit they got first
+        let texture =
+            Texture2D::from_rgba(1, 1, &[255; 4], false, &TextureParams::default()).unwrap();
+        assert_eq!(program.bind_texture(c"second", &texture), Some(0));
+        assert_eq!(program.bind_texture(c"first", &texture), Some(1));
+        assert_eq!(program.bind_texture(location(c"second"), &texture), Some(0));
+        assert_eq!(program.bind_texture(c"missing", &texture), None);
+        assert_eq!(program.get_uniform_i32(location(c"first")), 1);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{collections::VecDeque, marker::PhantomData, time::Duration};
+
+use gl::types::{GLenum, GLint, GLuint64};
+
+use crate::{
+    handle::{delete_later, ContextBound, GlObject},
+    GLHandle, NULL_HANDLE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QueryTarget {
+    /// Nanoseconds spent by the GPU between [`Query::begin`] and [`Query::end`]
+    TimeElapsed = gl::TIME_ELAPSED,
+    /// GPU time in nanoseconds once every previous command completed, see [`Query::timestamp`]
+    Timestamp = gl::TIMESTAMP,
+    /// Number of samples passing the depth and stencil tests
+    SamplesPassed = gl::SAMPLES_PASSED,
+    /// Whether any sample passed the depth and stencil tests
+    AnySamplesPassed = gl::ANY_SAMPLES_PASSED,
+    /// Like [`QueryTarget::AnySamplesPassed`], may report false positives but be faster
+    AnySamplesPassedConservative = gl::ANY_SAMPLES_PASSED_CONSERVATIVE,
+    /// Number of primitives emitted by the vertex processing stages
+    PrimitivesGenerated = gl::PRIMITIVES_GENERATED,
+}
+
+/// What [`crate::opengl::OpenGl::begin_conditional_render`] does while the query result is pending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ConditionalRenderMode {
+    /// Wait for the result
+    Wait = gl::QUERY_WAIT,
+    /// Render without waiting if the result isn't available yet
+    NoWait = gl::QUERY_NO_WAIT,
+    /// Like [`ConditionalRenderMode::Wait`], the result may be applied per framebuffer region
+    ByRegionWait = gl::QUERY_BY_REGION_WAIT,
+    ByRegionNoWait = gl::QUERY_BY_REGION_NO_WAIT,
+}
+
+pub struct Query {
+    id: GLHandle,
+    target: QueryTarget,
+    _context: ContextBound,
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        delete_later(GlObject::Query(self.id));
+    }
+}
+
+impl Query {
+    #[must_use]
+    pub fn new(target: QueryTarget) -> Self {
+        let mut id = NULL_HANDLE;
+        unsafe { gl::GenQueries(1, &mut id) };
+        Self {
+            id,
+            target,
+            _context: PhantomData,
+        }
+    }
+
+    pub(crate) const fn id(&self) -> GLHandle {
+        self.id
+    }
+
+    #[must_use]
+    pub const fn target(&self) -> QueryTarget {
+        self.target
+    }
+
+    pub fn begin(&mut self) {
+        unsafe { gl::BeginQuery(self.target as GLenum, self.id) };
+    }
+    pub fn end(&mut self) {
+        unsafe { gl::EndQuery(self.target as GLenum) };
+    }
+
+    /// Records the GPU time, for [`QueryTarget::Timestamp`] queries.
+    pub fn timestamp(&mut self) {
+        unsafe { gl::QueryCounter(self.id, gl::TIMESTAMP) };
+    }
+
+    /// Whether [`Query::result`] can be read without stalling.
+    #[must_use]
+    pub fn is_available(&self) -> bool {
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectiv(self.id, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+        available == GLint::from(gl::TRUE)
+    }
+
+    /// Blocks until the GPU has produced the result.
+    #[must_use]
+    pub fn result(&self) -> GLuint64 {
+        let mut result = 0;
+        unsafe { gl::GetQueryObjectui64v(self.id, gl::QUERY_RESULT, &mut result) };
+        result
+    }
+
+    #[must_use]
+    pub fn try_result(&self) -> Option<GLuint64> {
+        self.is_available().then(|| self.result())
+    }
+
+    /// Times the GPU commands issued while the returned guard is alive.
+    pub fn time(&mut self) -> GpuTimer<'_> {
+        GpuTimer::new(self)
+    }
+}
+
+/// Begins a [`QueryTarget::TimeElapsed`] query and ends it when dropped.
+pub struct GpuTimer<'a> {
+    pub query: &'a mut Query,
+}
+
+impl Drop for GpuTimer<'_> {
+    fn drop(&mut self) {
+        self.query.end();
+    }
+}
+
+impl<'a> GpuTimer<'a> {
+    pub fn new(query: &'a mut Query) -> Self {
+        query.begin();
+        Self { query }
+    }
+}
+
+/// Averages the GPU time of the last `window` scopes, e.g. one pass per frame.
+///
+/// Results are read only once available, so the average lags a few frames behind.
+pub struct RollingGpuTimer {
+    in_flight: VecDeque<Query>,
+    free: Vec<Query>,
+    samples: VecDeque<Duration>,
+    window: usize,
+}
+
+impl RollingGpuTimer {
+    #[must_use]
+    pub fn new(window: usize) -> Self {
+        Self {
+            in_flight: VecDeque::new(),
+            free: vec![],
+            samples: VecDeque::with_capacity(window.max(1)),
+            window: window.max(1),
+        }
+    }
+
+    /// Collects finished results, then times the commands issued while the guard is alive.
+    pub fn scope(&mut self) -> GpuTimer<'_> {
+        self.collect();
+        let query = self
+            .free
+            .pop()
+            .unwrap_or_else(|| Query::new(QueryTarget::TimeElapsed));
+        self.in_flight.push_back(query);
+        let last = self.in_flight.len() - 1;
+        GpuTimer::new(&mut self.in_flight[last])
+    }
+
+    /// Moves the results that are available into the samples, oldest first.
+    pub fn collect(&mut self) {
+        while let Some(result) = self.in_flight.front().and_then(Query::try_result) {
+            self.push_sample(Duration::from_nanos(result));
+            if let Some(query) = self.in_flight.pop_front() {
+                self.free.push(query);
+            }
+        }
+    }
+
+    fn push_sample(&mut self, sample: Duration) {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    #[must_use]
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    #[must_use]
+    pub fn average(&self) -> Duration {
+        let sum = self.samples.iter().sum::<Duration>();
+        sum.checked_div(self.samples.len() as u32)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::VecDeque, time::Duration};
+
+    use super::RollingGpuTimer;
+
+    #[test]
+    fn test_rolling_average() {
+        let mut timer = RollingGpuTimer {
+            in_flight: VecDeque::new(),
+            free: vec![],
+            samples: VecDeque::new(),
+            window: 3,
+        };
+        assert_eq!(timer.average(), Duration::ZERO);
+        for ms in [10, 1, 2, 3] {
+            timer.push_sample(Duration::from_millis(ms));
+        }
+        assert_eq!(timer.last(), Some(Duration::from_millis(3)));
+        assert_eq!(timer.average(), Duration::from_millis(2));
+    }
+}
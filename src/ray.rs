@@ -0,0 +1,90 @@
+use glam::Vec3;
+
+/// Half line from `origin` along the unit vector `direction`,
+/// intersections return the distance from the origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// `direction` is normalized
+    #[must_use]
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    #[must_use]
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Distance to the first hit with the box, 0 when the origin is inside
+    #[must_use]
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        // slab test, axes parallel to the ray give infinities that the min/max discard
+        let inverse = self.direction.recip();
+        let t1 = (min - self.origin) * inverse;
+        let t2 = (max - self.origin) * inverse;
+        let near = t1.min(t2).max_element().max(0.0);
+        let far = t1.max(t2).min_element();
+        (near <= far).then_some(near)
+    }
+
+    /// Distance to the first hit with the sphere, 0 when the origin is inside
+    #[must_use]
+    pub fn intersect_sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let to_center = center - self.origin;
+        let projection = to_center.dot(self.direction);
+        let squared_distance = projection.mul_add(-projection, to_center.length_squared());
+        let squared_radius = radius * radius;
+        if squared_distance > squared_radius {
+            return None;
+        }
+        let half_chord = (squared_radius - squared_distance).sqrt();
+        let (near, far) = (projection - half_chord, projection + half_chord);
+        if far < 0.0 {
+            None
+        } else {
+            Some(near.max(0.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::Ray;
+
+    #[test]
+    fn test_intersect_aabb() {
+        let ray = Ray::new(Vec3::new(-5.0, 0.5, 0.5), Vec3::X * 2.0);
+        let hit = ray.intersect_aabb(Vec3::ZERO, Vec3::ONE).unwrap();
+        assert!((hit - 5.0).abs() < 1e-5);
+        assert!(ray.at(hit).abs_diff_eq(Vec3::new(0.0, 0.5, 0.5), 1e-5));
+
+        // from inside, behind and to the side
+        let inside = Ray::new(Vec3::splat(0.5), Vec3::Y);
+        assert_eq!(inside.intersect_aabb(Vec3::ZERO, Vec3::ONE), Some(0.0));
+        let behind = Ray::new(Vec3::new(5.0, 0.5, 0.5), Vec3::X);
+        assert_eq!(behind.intersect_aabb(Vec3::ZERO, Vec3::ONE), None);
+        let side = Ray::new(Vec3::new(-5.0, 2.0, 0.5), Vec3::X);
+        assert_eq!(side.intersect_aabb(Vec3::ZERO, Vec3::ONE), None);
+    }
+
+    #[test]
+    fn test_intersect_sphere() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 10.0), Vec3::NEG_Z);
+        let hit = ray.intersect_sphere(Vec3::ZERO, 2.0).unwrap();
+        assert!((hit - 8.0).abs() < 1e-5);
+
+        assert_eq!(ray.intersect_sphere(Vec3::ZERO, 20.0), Some(0.0));
+        assert_eq!(ray.intersect_sphere(Vec3::new(0.0, 0.0, 20.0), 2.0), None);
+        assert_eq!(ray.intersect_sphere(Vec3::new(3.0, 0.0, 0.0), 2.0), None);
+    }
+}
@@ -0,0 +1,425 @@
+//! Multi-pass frames as a graph of passes reading and writing resources.
+//!
+//! Passes declare the textures and buffers they read and write, and
+//! [`RenderGraph::execute`] runs them in an order where every resource is written before
+//! it's read, whatever order they were added in. Passes writing the same resource run in
+//! the order they were added.
+//!
+//! ```no_run
+//! # use opengl_rend::{opengl::OpenGl, render_graph::{RenderGraph, RenderPass, ResourceKind}};
+//! # fn frame(gl: &mut OpenGl) {
+//! let mut graph = RenderGraph::new();
+//! let shadow_map = graph.resource("shadow map", ResourceKind::Texture);
+//! graph.add_pass(RenderPass::new("lit scene", |gl| { /* sample the shadow map */ }).reads(shadow_map));
+//! graph.add_pass(RenderPass::new("shadows", |gl| { /* draw the casters */ }).writes(shadow_map));
+//! graph.execute(gl).unwrap();
+//! # }
+//! ```
+
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use crate::{
+    framebuffer::Framebuffer,
+    opengl::{BarrierFlags, GlError, OpenGl},
+    NULL_HANDLE,
+};
+
+#[derive(Error, Debug)]
+pub enum RenderGraphError {
+    #[error("Passes depend on each other in a cycle: {0:?}")]
+    Cycle(Vec<String>),
+    #[error(transparent)]
+    GlError(#[from] GlError),
+}
+
+/// A resource of a [`RenderGraph`], returned by [`RenderGraph::resource`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+/// Decides the barriers needed to read a resource written by shaders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Texture,
+    Buffer,
+}
+
+impl ResourceKind {
+    /// Makes image or storage writes visible to every later read of the resource
+    const fn barriers(self) -> BarrierFlags {
+        match self {
+            Self::Texture => BarrierFlags::TextureFetch
+                .union(BarrierFlags::ShaderImageAccess)
+                .union(BarrierFlags::TextureUpdate)
+                .union(BarrierFlags::Framebuffer),
+            Self::Buffer => BarrierFlags::ShaderStorage
+                .union(BarrierFlags::VertexAttribArray)
+                .union(BarrierFlags::ElementArray)
+                .union(BarrierFlags::Uniform)
+                .union(BarrierFlags::Command)
+                .union(BarrierFlags::BufferUpdate),
+        }
+    }
+}
+
+struct Resource {
+    name: String,
+    kind: ResourceKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WriteAccess {
+    /// Rendered to as a framebuffer attachment, or uploaded to
+    Render,
+    /// Stored to by shaders as an image or storage buffer, needs a barrier before reading
+    Storage,
+}
+
+/// Where a pass draws, bound before it runs
+#[derive(Clone, Copy, Default)]
+pub enum PassTarget<'a> {
+    /// Whatever the pass binds itself
+    #[default]
+    Unchanged,
+    Window,
+    Framebuffer(&'a Framebuffer),
+}
+
+type Execute<'a> = Box<dyn FnMut(&mut OpenGl) + 'a>;
+
+/// A step of a [`RenderGraph`], running its closure with its target bound
+pub struct RenderPass<'a> {
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<(ResourceId, WriteAccess)>,
+    target: PassTarget<'a>,
+    execute: Execute<'a>,
+}
+
+impl<'a> RenderPass<'a> {
+    pub fn new(name: impl Into<String>, execute: impl FnMut(&mut OpenGl) + 'a) -> Self {
+        Self {
+            name: name.into(),
+            reads: vec![],
+            writes: vec![],
+            target: PassTarget::Unchanged,
+            execute: Box::new(execute),
+        }
+    }
+
+    /// Samples or reads the resource, after the passes writing it
+    #[must_use]
+    pub fn reads(mut self, resource: ResourceId) -> Self {
+        self.reads.push(resource);
+        self
+    }
+    /// Renders to or uploads to the resource
+    #[must_use]
+    pub fn writes(mut self, resource: ResourceId) -> Self {
+        self.writes.push((resource, WriteAccess::Render));
+        self
+    }
+    /// Stores to the resource from shaders, as an image or a storage buffer, so the passes
+    /// reading it need a memory barrier
+    #[must_use]
+    pub fn writes_storage(mut self, resource: ResourceId) -> Self {
+        self.writes.push((resource, WriteAccess::Storage));
+        self
+    }
+    #[must_use]
+    pub const fn target(mut self, target: PassTarget<'a>) -> Self {
+        self.target = target;
+        self
+    }
+}
+
+/// A pass in execution order and the barriers issued before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledPass {
+    pub name: String,
+    pub barriers: BarrierFlags,
+}
+
+/// Passes run in the order of the resources they read and write, see [the module](self).
+/// Build it every frame, the passes borrow what they draw.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    resources: Vec<Resource>,
+    passes: Vec<RenderPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resource(&mut self, name: impl Into<String>, kind: ResourceKind) -> ResourceId {
+        self.resources.push(Resource {
+            name: name.into(),
+            kind,
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, pass: RenderPass<'a>) {
+        self.passes.push(pass);
+    }
+
+    /// Indices of the passes that have to run before each pass: the writers of what it
+    /// only reads, and the latest writer added before it of what it writes, so a pass
+    /// reading and writing a resource runs between the writers around it
+    fn dependencies(&self) -> Vec<Vec<usize>> {
+        let writes = |pass: &RenderPass, resource: ResourceId| {
+            pass.writes.iter().any(|(written, _)| *written == resource)
+        };
+        self.passes
+            .iter()
+            .enumerate()
+            .map(|(i, pass)| {
+                let mut dependencies = vec![];
+                for &(resource, _) in &pass.writes {
+                    // the writers before it run before that one
+                    let previous = (0..i).rev().find(|&j| writes(&self.passes[j], resource));
+                    dependencies.extend(previous);
+                }
+                for &resource in &pass.reads {
+                    if !writes(pass, resource) {
+                        dependencies.extend(
+                            (0..self.passes.len())
+                                .filter(|&j| j != i && writes(&self.passes[j], resource)),
+                        );
+                    }
+                }
+                dependencies.sort_unstable();
+                dependencies.dedup();
+                dependencies
+            })
+            .collect()
+    }
+
+    /// Execution order, the passes ready first in the order they were added
+    fn order(&self) -> Result<Vec<usize>, RenderGraphError> {
+        let dependencies = self.dependencies();
+        let mut done = vec![false; self.passes.len()];
+        let mut order = Vec::with_capacity(self.passes.len());
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len())
+                .find(|&i| !done[i] && dependencies[i].iter().all(|&j| done[j]));
+            let Some(next) = next else {
+                let cycle = (0..self.passes.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| self.passes[i].name.clone())
+                    .collect();
+                return Err(RenderGraphError::Cycle(cycle));
+            };
+            done[next] = true;
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    /// The barriers before each pass of `order`, for what it reads of the storage writes
+    /// not yet made visible
+    fn barriers(&self, order: &[usize]) -> Vec<BarrierFlags> {
+        let mut pending = vec![false; self.resources.len()];
+        order
+            .iter()
+            .map(|&i| {
+                let pass = &self.passes[i];
+                let mut barriers = BarrierFlags::empty();
+                for resource in &pass.reads {
+                    if pending[resource.0] {
+                        barriers |= self.resources[resource.0].kind.barriers();
+                    }
+                }
+                // the barrier makes the earlier writes of the kinds it covers visible
+                for (pending, resource) in pending.iter_mut().zip(&self.resources) {
+                    if barriers.contains(resource.kind.barriers()) {
+                        *pending = false;
+                    }
+                }
+                for (resource, write) in &pass.writes {
+                    pending[resource.0] = *write == WriteAccess::Storage;
+                }
+                barriers
+            })
+            .collect()
+    }
+
+    /// The passes in execution order with their barriers, without running them
+    pub fn compile(&self) -> Result<Vec<CompiledPass>, RenderGraphError> {
+        let order = self.order()?;
+        let barriers = self.barriers(&order);
+        Ok(order
+            .iter()
+            .zip(barriers)
+            .map(|(&i, barriers)| CompiledPass {
+                name: self.passes[i].name.clone(),
+                barriers,
+            })
+            .collect())
+    }
+
+    /// Runs the passes in order, issuing their barriers and binding their targets first.
+    /// With [`OpenGl::set_error_checking`] it stops at the first pass causing a GL error.
+    pub fn execute(&mut self, gl: &mut OpenGl) -> Result<(), RenderGraphError> {
+        let order = self.order()?;
+        let barriers = self.barriers(&order);
+        for (i, barriers) in order.into_iter().zip(barriers) {
+            if !barriers.is_empty() {
+                gl.memory_barrier(barriers);
+            }
+            let pass = &mut self.passes[i];
+            match pass.target {
+                PassTarget::Unchanged => {}
+                PassTarget::Window => unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE);
+                },
+                PassTarget::Framebuffer(framebuffer) => framebuffer.bind(),
+            }
+            (pass.execute)(gl);
+            if gl.error_checking() {
+                gl.check_error()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The graph in Graphviz dot, passes as boxes and resources as ellipses
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph RenderGraph {\n");
+        for (i, resource) in self.resources.iter().enumerate() {
+            let _ = writeln!(dot, "    r{i} [label={:?}, shape=ellipse];", resource.name);
+        }
+        for (i, pass) in self.passes.iter().enumerate() {
+            let _ = writeln!(dot, "    p{i} [label={:?}, shape=box];", pass.name);
+            for resource in &pass.reads {
+                let _ = writeln!(dot, "    r{} -> p{i};", resource.0);
+            }
+            for (resource, write) in &pass.writes {
+                let style = match write {
+                    WriteAccess::Render => "",
+                    WriteAccess::Storage => " [style=dashed]",
+                };
+                let _ = writeln!(dot, "    p{i} -> r{}{style};", resource.0);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RenderGraph, RenderGraphError, RenderPass, ResourceKind};
+    use crate::opengl::BarrierFlags;
+
+    fn names(graph: &RenderGraph) -> Vec<String> {
+        graph
+            .compile()
+            .unwrap()
+            .into_iter()
+            .map(|pass| pass.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_order() {
+        let mut graph = RenderGraph::new();
+        let shadow = graph.resource("shadow", ResourceKind::Texture);
+        let hdr = graph.resource("hdr", ResourceKind::Texture);
+        graph.add_pass(RenderPass::new("tone mapping", |_| {}).reads(hdr));
+        graph.add_pass(RenderPass::new("lit", |_| {}).reads(shadow).writes(hdr));
+        graph.add_pass(RenderPass::new("shadows", |_| {}).writes(shadow));
+        graph.add_pass(RenderPass::new("ui", |_| {}));
+        assert_eq!(names(&graph), ["shadows", "lit", "tone mapping", "ui"]);
+    }
+
+    #[test]
+    fn test_writers_keep_order() {
+        let mut graph = RenderGraph::new();
+        let color = graph.resource("color", ResourceKind::Texture);
+        graph.add_pass(RenderPass::new("post", |_| {}).reads(color));
+        graph.add_pass(RenderPass::new("opaque", |_| {}).writes(color));
+        graph.add_pass(RenderPass::new("transparent", |_| {}).writes(color));
+        assert_eq!(names(&graph), ["opaque", "transparent", "post"]);
+    }
+
+    #[test]
+    fn test_read_write_between_writers() {
+        let mut graph = RenderGraph::new();
+        let color = graph.resource("color", ResourceKind::Texture);
+        graph.add_pass(RenderPass::new("opaque", |_| {}).writes(color));
+        graph.add_pass(
+            RenderPass::new("outline", |_| {})
+                .reads(color)
+                .writes(color),
+        );
+        graph.add_pass(RenderPass::new("transparent", |_| {}).writes(color));
+        assert_eq!(names(&graph), ["opaque", "outline", "transparent"]);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut graph = RenderGraph::new();
+        let a = graph.resource("a", ResourceKind::Texture);
+        let b = graph.resource("b", ResourceKind::Texture);
+        graph.add_pass(RenderPass::new("first", |_| {}).reads(a).writes(b));
+        graph.add_pass(RenderPass::new("second", |_| {}).reads(b).writes(a));
+        assert!(matches!(
+            graph.compile(),
+            Err(RenderGraphError::Cycle(passes)) if passes.len() == 2
+        ));
+    }
+
+    #[test]
+    fn test_barriers() {
+        let mut graph = RenderGraph::new();
+        let particles = graph.resource("particles", ResourceKind::Buffer);
+        let image = graph.resource("image", ResourceKind::Texture);
+        graph.add_pass(RenderPass::new("simulate", |_| {}).writes_storage(particles));
+        graph.add_pass(
+            RenderPass::new("draw", |_| {})
+                .reads(particles)
+                .writes(image),
+        );
+        graph.add_pass(RenderPass::new("blur", |_| {}).reads(image));
+        let passes = graph.compile().unwrap();
+        assert!(passes[0].barriers.is_empty());
+        assert!(passes[1].barriers.contains(BarrierFlags::ShaderStorage));
+        assert!(passes[2].barriers.is_empty());
+    }
+
+    #[test]
+    fn test_barriers_per_kind() {
+        let mut graph = RenderGraph::new();
+        let particles = graph.resource("particles", ResourceKind::Buffer);
+        let image = graph.resource("image", ResourceKind::Texture);
+        graph.add_pass(
+            RenderPass::new("simulate", |_| {})
+                .writes_storage(particles)
+                .writes_storage(image),
+        );
+        graph.add_pass(RenderPass::new("draw", |_| {}).reads(particles));
+        graph.add_pass(RenderPass::new("blur", |_| {}).reads(image));
+        let passes = graph.compile().unwrap();
+        assert!(passes[1].barriers.contains(BarrierFlags::ShaderStorage));
+        assert!(!passes[1].barriers.contains(BarrierFlags::TextureFetch));
+        // the buffer barrier left the image writes pending
+        assert!(passes[2].barriers.contains(BarrierFlags::TextureFetch));
+    }
+
+    #[test]
+    fn test_dot() {
+        let mut graph = RenderGraph::new();
+        let shadow = graph.resource("shadow", ResourceKind::Texture);
+        graph.add_pass(RenderPass::new("shadows", |_| {}).writes(shadow));
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph RenderGraph {"));
+        assert!(dot.contains("p0 [label=\"shadows\", shape=box];"));
+        assert!(dot.contains("p0 -> r0;"));
+    }
+}
@@ -0,0 +1,146 @@
+use std::rc::Rc;
+
+use glam::Vec4;
+
+use crate::{
+    assets::MeshHandle,
+    camera::Camera,
+    debug_draw::DebugDraw,
+    handle::ProgramId,
+    material::MaterialHandle,
+    mesh::{MeshError, NormalLines},
+    nodetree::{NodeId, NodeTree},
+    opengl::{OpenGl, PolygonMode, StateFlags},
+};
+
+/// A mesh drawn with a material at the world matrix of a node of its [`Scene`]
+#[derive(Clone)]
+pub struct SceneObject {
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub transform: NodeId,
+}
+
+/// Objects placed in a node tree, drawn together to share state changes
+#[derive(Default)]
+pub struct Scene {
+    pub nodes: NodeTree<()>,
+    objects: Vec<SceneObject>,
+}
+
+impl Scene {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            nodes: NodeTree::new(),
+            objects: vec![],
+        }
+    }
+
+    pub fn add(&mut self, object: SceneObject) {
+        self.objects.push(object);
+    }
+    #[must_use]
+    pub fn objects(&self) -> &[SceneObject] {
+        &self.objects
+    }
+    /// Keeps the objects for which `keep` returns true
+    pub fn retain(&mut self, keep: impl FnMut(&SceneObject) -> bool) {
+        self.objects.retain(keep);
+    }
+
+    /// Draws the objects whose node exists, sorted by program and then material so that
+    /// each is bound once, nearest to the camera first within a material.
+    pub fn render(&self, gl: &mut OpenGl, camera: &Camera) {
+        let eye = camera.position();
+        let view_projection = camera.view_projection();
+        let draws = self
+            .objects
+            .iter()
+            .filter_map(|object| Some((object, self.nodes.world_matrix(object.transform)?)))
+            .collect::<Vec<_>>();
+        let keys = draws
+            .iter()
+            .map(|(object, matrix)| {
+                (
+                    object.material.program().id(),
+                    Rc::as_ptr(&object.material) as usize,
+                    matrix.w_axis.truncate().distance_squared(eye),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut bound: Option<&MaterialHandle> = None;
+        for i in draw_order(&keys) {
+            let (object, matrix) = &draws[i];
+            if !bound.is_some_and(|material| Rc::ptr_eq(material, &object.material)) {
+                object.material.apply(gl);
+                object.material.set_view_projection(view_projection);
+                bound = Some(&object.material);
+            }
+            object.material.set_model_to_world(*matrix);
+            object.mesh.borrow_mut().render(gl);
+        }
+    }
+
+    /// Like [`Scene::render`] with only the edges of the triangles
+    pub fn render_wireframe(&self, gl: &mut OpenGl, camera: &Camera) {
+        let mut gl = gl.save_state(StateFlags::PolygonMode);
+        gl.polygon_mode(PolygonMode::Line);
+        self.render(&mut gl, camera);
+    }
+
+    /// Queues the normals of every object, see [`crate::mesh::Mesh::draw_normals`]
+    pub fn draw_normals(
+        &self,
+        debug_draw: &mut DebugDraw,
+        normals: NormalLines,
+        length: f32,
+        color: Vec4,
+    ) -> Result<(), MeshError> {
+        for object in &self.objects {
+            if let Some(matrix) = self.nodes.world_matrix(object.transform) {
+                object
+                    .mesh
+                    .borrow()
+                    .draw_normals(debug_draw, matrix, normals, length, color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Indices of `(program, material, distance)` keys sorted in that order
+fn draw_order(keys: &[(ProgramId, usize, f32)]) -> Vec<usize> {
+    let mut order = (0..keys.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let (program_a, material_a, distance_a) = keys[a];
+        let (program_b, material_b, distance_b) = keys[b];
+        program_a
+            .cmp(&program_b)
+            .then(material_a.cmp(&material_b))
+            .then(distance_a.total_cmp(&distance_b))
+    });
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handle::ProgramId;
+
+    use super::draw_order;
+
+    #[test]
+    fn test_draw_order() {
+        let keys = [
+            (ProgramId(2), 10, 1.0),
+            (ProgramId(1), 20, 5.0),
+            (ProgramId(2), 10, 0.5),
+            (ProgramId(1), 30, 0.0),
+            (ProgramId(1), 20, 2.0),
+        ];
+        // programs together, then materials, then front to back
+        assert_eq!(draw_order(&keys), [4, 1, 3, 2, 0]);
+        assert!(draw_order(&[]).is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+use std::ffi::{CStr, CString};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use glam::{Mat4, Vec3, Vec4};
+use thiserror::Error;
+
+use crate::{
+    handle::{delete_later, GlObject},
+    mesh::Mesh,
+    opengl::{Capability, ClearFlags, ClipDepth, DepthFunc, OpenGl, StateFlags},
+    program::{Program, Shader, ShaderType},
+    scene::Scene,
+    texture::Texture,
+    viewport::Viewport,
+    GLHandle, NULL_HANDLE,
+};
+
+/// Attribute location of the per-instance model matrix read by [`ShadowMap::cast_instanced`]
+pub const INSTANCE_MATRIX_LOCATION: GLuint = 2;
+
+const VERTEX_SHADER: &CStr = c"#version 330
+
+layout(location = 0) in vec4 position;
+
+uniform mat4 modelToWorld;
+uniform mat4 worldToLight;
+
+void main() {
+    gl_Position = worldToLight * (modelToWorld * position);
+}";
+
+const INSTANCED_VERTEX_SHADER: &CStr = c"#version 330
+
+layout(location = 0) in vec4 position;
+layout(location = 2) in mat4 modelToWorld;
+
+uniform mat4 worldToLight;
+
+void main() {
+    gl_Position = worldToLight * (modelToWorld * position);
+}";
+
+const FRAGMENT_SHADER: &CStr = c"#version 330
+
+void main() {}";
+
+#[derive(Error, Debug)]
+pub enum ShadowError {
+    #[error("Shadow shader error: {0:?}")]
+    ShaderError(CString),
+    #[error("Shadow framebuffer is incomplete, status {0:#x}")]
+    IncompleteFramebuffer(GLenum),
+}
+
+/// View and orthographic projection of a directional light shining along `direction`,
+/// covering the sphere at `center` with `radius`. The depth matches `clip_depth`.
+#[must_use]
+pub fn light_space_matrix(
+    direction: Vec3,
+    center: Vec3,
+    radius: f32,
+    clip_depth: ClipDepth,
+) -> Mat4 {
+    let direction = direction.normalize();
+    // the view can't look straight along its up vector
+    let up = if direction.cross(Vec3::Y).length_squared() < 1e-6 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let view = Mat4::look_to_rh(center - direction * radius, direction, up);
+    let projection = match clip_depth {
+        ClipDepth::NegativeOneToOne => {
+            Mat4::orthographic_rh_gl(-radius, radius, -radius, radius, 0.0, 2.0 * radius)
+        }
+        ClipDepth::ZeroToOne => {
+            Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 2.0 * radius)
+        }
+    };
+    projection * view
+}
+
+/// From clip space of the light to the zero to one coordinates and depth of the shadow map
+fn texture_bias(clip_depth: ClipDepth) -> Mat4 {
+    let z_scale = match clip_depth {
+        ClipDepth::NegativeOneToOne => 0.5,
+        ClipDepth::ZeroToOne => 1.0,
+    };
+    Mat4::from_cols(
+        Vec4::new(0.5, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 0.5, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, z_scale, 0.0),
+        Vec4::new(0.5, 0.5, 1.0 - z_scale, 1.0),
+    )
+}
+
+/// Depth of the scene seen from a directional light, for shaders to test whether
+/// a point is in shadow.
+///
+/// [`ShadowMap::depth_pass`] draws the shadow casters into a depth-only framebuffer.
+/// The depth texture compares against the reference depth when sampled, so receivers
+/// declare it as `sampler2DShadow` and look it up with `textureProj` at the position
+/// transformed by [`ShadowMap::texture_matrix`].
+pub struct ShadowMap {
+    framebuffer: GLHandle,
+    depth_texture: GLHandle,
+    size: GLsizei,
+    program: Program,
+    instanced_program: Program,
+    model_to_world_location: GLint,
+    world_to_light_location: GLint,
+    instanced_world_to_light_location: GLint,
+    light_space: Mat4,
+    /// Has to match the clip control of the context, see [`OpenGl::set_reverse_z`]
+    pub clip_depth: ClipDepth,
+    /// Factor and units of the polygon offset pushing casters away from the light,
+    /// against surfaces shadowing themselves
+    pub depth_bias: (f32, f32),
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        delete_later(GlObject::Framebuffer(self.framebuffer));
+        delete_later(GlObject::Texture(self.depth_texture));
+    }
+}
+
+impl ShadowMap {
+    /// A square shadow map `size` pixels wide
+    pub fn new(size: GLsizei) -> Result<Self, ShadowError> {
+        let vertex_shader =
+            Shader::new(VERTEX_SHADER, ShaderType::Vertex).map_err(ShadowError::ShaderError)?;
+        let instanced_vertex_shader = Shader::new(INSTANCED_VERTEX_SHADER, ShaderType::Vertex)
+            .map_err(ShadowError::ShaderError)?;
+        let fragment_shader =
+            Shader::new(FRAGMENT_SHADER, ShaderType::Fragment).map_err(ShadowError::ShaderError)?;
+        let program =
+            Program::new(&[&vertex_shader, &fragment_shader]).map_err(ShadowError::ShaderError)?;
+        let instanced_program = Program::new(&[&instanced_vertex_shader, &fragment_shader])
+            .map_err(ShadowError::ShaderError)?;
+
+        let mut depth_texture = NULL_HANDLE;
+        let mut framebuffer = NULL_HANDLE;
+        let status = unsafe {
+            gl::GenTextures(1, &mut depth_texture);
+            gl::BindTexture(gl::TEXTURE_2D, depth_texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH_COMPONENT24 as GLint,
+                size,
+                size,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+            // linear filtering compares the four nearest texels and blends the results
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_COMPARE_FUNC,
+                gl::LEQUAL as GLint,
+            );
+            // outside of the map is never in shadow
+            let border = [1.0f32; 4];
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_BORDER as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_BORDER as GLint,
+            );
+            gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_2D,
+                depth_texture,
+                0,
+            );
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE);
+            status
+        };
+        let shadow_map = Self {
+            framebuffer,
+            depth_texture,
+            size,
+            model_to_world_location: program.get_uniform_location(c"modelToWorld").unwrap_or(-1),
+            world_to_light_location: program.get_uniform_location(c"worldToLight").unwrap_or(-1),
+            instanced_world_to_light_location: instanced_program
+                .get_uniform_location(c"worldToLight")
+                .unwrap_or(-1),
+            program,
+            instanced_program,
+            light_space: Mat4::IDENTITY,
+            clip_depth: ClipDepth::NegativeOneToOne,
+            depth_bias: (2.0, 4.0),
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(ShadowError::IncompleteFramebuffer(status));
+        }
+        Ok(shadow_map)
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> GLsizei {
+        self.size
+    }
+
+    /// Points the light along `direction`, covering the sphere at `center` with `radius`
+    pub fn set_light(&mut self, direction: Vec3, center: Vec3, radius: f32) {
+        self.light_space = light_space_matrix(direction, center, radius, self.clip_depth);
+    }
+    pub fn set_light_space(&mut self, light_space: Mat4) {
+        self.light_space = light_space;
+    }
+    /// From world space to the clip space of the light
+    #[must_use]
+    pub const fn light_space(&self) -> Mat4 {
+        self.light_space
+    }
+    /// From world space to the shadow map coordinates and the depth compared against them
+    #[must_use]
+    pub fn texture_matrix(&self) -> Mat4 {
+        texture_bias(self.clip_depth) * self.light_space
+    }
+
+    /// Binds the depth texture to texture `unit`, for the receivers' `sampler2DShadow`
+    pub fn bind_texture(&self, unit: GLuint) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.depth_texture);
+        };
+    }
+
+    /// Renders the depth of what `casters` draws with [`ShadowMap::cast`] and the like,
+    /// then goes back to the default framebuffer and `viewport`.
+    ///
+    /// Depth and culling state is left as it was.
+    pub fn depth_pass(
+        &self,
+        gl: &mut OpenGl,
+        viewport: &Viewport,
+        casters: impl FnOnce(&mut OpenGl, &Self),
+    ) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer) };
+        gl.viewport(0, 0, self.size, self.size);
+        {
+            let mut gl = gl.save_state(StateFlags::Depth | StateFlags::Cull);
+            gl.enable(Capability::DepthTest);
+            gl.set_depth_mask(true);
+            gl.depth_func(DepthFunc::LessEqual);
+            gl.clear_depth(1.0);
+            gl.clear(ClearFlags::Depth);
+            // thin casters like planes have no back faces to draw
+            gl.disable(Capability::CullFace);
+            gl.enable(Capability::PolygonOffsetFill);
+            gl.polygon_offset(self.depth_bias.0, self.depth_bias.1);
+
+            gl.bind_program(&self.instanced_program);
+            self.instanced_program
+                .set_uniform(self.instanced_world_to_light_location, self.light_space);
+            gl.bind_program(&self.program);
+            self.program
+                .set_uniform(self.world_to_light_location, self.light_space);
+
+            casters(&mut gl, self);
+            gl.disable(Capability::PolygonOffsetFill);
+        }
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE) };
+        viewport.apply(gl);
+    }
+
+    /// The two passes of shadow mapping: the [`ShadowMap::depth_pass`] of `casters`,
+    /// then `receivers` drawn with the depth texture bound to texture `unit`.
+    pub fn render(
+        &self,
+        gl: &mut OpenGl,
+        viewport: &Viewport,
+        unit: GLuint,
+        casters: impl FnOnce(&mut OpenGl, &Self),
+        receivers: impl FnOnce(&mut OpenGl),
+    ) {
+        self.depth_pass(gl, viewport, casters);
+        self.bind_texture(unit);
+        receivers(gl);
+    }
+
+    /// Draws `mesh` as a caster, during the depth pass
+    pub fn cast(&self, gl: &mut OpenGl, mesh: &mut Mesh, model_to_world: Mat4) {
+        gl.bind_program(&self.program);
+        self.program
+            .set_uniform(self.model_to_world_location, model_to_world);
+        mesh.render(gl);
+    }
+    /// Draws instances of `mesh` as casters, during the depth pass. Their model matrices
+    /// are sourced at [`INSTANCE_MATRIX_LOCATION`].
+    pub fn cast_instanced(&self, gl: &mut OpenGl, mesh: &mut Mesh, instance_count: GLsizei) {
+        gl.bind_program(&self.instanced_program);
+        mesh.render_instanced(gl, instance_count);
+    }
+    /// Draws the objects of `scene` as casters, during the depth pass
+    pub fn cast_scene(&self, gl: &mut OpenGl, scene: &Scene) {
+        for object in scene.objects() {
+            if let Some(matrix) = scene.nodes.world_matrix(object.transform) {
+                self.cast(gl, &mut object.mesh.borrow_mut(), matrix);
+            }
+        }
+    }
+}
+
+impl Texture for ShadowMap {
+    fn bind_texture(&self, unit: GLuint) {
+        Self::bind_texture(self, unit);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::{light_space_matrix, texture_bias};
+    use crate::opengl::ClipDepth;
+
+    #[test]
+    fn test_light_space_matrix() {
+        let center = Vec3::new(10.0, 0.0, -5.0);
+        let direction = Vec3::new(-1.0, -2.0, -1.0);
+        for clip_depth in [ClipDepth::NegativeOneToOne, ClipDepth::ZeroToOne] {
+            let matrix =
+                texture_bias(clip_depth) * light_space_matrix(direction, center, 20.0, clip_depth);
+            let project = |point: Vec3| matrix.project_point3(point);
+            // the center is in the middle of the map
+            assert!(project(center).abs_diff_eq(Vec3::splat(0.5), 1e-5));
+            // the edge of the sphere nearest to the light is at depth 0
+            let near = project(center - direction.normalize() * 20.0);
+            assert!(near.z.abs() < 1e-5);
+            // points further along the light are deeper
+            assert!(project(center + direction).z > 0.5);
+        }
+        // straight down doesn't break the view
+        let down = light_space_matrix(Vec3::NEG_Y, Vec3::ZERO, 1.0, ClipDepth::ZeroToOne);
+        assert!(down.is_finite());
+        let origin = down.project_point3(Vec3::ZERO);
+        assert!(origin.x.abs() < 1e-6 && origin.y.abs() < 1e-6);
+    }
+}
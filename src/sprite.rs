@@ -0,0 +1,474 @@
+use std::{ffi::CString, marker::PhantomData, ops::Range};
+
+use gl::types::{GLint, GLsizei, GLuint};
+use glam::{Mat4, Vec2, Vec4};
+use thiserror::Error;
+
+use crate::{
+    buffer::{Buffer, Target, Usage},
+    color::color_texture_format,
+    handle::{delete_later, ContextBound, GlObject},
+    opengl::{BlendFactor, Capability, OpenGl, Primitive, StateFlags},
+    program::{Program, Shader, ShaderType},
+    texture::Texture,
+    vertex_attributes::{DataType, VertexArrayObject, VertexAttribute, VertexLayout},
+    GLHandle, NULL_HANDLE,
+};
+
+const VERTEX_SHADER: &std::ffi::CStr = c"#version 330
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+uniform mat4 projection;
+
+out vec2 sprite_uv;
+out vec4 sprite_color;
+
+void main() {
+    gl_Position = projection * vec4(position, 0.0, 1.0);
+    sprite_uv = uv;
+    sprite_color = color;
+}
+";
+
+const FRAGMENT_SHADER: &std::ffi::CStr = c"#version 330
+
+uniform sampler2D atlas;
+
+in vec2 sprite_uv;
+in vec4 sprite_color;
+
+out vec4 output_color;
+
+void main() {
+    output_color = texture(atlas, sprite_uv) * sprite_color;
+}
+";
+
+#[derive(Error, Debug)]
+pub enum SpriteError {
+    #[error("Sprite shader error: {0:?}")]
+    ShaderError(CString),
+    #[error("Expected {expected} bytes of RGBA pixels, got {actual}")]
+    PixelCount { expected: usize, actual: usize },
+}
+
+/// Projection of pixels from the top left of a `size` screen, y going down like
+/// [`crate::text::TextRenderer`]
+#[must_use]
+pub fn screen_projection(size: Vec2) -> Mat4 {
+    Mat4::orthographic_rh_gl(0.0, size.x, size.y, 0.0, -1.0, 1.0)
+}
+
+/// Part of a texture, in texture coordinates from its top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl UvRect {
+    /// The whole texture
+    pub const FULL: Self = Self {
+        min: Vec2::ZERO,
+        max: Vec2::ONE,
+    };
+
+    /// The same rect mirrored horizontally
+    #[must_use]
+    pub const fn flip_x(self) -> Self {
+        Self {
+            min: Vec2::new(self.max.x, self.min.y),
+            max: Vec2::new(self.min.x, self.max.y),
+        }
+    }
+}
+
+/// An RGBA texture sprites are cut from, sampled without filtering
+pub struct TextureAtlas {
+    texture: GLHandle,
+    width: u32,
+    height: u32,
+    _context: ContextBound,
+}
+
+impl Drop for TextureAtlas {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.texture));
+    }
+}
+
+impl TextureAtlas {
+    /// Uploads `width` by `height` RGBA pixels, rows from the top. With `srgb` the colors are
+    /// decoded to linear when sampled, see [`crate::app::AppConfig::srgb`].
+    pub fn from_rgba(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        srgb: bool,
+    ) -> Result<Self, SpriteError> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(SpriteError::PixelCount {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+        let mut texture = NULL_HANDLE;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                color_texture_format(srgb) as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr().cast(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+        };
+        Ok(Self {
+            texture,
+            width,
+            height,
+            _context: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The rect of `width` by `height` pixels at `x`, `y` from the top left
+    #[must_use]
+    pub fn region(&self, x: u32, y: u32, width: u32, height: u32) -> UvRect {
+        let size = Vec2::new(self.width as f32, self.height as f32);
+        UvRect {
+            min: Vec2::new(x as f32, y as f32) / size,
+            max: Vec2::new((x + width) as f32, (y + height) as f32) / size,
+        }
+    }
+
+    /// Cell `index` of a sheet split evenly into `columns` by `rows`, counting along rows
+    #[must_use]
+    pub fn cell(&self, columns: u32, rows: u32, index: u32) -> UvRect {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let cell = Vec2::new(columns as f32, rows as f32).recip();
+        let min = Vec2::new((index % columns) as f32, (index / columns) as f32) * cell;
+        UvRect {
+            min,
+            max: min + cell,
+        }
+    }
+}
+
+impl Texture for TextureAtlas {
+    fn bind_texture(&self, unit: GLuint) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        };
+    }
+}
+
+/// A textured quad, see [`SpriteBatch::draw`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Point rotated around and placed at `position`, from (0, 0) at the top left
+    /// to (1, 1) at the bottom right
+    pub origin: Vec2,
+    /// Radians, clockwise on screen
+    pub rotation: f32,
+    pub uv: UvRect,
+    /// Multiplies the texture
+    pub color: Vec4,
+}
+
+impl Sprite {
+    /// The whole texture untinted, with its top left corner at `position`
+    #[must_use]
+    pub const fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            position,
+            size,
+            origin: Vec2::ZERO,
+            rotation: 0.0,
+            uv: UvRect::FULL,
+            color: Vec4::ONE,
+        }
+    }
+    #[must_use]
+    pub const fn with_uv(mut self, uv: UvRect) -> Self {
+        self.uv = uv;
+        self
+    }
+    #[must_use]
+    pub const fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+    #[must_use]
+    pub const fn with_rotation(mut self, rotation: f32, origin: Vec2) -> Self {
+        self.rotation = rotation;
+        self.origin = origin;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct SpriteVertex {
+    position: Vec2,
+    uv: Vec2,
+    color: Vec4,
+}
+
+/// Vertices drawn with the same texture in one call
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Batch {
+    texture: GLHandle,
+    vertices: Range<usize>,
+}
+
+/// Appends the two triangles of `sprite`
+fn push_sprite(vertices: &mut Vec<SpriteVertex>, sprite: &Sprite) {
+    let (sin, cos) = sprite.rotation.sin_cos();
+    let corner = |x: f32, y: f32| {
+        let offset = (Vec2::new(x, y) - sprite.origin) * sprite.size;
+        SpriteVertex {
+            position: sprite.position
+                + Vec2::new(
+                    offset.x.mul_add(cos, -offset.y * sin),
+                    offset.x.mul_add(sin, offset.y * cos),
+                ),
+            uv: sprite.uv.min + (sprite.uv.max - sprite.uv.min) * Vec2::new(x, y),
+            color: sprite.color,
+        }
+    };
+    let [top_left, top_right, bottom_left, bottom_right] =
+        [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(x, y)| corner(x, y));
+    vertices.extend([
+        top_left,
+        bottom_left,
+        top_right,
+        top_right,
+        bottom_left,
+        bottom_right,
+    ]);
+}
+
+/// Extends the last batch if it has the same texture, or starts a new one
+fn add_to_batches(batches: &mut Vec<Batch>, texture: GLHandle, vertices: Range<usize>) {
+    match batches.last_mut() {
+        Some(batch) if batch.texture == texture && batch.vertices.end == vertices.start => {
+            batch.vertices.end = vertices.end;
+        }
+        _ => batches.push(Batch { texture, vertices }),
+    }
+}
+
+/// Draws 2D sprites, queued with [`SpriteBatch::draw`] and drawn in order by
+/// [`SpriteBatch::flush`].
+///
+/// The vertices are uploaded together to one buffer, and consecutive sprites from the same
+/// texture are drawn in a single call, so sprites should be grouped by atlas when possible.
+pub struct SpriteBatch {
+    program: Program,
+    vao: VertexArrayObject,
+    vertex_buffer: Buffer<SpriteVertex>,
+    // 1x1 white texture for colored rectangles
+    white: TextureAtlas,
+    projection_location: GLint,
+    vertices: Vec<SpriteVertex>,
+    batches: Vec<Batch>,
+    draw_calls: usize,
+}
+
+impl SpriteBatch {
+    pub fn new(gl: &mut OpenGl) -> Result<Self, SpriteError> {
+        let vertex_shader =
+            Shader::new(VERTEX_SHADER, ShaderType::Vertex).map_err(SpriteError::ShaderError)?;
+        let fragment_shader =
+            Shader::new(FRAGMENT_SHADER, ShaderType::Fragment).map_err(SpriteError::ShaderError)?;
+        let program =
+            Program::new(&[vertex_shader, fragment_shader]).map_err(SpriteError::ShaderError)?;
+        let projection_location = program.get_uniform_location(c"projection").unwrap_or(-1);
+
+        let mut vao = VertexArrayObject::new();
+        let vertex_buffer = Buffer::new(Target::ArrayBuffer);
+        let layout = VertexLayout::new()
+            .attribute(0, VertexAttribute::new(2, DataType::Float, false))
+            .attribute(1, VertexAttribute::new(2, DataType::Float, false))
+            .attribute(2, VertexAttribute::new(4, DataType::Float, false));
+        gl.bind_vertex_array(&vao);
+        gl.bind_buffer(&vertex_buffer);
+        vao.set_layout(&layout);
+        gl.unbind_vertex_array();
+        gl.unbind_buffer(Target::ArrayBuffer);
+
+        Ok(Self {
+            program,
+            vao,
+            vertex_buffer,
+            white: TextureAtlas::from_rgba(1, 1, &[u8::MAX; 4], false)?,
+            projection_location,
+            vertices: vec![],
+            batches: vec![],
+            draw_calls: 0,
+        })
+    }
+
+    /// Queues `sprite` cut from `atlas`, which has to be alive until the next flush
+    pub fn draw(&mut self, atlas: &TextureAtlas, sprite: &Sprite) {
+        let start = self.vertices.len();
+        push_sprite(&mut self.vertices, sprite);
+        add_to_batches(&mut self.batches, atlas.texture, start..self.vertices.len());
+    }
+
+    /// Queues a rectangle of a single color, `sprite.uv` is ignored
+    pub fn draw_rect(&mut self, sprite: &Sprite) {
+        let start = self.vertices.len();
+        push_sprite(&mut self.vertices, sprite);
+        add_to_batches(
+            &mut self.batches,
+            self.white.texture,
+            start..self.vertices.len(),
+        );
+    }
+
+    /// Draw calls made by the last flush
+    #[must_use]
+    pub const fn draw_calls(&self) -> usize {
+        self.draw_calls
+    }
+
+    /// Draws the queued sprites in order with `projection`, like [`screen_projection`],
+    /// and empties the queue.
+    ///
+    /// Leaves the depth and blend state as it was, the last texture stays bound to unit 0.
+    pub fn flush(&mut self, gl: &mut OpenGl, projection: Mat4) {
+        self.draw_calls = self.batches.len();
+        if self.vertices.is_empty() {
+            return;
+        }
+        gl.bind_buffer(&self.vertex_buffer);
+        self.vertex_buffer
+            .buffer_data(&self.vertices, Usage::StreamDraw);
+        gl.unbind_buffer(Target::ArrayBuffer);
+
+        let mut gl = gl.save_state(StateFlags::Depth | StateFlags::Blend);
+        gl.disable(Capability::DepthTest);
+        gl.enable(Capability::Blend);
+        gl.blend_func(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+
+        gl.bind_program(&self.program);
+        self.program
+            .set_uniform(self.projection_location, projection);
+        gl.bind_vertex_array(&self.vao);
+        unsafe { gl::ActiveTexture(gl::TEXTURE0) };
+        for batch in self.batches.drain(..) {
+            unsafe { gl::BindTexture(gl::TEXTURE_2D, batch.texture) };
+            gl.draw_arrays(
+                Primitive::Triangles,
+                batch.vertices.start as GLint,
+                batch.vertices.len() as GLsizei,
+            );
+        }
+        gl.unbind_vertex_array();
+        self.vertices.clear();
+        gl.unbind_program();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Vec2, Vec3, Vec4};
+
+    use super::{add_to_batches, push_sprite, screen_projection, Batch, Sprite, UvRect};
+
+    #[test]
+    fn test_push_sprite() {
+        let mut vertices = vec![];
+        let uv = UvRect {
+            min: Vec2::new(0.5, 0.0),
+            max: Vec2::new(1.0, 0.5),
+        };
+        push_sprite(
+            &mut vertices,
+            &Sprite::new(Vec2::new(10.0, 20.0), Vec2::new(4.0, 2.0)).with_uv(uv),
+        );
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(vertices[0].position, Vec2::new(10.0, 20.0));
+        assert_eq!(vertices[0].uv, uv.min);
+        assert_eq!(vertices[5].position, Vec2::new(14.0, 22.0));
+        assert_eq!(vertices[5].uv, uv.max);
+
+        // a quarter turn around the center
+        vertices.clear();
+        let sprite = Sprite::new(Vec2::ZERO, Vec2::splat(2.0))
+            .with_rotation(std::f32::consts::FRAC_PI_2, Vec2::splat(0.5))
+            .with_color(Vec4::ZERO);
+        push_sprite(&mut vertices, &sprite);
+        assert!((vertices[0].position - Vec2::new(1.0, -1.0)).length() < 1e-5);
+        assert_eq!(vertices[0].color, Vec4::ZERO);
+    }
+
+    #[test]
+    fn test_batches() {
+        let mut batches = vec![];
+        add_to_batches(&mut batches, 1, 0..6);
+        add_to_batches(&mut batches, 1, 6..12);
+        add_to_batches(&mut batches, 2, 12..18);
+        add_to_batches(&mut batches, 1, 18..24);
+        assert_eq!(
+            batches,
+            [
+                Batch {
+                    texture: 1,
+                    vertices: 0..12
+                },
+                Batch {
+                    texture: 2,
+                    vertices: 12..18
+                },
+                Batch {
+                    texture: 1,
+                    vertices: 18..24
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_screen_projection() {
+        let projection = screen_projection(Vec2::new(200.0, 100.0));
+        let top_left = projection.project_point3(Vec3::ZERO);
+        let bottom_right = projection.project_point3(Vec3::new(200.0, 100.0, 0.0));
+        assert!((top_left - Vec3::new(-1.0, 1.0, 0.0)).length() < 1e-6);
+        assert!((bottom_right - Vec3::new(1.0, -1.0, 0.0)).length() < 1e-6);
+    }
+}
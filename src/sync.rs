@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use gl::types::{GLenum, GLint, GLsizei, GLsizeiptr, GLsync, GLuint64};
+
+use crate::{
+    buffer::{Buffer, Target, Usage},
+    handle::{delete_later, GlObject},
+    texture::TextureFormat,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum WaitStatus {
+    /// The fence was signaled before the call
+    AlreadySignaled = gl::ALREADY_SIGNALED,
+    /// The fence was signaled during the call
+    ConditionSatisfied = gl::CONDITION_SATISFIED,
+    TimeoutExpired = gl::TIMEOUT_EXPIRED,
+    WaitFailed = gl::WAIT_FAILED,
+}
+
+impl WaitStatus {
+    const fn from_gl(status: GLenum) -> Self {
+        match status {
+            gl::ALREADY_SIGNALED => Self::AlreadySignaled,
+            gl::CONDITION_SATISFIED => Self::ConditionSatisfied,
+            gl::TIMEOUT_EXPIRED => Self::TimeoutExpired,
+            _ => Self::WaitFailed,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_signaled(self) -> bool {
+        matches!(self, Self::AlreadySignaled | Self::ConditionSatisfied)
+    }
+}
+
+/// Signaled once the GPU has executed every command issued before it,
+/// created with [`crate::opengl::OpenGl::fence_sync`].
+pub struct Fence {
+    sync: GLsync,
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        delete_later(GlObject::Sync(self.sync));
+    }
+}
+
+impl Fence {
+    pub(crate) fn new() -> Self {
+        let sync = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        Self { sync }
+    }
+
+    /// Blocks until the fence is signaled or `timeout` expires,
+    /// flushing the command queue so the fence can be reached.
+    #[must_use]
+    pub fn client_wait(&self, timeout: Duration) -> WaitStatus {
+        let timeout = GLuint64::try_from(timeout.as_nanos()).unwrap_or(GLuint64::MAX);
+        let status = unsafe { gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+        WaitStatus::from_gl(status)
+    }
+
+    /// Makes the GPU wait for the fence before executing later commands, without blocking.
+    pub fn wait(&self) {
+        unsafe { gl::WaitSync(self.sync, 0, gl::TIMEOUT_IGNORED) };
+    }
+
+    #[must_use]
+    pub fn is_signaled(&self) -> bool {
+        let mut status = 0;
+        unsafe {
+            gl::GetSynciv(
+                self.sync,
+                gl::SYNC_STATUS,
+                1,
+                std::ptr::null_mut(),
+                &mut status,
+            );
+        };
+        status == gl::SIGNALED as GLint
+    }
+}
+
+/// Pixels on their way into a pixel buffer, created with
+/// [`crate::opengl::OpenGl::read_pixels_async`]. Reading them once the fence is signaled
+/// doesn't stall the pipeline.
+pub struct PendingPixels {
+    buffer: Buffer<u8>,
+    fence: Fence,
+}
+
+impl PendingPixels {
+    pub(crate) fn new(
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: TextureFormat,
+    ) -> Self {
+        let mut buffer = Buffer::new(Target::PixelPackBuffer);
+        buffer.bind();
+        let size = width.max(0) as usize * height.max(0) as usize * format.pixel_size();
+        buffer.reserve_data_bytes(size as GLsizeiptr, Usage::StreamRead);
+        let (pixel_format, pixel_type) = format.pixel_format();
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            // into the bound buffer from its start
+            gl::ReadPixels(
+                x,
+                y,
+                width,
+                height,
+                pixel_format,
+                pixel_type,
+                std::ptr::null_mut(),
+            );
+        };
+        // a bound pack buffer would catch later reads
+        buffer.unbind();
+        Self {
+            buffer,
+            fence: Fence::new(),
+        }
+    }
+
+    /// Whether the pixels arrived, reading them won't block
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.fence.is_signaled()
+    }
+
+    /// Waits up to `timeout` for the pixels, rows from the bottom,
+    /// `Duration::ZERO` polls without blocking
+    pub fn read(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+        if !self.fence.client_wait(timeout).is_signaled() {
+            return None;
+        }
+        let pixels = self.buffer.map().ok().map(|pixels| pixels.to_vec());
+        self.buffer.unbind();
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WaitStatus;
+
+    #[test]
+    fn test_wait_status() {
+        assert!(WaitStatus::from_gl(gl::ALREADY_SIGNALED).is_signaled());
+        assert!(WaitStatus::from_gl(gl::CONDITION_SATISFIED).is_signaled());
+        assert!(!WaitStatus::from_gl(gl::TIMEOUT_EXPIRED).is_signaled());
+        assert_eq!(WaitStatus::from_gl(0), WaitStatus::WaitFailed);
+    }
+}
@@ -0,0 +1,307 @@
+//! Golden image tests: render a frame of an [`Application`] offscreen with
+//! [`render_once_to_image`] and compare it to a reference with [`assert_matches_golden`].
+//!
+//! ```no_run
+//! # use opengl_rend::{app::Application, testing::{assert_matches_golden, render_once_to_image}};
+//! # fn test<MyApp: Application>() {
+//! let image = render_once_to_image::<MyApp>((128, 128)).unwrap();
+//! assert_matches_golden(&image, "tests/golden/my_app.pam", 2);
+//! # }
+//! ```
+
+use std::{marker::PhantomData, path::Path};
+
+use gl::types::{GLenum, GLsizei};
+use glfw::Context;
+use thiserror::Error;
+
+use crate::{
+    app::{hidden_window, AppError, Application},
+    handle::{self, delete_later, ContextBound, GlObject},
+    image::{byte_count, ImageError},
+    input::Input,
+    viewport::Viewport,
+    GLHandle, NULL_HANDLE,
+};
+
+pub use crate::image::{ImageDiff, RgbaImage};
+
+/// Set to write the golden images instead of comparing against them
+pub const UPDATE_GOLDEN_VAR: &str = "UPDATE_GOLDEN";
+
+#[derive(Error, Debug)]
+pub enum TestingError {
+    #[error(transparent)]
+    AppError(#[from] AppError),
+    #[error("Offscreen framebuffer incomplete, status {0:#x}")]
+    Incomplete(GLenum),
+    #[error(transparent)]
+    ImageError(#[from] ImageError),
+    #[error("Invalid image: {0}")]
+    InvalidImage(&'static str),
+}
+
+/// Color and depth renderbuffers the frame is drawn into
+struct OffscreenTarget {
+    framebuffer: GLHandle,
+    color_renderbuffer: GLHandle,
+    depth_renderbuffer: GLHandle,
+    _context: ContextBound,
+}
+
+impl Drop for OffscreenTarget {
+    fn drop(&mut self) {
+        delete_later(GlObject::Framebuffer(self.framebuffer));
+        delete_later(GlObject::Renderbuffer(self.color_renderbuffer));
+        delete_later(GlObject::Renderbuffer(self.depth_renderbuffer));
+    }
+}
+
+impl OffscreenTarget {
+    /// Leaves the target bound
+    fn new(width: GLsizei, height: GLsizei) -> Result<Self, TestingError> {
+        let mut target = Self {
+            framebuffer: NULL_HANDLE,
+            color_renderbuffer: NULL_HANDLE,
+            depth_renderbuffer: NULL_HANDLE,
+            _context: PhantomData,
+        };
+        let status = unsafe {
+            gl::GenRenderbuffers(1, &mut target.color_renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, target.color_renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+            gl::GenRenderbuffers(1, &mut target.depth_renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, target.depth_renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, NULL_HANDLE);
+
+            gl::GenFramebuffers(1, &mut target.framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.framebuffer);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                target.color_renderbuffer,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                target.depth_renderbuffer,
+            );
+            gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(TestingError::Incomplete(status));
+        }
+        Ok(target)
+    }
+
+    /// Rows from the top, flipped from the bottom up order of GL
+    fn read_pixels(&self, width: GLsizei, height: GLsizei, size: usize) -> Vec<u8> {
+        let mut pixels = vec![0; size];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, NULL_HANDLE);
+        };
+        let row = size / height.max(1) as usize;
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for line in pixels.chunks(row).rev() {
+            flipped.extend_from_slice(line);
+        }
+        flipped
+    }
+}
+
+/// Renders a frame of `A` offscreen and returns it.
+///
+/// `A` is created on a hidden window of `size` pixels. [`Application::reshape`] and
+/// [`Application::update`] with no time elapsed are called, then [`Application::display`]
+/// with an offscreen framebuffer bound.
+///
+/// Applications binding framebuffer 0 themselves, to draw to the window after an offscreen
+/// pass, draw past the image.
+pub fn render_once_to_image<A: Application>(size: (u32, u32)) -> Result<RgbaImage, TestingError> {
+    let (width, height) = (size.0.max(1), size.1.max(1));
+    let bytes = byte_count(width, height).ok_or(TestingError::InvalidImage("too large"))?;
+    let (Ok(gl_width), Ok(gl_height)) = (GLsizei::try_from(width), GLsizei::try_from(height))
+    else {
+        return Err(TestingError::InvalidImage("too large"));
+    };
+    let (_glfw, window) = hidden_window(width, height)?;
+    let mut app = A::new(window)?;
+    let (width, height) = (gl_width, gl_height);
+    let target = OffscreenTarget::new(width, height)?;
+    app.reshape(&Viewport::new(width, height));
+    app.update(0.0, &Input::default());
+    unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, target.framebuffer) };
+    app.display();
+    let pixels = target.read_pixels(width, height, bytes);
+    drop(target);
+    // the window goes with the application, and what's still queued with its context
+    let context = app.window().window_ptr();
+    drop(app);
+    handle::forget_context(context);
+    RgbaImage::from_raw(size.0.max(1), size.1.max(1), pixels)
+        .ok_or(TestingError::InvalidImage("wrong pixel count"))
+}
+
+/// Fails unless the images have the same size and no channel differs by more than
+/// `tolerance`.
+#[track_caller]
+pub fn assert_images_match(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) {
+    let diff = actual.diff(expected, tolerance);
+    assert!(
+        diff.is_some(),
+        "image is {}x{}, expected {}x{}",
+        actual.width(),
+        actual.height(),
+        expected.width(),
+        expected.height()
+    );
+    let diff = diff.unwrap_or_default();
+    assert!(
+        diff.matches(),
+        "{} pixels differ by more than {tolerance}, up to {}",
+        diff.differing_pixels,
+        diff.max_channel_difference
+    );
+}
+
+/// Compares `actual` to the image saved at `path`, see [`assert_images_match`]. Writes it
+/// there instead when the file is missing or [`UPDATE_GOLDEN_VAR`] is set.
+#[track_caller]
+#[allow(clippy::panic)]
+pub fn assert_matches_golden(actual: &RgbaImage, path: impl AsRef<Path>, tolerance: u8) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() || !path.exists() {
+        if let Err(error) = actual.save(path) {
+            panic!("failed to save {}: {error}", path.display());
+        }
+        return;
+    }
+    match RgbaImage::load(path) {
+        Ok(expected) => assert_images_match(actual, &expected, tolerance),
+        Err(error) => panic!("failed to load {}: {error}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+    use glfw::PWindow;
+
+    use super::{assert_images_match, assert_matches_golden, render_once_to_image, RgbaImage};
+    use crate::{
+        app::{AppError, Application},
+        matrix_stack::MatrixStack,
+        mesh::Mesh,
+        opengl::{ClearFlags, OpenGl},
+        program::{GLLocation, Program, Shader, ShaderType},
+        viewport::Viewport,
+    };
+
+    /// Two green unit planes side by side, a quarter of the width each
+    struct PlanesApp {
+        window: PWindow,
+        gl: OpenGl,
+        program: Program,
+        model_to_clip: GLLocation,
+        plane: Mesh,
+    }
+
+    impl Application for PlanesApp {
+        fn new(mut window: PWindow) -> Result<Self, AppError> {
+            let gl = OpenGl::new(&mut window);
+            let vertex = Shader::new(
+                c"#version 330
+                layout(location = 0) in vec3 position;
+                uniform mat4 modelToClip;
+                void main() { gl_Position = modelToClip * vec4(position, 1.0); }",
+                ShaderType::Vertex,
+            )
+            .unwrap();
+            let fragment = Shader::new(
+                c"#version 330
+                out vec4 outColor;
+                void main() { outColor = vec4(0.0, 1.0, 0.0, 1.0); }",
+                ShaderType::Fragment,
+            )
+            .unwrap();
+            let program = Program::new(&[vertex, fragment]).unwrap();
+            let model_to_clip = program.get_uniform_location(c"modelToClip").unwrap();
+            Ok(Self {
+                window,
+                gl,
+                program,
+                model_to_clip,
+                plane: Mesh::new("resources/test/UnitPlane.xml").unwrap(),
+            })
+        }
+
+        fn display(&mut self) {
+            self.gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            self.gl.clear(ClearFlags::Color);
+            self.gl.bind_program(&self.program);
+            let mut stack = MatrixStack::new();
+            stack.uniform_scale(0.5);
+            for x in [-1.0, 1.0] {
+                stack.with_pushed(|stack| {
+                    stack.translate(Vec3::new(x, 0.0, 0.0));
+                    // the plane lies in XZ, facing the camera once rotated
+                    stack.rotate_x(90.0);
+                    self.program.set_uniform(self.model_to_clip, stack.top());
+                    self.plane.render(&mut self.gl);
+                });
+            }
+        }
+
+        fn reshape(&mut self, viewport: &Viewport) {
+            viewport.apply(&mut self.gl);
+        }
+
+        fn window(&self) -> &PWindow {
+            &self.window
+        }
+        fn window_mut(&mut self) -> &mut PWindow {
+            &mut self.window
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "1 pixels differ")]
+    fn test_assert_images_match() {
+        let image = RgbaImage::from_raw(2, 2, vec![100; 16]).unwrap();
+        let mut pixels = vec![100; 16];
+        pixels[0] = 200;
+        let other = RgbaImage::from_raw(2, 2, pixels).unwrap();
+        assert_images_match(&image, &other, 3);
+        assert_images_match(&image, &other, 0);
+    }
+
+    #[test]
+    fn test_render_once_to_image() {
+        let image = render_once_to_image::<PlanesApp>((16, 16)).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 16));
+        assert_eq!(image.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(3, 7), [0, 255, 0, 255]);
+        assert_eq!(image.pixel(8, 7), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(12, 8), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_mesh_golden() {
+        let image = render_once_to_image::<PlanesApp>((16, 16)).unwrap();
+        assert_matches_golden(&image, "resources/test/golden/planes.pam", 0);
+    }
+}
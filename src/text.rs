@@ -0,0 +1,300 @@
+use std::ffi::CString;
+
+use gl::types::{GLint, GLsizei};
+use glam::{Vec2, Vec4};
+use thiserror::Error;
+
+use crate::{
+    buffer::{Buffer, Target, Usage},
+    handle::{delete_later, GlObject},
+    opengl::{BlendFactor, Capability, OpenGl, Primitive, StateFlags},
+    program::{Program, Shader, ShaderType},
+    vertex_attributes::{DataType, VertexArrayObject, VertexAttribute, VertexLayout},
+    GLHandle, NULL_HANDLE,
+};
+
+mod font;
+
+use font::FONT;
+
+/// Side of a glyph in font pixels
+const GLYPH_SIZE: usize = 8;
+/// Distance between baselines in font pixels
+const LINE_HEIGHT: f32 = 10.0;
+const FIRST_CHAR: char = ' ';
+
+const VERTEX_SHADER: &std::ffi::CStr = c"#version 330
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+uniform vec2 screen_size;
+
+out vec2 glyph_uv;
+out vec4 glyph_color;
+
+void main() {
+    vec2 ndc = position / screen_size * 2.0 - 1.0;
+    gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+    glyph_uv = uv;
+    glyph_color = color;
+}
+";
+
+const FRAGMENT_SHADER: &std::ffi::CStr = c"#version 330
+
+uniform sampler2D atlas;
+
+in vec2 glyph_uv;
+in vec4 glyph_color;
+
+out vec4 output_color;
+
+void main() {
+    float coverage = texelFetch(atlas, ivec2(glyph_uv), 0).r;
+    output_color = vec4(glyph_color.rgb, glyph_color.a * coverage);
+}
+";
+
+#[derive(Error, Debug)]
+pub enum TextError {
+    #[error("Text shader error: {0:?}")]
+    ShaderError(CString),
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct TextVertex {
+    /// Pixels from the top left of the screen
+    position: Vec2,
+    /// Texels from the top left of the atlas
+    uv: Vec2,
+    color: Vec4,
+}
+
+/// The font glyphs side by side in a single row, one byte per texel
+fn atlas_pixels() -> Vec<u8> {
+    let width = FONT.len() * GLYPH_SIZE;
+    let mut pixels = vec![0; width * GLYPH_SIZE];
+    for (glyph, rows) in FONT.iter().enumerate() {
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..GLYPH_SIZE {
+                if row & (1 << x) != 0 {
+                    pixels[y * width + glyph * GLYPH_SIZE + x] = u8::MAX;
+                }
+            }
+        }
+    }
+    pixels
+}
+
+/// Index in [`FONT`], characters outside of printable ASCII are drawn as '?'
+fn glyph_index(c: char) -> usize {
+    let index = (c as usize).wrapping_sub(FIRST_CHAR as usize);
+    if index < FONT.len() {
+        index
+    } else {
+        '?' as usize - FIRST_CHAR as usize
+    }
+}
+
+/// Appends two triangles per visible character, '\n' starts a new line below `position`
+fn push_text(vertices: &mut Vec<TextVertex>, text: &str, position: Vec2, scale: f32, color: Vec4) {
+    let size = GLYPH_SIZE as f32 * scale;
+    let mut pen = position;
+    for c in text.chars() {
+        if c == '\n' {
+            pen = Vec2::new(position.x, LINE_HEIGHT.mul_add(scale, pen.y));
+            continue;
+        }
+        if c != ' ' {
+            let u = (glyph_index(c) * GLYPH_SIZE) as f32;
+            let corner = |x: f32, y: f32| TextVertex {
+                position: pen + Vec2::new(x, y) * size,
+                uv: Vec2::new(x.mul_add(GLYPH_SIZE as f32, u), y * GLYPH_SIZE as f32),
+                color,
+            };
+            let [top_left, top_right, bottom_left, bottom_right] =
+                [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(x, y)| corner(x, y));
+            vertices.extend([
+                top_left,
+                bottom_left,
+                top_right,
+                top_right,
+                bottom_left,
+                bottom_right,
+            ]);
+        }
+        pen.x += size;
+    }
+}
+
+/// Draws screen space text with the built in 8x8 bitmap font.
+///
+/// Strings are queued with [`TextRenderer::queue`] and drawn together by
+/// [`TextRenderer::draw`] in one draw call, on top of everything else.
+pub struct TextRenderer {
+    program: Program,
+    vao: VertexArrayObject,
+    vertex_buffer: Buffer<TextVertex>,
+    atlas: GLHandle,
+    screen_size_location: GLint,
+    vertices: Vec<TextVertex>,
+    scale: f32,
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.atlas));
+    }
+}
+
+impl TextRenderer {
+    pub fn new(gl: &mut OpenGl) -> Result<Self, TextError> {
+        let vertex_shader =
+            Shader::new(VERTEX_SHADER, ShaderType::Vertex).map_err(TextError::ShaderError)?;
+        let fragment_shader =
+            Shader::new(FRAGMENT_SHADER, ShaderType::Fragment).map_err(TextError::ShaderError)?;
+        let program =
+            Program::new(&[vertex_shader, fragment_shader]).map_err(TextError::ShaderError)?;
+        let screen_size_location = program.get_uniform_location(c"screen_size").unwrap_or(-1);
+
+        let mut vao = VertexArrayObject::new();
+        let vertex_buffer = Buffer::new(Target::ArrayBuffer);
+        let layout = VertexLayout::new()
+            .attribute(0, VertexAttribute::new(2, DataType::Float, false))
+            .attribute(1, VertexAttribute::new(2, DataType::Float, false))
+            .attribute(2, VertexAttribute::new(4, DataType::Float, false));
+        gl.bind_vertex_array(&vao);
+        gl.bind_buffer(&vertex_buffer);
+        vao.set_layout(&layout);
+        gl.unbind_vertex_array();
+        gl.unbind_buffer(Target::ArrayBuffer);
+
+        let mut atlas = NULL_HANDLE;
+        let pixels = atlas_pixels();
+        unsafe {
+            gl::GenTextures(1, &mut atlas);
+            gl::BindTexture(gl::TEXTURE_2D, atlas);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R8 as GLint,
+                (FONT.len() * GLYPH_SIZE) as GLsizei,
+                GLYPH_SIZE as GLsizei,
+                0,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr().cast(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+        };
+
+        Ok(Self {
+            program,
+            vao,
+            vertex_buffer,
+            atlas,
+            screen_size_location,
+            vertices: vec![],
+            scale: 2.0,
+        })
+    }
+
+    /// Screen pixels per font pixel
+    #[must_use]
+    pub const fn scale(&self) -> f32 {
+        self.scale
+    }
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Size in pixels of `text` drawn at the current scale
+    #[must_use]
+    pub fn measure(&self, text: &str) -> Vec2 {
+        let columns = text.lines().map(|line| line.chars().count()).max();
+        let lines = text.lines().count();
+        let width = columns.unwrap_or_default() * GLYPH_SIZE;
+        let height = match lines {
+            0 => 0.0,
+            lines => ((lines - 1) as f32).mul_add(LINE_HEIGHT, GLYPH_SIZE as f32),
+        };
+        Vec2::new(width as f32, height) * self.scale
+    }
+
+    /// Adds `text` with its top left corner at `position`, in pixels from the top left of the screen
+    pub fn queue(&mut self, text: &str, position: Vec2, color: Vec4) {
+        push_text(&mut self.vertices, text, position, self.scale, color);
+    }
+
+    /// Draws the queued text over a `width` by `height` viewport and empties the queue.
+    ///
+    /// Leaves the depth and blend state as it was, the atlas stays bound to texture unit 0.
+    pub fn draw(&mut self, gl: &mut OpenGl, width: i32, height: i32) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        gl.bind_buffer(&self.vertex_buffer);
+        self.vertex_buffer
+            .buffer_data(&self.vertices, Usage::StreamDraw);
+        gl.unbind_buffer(Target::ArrayBuffer);
+
+        let mut gl = gl.save_state(StateFlags::Depth | StateFlags::Blend);
+        gl.disable(Capability::DepthTest);
+        gl.enable(Capability::Blend);
+        gl.blend_func(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+
+        gl.bind_program(&self.program);
+        self.program.set_uniform(
+            self.screen_size_location,
+            Vec2::new(width as f32, height as f32),
+        );
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.atlas);
+        };
+        gl.bind_vertex_array(&self.vao);
+        gl.draw_arrays(Primitive::Triangles, 0, self.vertices.len() as GLsizei);
+        gl.unbind_vertex_array();
+        self.vertices.clear();
+        gl.unbind_program();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Vec2, Vec4};
+
+    use super::{atlas_pixels, glyph_index, push_text, FONT, GLYPH_SIZE};
+
+    #[test]
+    fn test_atlas() {
+        let pixels = atlas_pixels();
+        let width = FONT.len() * GLYPH_SIZE;
+        assert_eq!(pixels.len(), width * GLYPH_SIZE);
+        // '_' only fills its bottom row
+        let underscore = glyph_index('_') * GLYPH_SIZE;
+        assert!((0..GLYPH_SIZE).all(|x| pixels[7 * width + underscore + x] == u8::MAX));
+        assert!((0..GLYPH_SIZE).all(|x| pixels[6 * width + underscore + x] == 0));
+        assert_eq!(glyph_index('é'), glyph_index('?'));
+    }
+
+    #[test]
+    fn test_push_text() {
+        let mut vertices = vec![];
+        push_text(&mut vertices, "a b\nc", Vec2::new(4.0, 2.0), 2.0, Vec4::ONE);
+        // spaces and newlines don't produce quads
+        assert_eq!(vertices.len(), 3 * 6);
+        assert_eq!(vertices[0].position, Vec2::new(4.0, 2.0));
+        // 'b' after 'a' and the space
+        assert_eq!(vertices[6].position, Vec2::new(4.0 + 32.0, 2.0));
+        // 'c' on the second line
+        assert_eq!(vertices[12].position, Vec2::new(4.0, 2.0 + 20.0));
+        let u = (glyph_index('c') * GLYPH_SIZE) as f32;
+        assert_eq!(vertices[12].uv, Vec2::new(u, 0.0));
+    }
+}
@@ -0,0 +1,1569 @@
+use std::{cell::OnceCell, marker::PhantomData, ops::Range, rc::Rc};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use thiserror::Error;
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{
+    buffer::{Buffer, Target, Usage},
+    gl_api,
+    handle::{self, delete_later, ContextBound, GlObject, TextureId},
+    opengl::DepthFunc,
+    sync::Fence,
+    GLHandle, NULL_HANDLE,
+};
+
+#[cfg(feature = "ktx")]
+mod ktx;
+#[cfg(feature = "ktx")]
+pub use ktx::{Ktx, KtxError};
+
+#[derive(Error, Debug)]
+pub enum TextureError {
+    #[error("Expected {expected} bytes of pixels, found {actual}")]
+    PixelCount { expected: usize, actual: usize },
+    #[error("Layer {layer} out of the {layers} of the texture")]
+    LayerOutOfRange { layer: u32, layers: u32 },
+    #[error("Only textures with immutable storage can be viewed")]
+    NotViewable,
+    #[error("Texels of {from:?} can't be viewed as {to:?}")]
+    IncompatibleFormat {
+        from: TextureFormat,
+        to: TextureFormat,
+    },
+    #[error("View of levels {levels:?} and layers {layers:?} out of the texture")]
+    ViewOutOfRange {
+        levels: Range<u32>,
+        layers: Range<u32>,
+    },
+    #[error("Expected {expected} bytes of level {level}, found {actual}")]
+    LevelSize {
+        level: u32,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("A texture needs at least a level")]
+    NoLevels,
+    #[error("Compressed textures can't be updated with pixels")]
+    Compressed,
+    #[cfg(feature = "ktx")]
+    #[error(transparent)]
+    Ktx(#[from] KtxError),
+}
+
+/// How the texels of a texture are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8,
+    Rgba8,
+    /// RGBA decoded to linear when sampled, see [`crate::app::AppConfig::srgb`]
+    Srgb8Alpha8,
+    Rgba16F,
+    Rgba32F,
+    Depth32F,
+}
+
+impl TextureFormat {
+    pub(crate) const fn internal_format(self) -> GLenum {
+        match self {
+            Self::R8 => gl::R8,
+            Self::Rgba8 => gl::RGBA8,
+            Self::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            Self::Rgba16F => gl::RGBA16F,
+            Self::Rgba32F => gl::RGBA32F,
+            Self::Depth32F => gl::DEPTH_COMPONENT32F,
+        }
+    }
+
+    /// Format and type of the pixels uploaded, half floats for `Rgba16F`
+    pub(crate) const fn pixel_format(self) -> (GLenum, GLenum) {
+        match self {
+            Self::R8 => (gl::RED, gl::UNSIGNED_BYTE),
+            Self::Rgba8 | Self::Srgb8Alpha8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            Self::Rgba16F => (gl::RGBA, gl::HALF_FLOAT),
+            Self::Rgba32F => (gl::RGBA, gl::FLOAT),
+            Self::Depth32F => (gl::DEPTH_COMPONENT, gl::FLOAT),
+        }
+    }
+
+    /// Bytes of an uploaded pixel
+    #[must_use]
+    pub const fn pixel_size(self) -> usize {
+        match self {
+            Self::R8 => 1,
+            Self::Rgba8 | Self::Srgb8Alpha8 | Self::Depth32F => 4,
+            Self::Rgba16F => 8,
+            Self::Rgba32F => 16,
+        }
+    }
+
+    /// Whether texels stored as `self` can be read as `other` by a view, only color formats
+    /// of the same size are interchangeable
+    #[must_use]
+    pub fn can_view_as(self, other: Self) -> bool {
+        self == other
+            || (self != Self::Depth32F
+                && other != Self::Depth32F
+                && self.pixel_size() == other.pixel_size())
+    }
+
+    fn check_pixels(self, texels: usize, pixels: &[u8]) -> Result<(), TextureError> {
+        let expected = texels * self.pixel_size();
+        if pixels.len() == expected {
+            Ok(())
+        } else {
+            Err(TextureError::PixelCount {
+                expected,
+                actual: pixels.len(),
+            })
+        }
+    }
+}
+
+// from EXT_texture_compression_s3tc and EXT_texture_sRGB, missing from the core bindings
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+
+// from EXT_texture_filter_anisotropic, core only since 4.6
+pub(crate) const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MinFilter {
+    Nearest = gl::NEAREST,
+    Linear = gl::LINEAR,
+    NearestMipmapNearest = gl::NEAREST_MIPMAP_NEAREST,
+    LinearMipmapNearest = gl::LINEAR_MIPMAP_NEAREST,
+    NearestMipmapLinear = gl::NEAREST_MIPMAP_LINEAR,
+    LinearMipmapLinear = gl::LINEAR_MIPMAP_LINEAR,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MagFilter {
+    Nearest = gl::NEAREST,
+    Linear = gl::LINEAR,
+}
+
+/// How texture coordinates outside `[0, 1]` are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Wrap {
+    Repeat = gl::REPEAT,
+    MirroredRepeat = gl::MIRRORED_REPEAT,
+    ClampToEdge = gl::CLAMP_TO_EDGE,
+    /// The border color of [`TextureParams::border_color`]
+    ClampToBorder = gl::CLAMP_TO_BORDER,
+    MirrorClampToEdge = gl::MIRROR_CLAMP_TO_EDGE,
+}
+
+/// How a texture is sampled, applied when it's created. The default filters mipmaps
+/// linearly and repeats past the edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureParams {
+    min_filter: MinFilter,
+    mag_filter: MagFilter,
+    /// `[s, t, r]`
+    wrap: [Wrap; 3],
+    border_color: Vec4,
+    lod_range: (f32, f32),
+    compare: Option<DepthFunc>,
+    anisotropy: f32,
+}
+
+impl Default for TextureParams {
+    fn default() -> Self {
+        Self {
+            min_filter: MinFilter::LinearMipmapLinear,
+            mag_filter: MagFilter::Linear,
+            wrap: [Wrap::Repeat; 3],
+            border_color: Vec4::ZERO,
+            lod_range: (-1000.0, 1000.0),
+            compare: None,
+            anisotropy: 1.0,
+        }
+    }
+}
+
+impl TextureParams {
+    /// Linear filtering without mipmaps, clamped to the edges,
+    /// for render targets and data textures
+    #[must_use]
+    pub fn clamped() -> Self {
+        Self::default()
+            .min_filter(MinFilter::Linear)
+            .wrap(Wrap::ClampToEdge)
+    }
+
+    #[must_use]
+    pub const fn min_filter(mut self, filter: MinFilter) -> Self {
+        self.min_filter = filter;
+        self
+    }
+    #[must_use]
+    pub const fn mag_filter(mut self, filter: MagFilter) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+    /// Wraps every coordinate the same
+    #[must_use]
+    pub const fn wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = [wrap; 3];
+        self
+    }
+    #[must_use]
+    pub const fn wrap_s(mut self, wrap: Wrap) -> Self {
+        self.wrap[0] = wrap;
+        self
+    }
+    #[must_use]
+    pub const fn wrap_t(mut self, wrap: Wrap) -> Self {
+        self.wrap[1] = wrap;
+        self
+    }
+    #[must_use]
+    pub const fn wrap_r(mut self, wrap: Wrap) -> Self {
+        self.wrap[2] = wrap;
+        self
+    }
+    #[must_use]
+    pub const fn border_color(mut self, color: Vec4) -> Self {
+        self.border_color = color;
+        self
+    }
+    /// Clamps the level of detail sampled, levels being 0 at the base
+    #[must_use]
+    pub const fn lod_range(mut self, min: f32, max: f32) -> Self {
+        self.lod_range = (min, max);
+        self
+    }
+    /// Depth textures compare the reference of `sampler2DShadow` lookups with `func`
+    /// instead of returning the depth
+    #[must_use]
+    pub const fn compare(mut self, func: DepthFunc) -> Self {
+        self.compare = Some(func);
+        self
+    }
+    /// Samples up to `anisotropy` times along the view direction on surfaces seen at
+    /// grazing angles, clamped to [`crate::opengl::OpenGl::max_anisotropy`]
+    #[must_use]
+    pub const fn anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    /// Sets the parameters of the texture bound to `target`
+    fn apply(&self, target: GLenum) {
+        unsafe {
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, self.min_filter as GLint);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, self.mag_filter as GLint);
+            for (parameter, wrap) in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R]
+                .into_iter()
+                .zip(self.wrap)
+            {
+                gl::TexParameteri(target, parameter, wrap as GLint);
+            }
+            gl::TexParameterfv(
+                target,
+                gl::TEXTURE_BORDER_COLOR,
+                self.border_color.as_ref().as_ptr(),
+            );
+            gl::TexParameterf(target, gl::TEXTURE_MIN_LOD, self.lod_range.0);
+            gl::TexParameterf(target, gl::TEXTURE_MAX_LOD, self.lod_range.1);
+            if let Some(func) = self.compare {
+                gl::TexParameteri(
+                    target,
+                    gl::TEXTURE_COMPARE_MODE,
+                    gl::COMPARE_REF_TO_TEXTURE as GLint,
+                );
+                gl::TexParameteri(target, gl::TEXTURE_COMPARE_FUNC, func as GLint);
+            } else {
+                gl::TexParameteri(target, gl::TEXTURE_COMPARE_MODE, gl::NONE as GLint);
+            }
+            if self.anisotropy > 1.0 {
+                let mut max = 1.0;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+                gl::TexParameterf(target, TEXTURE_MAX_ANISOTROPY, self.anisotropy.min(max));
+            }
+        };
+    }
+}
+
+/// Block compressed formats, uploaded as compressed and decoded when sampled.
+/// Every block covers 4x4 texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedFormat {
+    /// DXT1, RGB with 1 bit alpha
+    Bc1,
+    Bc1Srgb,
+    /// DXT5, RGBA
+    Bc3,
+    Bc3Srgb,
+    /// Single channel
+    Bc4,
+    /// Two channels, like normal maps
+    Bc5,
+    /// HDR RGB
+    Bc6H,
+    Bc7,
+    Bc7Srgb,
+    Etc2Rgb,
+    Etc2Srgb,
+    Etc2Rgba,
+    Etc2SrgbAlpha,
+}
+
+impl CompressedFormat {
+    const fn internal_format(self) -> GLenum {
+        match self {
+            Self::Bc1 => COMPRESSED_RGBA_S3TC_DXT1,
+            Self::Bc1Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            Self::Bc3 => COMPRESSED_RGBA_S3TC_DXT5,
+            Self::Bc3Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            Self::Bc4 => gl::COMPRESSED_RED_RGTC1,
+            Self::Bc5 => gl::COMPRESSED_RG_RGTC2,
+            Self::Bc6H => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            Self::Bc7 => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            Self::Bc7Srgb => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            Self::Etc2Rgb => gl::COMPRESSED_RGB8_ETC2,
+            Self::Etc2Srgb => gl::COMPRESSED_SRGB8_ETC2,
+            Self::Etc2Rgba => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            Self::Etc2SrgbAlpha => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        }
+    }
+
+    /// Bytes of a 4x4 block
+    #[must_use]
+    pub const fn block_size(self) -> usize {
+        match self {
+            Self::Bc1 | Self::Bc1Srgb | Self::Bc4 | Self::Etc2Rgb | Self::Etc2Srgb => 8,
+            Self::Bc3
+            | Self::Bc3Srgb
+            | Self::Bc5
+            | Self::Bc6H
+            | Self::Bc7
+            | Self::Bc7Srgb
+            | Self::Etc2Rgba
+            | Self::Etc2SrgbAlpha => 16,
+        }
+    }
+
+    /// Bytes of a `width` by `height` image, partial blocks at the edges taking a whole one
+    #[must_use]
+    pub const fn image_size(self, width: u32, height: u32) -> usize {
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * self.block_size()
+    }
+}
+
+/// Something that can be sampled from a texture unit,
+/// see [`crate::program::Program::bind_texture`]
+pub trait Texture {
+    /// Binds the texture to texture `unit`
+    fn bind_texture(&self, unit: GLuint);
+
+    /// A texture sharing the mip `levels` and `layers` of this one without copying them,
+    /// its texels read as `format`, like an sRGB view of a linear texture.
+    /// Only textures with immutable storage can be viewed, which needs GL 4.3.
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        let _ = (format, levels, layers);
+        Err(TextureError::NotViewable)
+    }
+}
+
+/// Shared textures bind the texture they point to
+impl<T: Texture + ?Sized> Texture for Rc<T> {
+    fn bind_texture(&self, unit: GLuint) {
+        T::bind_texture(self, unit);
+    }
+
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        T::view(self, format, levels, layers)
+    }
+}
+
+/// Textures whose levels can be bound as images or attached to framebuffers,
+/// see [`crate::opengl::OpenGl::bind_image_texture`] and [`crate::framebuffer::Framebuffer`]
+pub(crate) trait ImageTexture {
+    fn image(&self) -> TextureImage;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TextureImage {
+    pub id: GLHandle,
+    pub target: GLenum,
+    /// Layers, slices or faces that can be attached one at a time
+    pub layers: u32,
+}
+
+impl TextureImage {
+    /// Whether the texture has layers, slices or faces
+    pub const fn is_layered(&self) -> bool {
+        matches!(
+            self.target,
+            gl::TEXTURE_2D_ARRAY | gl::TEXTURE_3D | gl::TEXTURE_CUBE_MAP
+        )
+    }
+}
+
+/// Immutable storage of a texture, which views can share
+#[derive(Debug, Clone, Copy)]
+struct Storage {
+    id: GLHandle,
+    target: GLenum,
+    format: TextureFormat,
+    levels: u32,
+    layers: u32,
+}
+
+impl Storage {
+    fn view(
+        self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        if !self.format.can_view_as(format) {
+            return Err(TextureError::IncompatibleFormat {
+                from: self.format,
+                to: format,
+            });
+        }
+        if levels.is_empty()
+            || levels.end > self.levels
+            || layers.is_empty()
+            || layers.end > self.layers
+        {
+            return Err(TextureError::ViewOutOfRange { levels, layers });
+        }
+        // texture views are core since 4.3, without them the storage is mutable too
+        if handle::context_version() < (4, 3) {
+            return Err(TextureError::NotViewable);
+        }
+        let mut id = NULL_HANDLE;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::TextureView(
+                id,
+                self.target,
+                self.id,
+                format.internal_format(),
+                levels.start,
+                levels.len() as GLuint,
+                layers.start,
+                layers.len() as GLuint,
+            );
+        };
+        Ok(TextureView {
+            storage: Self {
+                id,
+                format,
+                levels: levels.len() as u32,
+                layers: layers.len() as u32,
+                ..self
+            },
+            _context: PhantomData,
+        })
+    }
+}
+
+/// Texels of another texture read through a different format, levels or layers,
+/// see [`Texture::view`]. It stays valid after the texture viewed is dropped.
+pub struct TextureView {
+    storage: Storage,
+    _context: ContextBound,
+}
+
+impl Drop for TextureView {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.storage.id));
+    }
+}
+
+impl TextureView {
+    #[must_use]
+    pub const fn id(&self) -> TextureId {
+        TextureId(self.storage.id)
+    }
+    #[must_use]
+    pub const fn format(&self) -> TextureFormat {
+        self.storage.format
+    }
+}
+
+impl Texture for TextureView {
+    fn bind_texture(&self, unit: GLuint) {
+        bind_unit(unit, self.storage.target, self.storage.id);
+    }
+
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        self.storage.view(format, levels, layers)
+    }
+}
+
+impl ImageTexture for TextureView {
+    fn image(&self) -> TextureImage {
+        TextureImage {
+            id: self.storage.id,
+            target: self.storage.target,
+            // views of volumes cover every slice, their depth unknown here
+            layers: if self.storage.target == gl::TEXTURE_3D {
+                u32::MAX
+            } else {
+                self.storage.layers
+            },
+        }
+    }
+}
+
+/// An RGBA or compressed texture with mipmaps
+pub struct Texture2D {
+    id: GLHandle,
+    /// `None` when compressed
+    format: Option<TextureFormat>,
+    width: u32,
+    height: u32,
+    levels: u32,
+    _context: ContextBound,
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.id));
+    }
+}
+
+impl Texture2D {
+    /// Uploads `width` by `height` RGBA pixels, rows from the bottom, and builds their
+    /// mipmaps. With `srgb` the colors are decoded to linear when sampled,
+    /// see [`crate::app::AppConfig::srgb`].
+    pub fn from_rgba(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        srgb: bool,
+        params: &TextureParams,
+    ) -> Result<Self, TextureError> {
+        let format = if srgb {
+            TextureFormat::Srgb8Alpha8
+        } else {
+            TextureFormat::Rgba8
+        };
+        format.check_pixels(width as usize * height as usize, pixels)?;
+        Ok(Self::new_rgba(width, height, pixels, format, params))
+    }
+
+    /// [`Texture2D::from_rgba`] with `pixels` already checked
+    fn new_rgba(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        format: TextureFormat,
+        params: &TextureParams,
+    ) -> Self {
+        // down to 1x1
+        let levels = u32::BITS - width.max(height).max(1).leading_zeros();
+        let mut id = NULL_HANDLE;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+        };
+        allocate_2d(
+            gl::TEXTURE_2D,
+            &[gl::TEXTURE_2D],
+            levels,
+            format,
+            width,
+            height,
+        );
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                width as GLsizei,
+                height as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr().cast(),
+            );
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        };
+        gl_api::trace_call("glTexStorage2D", || {
+            format!("{id}, {levels}, {format:?}, {width}x{height}")
+        });
+        params.apply(gl::TEXTURE_2D);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+        };
+        Self {
+            id,
+            format: Some(format),
+            width,
+            height,
+            levels,
+            _context: PhantomData,
+        }
+    }
+
+    /// Uploads the compressed mip `levels` of a `width` by `height` texture, from the base
+    /// level down. Levels missing from the 1x1 one aren't sampled.
+    pub fn from_compressed(
+        width: u32,
+        height: u32,
+        format: CompressedFormat,
+        levels: &[&[u8]],
+        params: &TextureParams,
+    ) -> Result<Self, TextureError> {
+        if levels.is_empty() {
+            return Err(TextureError::NoLevels);
+        }
+        let level_size = |level: u32| ((width >> level).max(1), (height >> level).max(1));
+        for (level, data) in (0..).zip(levels) {
+            let (width, height) = level_size(level);
+            let expected = format.image_size(width, height);
+            if data.len() != expected {
+                return Err(TextureError::LevelSize {
+                    level,
+                    expected,
+                    actual: data.len(),
+                });
+            }
+        }
+        let mut id = NULL_HANDLE;
+        let storage = has_texture_storage();
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            if storage {
+                gl::TexStorage2D(
+                    gl::TEXTURE_2D,
+                    levels.len() as GLsizei,
+                    format.internal_format(),
+                    width as GLsizei,
+                    height as GLsizei,
+                );
+            } else {
+                gl::TexParameteri(
+                    gl::TEXTURE_2D,
+                    gl::TEXTURE_MAX_LEVEL,
+                    levels.len() as GLint - 1,
+                );
+            }
+            for (level, data) in (0..).zip(levels) {
+                let (width, height) = level_size(level);
+                if storage {
+                    gl::CompressedTexSubImage2D(
+                        gl::TEXTURE_2D,
+                        level as GLint,
+                        0,
+                        0,
+                        width as GLsizei,
+                        height as GLsizei,
+                        format.internal_format(),
+                        data.len() as GLsizei,
+                        data.as_ptr().cast(),
+                    );
+                } else {
+                    gl::CompressedTexImage2D(
+                        gl::TEXTURE_2D,
+                        level as GLint,
+                        format.internal_format(),
+                        width as GLsizei,
+                        height as GLsizei,
+                        0,
+                        data.len() as GLsizei,
+                        data.as_ptr().cast(),
+                    );
+                }
+            }
+        };
+        gl_api::trace_call("glCompressedTexSubImage2D", || {
+            format!("{id}, {}, {format:?}, {width}x{height}", levels.len())
+        });
+        // immutable textures sample only their levels, even with mipmap filters
+        params.apply(gl::TEXTURE_2D);
+        unsafe { gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE) };
+        Ok(Self {
+            id,
+            format: None,
+            width,
+            height,
+            levels: levels.len() as u32,
+            _context: PhantomData,
+        })
+    }
+
+    /// Replaces the pixels through the `staging` pixel unpack buffer, so the call returns
+    /// once they are copied there and the GPU transfers them on its own. The fence is
+    /// signaled when the texture is updated. Reusing `staging` every frame, like when
+    /// streaming video, doesn't wait for the previous transfer since its storage is orphaned.
+    pub fn upload_async(
+        &mut self,
+        staging: &mut Buffer<u8>,
+        pixels: &[u8],
+    ) -> Result<Fence, TextureError> {
+        let format = self.format.ok_or(TextureError::Compressed)?;
+        format.check_pixels(self.width as usize * self.height as usize, pixels)?;
+        debug_assert_eq!(staging.target(), Target::PixelUnpackBuffer);
+        staging.bind();
+        staging.buffer_data(pixels, Usage::StreamDraw);
+        let (pixel_format, pixel_type) = format.pixel_format();
+        gl_api::trace_call("glTexSubImage2D", || self.id.to_string());
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            // from the bound buffer from its start
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as GLsizei,
+                self.height as GLsizei,
+                pixel_format,
+                pixel_type,
+                std::ptr::null(),
+            );
+            if self.levels > 1 {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, NULL_HANDLE);
+        };
+        // a bound unpack buffer would catch later uploads
+        staging.unbind();
+        Ok(Fence::new())
+    }
+
+    /// Uploads a KTX or KTX2 file of a compressed 2D texture
+    #[cfg(feature = "ktx")]
+    pub fn from_ktx(bytes: &[u8], params: &TextureParams) -> Result<Self, TextureError> {
+        let ktx = Ktx::parse(bytes)?;
+        Self::from_compressed(ktx.width, ktx.height, ktx.format, &ktx.levels, params)
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> TextureId {
+        TextureId(self.id)
+    }
+    #[must_use]
+    pub const fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    #[must_use]
+    pub const fn levels(&self) -> u32 {
+        self.levels
+    }
+}
+
+impl Texture for Texture2D {
+    fn bind_texture(&self, unit: GLuint) {
+        bind_unit(unit, gl::TEXTURE_2D, self.id);
+    }
+
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        Storage {
+            id: self.id,
+            target: gl::TEXTURE_2D,
+            format: self.format.ok_or(TextureError::NotViewable)?,
+            levels: self.levels,
+            layers: 1,
+        }
+        .view(format, levels, layers)
+    }
+}
+
+impl ImageTexture for Texture2D {
+    fn image(&self) -> TextureImage {
+        TextureImage {
+            id: self.id,
+            target: gl::TEXTURE_2D,
+            layers: 1,
+        }
+    }
+}
+
+/// Binds texture `id` of `target` to texture `unit`
+fn bind_unit(unit: GLuint, target: GLenum, id: GLHandle) {
+    gl_api::trace_call("glBindTexture", || format!("{unit}, {target:#x}, {id}"));
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(target, id);
+    };
+}
+
+/// Creates a texture of `target` sampled with `params` and leaves it bound
+fn new_texture(target: GLenum, params: &TextureParams) -> GLHandle {
+    let mut id = NULL_HANDLE;
+    unsafe {
+        gl::GenTextures(1, &mut id);
+        gl::BindTexture(target, id);
+    };
+    gl_api::trace_call("glGenTextures", || format!("{target:#x}, {id}"));
+    params.apply(target);
+    id
+}
+
+/// Whether the current context has `glTexStorage*`, core since 4.2
+fn has_texture_storage() -> bool {
+    handle::context_version() >= (4, 2)
+}
+
+/// Allocates `levels` levels of the texture bound to `target`, immutable when the context
+/// has texture storage, else level by level for each of `images`, the faces of a cubemap
+fn allocate_2d(
+    target: GLenum,
+    images: &[GLenum],
+    levels: u32,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+) {
+    if has_texture_storage() {
+        unsafe {
+            gl::TexStorage2D(
+                target,
+                levels as GLsizei,
+                format.internal_format(),
+                width as GLsizei,
+                height as GLsizei,
+            );
+        };
+        return;
+    }
+    let (pixel_format, pixel_type) = format.pixel_format();
+    unsafe {
+        gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, levels as GLint - 1);
+        for level in 0..levels {
+            for &image in images {
+                gl::TexImage2D(
+                    image,
+                    level as GLint,
+                    format.internal_format() as GLint,
+                    (width >> level).max(1) as GLsizei,
+                    (height >> level).max(1) as GLsizei,
+                    0,
+                    pixel_format,
+                    pixel_type,
+                    std::ptr::null(),
+                );
+            }
+        }
+    };
+}
+
+/// Allocates the single level of the 3D or array texture bound to `target`, see
+/// [`allocate_2d`]
+fn allocate_3d(target: GLenum, format: TextureFormat, width: u32, height: u32, depth: u32) {
+    unsafe {
+        if has_texture_storage() {
+            gl::TexStorage3D(
+                target,
+                1,
+                format.internal_format(),
+                width as GLsizei,
+                height as GLsizei,
+                depth as GLsizei,
+            );
+        } else {
+            let (pixel_format, pixel_type) = format.pixel_format();
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::TexImage3D(
+                target,
+                0,
+                format.internal_format() as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                depth as GLsizei,
+                0,
+                pixel_format,
+                pixel_type,
+                std::ptr::null(),
+            );
+        }
+    };
+}
+
+/// Uploads the `width` by `height` by `depth` block of `texture` from `z` on
+#[allow(clippy::too_many_arguments)]
+fn upload_3d(
+    target: GLenum,
+    texture: GLHandle,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    z: u32,
+    depth: u32,
+    pixels: &[u8],
+) -> Result<(), TextureError> {
+    format.check_pixels(width as usize * height as usize * depth as usize, pixels)?;
+    let (pixel_format, pixel_type) = format.pixel_format();
+    gl_api::trace_call("glTexSubImage3D", || {
+        format!("{target:#x}, {texture}, {z}, {width}x{height}x{depth}")
+    });
+    unsafe {
+        gl::BindTexture(target, texture);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexSubImage3D(
+            target,
+            0,
+            0,
+            0,
+            z as GLint,
+            width as GLsizei,
+            height as GLsizei,
+            depth as GLsizei,
+            pixel_format,
+            pixel_type,
+            pixels.as_ptr().cast(),
+        );
+        gl::BindTexture(target, NULL_HANDLE);
+    };
+    Ok(())
+}
+
+/// Layers of 2D textures of the same size and format, sampled as a `sampler2DArray`,
+/// like the cascades of a shadow map
+pub struct Texture2DArray {
+    id: GLHandle,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    layers: u32,
+    _context: ContextBound,
+}
+
+impl Drop for Texture2DArray {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.id));
+    }
+}
+
+impl Texture2DArray {
+    /// Allocates `layers` layers of `width` by `height` texels, their content undefined,
+    /// usually sampled with [`TextureParams::clamped`]
+    #[must_use]
+    pub fn new(
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+        params: &TextureParams,
+    ) -> Self {
+        let id = new_texture(gl::TEXTURE_2D_ARRAY, params);
+        allocate_3d(gl::TEXTURE_2D_ARRAY, format, width, height, layers);
+        unsafe { gl::BindTexture(gl::TEXTURE_2D_ARRAY, NULL_HANDLE) };
+        Self {
+            id,
+            format,
+            width,
+            height,
+            layers,
+            _context: PhantomData,
+        }
+    }
+
+    /// Replaces the texels of `layer`, rows from the bottom
+    pub fn upload_layer(&mut self, layer: u32, pixels: &[u8]) -> Result<(), TextureError> {
+        if layer >= self.layers {
+            return Err(TextureError::LayerOutOfRange {
+                layer,
+                layers: self.layers,
+            });
+        }
+        upload_3d(
+            gl::TEXTURE_2D_ARRAY,
+            self.id,
+            self.format,
+            self.width,
+            self.height,
+            layer,
+            1,
+            pixels,
+        )
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> TextureId {
+        TextureId(self.id)
+    }
+    #[must_use]
+    pub const fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    #[must_use]
+    pub const fn layers(&self) -> u32 {
+        self.layers
+    }
+}
+
+impl Texture for Texture2DArray {
+    fn bind_texture(&self, unit: GLuint) {
+        bind_unit(unit, gl::TEXTURE_2D_ARRAY, self.id);
+    }
+
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        Storage {
+            id: self.id,
+            target: gl::TEXTURE_2D_ARRAY,
+            format: self.format,
+            levels: 1,
+            layers: self.layers,
+        }
+        .view(format, levels, layers)
+    }
+}
+
+impl ImageTexture for Texture2DArray {
+    fn image(&self) -> TextureImage {
+        TextureImage {
+            id: self.id,
+            target: gl::TEXTURE_2D_ARRAY,
+            layers: self.layers,
+        }
+    }
+}
+
+/// A volume of texels sampled as a `sampler3D`, filtered across slices too
+pub struct Texture3D {
+    id: GLHandle,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    depth: u32,
+    _context: ContextBound,
+}
+
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.id));
+    }
+}
+
+impl Texture3D {
+    /// Allocates `width` by `height` by `depth` texels, their content undefined,
+    /// usually sampled with [`TextureParams::clamped`]
+    #[must_use]
+    pub fn new(
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        params: &TextureParams,
+    ) -> Self {
+        let id = new_texture(gl::TEXTURE_3D, params);
+        allocate_3d(gl::TEXTURE_3D, format, width, height, depth);
+        unsafe { gl::BindTexture(gl::TEXTURE_3D, NULL_HANDLE) };
+        Self {
+            id,
+            format,
+            width,
+            height,
+            depth,
+            _context: PhantomData,
+        }
+    }
+
+    /// Replaces every texel, slice after slice with rows from the bottom
+    pub fn upload(&mut self, pixels: &[u8]) -> Result<(), TextureError> {
+        upload_3d(
+            gl::TEXTURE_3D,
+            self.id,
+            self.format,
+            self.width,
+            self.height,
+            0,
+            self.depth,
+            pixels,
+        )
+    }
+
+    /// Replaces the texels of slice `z`
+    pub fn upload_slice(&mut self, z: u32, pixels: &[u8]) -> Result<(), TextureError> {
+        if z >= self.depth {
+            return Err(TextureError::LayerOutOfRange {
+                layer: z,
+                layers: self.depth,
+            });
+        }
+        upload_3d(
+            gl::TEXTURE_3D,
+            self.id,
+            self.format,
+            self.width,
+            self.height,
+            z,
+            1,
+            pixels,
+        )
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> TextureId {
+        TextureId(self.id)
+    }
+    #[must_use]
+    pub const fn size(&self) -> (u32, u32, u32) {
+        (self.width, self.height, self.depth)
+    }
+}
+
+impl Texture for Texture3D {
+    fn bind_texture(&self, unit: GLuint) {
+        bind_unit(unit, gl::TEXTURE_3D, self.id);
+    }
+
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        Storage {
+            id: self.id,
+            target: gl::TEXTURE_3D,
+            format: self.format,
+            levels: 1,
+            layers: 1,
+        }
+        .view(format, levels, layers)
+    }
+}
+
+impl ImageTexture for Texture3D {
+    fn image(&self) -> TextureImage {
+        TextureImage {
+            id: self.id,
+            target: gl::TEXTURE_3D,
+            layers: self.depth,
+        }
+    }
+}
+
+/// The faces of a [`Cubemap`], in the order of their layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [Self; 6] = [
+        Self::PositiveX,
+        Self::NegativeX,
+        Self::PositiveY,
+        Self::NegativeY,
+        Self::PositiveZ,
+        Self::NegativeZ,
+    ];
+
+    /// Layer of the face, see [`crate::framebuffer::Framebuffer::attach_layer`]
+    #[must_use]
+    pub const fn layer(self) -> u32 {
+        self as u32
+    }
+
+    const fn target(self) -> GLenum {
+        gl::TEXTURE_CUBE_MAP_POSITIVE_X + self as GLenum
+    }
+
+    /// View matrix rendering the face seen from `eye`, like for environment maps or point
+    /// light shadows, with a 90 degrees square perspective projection
+    #[must_use]
+    pub fn view_matrix(self, eye: Vec3) -> Mat4 {
+        // faces are upside down, following the RenderMan convention
+        let (direction, up) = match self {
+            Self::PositiveX => (Vec3::X, Vec3::NEG_Y),
+            Self::NegativeX => (Vec3::NEG_X, Vec3::NEG_Y),
+            Self::PositiveY => (Vec3::Y, Vec3::Z),
+            Self::NegativeY => (Vec3::NEG_Y, Vec3::NEG_Z),
+            Self::PositiveZ => (Vec3::Z, Vec3::NEG_Y),
+            Self::NegativeZ => (Vec3::NEG_Z, Vec3::NEG_Y),
+        };
+        Mat4::look_to_rh(eye, direction, up)
+    }
+}
+
+/// Six square faces sampled with a direction as a `samplerCube`. Enable
+/// [`crate::opengl::Capability::TextureCubeMapSeamless`] to filter across their edges.
+pub struct Cubemap {
+    id: GLHandle,
+    format: TextureFormat,
+    size: u32,
+    levels: u32,
+    _context: ContextBound,
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        delete_later(GlObject::Texture(self.id));
+    }
+}
+
+impl Cubemap {
+    /// Allocates `levels` mip levels of faces `size` texels wide, their content undefined
+    #[must_use]
+    pub fn new(size: u32, levels: u32, format: TextureFormat, params: &TextureParams) -> Self {
+        let id = new_texture(gl::TEXTURE_CUBE_MAP, params);
+        let faces = CubeFace::ALL.map(CubeFace::target);
+        allocate_2d(
+            gl::TEXTURE_CUBE_MAP,
+            &faces,
+            levels.max(1),
+            format,
+            size,
+            size,
+        );
+        unsafe { gl::BindTexture(gl::TEXTURE_CUBE_MAP, NULL_HANDLE) };
+        Self {
+            id,
+            format,
+            size,
+            levels: levels.max(1),
+            _context: PhantomData,
+        }
+    }
+
+    /// Replaces the base level of `face`, rows from the bottom
+    pub fn upload_face(&mut self, face: CubeFace, pixels: &[u8]) -> Result<(), TextureError> {
+        self.format
+            .check_pixels(self.size as usize * self.size as usize, pixels)?;
+        let (pixel_format, pixel_type) = self.format.pixel_format();
+        gl_api::trace_call("glTexSubImage2D", || format!("{face:?}, {}", self.id));
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                face.target(),
+                0,
+                0,
+                0,
+                self.size as GLsizei,
+                self.size as GLsizei,
+                pixel_format,
+                pixel_type,
+                pixels.as_ptr().cast(),
+            );
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, NULL_HANDLE);
+        };
+        Ok(())
+    }
+
+    /// Builds the levels past the base from it, after uploading or rendering to it
+    pub fn generate_mipmaps(&mut self) {
+        gl_api::trace_call("glGenerateMipmap", || self.id.to_string());
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, NULL_HANDLE);
+        };
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> TextureId {
+        TextureId(self.id)
+    }
+    #[must_use]
+    pub const fn size(&self) -> u32 {
+        self.size
+    }
+    #[must_use]
+    pub const fn levels(&self) -> u32 {
+        self.levels
+    }
+}
+
+impl Texture for Cubemap {
+    fn bind_texture(&self, unit: GLuint) {
+        bind_unit(unit, gl::TEXTURE_CUBE_MAP, self.id);
+    }
+
+    fn view(
+        &self,
+        format: TextureFormat,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) -> Result<TextureView, TextureError> {
+        Storage {
+            id: self.id,
+            target: gl::TEXTURE_CUBE_MAP,
+            format: self.format,
+            levels: self.levels,
+            layers: 6,
+        }
+        .view(format, levels, layers)
+    }
+}
+
+impl ImageTexture for Cubemap {
+    fn image(&self) -> TextureImage {
+        TextureImage {
+            id: self.id,
+            target: gl::TEXTURE_CUBE_MAP,
+            layers: 6,
+        }
+    }
+}
+
+/// Textures to bind when an asset is missing, so samplers never read an incomplete texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultTexture {
+    White,
+    Black,
+    /// The normal pointing straight out of the surface, `(0.5, 0.5, 1)` in normal maps
+    FlatNormal,
+    /// Magenta and black squares, standing out where a texture failed to load
+    Checkerboard,
+}
+
+const CHECKERBOARD_SIZE: u32 = 64;
+const CHECKERBOARD_CELL: u32 = 8;
+
+/// RGBA pixels of `size` by `size` magenta and black squares `cell` texels wide
+fn checkerboard_pixels(size: u32, cell: u32) -> Vec<u8> {
+    (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x / cell + y / cell).is_multiple_of(2)))
+        .flat_map(|magenta| {
+            if magenta {
+                [u8::MAX, 0, u8::MAX, u8::MAX]
+            } else {
+                [0, 0, 0, u8::MAX]
+            }
+        })
+        .collect()
+}
+
+/// The [`DefaultTexture`]s, each created the first time it's asked for.
+/// Keep it next to the [`crate::opengl::OpenGl`] of the context they belong to.
+#[derive(Default)]
+pub struct DefaultTextures {
+    white: OnceCell<Texture2D>,
+    black: OnceCell<Texture2D>,
+    flat_normal: OnceCell<Texture2D>,
+    checkerboard: OnceCell<Texture2D>,
+}
+
+impl DefaultTextures {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, texture: DefaultTexture) -> &Texture2D {
+        let pixel = |pixel: [u8; 4]| {
+            Texture2D::new_rgba(
+                1,
+                1,
+                &pixel,
+                TextureFormat::Rgba8,
+                &TextureParams::default(),
+            )
+        };
+        match texture {
+            DefaultTexture::White => self.white.get_or_init(|| pixel([u8::MAX; 4])),
+            DefaultTexture::Black => self.black.get_or_init(|| pixel([0, 0, 0, u8::MAX])),
+            DefaultTexture::FlatNormal => self
+                .flat_normal
+                .get_or_init(|| pixel([128, 128, u8::MAX, u8::MAX])),
+            DefaultTexture::Checkerboard => self.checkerboard.get_or_init(|| {
+                Texture2D::new_rgba(
+                    CHECKERBOARD_SIZE,
+                    CHECKERBOARD_SIZE,
+                    &checkerboard_pixels(CHECKERBOARD_SIZE, CHECKERBOARD_CELL),
+                    TextureFormat::Rgba8,
+                    &TextureParams::default().mag_filter(MagFilter::Nearest),
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        buffer::{Buffer, Target},
+        framebuffer::{Attachment, Framebuffer},
+        opengl::OpenGl,
+        sync::PendingPixels,
+    };
+
+    use glam::Vec3;
+
+    use super::{
+        checkerboard_pixels, CompressedFormat, CubeFace, MinFilter, Storage, Texture2D,
+        TextureError, TextureFormat, TextureParams, Wrap,
+    };
+
+    #[test]
+    fn test_pixel_count() {
+        // checked before any GL call
+        assert!(matches!(
+            Texture2D::from_rgba(2, 2, &[0; 12], false, &TextureParams::default()),
+            Err(TextureError::PixelCount {
+                expected: 16,
+                actual: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn test_compressed_level_size() {
+        assert_eq!(CompressedFormat::Bc1.image_size(4, 4), 8);
+        assert_eq!(CompressedFormat::Bc7.image_size(5, 1), 32);
+        // checked before any GL call
+        assert!(matches!(
+            Texture2D::from_compressed(
+                8,
+                8,
+                CompressedFormat::Bc1,
+                &[&[0; 32], &[0; 16]],
+                &TextureParams::default()
+            ),
+            Err(TextureError::LevelSize {
+                level: 1,
+                expected: 8,
+                actual: 16
+            })
+        ));
+        assert!(matches!(
+            Texture2D::from_compressed(8, 8, CompressedFormat::Bc1, &[], &TextureParams::default()),
+            Err(TextureError::NoLevels)
+        ));
+    }
+
+    #[test]
+    fn test_texture_params() {
+        let params = TextureParams::clamped().wrap_t(Wrap::Repeat);
+        assert_eq!(params.min_filter, MinFilter::Linear);
+        assert_eq!(
+            params.wrap,
+            [Wrap::ClampToEdge, Wrap::Repeat, Wrap::ClampToEdge]
+        );
+        assert_eq!(params.compare, None);
+    }
+
+    #[test]
+    fn test_cube_face_view_matrix() {
+        let eye = Vec3::new(1.0, 2.0, 3.0);
+        for face in CubeFace::ALL {
+            // every face looks down its axis
+            let axis = match face {
+                CubeFace::PositiveX => Vec3::X,
+                CubeFace::NegativeX => Vec3::NEG_X,
+                CubeFace::PositiveY => Vec3::Y,
+                CubeFace::NegativeY => Vec3::NEG_Y,
+                CubeFace::PositiveZ => Vec3::Z,
+                CubeFace::NegativeZ => Vec3::NEG_Z,
+            };
+            let view = face.view_matrix(eye).transform_point3(eye + axis);
+            assert!(view.abs_diff_eq(Vec3::NEG_Z, 1e-6), "{face:?}: {view}");
+        }
+        assert_eq!(
+            CubeFace::NegativeY.target(),
+            gl::TEXTURE_CUBE_MAP_NEGATIVE_Y
+        );
+    }
+
+    #[test]
+    fn test_checkerboard_pixels() {
+        let pixels = checkerboard_pixels(4, 2);
+        assert_eq!(pixels.len(), 4 * 4 * 4);
+        let magenta = |x: usize, y: usize| pixels[(y * 4 + x) * 4] == u8::MAX;
+        assert!(magenta(0, 0) && magenta(1, 1) && magenta(2, 2));
+        assert!(!magenta(2, 0) && !magenta(0, 3));
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel[3] == u8::MAX));
+    }
+
+    #[test]
+    fn test_check_pixels() {
+        assert!(TextureFormat::R8.check_pixels(6, &[0; 6]).is_ok());
+        assert!(TextureFormat::Rgba16F.check_pixels(2, &[0; 16]).is_ok());
+        assert!(matches!(
+            TextureFormat::Depth32F.check_pixels(3, &[0; 8]),
+            Err(TextureError::PixelCount {
+                expected: 12,
+                actual: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn test_view_checks() {
+        assert!(TextureFormat::Rgba8.can_view_as(TextureFormat::Srgb8Alpha8));
+        assert!(!TextureFormat::Rgba8.can_view_as(TextureFormat::Depth32F));
+        assert!(!TextureFormat::R8.can_view_as(TextureFormat::Rgba16F));
+
+        // checked before any GL call
+        let storage = Storage {
+            id: 0,
+            target: gl::TEXTURE_2D_ARRAY,
+            format: TextureFormat::Rgba16F,
+            levels: 1,
+            layers: 4,
+        };
+        assert!(matches!(
+            storage.view(TextureFormat::Rgba8, 0..1, 0..4),
+            Err(TextureError::IncompatibleFormat { .. })
+        ));
+        assert!(matches!(
+            storage.view(TextureFormat::Rgba16F, 0..1, 2..5),
+            Err(TextureError::ViewOutOfRange { .. })
+        ));
+        assert!(matches!(
+            storage.view(TextureFormat::Rgba16F, 1..1, 0..1),
+            Err(TextureError::ViewOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pixel_transfer_round_trip() {
+        let (_gl, _context) = OpenGl::new_headless().unwrap();
+        let mut texture =
+            Texture2D::from_rgba(2, 2, &[0; 16], false, &TextureParams::default()).unwrap();
+        let pixels: Vec<u8> = (0..16).collect();
+        let mut staging = Buffer::new(Target::PixelUnpackBuffer);
+        texture.upload_async(&mut staging, &pixels).unwrap();
+
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.attach(Attachment::Color(0), &texture, 0);
+        framebuffer.check_status().unwrap();
+        let mut pending = PendingPixels::new(0, 0, 2, 2, TextureFormat::Rgba8);
+        assert_eq!(pending.read(Duration::from_secs(1)), Some(pixels));
+    }
+}
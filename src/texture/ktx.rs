@@ -0,0 +1,252 @@
+use thiserror::Error;
+
+use super::CompressedFormat;
+
+const KTX1_IDENTIFIER: [u8; 12] = *b"\xABKTX 11\xBB\r\n\x1A\n";
+const KTX2_IDENTIFIER: [u8; 12] = *b"\xABKTX 20\xBB\r\n\x1A\n";
+const KTX1_HEADER_SIZE: usize = 64;
+const KTX2_LEVEL_INDEX: usize = 80;
+
+#[derive(Error, Debug)]
+pub enum KtxError {
+    #[error("Not a KTX or KTX2 file")]
+    NotKtx,
+    #[error("File ends before {0} bytes")]
+    Truncated(usize),
+    #[error("Unsupported OpenGL internal format {0:#x}")]
+    UnsupportedGlFormat(u32),
+    #[error("Unsupported Vulkan format {0}")]
+    UnsupportedVkFormat(u32),
+    #[error("Supercompression scheme {0} not supported")]
+    Supercompressed(u32),
+    #[error("Only single 2D textures are supported, not arrays, cubemaps or volumes")]
+    NotTexture2D,
+}
+
+/// A compressed 2D texture in a KTX or KTX2 file, borrowing its levels from the file
+#[derive(Debug)]
+pub struct Ktx<'a> {
+    pub format: CompressedFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Mip levels from the base one down
+    pub levels: Vec<&'a [u8]>,
+}
+
+impl<'a> Ktx<'a> {
+    /// Parses a KTX file, telling the version from its identifier
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, KtxError> {
+        match bytes.get(..12) {
+            Some(identifier) if identifier == KTX1_IDENTIFIER => Self::parse_ktx1(bytes),
+            Some(identifier) if identifier == KTX2_IDENTIFIER => Self::parse_ktx2(bytes),
+            _ => Err(KtxError::NotKtx),
+        }
+    }
+
+    fn parse_ktx1(bytes: &'a [u8]) -> Result<Self, KtxError> {
+        let swap = match read_u32(bytes, 12)? {
+            0x0403_0201 => false,
+            0x0102_0304 => true,
+            _ => return Err(KtxError::NotKtx),
+        };
+        let [_, gl_type, _, _, internal_format, _, width, height, depth, array_elements, faces, levels, key_values] =
+            read_fields(bytes, 12, swap)?;
+        // uncompressed textures have a type
+        if gl_type != 0 {
+            return Err(KtxError::UnsupportedGlFormat(internal_format));
+        }
+        let format = FORMATS
+            .into_iter()
+            .find(|format| format.internal_format() == internal_format)
+            .or(match internal_format {
+                // RGB only DXT1 decodes the same
+                0x83F0 => Some(CompressedFormat::Bc1),
+                0x8C4C => Some(CompressedFormat::Bc1Srgb),
+                _ => None,
+            })
+            .ok_or(KtxError::UnsupportedGlFormat(internal_format))?;
+        if height == 0 || depth != 0 || array_elements != 0 || faces != 1 {
+            return Err(KtxError::NotTexture2D);
+        }
+
+        let mut offset = KTX1_HEADER_SIZE + key_values as usize;
+        let levels = (0..levels.max(1))
+            .map(|_| {
+                let size = read_u32(bytes, offset)?;
+                let size = if swap { size.swap_bytes() } else { size } as usize;
+                let level = slice(bytes, offset + 4, size)?;
+                // levels are padded to 4 bytes
+                offset = (offset + 4 + size).next_multiple_of(4);
+                Ok(level)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    fn parse_ktx2(bytes: &'a [u8]) -> Result<Self, KtxError> {
+        let [vk_format, _, width, height, depth, layers, faces, levels, supercompression] =
+            read_fields(bytes, 12, false)?;
+        let format =
+            vk_format_to_compressed(vk_format).ok_or(KtxError::UnsupportedVkFormat(vk_format))?;
+        if supercompression != 0 {
+            return Err(KtxError::Supercompressed(supercompression));
+        }
+        if height == 0 || depth != 0 || layers != 0 || faces != 1 {
+            return Err(KtxError::NotTexture2D);
+        }
+
+        let levels = (0..levels.max(1) as usize)
+            .map(|level| {
+                let entry = KTX2_LEVEL_INDEX + level * 24;
+                let offset = read_u64(bytes, entry)?;
+                let length = read_u64(bytes, entry + 8)?;
+                slice(bytes, offset as usize, length as usize)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+}
+
+const FORMATS: [CompressedFormat; 13] = [
+    CompressedFormat::Bc1,
+    CompressedFormat::Bc1Srgb,
+    CompressedFormat::Bc3,
+    CompressedFormat::Bc3Srgb,
+    CompressedFormat::Bc4,
+    CompressedFormat::Bc5,
+    CompressedFormat::Bc6H,
+    CompressedFormat::Bc7,
+    CompressedFormat::Bc7Srgb,
+    CompressedFormat::Etc2Rgb,
+    CompressedFormat::Etc2Srgb,
+    CompressedFormat::Etc2Rgba,
+    CompressedFormat::Etc2SrgbAlpha,
+];
+
+/// `VkFormat` values of the block compressed formats
+const fn vk_format_to_compressed(vk_format: u32) -> Option<CompressedFormat> {
+    Some(match vk_format {
+        131 | 133 => CompressedFormat::Bc1,
+        132 | 134 => CompressedFormat::Bc1Srgb,
+        137 => CompressedFormat::Bc3,
+        138 => CompressedFormat::Bc3Srgb,
+        139 => CompressedFormat::Bc4,
+        141 => CompressedFormat::Bc5,
+        143 => CompressedFormat::Bc6H,
+        145 => CompressedFormat::Bc7,
+        146 => CompressedFormat::Bc7Srgb,
+        147 => CompressedFormat::Etc2Rgb,
+        148 => CompressedFormat::Etc2Srgb,
+        151 => CompressedFormat::Etc2Rgba,
+        152 => CompressedFormat::Etc2SrgbAlpha,
+        _ => return None,
+    })
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], KtxError> {
+    bytes
+        .get(offset..offset + len)
+        .ok_or(KtxError::Truncated(offset + len))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, KtxError> {
+    let slice = slice(bytes, offset, 4)?;
+    Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+/// `N` consecutive words from `offset`, their bytes swapped with `swap`
+fn read_fields<const N: usize>(
+    bytes: &[u8],
+    offset: usize,
+    swap: bool,
+) -> Result<[u32; N], KtxError> {
+    let mut fields = [0; N];
+    for (i, field) in fields.iter_mut().enumerate() {
+        let value = read_u32(bytes, offset + i * 4)?;
+        *field = if swap { value.swap_bytes() } else { value };
+    }
+    Ok(fields)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, KtxError> {
+    let low = read_u32(bytes, offset)?;
+    let high = read_u32(bytes, offset + 4)?;
+    Ok(u64::from(low) | u64::from(high) << 32)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::texture::CompressedFormat;
+
+    use super::{Ktx, KtxError, KTX1_IDENTIFIER, KTX2_IDENTIFIER};
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_parse_ktx1() {
+        let mut bytes = KTX1_IDENTIFIER.to_vec();
+        // BC1 8x8 with 2 levels and 4 bytes of key values
+        bytes.extend(words(&[
+            0x0403_0201,
+            0,
+            1,
+            0,
+            0x83F1,
+            0x1908,
+            8,
+            8,
+            0,
+            0,
+            1,
+            2,
+            4,
+            0,
+        ]));
+        bytes.extend(words(&[32]));
+        bytes.extend([1; 32]);
+        bytes.extend(words(&[8]));
+        bytes.extend([2; 8]);
+        let ktx = Ktx::parse(&bytes).unwrap();
+        assert_eq!(ktx.format, CompressedFormat::Bc1);
+        assert_eq!((ktx.width, ktx.height), (8, 8));
+        assert_eq!(ktx.levels, vec![&[1; 32][..], &[2; 8][..]]);
+
+        assert!(matches!(
+            Ktx::parse(&bytes[..bytes.len() - 1]),
+            Err(KtxError::Truncated(_))
+        ));
+        assert!(matches!(Ktx::parse(&bytes[1..]), Err(KtxError::NotKtx)));
+    }
+
+    #[test]
+    fn test_parse_ktx2() {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        // BC7 sRGB 4x4 with a level
+        bytes.extend(words(&[146, 1, 4, 4, 0, 0, 1, 1, 0]));
+        bytes.extend(words(&[0; 8]));
+        bytes.extend(words(&[104, 0, 16, 0, 16, 0]));
+        bytes.extend([3; 16]);
+        let ktx = Ktx::parse(&bytes).unwrap();
+        assert_eq!(ktx.format, CompressedFormat::Bc7Srgb);
+        assert_eq!(ktx.levels, vec![&[3; 16][..]]);
+
+        // supercompressed with BasisLZ
+        bytes[44] = 1;
+        assert!(matches!(
+            Ktx::parse(&bytes),
+            Err(KtxError::Supercompressed(1))
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use std::ops::Mul;
+
+use glam::{Mat4, Quat, Vec3};
+
+/// Translation, rotation and scale. As a matrix it scales first, then rotates, then translates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    #[must_use]
+    pub const fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+    #[must_use]
+    pub const fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+    #[must_use]
+    pub const fn from_rotation(rotation: Quat) -> Self {
+        Self {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+    #[must_use]
+    pub const fn from_scale(scale: Vec3) -> Self {
+        Self {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Decomposes an affine matrix, shear and perspective are lost.
+    #[must_use]
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+    #[must_use]
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    #[must_use]
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.rotation * (self.scale * point) + self.translation
+    }
+    #[must_use]
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.rotation * (self.scale * vector)
+    }
+
+    /// Linear interpolation of every component, the rotation is normalized afterwards.
+    /// Cheaper than [`Transform::slerp`], the angular speed isn't constant.
+    #[must_use]
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.lerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+    /// Like [`Transform::lerp`], with spherical interpolation of the rotation.
+    #[must_use]
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            rotation: self.rotation.slerp(other.rotation, t),
+            ..self.lerp(other, t)
+        }
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(value: Transform) -> Self {
+        value.to_matrix()
+    }
+}
+
+/// Applies `rhs` first, then `self`. Exact as long as the scale of `self` is uniform.
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            translation: self.transform_point(rhs.translation),
+            rotation: self.rotation * rhs.rotation,
+            scale: self.scale * rhs.scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Quat, Vec3};
+
+    use super::Transform;
+
+    fn transform() -> Transform {
+        Transform::new(
+            Vec3::new(1.0, 2.0, 3.0),
+            Quat::from_rotation_y(f32::to_radians(90.0)),
+            Vec3::new(2.0, 1.0, 0.5),
+        )
+    }
+
+    #[test]
+    fn test_matrix_round_trip() {
+        let transform = transform();
+        let decomposed = Transform::from_matrix(transform.to_matrix());
+        assert!(decomposed
+            .translation
+            .abs_diff_eq(transform.translation, 1e-5));
+        assert!(decomposed.rotation.abs_diff_eq(transform.rotation, 1e-5));
+        assert!(decomposed.scale.abs_diff_eq(transform.scale, 1e-5));
+
+        let point = Vec3::new(1.0, -1.0, 2.0);
+        assert!(transform
+            .transform_point(point)
+            .abs_diff_eq(Mat4::from(transform).transform_point3(point), 1e-5));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let end = transform();
+        let half = Transform::IDENTITY.slerp(&end, 0.5);
+        assert!(half.translation.abs_diff_eq(Vec3::new(0.5, 1.0, 1.5), 1e-5));
+        assert!(half
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_y(f32::to_radians(45.0)), 1e-5));
+        assert!(Transform::IDENTITY
+            .lerp(&end, 1.0)
+            .rotation
+            .abs_diff_eq(end.rotation, 1e-5));
+    }
+
+    #[test]
+    fn test_compose() {
+        let parent = Transform::new(Vec3::X, Quat::from_rotation_z(1.0), Vec3::splat(2.0));
+        let child = transform();
+        let composed = (parent * child).to_matrix();
+        assert!(composed.abs_diff_eq(parent.to_matrix() * child.to_matrix(), 1e-5));
+    }
+}
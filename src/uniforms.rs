@@ -1,11 +1,21 @@
-use gl::types::GLint;
-use glam::{Vec2, Vec3, Vec4};
+use gl::types::{GLint, GLsizei};
+use glam::{BVec2, BVec3, BVec4, IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+
+use crate::{handle, GLHandle};
 
 mod private {
     pub trait Sealed {}
 }
-pub(crate) trait SetUniform: private::Sealed {
+pub(crate) trait SetUniform: private::Sealed + std::fmt::Debug {
+    /// Sets the uniform of the program in use
     fn set_uniform(&self, location: GLint);
+    /// Sets the uniform of `program`, bound or not
+    fn set_program_uniform(&self, program: GLHandle, location: GLint);
+}
+
+/// Whether the current context has `glProgramUniform*`, core since 4.1
+pub(crate) fn has_program_uniforms() -> bool {
+    handle::context_version() >= (4, 1)
 }
 
 impl private::Sealed for f32 {}
@@ -14,6 +24,9 @@ impl SetUniform for f32 {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform1f(location, *self) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform1f(program, location, *self) }
+    }
 }
 
 impl private::Sealed for (f32, f32) {}
@@ -22,6 +35,9 @@ impl SetUniform for (f32, f32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform2f(location, self.0, self.1) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform2f(program, location, self.0, self.1) }
+    }
 }
 
 impl private::Sealed for Vec2 {}
@@ -30,6 +46,9 @@ impl SetUniform for Vec2 {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform2f(location, self.x, self.y) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform2f(program, location, self.x, self.y) }
+    }
 }
 
 impl private::Sealed for (f32, f32, f32) {}
@@ -38,6 +57,9 @@ impl SetUniform for (f32, f32, f32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform3f(location, self.0, self.1, self.2) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform3f(program, location, self.0, self.1, self.2) }
+    }
 }
 
 impl private::Sealed for Vec3 {}
@@ -46,6 +68,9 @@ impl SetUniform for Vec3 {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform3f(location, self.x, self.y, self.z) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform3f(program, location, self.x, self.y, self.z) }
+    }
 }
 impl private::Sealed for (f32, f32, f32, f32) {}
 
@@ -53,6 +78,9 @@ impl SetUniform for (f32, f32, f32, f32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform4f(location, self.0, self.1, self.2, self.3) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform4f(program, location, self.0, self.1, self.2, self.3) }
+    }
 }
 
 impl private::Sealed for Vec4 {}
@@ -61,6 +89,9 @@ impl SetUniform for Vec4 {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform4f(location, self.x, self.y, self.z, self.w) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform4f(program, location, self.x, self.y, self.z, self.w) }
+    }
 }
 
 impl private::Sealed for i32 {}
@@ -69,6 +100,9 @@ impl SetUniform for i32 {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform1i(location, *self) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform1i(program, location, *self) }
+    }
 }
 
 impl private::Sealed for (i32, i32) {}
@@ -77,6 +111,9 @@ impl SetUniform for (i32, i32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform2i(location, self.0, self.1) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform2i(program, location, self.0, self.1) }
+    }
 }
 
 impl private::Sealed for (i32, i32, i32) {}
@@ -85,6 +122,9 @@ impl SetUniform for (i32, i32, i32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform3i(location, self.0, self.1, self.2) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform3i(program, location, self.0, self.1, self.2) }
+    }
 }
 impl private::Sealed for (i32, i32, i32, i32) {}
 
@@ -92,6 +132,112 @@ impl SetUniform for (i32, i32, i32, i32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform4i(location, self.0, self.1, self.2, self.3) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform4i(program, location, self.0, self.1, self.2, self.3) }
+    }
+}
+
+impl private::Sealed for IVec2 {}
+
+impl SetUniform for IVec2 {
+    fn set_uniform(&self, location: GLint) {
+        unsafe { gl::Uniform2i(location, self.x, self.y) }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform2i(program, location, self.x, self.y) }
+    }
+}
+
+impl private::Sealed for IVec3 {}
+
+impl SetUniform for IVec3 {
+    fn set_uniform(&self, location: GLint) {
+        unsafe { gl::Uniform3i(location, self.x, self.y, self.z) }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform3i(program, location, self.x, self.y, self.z) }
+    }
+}
+
+impl private::Sealed for IVec4 {}
+
+impl SetUniform for IVec4 {
+    fn set_uniform(&self, location: GLint) {
+        unsafe { gl::Uniform4i(location, self.x, self.y, self.z, self.w) }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform4i(program, location, self.x, self.y, self.z, self.w) }
+    }
+}
+
+// GLSL bools are set as integers, 0 is false
+
+impl private::Sealed for bool {}
+
+impl SetUniform for bool {
+    fn set_uniform(&self, location: GLint) {
+        i32::from(*self).set_uniform(location);
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        i32::from(*self).set_program_uniform(program, location);
+    }
+}
+
+impl private::Sealed for (bool, bool) {}
+
+impl SetUniform for (bool, bool) {
+    fn set_uniform(&self, location: GLint) {
+        (i32::from(self.0), i32::from(self.1)).set_uniform(location);
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        (i32::from(self.0), i32::from(self.1)).set_program_uniform(program, location);
+    }
+}
+
+impl private::Sealed for BVec2 {}
+
+impl SetUniform for BVec2 {
+    fn set_uniform(&self, location: GLint) {
+        (self.x, self.y).set_uniform(location);
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        (self.x, self.y).set_program_uniform(program, location);
+    }
+}
+
+impl private::Sealed for BVec3 {}
+
+impl SetUniform for BVec3 {
+    fn set_uniform(&self, location: GLint) {
+        (i32::from(self.x), i32::from(self.y), i32::from(self.z)).set_uniform(location);
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        (i32::from(self.x), i32::from(self.y), i32::from(self.z))
+            .set_program_uniform(program, location);
+    }
+}
+
+impl private::Sealed for BVec4 {}
+
+impl SetUniform for BVec4 {
+    fn set_uniform(&self, location: GLint) {
+        (
+            i32::from(self.x),
+            i32::from(self.y),
+            i32::from(self.z),
+            i32::from(self.w),
+        )
+            .set_uniform(location);
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        (
+            i32::from(self.x),
+            i32::from(self.y),
+            i32::from(self.z),
+            i32::from(self.w),
+        )
+            .set_program_uniform(program, location);
+    }
 }
 
 impl private::Sealed for u32 {}
@@ -100,6 +246,9 @@ impl SetUniform for u32 {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform1ui(location, *self) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform1ui(program, location, *self) }
+    }
 }
 
 impl private::Sealed for (u32, u32) {}
@@ -108,6 +257,9 @@ impl SetUniform for (u32, u32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform2ui(location, self.0, self.1) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform2ui(program, location, self.0, self.1) }
+    }
 }
 
 impl private::Sealed for (u32, u32, u32) {}
@@ -116,6 +268,9 @@ impl SetUniform for (u32, u32, u32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform3ui(location, self.0, self.1, self.2) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform3ui(program, location, self.0, self.1, self.2) }
+    }
 }
 impl private::Sealed for (u32, u32, u32, u32) {}
 
@@ -123,18 +278,367 @@ impl SetUniform for (u32, u32, u32, u32) {
     fn set_uniform(&self, location: GLint) {
         unsafe { gl::Uniform4ui(location, self.0, self.1, self.2, self.3) }
     }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform4ui(program, location, self.0, self.1, self.2, self.3) }
+    }
 }
 
-impl private::Sealed for [f32; 16] {}
-impl SetUniform for [f32; 16] {
+impl private::Sealed for UVec2 {}
+
+impl SetUniform for UVec2 {
     fn set_uniform(&self, location: GLint) {
-        unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, self.as_ptr()) }
+        unsafe { gl::Uniform2ui(location, self.x, self.y) }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform2ui(program, location, self.x, self.y) }
     }
 }
 
-impl private::Sealed for glam::Mat4 {}
-impl SetUniform for glam::Mat4 {
+impl private::Sealed for UVec3 {}
+
+impl SetUniform for UVec3 {
     fn set_uniform(&self, location: GLint) {
-        unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, self.to_cols_array().as_ptr()) }
+        unsafe { gl::Uniform3ui(location, self.x, self.y, self.z) }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform3ui(program, location, self.x, self.y, self.z) }
+    }
+}
+
+impl private::Sealed for UVec4 {}
+
+impl SetUniform for UVec4 {
+    fn set_uniform(&self, location: GLint) {
+        unsafe { gl::Uniform4ui(location, self.x, self.y, self.z, self.w) }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        unsafe { gl::ProgramUniform4ui(program, location, self.x, self.y, self.z, self.w) }
+    }
+}
+
+/// A matrix stored row by row, uploaded transposed so that GLSL still gets it column by column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transposed<T>(pub T);
+
+/// Square matrices of floats, column by column
+pub(crate) trait Matrix: std::fmt::Debug {
+    type Floats: AsRef<[f32]>;
+    fn floats(&self) -> Self::Floats;
+}
+
+/// Sets a 2x2, 3x3 or 4x4 matrix from its floats, of `program` or of the one in use
+fn set_matrix(program: Option<GLHandle>, location: GLint, transpose: bool, floats: &[f32]) {
+    let transpose = if transpose { gl::TRUE } else { gl::FALSE };
+    let value = floats.as_ptr();
+    unsafe {
+        match (program, floats.len()) {
+            (None, 4) => gl::UniformMatrix2fv(location, 1, transpose, value),
+            (None, 9) => gl::UniformMatrix3fv(location, 1, transpose, value),
+            (None, _) => gl::UniformMatrix4fv(location, 1, transpose, value),
+            (Some(program), 4) => {
+                gl::ProgramUniformMatrix2fv(program, location, 1, transpose, value);
+            }
+            (Some(program), 9) => {
+                gl::ProgramUniformMatrix3fv(program, location, 1, transpose, value);
+            }
+            (Some(program), _) => {
+                gl::ProgramUniformMatrix4fv(program, location, 1, transpose, value);
+            }
+        }
+    }
+}
+
+macro_rules! matrix {
+    ($type:ty, $floats:ty, |$matrix:ident| $to_floats:expr) => {
+        impl Matrix for $type {
+            type Floats = $floats;
+            fn floats(&self) -> Self::Floats {
+                let $matrix = self;
+                $to_floats
+            }
+        }
+        impl private::Sealed for $type {}
+        impl SetUniform for $type {
+            fn set_uniform(&self, location: GLint) {
+                set_matrix(None, location, false, self.floats().as_ref());
+            }
+            fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+                set_matrix(Some(program), location, false, self.floats().as_ref());
+            }
+        }
+    };
+}
+
+matrix!([f32; 4], [f32; 4], |matrix| *matrix);
+matrix!([f32; 9], [f32; 9], |matrix| *matrix);
+matrix!([f32; 16], [f32; 16], |matrix| *matrix);
+// one array per column, like `to_cols_array_2d` of glam
+matrix!([[f32; 2]; 2], [f32; 4], |matrix| bytemuck::cast(*matrix));
+matrix!([[f32; 3]; 3], [f32; 9], |matrix| bytemuck::cast(*matrix));
+matrix!([[f32; 4]; 4], [f32; 16], |matrix| bytemuck::cast(*matrix));
+matrix!(glam::Mat2, [f32; 4], |matrix| matrix.to_cols_array());
+matrix!(glam::Mat3, [f32; 9], |matrix| matrix.to_cols_array());
+matrix!(glam::Mat4, [f32; 16], |matrix| matrix.to_cols_array());
+#[cfg(feature = "nalgebra")]
+matrix!(nalgebra::Matrix2<f32>, [f32; 4], |matrix| bytemuck::cast(
+    <[[f32; 2]; 2]>::from(*matrix)
+));
+#[cfg(feature = "nalgebra")]
+matrix!(nalgebra::Matrix3<f32>, [f32; 9], |matrix| bytemuck::cast(
+    <[[f32; 3]; 3]>::from(*matrix)
+));
+#[cfg(feature = "nalgebra")]
+matrix!(nalgebra::Matrix4<f32>, [f32; 16], |matrix| bytemuck::cast(
+    <[[f32; 4]; 4]>::from(*matrix)
+));
+
+impl<T: Matrix> private::Sealed for Transposed<T> {}
+impl<T: Matrix> SetUniform for Transposed<T> {
+    fn set_uniform(&self, location: GLint) {
+        set_matrix(None, location, true, self.0.floats().as_ref());
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        set_matrix(Some(program), location, true, self.0.floats().as_ref());
+    }
+}
+
+// slices set arrays of uniforms from `location` on, glam types are laid out like the
+// matching GLSL types so they can be passed as floats
+macro_rules! slice_uniform {
+    ($type:ty, $function:ident, $program_function:ident) => {
+        impl private::Sealed for &[$type] {}
+        impl SetUniform for &[$type] {
+            fn set_uniform(&self, location: GLint) {
+                unsafe { gl::$function(location, self.len() as GLsizei, self.as_ptr().cast()) }
+            }
+            fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+                unsafe {
+                    gl::$program_function(
+                        program,
+                        location,
+                        self.len() as GLsizei,
+                        self.as_ptr().cast(),
+                    );
+                }
+            }
+        }
+    };
+    ($type:ty, $function:ident, $program_function:ident, matrix) => {
+        impl private::Sealed for &[$type] {}
+        impl SetUniform for &[$type] {
+            fn set_uniform(&self, location: GLint) {
+                unsafe {
+                    gl::$function(
+                        location,
+                        self.len() as GLsizei,
+                        gl::FALSE,
+                        self.as_ptr().cast(),
+                    );
+                }
+            }
+            fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+                unsafe {
+                    gl::$program_function(
+                        program,
+                        location,
+                        self.len() as GLsizei,
+                        gl::FALSE,
+                        self.as_ptr().cast(),
+                    );
+                }
+            }
+        }
+    };
+}
+
+slice_uniform!(f32, Uniform1fv, ProgramUniform1fv);
+slice_uniform!(Vec2, Uniform2fv, ProgramUniform2fv);
+slice_uniform!(Vec3, Uniform3fv, ProgramUniform3fv);
+slice_uniform!(Vec4, Uniform4fv, ProgramUniform4fv);
+slice_uniform!(i32, Uniform1iv, ProgramUniform1iv);
+slice_uniform!(IVec2, Uniform2iv, ProgramUniform2iv);
+slice_uniform!(IVec3, Uniform3iv, ProgramUniform3iv);
+slice_uniform!(IVec4, Uniform4iv, ProgramUniform4iv);
+slice_uniform!(u32, Uniform1uiv, ProgramUniform1uiv);
+slice_uniform!(UVec2, Uniform2uiv, ProgramUniform2uiv);
+slice_uniform!(UVec3, Uniform3uiv, ProgramUniform3uiv);
+slice_uniform!(UVec4, Uniform4uiv, ProgramUniform4uiv);
+slice_uniform!(
+    glam::Mat2,
+    UniformMatrix2fv,
+    ProgramUniformMatrix2fv,
+    matrix
+);
+slice_uniform!(
+    glam::Mat3,
+    UniformMatrix3fv,
+    ProgramUniformMatrix3fv,
+    matrix
+);
+slice_uniform!(
+    glam::Mat4,
+    UniformMatrix4fv,
+    ProgramUniformMatrix4fv,
+    matrix
+);
+
+/// Uniform support for types of other crates, by conversion to one the crate can set,
+/// like a matrix of another math library to [`glam::Mat4`], printed with [`Debug`] in traces
+#[allow(private_bounds)]
+pub trait IntoUniform: std::fmt::Debug {
+    type Uniform: SetUniform;
+    fn to_uniform(&self) -> Self::Uniform;
+}
+
+impl<T: IntoUniform> private::Sealed for T {}
+impl<T: IntoUniform> SetUniform for T {
+    fn set_uniform(&self, location: GLint) {
+        self.to_uniform().set_uniform(location);
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        self.to_uniform().set_program_uniform(program, location);
+    }
+}
+
+/// A uniform value kept around to be set later, like the parameters of a
+/// [`Material`](crate::material::Material)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Int(i32),
+    UInt(u32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat2(glam::Mat2),
+    Mat3(glam::Mat3),
+    Mat4(glam::Mat4),
+    /// Texture unit a sampler reads from, the texture has to be bound to it separately
+    Sampler(i32),
+}
+
+impl From<f32> for UniformValue {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+impl From<i32> for UniformValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+impl From<bool> for UniformValue {
+    fn from(value: bool) -> Self {
+        Self::Int(value.into())
+    }
+}
+impl From<u32> for UniformValue {
+    fn from(value: u32) -> Self {
+        Self::UInt(value)
+    }
+}
+impl From<Vec2> for UniformValue {
+    fn from(value: Vec2) -> Self {
+        Self::Vec2(value)
+    }
+}
+impl From<Vec3> for UniformValue {
+    fn from(value: Vec3) -> Self {
+        Self::Vec3(value)
+    }
+}
+impl From<Vec4> for UniformValue {
+    fn from(value: Vec4) -> Self {
+        Self::Vec4(value)
+    }
+}
+impl From<(f32, f32, f32, f32)> for UniformValue {
+    fn from(value: (f32, f32, f32, f32)) -> Self {
+        Self::Vec4(value.into())
+    }
+}
+impl From<glam::Mat2> for UniformValue {
+    fn from(value: glam::Mat2) -> Self {
+        Self::Mat2(value)
+    }
+}
+impl From<glam::Mat3> for UniformValue {
+    fn from(value: glam::Mat3) -> Self {
+        Self::Mat3(value)
+    }
+}
+impl From<glam::Mat4> for UniformValue {
+    fn from(value: glam::Mat4) -> Self {
+        Self::Mat4(value)
+    }
+}
+
+impl private::Sealed for UniformValue {}
+impl SetUniform for UniformValue {
+    fn set_uniform(&self, location: GLint) {
+        match self {
+            Self::Float(value) => value.set_uniform(location),
+            Self::Int(value) | Self::Sampler(value) => value.set_uniform(location),
+            Self::UInt(value) => value.set_uniform(location),
+            Self::Vec2(value) => value.set_uniform(location),
+            Self::Vec3(value) => value.set_uniform(location),
+            Self::Vec4(value) => value.set_uniform(location),
+            Self::Mat2(value) => value.set_uniform(location),
+            Self::Mat3(value) => value.set_uniform(location),
+            Self::Mat4(value) => value.set_uniform(location),
+        }
+    }
+    fn set_program_uniform(&self, program: GLHandle, location: GLint) {
+        match self {
+            Self::Float(value) => value.set_program_uniform(program, location),
+            Self::Int(value) | Self::Sampler(value) => value.set_program_uniform(program, location),
+            Self::UInt(value) => value.set_program_uniform(program, location),
+            Self::Vec2(value) => value.set_program_uniform(program, location),
+            Self::Vec3(value) => value.set_program_uniform(program, location),
+            Self::Vec4(value) => value.set_program_uniform(program, location),
+            Self::Mat2(value) => value.set_program_uniform(program, location),
+            Self::Mat3(value) => value.set_program_uniform(program, location),
+            Self::Mat4(value) => value.set_program_uniform(program, location),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Mat4;
+
+    use super::{IntoUniform, Matrix, SetUniform};
+
+    #[test]
+    fn test_matrix_floats() {
+        // exact copies, compared bit by bit
+        let bits = |floats: [f32; 16]| floats.map(f32::to_bits);
+        let matrix = Mat4::from_cols_array(&std::array::from_fn(|i| i as f32));
+        assert_eq!(
+            bits(matrix.to_cols_array_2d().floats()),
+            bits(matrix.floats())
+        );
+        assert_eq!(bits(matrix.to_cols_array().floats()), bits(matrix.floats()));
+        assert_eq!(
+            [[1.0, 2.0], [3.0, 4.0]].floats().map(f32::to_bits),
+            [1.0, 2.0, 3.0, 4.0].map(f32::to_bits)
+        );
+    }
+
+    #[test]
+    fn test_into_uniform() {
+        #[derive(Debug)]
+        struct Celsius(f32);
+        impl IntoUniform for Celsius {
+            type Uniform = f32;
+            fn to_uniform(&self) -> f32 {
+                self.0
+            }
+        }
+        const fn settable<T: SetUniform>(_: &T) {}
+
+        let temperature = Celsius(21.5);
+        settable(&temperature);
+        assert_eq!(temperature.to_uniform().to_bits(), 21.5f32.to_bits());
     }
 }
@@ -1,6 +1,14 @@
-use gl::types::{GLenum, GLint, GLsizei, GLuint};
+use std::marker::PhantomData;
 
-use crate::{opengl::IndexSize, GLHandle, NULL_HANDLE};
+use gl::types::{GLenum, GLint, GLintptr, GLsizei, GLuint};
+
+use crate::{
+    buffer::Buffer,
+    gl_api,
+    handle::{self, delete_later, ContextBound, GlObject, VaoId},
+    opengl::IndexSize,
+    GLHandle, NULL_HANDLE,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
@@ -13,7 +21,11 @@ pub enum DataType {
     UnsignedInt = gl::UNSIGNED_INT,
     Double = gl::DOUBLE,
     Float = gl::FLOAT,
+    HalfFloat = gl::HALF_FLOAT,
     Fixed = gl::FIXED,
+    /// Four components packed in a single 32-bit value, 10 bits for xyz and 2 bits for w.
+    Int2101010Rev = gl::INT_2_10_10_10_REV,
+    UnsignedInt2101010Rev = gl::UNSIGNED_INT_2_10_10_10_REV,
 }
 
 impl From<IndexSize> for DataType {
@@ -31,14 +43,27 @@ impl DataType {
     pub const fn size(&self) -> usize {
         match self {
             Self::Byte | Self::UnsignedByte => 1,
-            Self::Short | Self::UnsignedShort | Self::Fixed => 2,
-            Self::Int | Self::UnsignedInt | Self::Float => 4,
+            Self::Short | Self::UnsignedShort | Self::HalfFloat => 2,
+            Self::Int
+            | Self::UnsignedInt
+            | Self::Float
+            | Self::Fixed
+            | Self::Int2101010Rev
+            | Self::UnsignedInt2101010Rev => 4,
             Self::Double => 8,
         }
     }
     #[must_use]
     pub fn is_floating_point(self) -> bool {
-        self == Self::Float || self == Self::Double || self == Self::Fixed
+        self == Self::Float
+            || self == Self::Double
+            || self == Self::HalfFloat
+            || self == Self::Fixed
+    }
+    /// Packed types store all the components of an attribute in a single value.
+    #[must_use]
+    pub const fn is_packed(self) -> bool {
+        matches!(self, Self::Int2101010Rev | Self::UnsignedInt2101010Rev)
     }
 }
 
@@ -47,6 +72,8 @@ pub struct VertexAttribute {
     pub components: GLint,
     pub data_type: DataType,
     pub normalized: bool,
+    /// Number of consecutive locations taken up, one per matrix column.
+    pub columns: GLuint,
 }
 
 impl VertexAttribute {
@@ -56,27 +83,123 @@ impl VertexAttribute {
             components,
             data_type,
             normalized,
+            columns: 1,
+        }
+    }
+
+    /// A matrix attribute, each column is a vector of `rows` components in its own location.
+    #[must_use]
+    pub const fn matrix(columns: GLuint, rows: GLint, data_type: DataType) -> Self {
+        Self {
+            components: rows,
+            data_type,
+            normalized: false,
+            columns,
+        }
+    }
+
+    #[must_use]
+    pub const fn mat3() -> Self {
+        Self::matrix(3, 3, DataType::Float)
+    }
+
+    #[must_use]
+    pub const fn mat4() -> Self {
+        Self::matrix(4, 4, DataType::Float)
+    }
+
+    #[must_use]
+    pub const fn column_size(&self) -> usize {
+        if self.data_type.is_packed() {
+            self.data_type.size()
+        } else {
+            self.data_type.size() * self.components as usize
         }
     }
 
     #[must_use]
     pub const fn size(&self) -> usize {
-        self.data_type.size() * self.components as usize
+        self.column_size() * self.columns as usize
+    }
+
+    #[must_use]
+    pub const fn locations(&self) -> GLuint {
+        self.columns
     }
 
     #[must_use]
     pub fn is_floating_point(&self) -> bool {
         self.data_type.is_floating_point()
     }
+
+    /// Integral attributes are read as integers in the shader, without conversion to float.
+    #[must_use]
+    pub fn is_integral(&self) -> bool {
+        !(self.is_floating_point() || self.normalized || self.data_type.is_packed())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LayoutAttribute {
+    pub location: GLuint,
+    pub attribute: VertexAttribute,
+    pub offset: usize,
+}
+
+/// Describes interleaved vertex data, computing offsets and stride from the attributes in order.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct VertexLayout {
+    attributes: Vec<LayoutAttribute>,
+    stride: usize,
+}
+
+impl VertexLayout {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            attributes: vec![],
+            stride: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn attribute(mut self, location: GLuint, attribute: VertexAttribute) -> Self {
+        self.attributes.push(LayoutAttribute {
+            location,
+            attribute,
+            offset: self.stride,
+        });
+        self.stride += attribute.size();
+        self
+    }
+
+    #[must_use]
+    pub const fn stride(&self) -> usize {
+        self.stride
+    }
+
+    #[must_use]
+    pub fn attributes(&self) -> &[LayoutAttribute] {
+        &self.attributes
+    }
+
+    #[must_use]
+    pub fn offset(&self, location: GLuint) -> Option<usize> {
+        self.attributes
+            .iter()
+            .find(|a| a.location == location)
+            .map(|a| a.offset)
+    }
 }
 
 pub struct VertexArrayObject {
     id: GLHandle,
+    _context: ContextBound,
 }
 
 impl Drop for VertexArrayObject {
     fn drop(&mut self) {
-        unsafe { gl::DeleteVertexArrays(1, &self.id) };
+        delete_later(GlObject::VertexArray(self.id));
     }
 }
 impl VertexArrayObject {
@@ -84,16 +207,28 @@ impl VertexArrayObject {
     pub fn new() -> Self {
         let mut id = NULL_HANDLE;
         unsafe { gl::GenVertexArrays(1, &mut id) };
-        Self { id }
+        gl_api::trace_call("glGenVertexArrays", || id.to_string());
+        Self {
+            id,
+            _context: PhantomData,
+        }
+    }
+    #[must_use]
+    pub const fn id(&self) -> VaoId {
+        VaoId(self.id)
     }
     pub fn bind(&mut self) {
+        gl_api::trace_call("glBindVertexArray", || self.id.to_string());
+        handle::bound_directly();
         unsafe { gl::BindVertexArray(self.id) };
     }
 
     pub fn unbind(&mut self) {
-        unsafe { gl::BindVertexArray(NULL_HANDLE) };
+        Self::unbind_all();
     }
     pub fn unbind_all() {
+        gl_api::trace_call("glBindVertexArray", || NULL_HANDLE.to_string());
+        handle::bound_directly();
         unsafe { gl::BindVertexArray(NULL_HANDLE) };
     }
 
@@ -105,6 +240,9 @@ impl VertexArrayObject {
         offset: GLint,
     ) {
         // Set the VertexAttribute pointer in this location
+        gl_api::trace_call("glVertexAttribPointer", || {
+            format!("{}, {location}, {attribute:?}, {stride}, {offset}", self.id)
+        });
 
         let components = attribute.components;
         let data_type = attribute.data_type as GLenum;
@@ -114,37 +252,151 @@ impl VertexArrayObject {
             gl::FALSE
         };
 
-        // Compute the attribute pointer
-        let mut pointer = std::ptr::null_mut::<u8>(); // Actual base pointer is in VBO
-        pointer = pointer.wrapping_add(offset as usize);
-        // let pointer = offset;
-
-        if attribute.is_floating_point() || attribute.normalized {
-            unsafe {
-                gl::VertexAttribPointer(
-                    location,
-                    components,
-                    data_type,
-                    normalized,
-                    stride,
-                    pointer as *const _,
-                );
-            };
+        // Matrices take up one location per column
+        for column in 0..attribute.locations() {
+            let location = location + column;
+            let offset = offset as usize + column as usize * attribute.column_size();
+
+            // Compute the attribute pointer
+            let mut pointer = std::ptr::null_mut::<u8>(); // Actual base pointer is in VBO
+            pointer = pointer.wrapping_add(offset);
+
+            if attribute.data_type == DataType::Double {
+                // doubles are kept in double precision, read as dvec in the shader
+                unsafe {
+                    gl::VertexAttribLPointer(
+                        location,
+                        components,
+                        data_type,
+                        stride,
+                        pointer as *const _,
+                    );
+                };
+            } else if !attribute.is_integral() {
+                unsafe {
+                    gl::VertexAttribPointer(
+                        location,
+                        components,
+                        data_type,
+                        normalized,
+                        stride,
+                        pointer as *const _,
+                    );
+                };
+            } else {
+                // integral
+                unsafe {
+                    gl::VertexAttribIPointer(
+                        location,
+                        components,
+                        data_type,
+                        stride,
+                        pointer as *const _,
+                    );
+                };
+            }
+
+            // Finally, we enable the VertexAttribute in this location
+            unsafe { gl::EnableVertexAttribArray(location) };
+        }
+    }
+
+    pub fn set_attribute_divisor(&mut self, location: GLuint, divisor: GLuint) {
+        unsafe { gl::VertexAttribDivisor(location, divisor) };
+    }
+
+    /// Sets the divisor of every location taken up by `attribute`.
+    pub fn set_attribute_divisors(
+        &mut self,
+        location: GLuint,
+        attribute: &VertexAttribute,
+        divisor: GLuint,
+    ) {
+        for column in 0..attribute.locations() {
+            self.set_attribute_divisor(location + column, divisor);
+        }
+    }
+
+    /// Declares the format of an attribute and the binding point it sources data from,
+    /// buffers are then attached to the binding point with [`Self::bind_vertex_buffer`].
+    pub fn set_format(
+        &mut self,
+        location: GLuint,
+        attribute: &VertexAttribute,
+        relative_offset: GLuint,
+        binding: GLuint,
+    ) {
+        let components = attribute.components;
+        let data_type = attribute.data_type as GLenum;
+        let normalized = if attribute.normalized {
+            gl::TRUE
         } else {
-            // integral
-            unsafe {
-                gl::VertexAttribIPointer(
-                    location,
-                    components,
-                    data_type,
-                    stride,
-                    pointer as *const _,
-                );
-            };
+            gl::FALSE
+        };
+
+        for column in 0..attribute.locations() {
+            let location = location + column;
+            let relative_offset =
+                relative_offset + (column as usize * attribute.column_size()) as GLuint;
+            if attribute.data_type == DataType::Double {
+                unsafe {
+                    gl::VertexAttribLFormat(location, components, data_type, relative_offset);
+                };
+            } else if !attribute.is_integral() {
+                unsafe {
+                    gl::VertexAttribFormat(
+                        location,
+                        components,
+                        data_type,
+                        normalized,
+                        relative_offset,
+                    );
+                };
+            } else {
+                // integral
+                unsafe {
+                    gl::VertexAttribIFormat(location, components, data_type, relative_offset);
+                };
+            }
+            unsafe { gl::VertexAttribBinding(location, binding) };
+            unsafe { gl::EnableVertexAttribArray(location) };
         }
+    }
 
-        // Finally, we enable the VertexAttribute in this location
-        unsafe { gl::EnableVertexAttribArray(location) };
+    pub fn set_layout_format(&mut self, layout: &VertexLayout, binding: GLuint) {
+        for attrib in layout.attributes() {
+            self.set_format(
+                attrib.location,
+                &attrib.attribute,
+                attrib.offset as GLuint,
+                binding,
+            );
+        }
+    }
+
+    pub fn bind_vertex_buffer<T: Default>(
+        &mut self,
+        binding: GLuint,
+        buffer: &Buffer<T>,
+        offset: GLintptr,
+        stride: GLsizei,
+    ) {
+        unsafe { gl::BindVertexBuffer(binding, buffer.id().raw(), offset, stride) };
+    }
+
+    pub fn set_binding_divisor(&mut self, binding: GLuint, divisor: GLuint) {
+        unsafe { gl::VertexBindingDivisor(binding, divisor) };
+    }
+
+    pub fn set_layout(&mut self, layout: &VertexLayout) {
+        for attrib in layout.attributes() {
+            self.set_attribute(
+                attrib.location,
+                &attrib.attribute,
+                layout.stride() as GLsizei,
+                attrib.offset as GLint,
+            );
+        }
     }
 }
 
@@ -153,3 +405,51 @@ impl Default for VertexArrayObject {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{DataType, VertexAttribute, VertexLayout};
+
+    #[test]
+    fn test_layout_offsets() {
+        let vec3 = VertexAttribute::new(3, DataType::Float, false);
+        let color = VertexAttribute::new(4, DataType::UnsignedByte, true);
+        let uv = VertexAttribute::new(2, DataType::Float, false);
+        let layout = VertexLayout::new()
+            .attribute(0, vec3)
+            .attribute(1, color)
+            .attribute(2, uv);
+
+        assert_eq!(layout.stride(), 12 + 4 + 8);
+        assert_eq!(layout.offset(0), Some(0));
+        assert_eq!(layout.offset(1), Some(12));
+        assert_eq!(layout.offset(2), Some(16));
+        assert_eq!(layout.offset(3), None);
+    }
+
+    #[test]
+    fn test_matrix_attribute() {
+        let mat4 = VertexAttribute::mat4();
+        assert_eq!(mat4.locations(), 4);
+        assert_eq!(mat4.column_size(), 16);
+        assert_eq!(mat4.size(), 64);
+        assert_eq!(VertexAttribute::mat3().size(), 36);
+
+        let layout = VertexLayout::new()
+            .attribute(0, mat4)
+            .attribute(4, VertexAttribute::new(4, DataType::Float, false));
+        assert_eq!(layout.stride(), 64 + 16);
+        assert_eq!(layout.offset(4), Some(64));
+    }
+
+    #[test]
+    fn test_packed_attribute() {
+        let normal = VertexAttribute::new(4, DataType::Int2101010Rev, true);
+        assert_eq!(normal.size(), 4);
+        assert!(!normal.is_integral());
+        let half = VertexAttribute::new(2, DataType::HalfFloat, false);
+        assert_eq!(half.size(), 4);
+        assert!(!half.is_integral());
+        assert!(VertexAttribute::new(1, DataType::Int, false).is_integral());
+    }
+}
@@ -0,0 +1,132 @@
+use gl::types::GLsizei;
+use glam::Vec2;
+
+use crate::opengl::OpenGl;
+
+/// The framebuffer size and the area rendered to, kept up to date by
+/// [`run_app`](crate::app::run_app) before [`Application::reshape`](crate::app::Application::reshape).
+///
+/// With a fixed aspect ratio the area is the largest one fitting the framebuffer,
+/// centered with bars on the sides or above and below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    width: i32,
+    height: i32,
+    fixed_aspect_ratio: Option<f32>,
+}
+
+impl Viewport {
+    #[must_use]
+    pub const fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            fixed_aspect_ratio: None,
+        }
+    }
+    /// Letterboxes the area to `aspect_ratio`, width over height
+    #[must_use]
+    pub const fn with_fixed_aspect_ratio(mut self, aspect_ratio: Option<f32>) -> Self {
+        self.fixed_aspect_ratio = aspect_ratio;
+        self
+    }
+
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+    }
+    pub fn set_fixed_aspect_ratio(&mut self, aspect_ratio: Option<f32>) {
+        self.fixed_aspect_ratio = aspect_ratio;
+    }
+    #[must_use]
+    pub const fn fixed_aspect_ratio(&self) -> Option<f32> {
+        self.fixed_aspect_ratio
+    }
+
+    /// Size of the whole framebuffer in pixels
+    #[must_use]
+    pub const fn framebuffer_size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// `(x, y, width, height)` of the rendered area in pixels from the bottom left
+    #[must_use]
+    pub fn rect(&self) -> (i32, i32, i32, i32) {
+        let (width, height) = (self.width.max(0), self.height.max(0));
+        let Some(aspect_ratio) = self.fixed_aspect_ratio.filter(|ratio| *ratio > 0.0) else {
+            return (0, 0, width, height);
+        };
+        let (area_width, area_height) = if width as f32 > height as f32 * aspect_ratio {
+            ((height as f32 * aspect_ratio).round() as i32, height)
+        } else {
+            (width, (width as f32 / aspect_ratio).round() as i32)
+        };
+        (
+            (width - area_width) / 2,
+            (height - area_height) / 2,
+            area_width,
+            area_height,
+        )
+    }
+    /// Size of the rendered area in pixels
+    #[must_use]
+    pub fn size(&self) -> Vec2 {
+        let (_, _, width, height) = self.rect();
+        Vec2::new(width as f32, height as f32)
+    }
+    /// Width over height of the rendered area, 1 while it's empty
+    #[must_use]
+    pub fn aspect_ratio(&self) -> f32 {
+        let (_, _, width, height) = self.rect();
+        if width > 0 && height > 0 {
+            width as f32 / height as f32
+        } else {
+            1.0
+        }
+    }
+
+    /// Converts pixels from the top left of the framebuffer, like the cursor position,
+    /// to pixels from the top left of the rendered area.
+    #[must_use]
+    pub fn to_area(&self, x: f32, y: f32) -> Vec2 {
+        let (left, bottom, _, height) = self.rect();
+        let top = self.height - bottom - height;
+        Vec2::new(x - left as f32, y - top as f32)
+    }
+
+    /// Sets the GL viewport to the rendered area
+    pub fn apply(&self, gl: &mut OpenGl) {
+        let (x, y, width, height) = self.rect();
+        gl.viewport(x, y, width as GLsizei, height as GLsizei);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+
+    use super::Viewport;
+
+    #[test]
+    fn test_viewport() {
+        let mut viewport = Viewport::new(800, 600);
+        assert_eq!(viewport.rect(), (0, 0, 800, 600));
+        assert!((viewport.aspect_ratio() - 4.0 / 3.0).abs() < 1e-6);
+
+        // bars on the sides
+        viewport.set_fixed_aspect_ratio(Some(1.0));
+        assert_eq!(viewport.rect(), (100, 0, 600, 600));
+        assert_eq!(viewport.to_area(100.0, 10.0), Vec2::new(0.0, 10.0));
+
+        // bars above and below
+        viewport.resize(800, 800);
+        viewport.set_fixed_aspect_ratio(Some(2.0));
+        assert_eq!(viewport.rect(), (0, 200, 800, 400));
+        assert_eq!(viewport.size(), Vec2::new(800.0, 400.0));
+        assert_eq!(viewport.to_area(0.0, 200.0), Vec2::ZERO);
+
+        // minimized windows report an empty framebuffer
+        viewport.resize(0, 0);
+        assert!((viewport.aspect_ratio() - 1.0).abs() < f32::EPSILON);
+    }
+}